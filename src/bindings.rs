@@ -0,0 +1,1657 @@
+//! the wasm-bindgen surface: JsElasticHashTable and its helper types, JsElasticHashTableU64 and runSoak
+//!
+//! Only built with the wasm feature (on by default); the table itself lives in crate::elastic.
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use wasm_bindgen::prelude::*;
+
+use crate::elastic::{Slot, SnapshotIter};
+use crate::{json, testing, ElasticHashTable, InsertError, SlotId, TableObserver, TableOptions};
+
+mod batch;
+mod bytes_table;
+mod counter;
+mod entry;
+mod harness;
+mod int_table;
+mod numbers;
+mod pool;
+mod registry;
+mod snapshot;
+mod stored;
+mod validate;
+mod values_table;
+
+pub use batch::JsInsertBatch;
+pub use bytes_table::JsElasticHashTableBytes;
+pub use counter::JsCounterTable;
+pub use entry::JsEntry;
+pub use harness::JsComparisonHarness;
+pub use int_table::JsElasticHashTableU64;
+pub use registry::{JsNamespace, JsTableRegistry};
+pub use snapshot::SnapshotImport;
+pub use validate::ValidationError;
+pub use values_table::JsElasticHashTableValues;
+use batch::BatchBuffers;
+use numbers::{json_int, number_to_u64, JsInt};
+use pool::ValuePool;
+use stored::StoredValue;
+use validate::{JsKey, KeyRules, Violation};
+
+
+#[wasm_bindgen]
+extern "C" {
+    /// JS callback receiving the current value and returning the new one
+    #[wasm_bindgen(typescript_type = "(value: string) => string")]
+    pub type UpdateCallback;
+
+    #[wasm_bindgen(method, js_name = call)]
+    fn call_with(this: &UpdateCallback, context: &JsValue, value: &str) -> JsValue;
+
+    /// JS predicate over an entry
+    #[wasm_bindgen(typescript_type = "(key: string, value: string) => boolean")]
+    pub type EntryPredicate;
+
+    #[wasm_bindgen(method, js_name = call)]
+    fn call_with(this: &EntryPredicate, context: &JsValue, key: &str, value: &str) -> JsValue;
+
+    /// JS callback notified of an entry
+    #[wasm_bindgen(typescript_type = "(key: string, value: string) => void")]
+    pub type EntryCallback;
+
+    #[wasm_bindgen(method, js_name = call)]
+    fn call_with(this: &EntryCallback, context: &JsValue, key: &str, value: &str) -> JsValue;
+
+    /// JS callback of onPressure
+    #[wasm_bindgen(typescript_type = "(loadFactor: number, remainingCapacity: number) => void")]
+    pub type PressureCallback;
+
+    #[wasm_bindgen(method, js_name = call)]
+    fn call_with(this: &PressureCallback, context: &JsValue, load_factor: f64, remaining: usize) -> JsValue;
+
+    /// argument of setObserver; both callbacks are optional
+    #[wasm_bindgen(typescript_type = "{ onInsert?: (key: string, value: string) => void, \
+                                       onRemove?: (key: string, value: string) => void }")]
+    pub type ObserverCallbacks;
+
+    #[wasm_bindgen(method, getter, js_name = onInsert)]
+    fn on_insert(this: &ObserverCallbacks) -> Option<EntryCallback>;
+
+    #[wasm_bindgen(method, getter, js_name = onRemove)]
+    fn on_remove(this: &ObserverCallbacks) -> Option<EntryCallback>;
+}
+
+/// position of a JS iteration over a table, see JsElasticHashTable::entriesCursor
+#[wasm_bindgen]
+pub struct EntryCursor {
+    level: usize,
+    idx: usize,
+    /// table generation the cursor was created at
+    generation: u64,
+}
+
+/// entries copied out of a table by entriesSnapshot; unaffected by later changes to the table
+#[wasm_bindgen]
+pub struct EntriesSnapshot {
+    entries: SnapshotIter<JsKey, StoredValue>,
+}
+
+#[wasm_bindgen]
+impl EntriesSnapshot {
+    /// the next entry as [key, value], or undefined at the end
+    #[wasm_bindgen(js_name = next)]
+    pub fn next_entry(&mut self) -> Option<Vec<String>> {
+        self.entries.next().map(|(k, v)| vec![k.into_string(), v.into_string()])
+    }
+
+    /// number of entries not yet returned
+    #[wasm_bindgen(getter)]
+    pub fn remaining(&self) -> usize {
+        self.entries.len()
+    }
+}
+
+/// thrown by nextEntry once the table has changed since its cursor was created
+#[wasm_bindgen]
+#[derive(Debug)]
+pub struct ModifiedDuringIteration {
+    expected: u64,
+    actual: u64,
+}
+
+#[wasm_bindgen]
+impl ModifiedDuringIteration {
+    /// always "ERR_MODIFIED_DURING_ITERATION"
+    #[wasm_bindgen(getter)]
+    pub fn code(&self) -> String {
+        "ERR_MODIFIED_DURING_ITERATION".into()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn message(&self) -> String {
+        format!(
+            "table was modified during iteration (generation {} at start, now {})",
+            self.expected, self.actual
+        )
+    }
+}
+
+/// a page of entries and the cursor of the next one, see entriesPage
+type Page = (Vec<(String, String)>, Option<u32>);
+
+/// thrown by entriesPage: a ValidationError for a zero limit, or ModifiedDuringIteration
+#[derive(Debug)]
+pub enum PageError {
+    Invalid(ValidationError),
+    Modified(ModifiedDuringIteration),
+}
+
+impl From<PageError> for JsValue {
+    fn from(err: PageError) -> JsValue {
+        match err {
+            PageError::Invalid(err) => err.into(),
+            PageError::Modified(err) => err.into(),
+        }
+    }
+}
+
+/// the JS callbacks of setObserver
+struct JsObserver {
+    on_insert: Option<EntryCallback>,
+    on_remove: Option<EntryCallback>,
+}
+
+thread_local! {
+    /// the binding's own observer slot, by table id: JS functions are not Send, which the core table's observer
+    /// must be, so it is an ObserverSlot that looks its callbacks up here instead of holding them
+    static JS_OBSERVERS: RefCell<HashMap<u64, Rc<JsObserver>>> = RefCell::default();
+    /// the onPressure callbacks, by table id, for the same reason
+    static JS_PRESSURE: RefCell<HashMap<u64, Rc<PressureCallback>>> = RefCell::default();
+}
+
+/// forwards the binding's table mutations to the JsObserver registered under the table's id
+struct ObserverSlot {
+    id: u64,
+}
+
+impl ObserverSlot {
+    /// taken out of the slot first, so a callback may call setObserver
+    fn observer(&self) -> Option<Rc<JsObserver>> {
+        JS_OBSERVERS.with(|observers| observers.borrow().get(&self.id).cloned())
+    }
+}
+
+impl TableObserver<JsKey, StoredValue> for ObserverSlot {
+    fn on_insert(&mut self, key: &JsKey, value: &StoredValue, _slot: SlotId) {
+        if let Some(callback) = self.observer().as_ref().and_then(|o| o.on_insert.as_ref()) {
+            callback.call_with(&JsValue::NULL, key.as_str(), &value.get());
+        }
+    }
+
+    fn on_remove(&mut self, key: &JsKey, value: &StoredValue) {
+        if let Some(callback) = self.observer().as_ref().and_then(|o| o.on_remove.as_ref()) {
+            callback.call_with(&JsValue::NULL, key.as_str(), &value.get());
+        }
+    }
+}
+
+#[wasm_bindgen]
+pub struct JsElasticHashTable {
+    table: ElasticHashTable<JsKey, StoredValue>,
+    rules: KeyRules,
+    /// Some when values are deduplicated
+    pool: Option<ValuePool>,
+    /// tells the JsEntry handles of different tables apart
+    id: u64,
+    /// lent to each JsInsertBatch and handed back, cleared, by its commit
+    batch_buffers: BatchBuffers,
+}
+
+impl JsElasticHashTable {
+    fn from_parts(table: ElasticHashTable<JsKey, StoredValue>, rules: KeyRules) -> Self {
+        static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+        let pool = rules.dedup().then(|| ValuePool::new(table.delta()));
+        let id = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+        JsElasticHashTable {
+            table,
+            rules,
+            pool,
+            id,
+            batch_buffers: BatchBuffers::default(),
+        }
+    }
+
+    /// the fields of a getOptions object as (table options, input rules, metadata, singleLevel)
+    fn parse_options(fields: &[(String, String)]) -> Result<(TableOptions, KeyRules, bool, bool), String> {
+        let extra = [&KeyRules::FIELDS[..], &["metadata", "singleLevel"]].concat();
+        let options = TableOptions::from_fields(fields, &extra)?;
+        let rules = KeyRules::from_fields(fields)?;
+        let flag = |flag: &str| match fields.iter().find(|(name, _)| name == flag) {
+            Some((name, value)) => json::parse(name, value),
+            None => Ok(false),
+        };
+        let (metadata, single_level) = (flag("metadata")?, flag("singleLevel")?);
+        if options.capacity == 0 || !(0.0 < options.delta && options.delta < 1.0) {
+            return Err("capacity must be positive and delta between 0 and 1".into());
+        }
+        Ok((options, rules, metadata, single_level))
+    }
+
+    /// withOptions, failing with the reason
+    fn from_options(options: &str) -> Result<Self, String> {
+        let (options, rules, metadata, single_level) = Self::parse_options(&json::flat_object(options)?)?;
+        let table = if single_level {
+            ElasticHashTable::single_level_with_hasher(options.capacity, options.delta, options.seed)
+        } else {
+            ElasticHashTable::with_options(options)
+        };
+        let table = if metadata { table.with_metadata(None) } else { table };
+        Ok(Self::from_parts(table, rules))
+    }
+
+    /// insert, with index as the position reported by a ValidationError
+    fn insert_at(&mut self, key: String, value: String, index: Option<u32>) -> Result<(), ValidationError> {
+        let key = self.rules.table_key(&key);
+        self.rules.check(key.as_str(), &value).map_err(|v| ValidationError::new(&v, index))?;
+        let value = Self::store_value(&self.rules, &mut self.pool, value);
+        match self.table.insert_or_update(key, value) {
+            Ok(old) => Self::drop_replaced(&mut self.pool, old),
+            Err(err) => return Err(Self::table_full(&mut self.pool, err, index)),
+        }
+        Ok(())
+    }
+
+    /// key and value of a fromEntries element, if it is an array of two strings
+    fn entry_pair(entry: &JsValue) -> Option<(String, String)> {
+        let pair = entry.dyn_ref::<js_sys::Array>().filter(|pair| pair.length() == 2)?;
+        Some((pair.get(0).as_string()?, pair.get(1).as_string()?))
+    }
+
+    /// searchMany's values
+    fn search_all(&self, keys: Vec<String>) -> Vec<Option<String>> {
+        keys.into_iter().map(|key| self.search(key)).collect()
+    }
+
+    /// value in the form this table stores it
+    fn store_value(rules: &KeyRules, pool: &mut Option<ValuePool>, value: String) -> StoredValue {
+        match pool {
+            Some(pool) => pool.intern(value),
+            None => StoredValue::new(value, rules.compress_above()),
+        }
+    }
+
+    /// up to limit entries from slot position cursor on (levels laid end to end),
+    /// with the position of the next occupied slot if there is one
+    /// ERR_INVALID_ARGUMENT for a zero limit, whose next cursor would never advance
+    fn page(&self, cursor: usize, limit: usize) -> Result<Page, ValidationError> {
+        if limit == 0 {
+            let reason = "limit must be positive".to_string();
+            return Err(ValidationError::new(&Violation::InvalidArgument(reason), None));
+        }
+        let mut items = Vec::with_capacity(limit.min(self.table.num_inserts));
+        let mut start = 0;
+        for level in &self.table.levels {
+            let end = start + level.len();
+            for pos in cursor.max(start)..end {
+                if let Slot::Occupied(k, v) = &level[pos - start] {
+                    if items.len() == limit {
+                        return Ok((items, Some(pos as u32)));
+                    }
+                    items.push((k.as_str().to_string(), v.get().into_owned()));
+                }
+            }
+            start = end;
+        }
+        Ok((items, None))
+    }
+
+    /// the entries of shard, one of total, see entriesForShard
+    fn shard(&self, shard: u32, total: u32) -> Vec<(String, String)> {
+        // hash prefixes of the smallest width that gives every shard one, dealt out round-robin;
+        // a shard gets at most two
+        let bits = total.next_power_of_two().trailing_zeros();
+        (u64::from(shard)..1 << bits)
+            .step_by(total as usize)
+            .flat_map(|prefix| self.table.iter_hash_range(prefix, bits))
+            .map(|(k, v)| (k.as_str().to_string(), v.get().into_owned()))
+            .collect()
+    }
+
+    /// give back the pool reference of a value an insert replaced
+    fn drop_replaced(pool: &mut Option<ValuePool>, old: Option<StoredValue>) {
+        if let (Some(pool), Some(old)) = (pool, old) {
+            pool.release(&old);
+        }
+    }
+
+    /// ERR_TABLE_FULL for a failed insert, releasing the value it had pooled
+    fn table_full(
+        pool: &mut Option<ValuePool>,
+        err: InsertError<JsKey, StoredValue>,
+        index: Option<u32>,
+    ) -> ValidationError {
+        let error = ValidationError::new(&Violation::from(err.error()), index);
+        Self::drop_replaced(pool, Some(err.into_parts().1));
+        error
+    }
+
+    /// a value leaving the table
+    fn release_value(&mut self, value: StoredValue) -> String {
+        if let Some(pool) = &mut self.pool {
+            pool.release(&value);
+        }
+        value.into_string()
+    }
+}
+
+/// frees the table's observer and pressure slots
+impl Drop for JsElasticHashTable {
+    fn drop(&mut self) {
+        // try_with: the thread-locals may already be gone when a table is dropped during thread exit
+        let _ = JS_OBSERVERS.try_with(|observers| observers.borrow_mut().remove(&self.id));
+        let _ = JS_PRESSURE.try_with(|callbacks| callbacks.borrow_mut().remove(&self.id));
+    }
+}
+
+#[wasm_bindgen]
+impl JsElasticHashTable {
+    /// throws ERR_INVALID_ARGUMENT unless capacity is positive and delta between 0 and 1
+    #[wasm_bindgen(constructor)]
+    pub fn new(capacity: usize, delta: f64) -> Result<JsElasticHashTable, ValidationError> {
+        ValidationError::check_dimensions(capacity, delta)?;
+        Ok(Self::from_parts(ElasticHashTable::new(capacity, delta), KeyRules::default()))
+    }
+
+    /// build a table from the JSON returned by getOptions
+    /// besides the table options it accepts the input rules maxKeyLength, maxValueLength (UTF-8 bytes),
+    /// keyPattern (allowed key bytes, e.g. "a-z0-9_-"), normalize ("nfc" or "none") and caseInsensitive,
+    /// which matches keys case-folded while keeping the casing they were inserted with;
+    /// strictUnicode rejects keys and values containing U+FFFD, which is what an unpaired surrogate
+    /// becomes on the way in (see JsElasticHashTableBytes for such strings);
+    /// compressAbove (bytes) stores longer values compressed and needs the compress feature;
+    /// dedup stores each distinct value once, shared by every entry holding it;
+    /// metadata keeps a number beside every entry, see insertTagged;
+    /// singleLevel builds one level of the full capacity, see ElasticHashTable::single_level
+    #[wasm_bindgen(js_name = withOptions)]
+    pub fn with_options(options: &str) -> Result<JsElasticHashTable, JsValue> {
+        Self::from_options(options).map_err(|e| JsValue::from_str(&e))
+    }
+
+    /// build a table from withOptions' options JSON and fill it from entries, an array of [key, value] pairs,
+    /// as insert would in order. throws the first ValidationError insert would, with the pair's position as
+    /// its index, or ERR_INVALID_ARGUMENT for bad options or, with the index, for an element that is not
+    /// a pair of strings
+    #[wasm_bindgen(js_name = fromEntries)]
+    pub fn from_entries(options: &str, entries: Vec<JsValue>) -> Result<JsElasticHashTable, ValidationError> {
+        let mut table = Self::from_options(options)
+            .map_err(|reason| ValidationError::new(&Violation::InvalidArgument(reason), None))?;
+        for (index, entry) in entries.iter().enumerate() {
+            let Some((key, value)) = Self::entry_pair(entry) else {
+                let reason = format!("entry {index} is not a [key, value] pair of strings");
+                return Err(ValidationError::new(&Violation::InvalidArgument(reason), Some(index as u32)));
+            };
+            table.insert_at(key, value, Some(index as u32))?;
+        }
+        Ok(table)
+    }
+
+    /// construction parameters and input rules as a JSON string
+    #[wasm_bindgen(js_name = getOptions)]
+    pub fn get_options(&self) -> String {
+        let mut json = self.table.options().to_json();
+        let metadata = self.table.has_metadata().then(|| "\"metadata\":true".to_string());
+        let single_level = self.table.is_single_level().then(|| "\"singleLevel\":true".to_string());
+        for field in self.rules.json_fields().into_iter().chain(metadata).chain(single_level) {
+            json.insert(json.len() - 1, ',');
+            json.insert_str(json.len() - 1, &field);
+        }
+        json
+    }
+
+    /// insert, or replace the value if key is present
+    /// throws a ValidationError with code ERR_INVALID_KEY or ERR_INVALID_VALUE,
+    /// leaving the table untouched, if the entry breaks the input rules,
+    /// or with ERR_TABLE_FULL, likewise, when key is new and the table has no room (see remainingCapacity)
+    /// the key is stored normalized, and the rules apply to the normalized form
+    #[wasm_bindgen]
+    pub fn insert(&mut self, key: String, value: String) -> Result<(), ValidationError> {
+        self.insert_at(key, value, None)
+    }
+
+    /// insert keys[i] with values[i] for every i, in order, with one call across the wasm boundary;
+    /// returns the number of pairs inserted or replaced. stops at the first pair insert would throw for,
+    /// with the pair's position as the ValidationError's index; the pairs before it stay inserted.
+    /// throws ERR_INVALID_ARGUMENT, inserting nothing, when the arrays differ in length
+    #[wasm_bindgen(js_name = insertMany)]
+    pub fn insert_many(&mut self, keys: Vec<String>, values: Vec<String>) -> Result<usize, ValidationError> {
+        if keys.len() != values.len() {
+            let reason = format!("{} keys but {} values", keys.len(), values.len());
+            return Err(ValidationError::new(&Violation::InvalidArgument(reason), None));
+        }
+        let count = keys.len();
+        for (index, (key, value)) in keys.into_iter().zip(values).enumerate() {
+            self.insert_at(key, value, Some(index as u32))?;
+        }
+        Ok(count)
+    }
+
+    #[wasm_bindgen]
+    pub fn search(&self, key: String) -> Option<String> {
+        self.table.get(&self.rules.table_key(&key)).map(|v| v.get().into_owned())
+    }
+
+    /// the value of every key, aligned with keys: null where search returns undefined
+    #[wasm_bindgen(js_name = searchMany, unchecked_return_type = "(string | null)[]")]
+    pub fn search_many(&self, keys: Vec<String>) -> Vec<JsValue> {
+        self.search_all(keys).into_iter().map(|value| value.map_or(JsValue::NULL, JsValue::from)).collect()
+    }
+
+    /// search without allocating a JS string: copy the UTF-8 bytes of key's value into the front of buffer
+    /// returns the number of bytes written (0 for an empty value), -1 if key is absent,
+    /// or -(n + 2) if the value needs n bytes and buffer is shorter; buffer is untouched unless bytes are written
+    #[wasm_bindgen(js_name = searchInto)]
+    pub fn search_into(&self, key: &str, buffer: &mut [u8]) -> i32 {
+        let Some(value) = self.table.get(&self.rules.table_key(key)) else {
+            return -1;
+        };
+        let bytes = value.get();
+        let len = bytes.len();
+        if len > buffer.len() {
+            return i32::try_from(len).map_or(i32::MIN, |n| -n.saturating_add(2));
+        }
+        buffer[..len].copy_from_slice(bytes.as_bytes());
+        len as i32
+    }
+
+    /// insert with tag kept beside the entry, for a table built with the metadata option;
+    /// plain inserts record 0. tags are integers from 0 to 2^53 - 1, anything else throws ERR_INVALID_TAG;
+    /// throws like insert otherwise
+    #[wasm_bindgen(js_name = insertTagged)]
+    pub fn insert_tagged(&mut self, key: String, value: String, tag: f64) -> Result<(), ValidationError> {
+        let tag = number_to_u64(tag).ok_or_else(|| ValidationError::new(&Violation::Tag, None))?;
+        let key = self.rules.table_key(&key);
+        self.rules.check(key.as_str(), &value).map_err(|v| ValidationError::new(&v, None))?;
+        let value = Self::store_value(&self.rules, &mut self.pool, value);
+        match self.table.upsert_tagged(key, value, tag) {
+            Ok((_, old)) => Self::drop_replaced(&mut self.pool, old),
+            Err(err) => return Err(Self::table_full(&mut self.pool, err, None)),
+        }
+        Ok(())
+    }
+
+    /// key's value with its tag as JSON `{"value": string, "meta": number}`, meta being a string past 2^53 - 1,
+    /// or undefined when key is absent or the table keeps no metadata
+    #[wasm_bindgen(js_name = getWithMeta)]
+    pub fn get_with_meta(&self, key: &str) -> Option<String> {
+        let (value, meta) = self.table.get_with_meta(&self.rules.table_key(key))?;
+        Some(format!("{{\"value\":{},\"meta\":{}}}", json::quote(&value.get()), json_int(meta)))
+    }
+
+    /// everything a lookup of key saw, as JSON for debugging tools:
+    /// `{"found": true, "value": string, "slot": {"level": number, "index": number}, "probes": number}`
+    /// or `{"found": false, "probes": number}`; probes counts every slot inspected, tombstones included
+    #[wasm_bindgen(js_name = getDetailed)]
+    pub fn get_detailed(&self, key: &str) -> String {
+        let (pos, probes) = self.table.probe(&self.rules.table_key(key));
+        match (pos, self.table.value_at(pos)) {
+            (Some((level, index)), Some(value)) => format!(
+                "{{\"found\":true,\"value\":{},\"slot\":{{\"level\":{},\"index\":{}}},\"probes\":{}}}",
+                json::quote(&value.get()),
+                json_int(level as u64),
+                json_int(index as u64),
+                json_int(probes as u64)
+            ),
+            _ => format!("{{\"found\":false,\"probes\":{}}}", json_int(probes as u64)),
+        }
+    }
+
+    #[wasm_bindgen]
+    pub fn has(&self, key: String) -> bool {
+        self.table.get(&self.rules.table_key(&key)).is_some()
+    }
+
+    /// remove key, returning its value
+    #[wasm_bindgen]
+    pub fn remove(&mut self, key: String) -> Option<String> {
+        let value = self.table.remove(&self.rules.table_key(&key))?;
+        Some(self.release_value(value))
+    }
+
+    /// number of mutations so far, see ElasticHashTable::generation; a BigInt once past 2^53 - 1
+    #[wasm_bindgen(getter, unchecked_return_type = "number | bigint")]
+    pub fn generation(&self) -> JsValue {
+        JsInt::new(self.table.generation()).into()
+    }
+
+    /// the 64-bit hash the table files key under, see ElasticHashTable::hash_key; always a BigInt
+    #[wasm_bindgen(js_name = hashKey)]
+    pub fn hash_key(&self, key: &str) -> u64 {
+        self.table.hash_key(&self.rules.table_key(key))
+    }
+
+    /// every entry as a [key, value] array, in canonical (level, slot) order, for
+    /// `for (const [k, v] of table.entries())`; a copy, so the table can be changed during the loop
+    #[wasm_bindgen(unchecked_return_type = "[string, string][]")]
+    pub fn entries(&self) -> js_sys::Array {
+        self.table
+            .iter()
+            .map(|(k, v)| js_sys::Array::of2(&k.as_str().into(), &v.get().as_ref().into()))
+            .collect()
+    }
+
+    /// start iterating over the entries without copying them, in canonical (level, slot) order;
+    /// pass the cursor to nextEntry
+    #[wasm_bindgen(js_name = entriesCursor)]
+    pub fn entries_cursor(&self) -> EntryCursor {
+        EntryCursor {
+            level: 0,
+            idx: 0,
+            generation: self.table.generation(),
+        }
+    }
+
+    /// the next entry of cursor as [key, value], or undefined at the end
+    /// throws ModifiedDuringIteration if the table was mutated since entriesCursor returned the cursor
+    #[wasm_bindgen(js_name = nextEntry)]
+    pub fn next_entry(&self, cursor: &mut EntryCursor) -> Result<Option<Vec<String>>, ModifiedDuringIteration> {
+        if cursor.generation != self.table.generation() {
+            return Err(ModifiedDuringIteration {
+                expected: cursor.generation,
+                actual: self.table.generation(),
+            });
+        }
+        while cursor.level < self.table.levels.len() {
+            let level = &self.table.levels[cursor.level];
+            if cursor.idx >= level.len() {
+                cursor.level += 1;
+                cursor.idx = 0;
+                continue;
+            }
+            cursor.idx += 1;
+            if let Slot::Occupied(k, v) = &level[cursor.idx - 1] {
+                return Ok(Some(vec![k.as_str().to_string(), v.get().into_owned()]));
+            }
+        }
+        Ok(None)
+    }
+
+    /// copy every entry out now, in canonical order, so the table can be changed while the copy is walked
+    /// takes memory for the whole table at once; see entriesPage for large tables
+    #[wasm_bindgen(js_name = entriesSnapshot)]
+    pub fn entries_snapshot(&self) -> EntriesSnapshot {
+        EntriesSnapshot {
+            entries: self.table.iter_snapshot(),
+        }
+    }
+
+    /// one page of entries as JSON: `{"items": [[key, value], ...], "nextCursor": number | null, "generation": number}`;
+    /// a generation past 2^53 - 1 is written as a string, and no longer matches any generation argument
+    /// start with cursor 0 and no generation, then pass back nextCursor and generation until nextCursor is null;
+    /// every page but the last holds exactly limit items, and pages follow the canonical order.
+    /// cursors are slot positions, valid only while the generation is unchanged: a changed table throws
+    /// ModifiedDuringIteration. a zero limit throws ERR_INVALID_ARGUMENT
+    #[wasm_bindgen(js_name = entriesPage)]
+    pub fn entries_page(&self, cursor: u32, limit: u32, generation: Option<f64>) -> Result<String, PageError> {
+        let current = self.table.generation();
+        if let Some(expected) = generation {
+            // compared as integers: past 2^53 nearby generations round to the same f64
+            if number_to_u64(expected) != Some(current) {
+                return Err(PageError::Modified(ModifiedDuringIteration {
+                    expected: expected as u64,
+                    actual: current,
+                }));
+            }
+        }
+        let (items, next) = self.page(cursor as usize, limit as usize).map_err(PageError::Invalid)?;
+        let items: Vec<String> = items
+            .iter()
+            .map(|(k, v)| format!("[{},{}]", json::quote(k), json::quote(v)))
+            .collect();
+        let next = next.map_or("null".to_string(), |n| n.to_string());
+        Ok(format!(
+            "{{\"items\":[{}],\"nextCursor\":{},\"generation\":{}}}",
+            items.join(","),
+            next,
+            json_int(current)
+        ))
+    }
+
+    /// the entries of one of totalShards shards as JSON `[[key, value], ...]`; the shards split the
+    /// table by key hash, so together they hold every entry exactly once, and a key stays in its shard
+    /// as the table changes, for scanning a table in deterministic pieces
+    #[wasm_bindgen(js_name = entriesForShard)]
+    pub fn entries_for_shard(&self, shard: u32, total_shards: u32) -> Result<String, JsValue> {
+        if shard >= total_shards {
+            return Err(JsValue::from_str("shard must be below totalShards"));
+        }
+        let items: Vec<String> = self
+            .shard(shard, total_shards)
+            .iter()
+            .map(|(k, v)| format!("[{},{}]", json::quote(k), json::quote(v)))
+            .collect();
+        Ok(format!("[{}]", items.join(",")))
+    }
+
+    /// every key, with the casing it was inserted with, in canonical order
+    #[wasm_bindgen]
+    pub fn keys(&self) -> Vec<String> {
+        self.table.keys().map(|k| k.as_str().to_string()).collect()
+    }
+
+    /// remove an arbitrary entry, returned as [key, value], or undefined when the table is empty
+    #[wasm_bindgen]
+    pub fn pop(&mut self) -> Option<Vec<String>> {
+        let (k, v) = self.table.pop()?;
+        Some(vec![k.into_string(), self.release_value(v)])
+    }
+
+    /// the walk a search for key takes as JSON, see ExplainReport::to_json; probeCount is left alone
+    #[wasm_bindgen]
+    pub fn explain(&self, key: &str) -> String {
+        self.table.explain(&self.rules.table_key(key)).to_json()
+    }
+
+    /// the table's counters as JSON, the same object as the core stats_json
+    /// except that integers past 2^53 - 1 are written as strings
+    #[wasm_bindgen(js_name = getStats)]
+    pub fn get_stats(&self) -> String {
+        self.table.stats().to_json_with(json_int)
+    }
+
+    /// per-level probe counters as JSON, see ElasticHashTable::probe_stats and ProbeStats::to_json;
+    /// integers past 2^53 - 1 are written as strings
+    #[wasm_bindgen(js_name = probeStats)]
+    pub fn probe_stats(&self) -> String {
+        self.table.probe_stats().to_json_with(json_int)
+    }
+
+    /// remove every entry, keeping the table's slots allocated for refilling
+    #[wasm_bindgen]
+    pub fn clear(&mut self) {
+        match &mut self.pool {
+            Some(pool) => self.table.drain().for_each(|(_, value)| pool.release(&value)),
+            None => self.table.clear(),
+        }
+    }
+
+    #[wasm_bindgen(js_name = resetProbeStats)]
+    pub fn reset_probe_stats(&mut self) {
+        self.table.reset_probe_stats();
+    }
+
+    /// per-level occupancy report, see ElasticHashTable::status_string
+    #[wasm_bindgen(js_name = statusString)]
+    pub fn status_string(&self) -> String {
+        self.table.status_string()
+    }
+
+    /// number of stored entries
+    #[wasm_bindgen(getter)]
+    pub fn len(&self) -> usize {
+        self.table.len()
+    }
+
+    #[wasm_bindgen(getter, js_name = isEmpty)]
+    pub fn is_empty(&self) -> bool {
+        self.table.is_empty()
+    }
+
+    /// total number of slots
+    #[wasm_bindgen(getter)]
+    pub fn capacity(&self) -> usize {
+        self.table.capacity()
+    }
+
+    /// entries the table accepts before insert throws
+    #[wasm_bindgen(getter, js_name = maxInserts)]
+    pub fn max_inserts(&self) -> usize {
+        self.table.max_inserts()
+    }
+
+    /// new keys the table still accepts; when 0, resize or allocate a bigger table before inserting
+    #[wasm_bindgen(getter, js_name = remainingCapacity)]
+    pub fn remaining_capacity(&self) -> usize {
+        self.table.remaining_capacity()
+    }
+
+    /// move every entry into a table of newCapacity slots, see ElasticHashTable::resize; throws
+    /// ERR_INVALID_ARGUMENT, leaving the table as it was, when newCapacity cannot hold the current entries
+    #[wasm_bindgen]
+    pub fn resize(&mut self, new_capacity: usize) -> Result<(), ValidationError> {
+        self.table.resize(new_capacity).map_err(|err| ValidationError::new(&Violation::from(&err), None))
+    }
+
+    /// whether inserting a new key would throw
+    #[wasm_bindgen(getter, js_name = isFull)]
+    pub fn is_full(&self) -> bool {
+        self.table.is_full()
+    }
+
+    /// len / capacity
+    #[wasm_bindgen(getter, js_name = loadFactor)]
+    pub fn load_factor(&self) -> f64 {
+        self.table.load_factor()
+    }
+
+    /// each level's [occupied, size] as JSON, level 0 first, e.g. `[[310,512],[98,256]]`
+    #[wasm_bindgen(js_name = levelStats)]
+    pub fn level_stats(&self) -> String {
+        let levels: Vec<String> =
+            self.table.level_stats().iter().map(|(occupied, size)| format!("[{},{}]", occupied, size)).collect();
+        format!("[{}]", levels.join(","))
+    }
+
+    /// slots inspected along probe sequences so far, see ElasticHashTable::probe_count;
+    /// a BigInt once past 2^53 - 1
+    #[wasm_bindgen(getter, js_name = probeCount, unchecked_return_type = "number | bigint")]
+    pub fn probe_count(&self) -> JsValue {
+        JsInt::new(self.table.probe_count()).into()
+    }
+
+    /// approximate bytes held by the table: the slot arrays plus keys and values as stored,
+    /// so compressed values count at their compressed size and deduplicated ones once
+    #[wasm_bindgen(js_name = memoryUsage)]
+    pub fn memory_usage(&self) -> usize {
+        let mut slots = self.table.capacity() * std::mem::size_of::<Slot<JsKey, StoredValue>>();
+        if self.table.has_metadata() {
+            slots += self.table.capacity() * std::mem::size_of::<u64>();
+        }
+        let entries: usize = self.table.iter().map(|(k, v)| k.as_str().len() + v.owned_len()).sum();
+        slots + entries + self.pool.as_ref().map_or(0, ValuePool::memory_usage)
+    }
+
+    /// number of distinct values held, or undefined when the table does not deduplicate
+    #[wasm_bindgen(js_name = distinctValueCount)]
+    pub fn distinct_value_count(&self) -> Option<usize> {
+        self.pool.as_ref().map(ValuePool::distinct_values)
+    }
+
+    /// total value bytes as inserted over total value bytes as stored; 1 when nothing is compressed
+    #[wasm_bindgen(js_name = compressionRatio)]
+    pub fn compression_ratio(&self) -> f64 {
+        let (len, stored) = self
+            .table
+            .values()
+            .fold((0, 0), |(len, stored), v| (len + v.len(), stored + v.stored_len()));
+        if stored == 0 {
+            1.0
+        } else {
+            len as f64 / stored as f64
+        }
+    }
+
+    /// call observer.onInsert(key, value) after every insert and observer.onRemove(key, value)
+    /// after every removal, including those of pop and splitOff; replaces any previous observer
+    #[wasm_bindgen(js_name = setObserver)]
+    pub fn set_observer(&mut self, observer: &ObserverCallbacks) {
+        let observer = JsObserver {
+            on_insert: observer.on_insert(),
+            on_remove: observer.on_remove(),
+        };
+        JS_OBSERVERS.with(|observers| observers.borrow_mut().insert(self.id, Rc::new(observer)));
+        self.table.set_observer(Box::new(ObserverSlot { id: self.id }));
+    }
+
+    /// call callback(loadFactor, remainingCapacity) once each time an insert (insert, insertTagged, a batch
+    /// commit, ...) takes the load factor to or past one of thresholds, e.g. `onPressure([0.8, 0.95], slowDown)`;
+    /// a threshold fires again after removals or growth have taken the load factor back below it.
+    /// thresholds must lie in (0, 1]; replaces any previous pressure callback
+    #[wasm_bindgen(js_name = onPressure)]
+    pub fn on_pressure(&mut self, thresholds: Vec<f64>, callback: PressureCallback) -> Result<(), JsValue> {
+        let id = self.id;
+        // the core callback must be Send and Sync, so it holds only the id; the callback is taken out of the
+        // slot before it runs, so it may call onPressure itself
+        let forward = move |load_factor, remaining| {
+            if let Some(callback) = JS_PRESSURE.with(|callbacks| callbacks.borrow().get(&id).cloned()) {
+                callback.call_with(&JsValue::NULL, load_factor, remaining);
+            }
+        };
+        self.table.set_pressure_callback(&thresholds, Box::new(forward)).map_err(|e| JsValue::from_str(&e))?;
+        JS_PRESSURE.with(|callbacks| callbacks.borrow_mut().insert(id, Rc::new(callback)));
+        Ok(())
+    }
+
+    /// move every entry for which predicate(key, value) is truthy into a new table
+    #[wasm_bindgen(js_name = splitOff)]
+    pub fn split_off(&mut self, predicate: &EntryPredicate) -> JsElasticHashTable {
+        let table = self
+            .table
+            .split_off(|k, v| predicate.call_with(&JsValue::NULL, k.as_str(), &v.get()).is_truthy());
+        let mut split = Self::from_parts(table, self.rules.clone());
+        if let (Some(pool), Some(split_pool)) = (&mut self.pool, &mut split.pool) {
+            for v in split.table.values() {
+                pool.release(v);
+                split_pool.adopt(v);
+            }
+        }
+        split
+    }
+
+    /// replace the value under key with callback(value); returns false if key is absent
+    /// a new value longer than maxValueLength throws ERR_INVALID_VALUE and keeps the old one
+    #[wasm_bindgen]
+    pub fn update(&mut self, key: String, callback: &UpdateCallback) -> Result<bool, JsValue> {
+        let JsElasticHashTable { table, rules, pool, .. } = self;
+        let updated = table.modify(&rules.table_key(&key), |v| {
+            let next = callback
+                .call_with(&JsValue::NULL, &v.get())
+                .as_string()
+                .ok_or_else(|| JsValue::from_str("update callback must return a string"))?;
+            rules.check_value(&next).map_err(|e| ValidationError::new(&e, None))?;
+            let next = Self::store_value(rules, pool, next);
+            if let Some(pool) = pool {
+                pool.release(v);
+            }
+            *v = next;
+            Ok(())
+        });
+        match updated {
+            None => Ok(false),
+            Some(result) => result.map(|()| true),
+        }
+    }
+
+    /// add suffix to the end of the value under key, in its slot; returns false if key is absent
+    /// a value grown past maxValueLength throws ERR_INVALID_VALUE and keeps the old one
+    #[wasm_bindgen]
+    pub fn append(&mut self, key: String, suffix: &str) -> Result<bool, ValidationError> {
+        let JsElasticHashTable { table, rules, pool, .. } = self;
+        let appended = table.modify(&rules.table_key(&key), |v| {
+            let next = v.get().into_owned() + suffix;
+            rules.check_value(&next).map_err(|e| ValidationError::new(&e, None))?;
+            let next = Self::store_value(rules, pool, next);
+            if let Some(pool) = pool {
+                pool.release(v);
+            }
+            *v = next;
+            Ok(())
+        });
+        appended.map_or(Ok(false), |result| result.map(|()| true))
+    }
+}
+
+/// run a soak from JS: takes a JSON config (see testing::SoakConfig) and returns the report as a JSON string
+#[wasm_bindgen(js_name = runSoak)]
+pub fn run_soak(config_json: &str) -> Result<JsValue, JsValue> {
+    let config = testing::SoakConfig::from_json(config_json).map_err(|e| JsValue::from_str(&e))?;
+    let report = testing::soak(config).map_err(|e| JsValue::from_str(&e))?;
+    Ok(JsValue::from_str(&report.to_json()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::STATS_FIELDS;
+
+    fn js_table(options: &str) -> JsElasticHashTable {
+        JsElasticHashTable::with_options(options).expect("invalid options")
+    }
+
+    #[test]
+    fn test_js_keys_are_not_normalized_by_default() {
+        let mut table = js_table(r#"{"capacity": 64, "delta": 0.1, "seed": 1}"#);
+        table.insert("caf\u{e9}".into(), "composed".into()).unwrap();
+        table.insert("cafe\u{301}".into(), "decomposed".into()).unwrap();
+        assert_eq!(table.search("caf\u{e9}".into()), Some("composed".into()));
+        assert_eq!(table.search("cafe\u{301}".into()), Some("decomposed".into()));
+        assert_eq!(table.remove("cafe\u{301}".into()), Some("decomposed".into()));
+        assert!(table.has("caf\u{e9}".into()));
+    }
+
+    #[test]
+    fn test_js_case_insensitive_keys() {
+        let mut table = js_table(r#"{"capacity": 64, "delta": 0.1, "seed": 1, "caseInsensitive": true}"#);
+        assert!(table.get_options().ends_with(r#""caseInsensitive":true}"#));
+        table.insert("X-Request-Id".into(), "1".into()).unwrap();
+        table.insert("accept".into(), "2".into()).unwrap();
+
+        assert_eq!(table.search("x-request-id".into()), Some("1".into()));
+        assert!(table.has("ACCEPT".into()));
+        let mut keys = table.keys();
+        keys.sort();
+        assert_eq!(keys, vec!["X-Request-Id", "accept"]);
+
+        assert_eq!(table.remove("X-REQUEST-ID".into()), Some("1".into()));
+        assert!(!table.has("X-Request-Id".into()));
+
+        let exact = js_table(r#"{"capacity": 64, "delta": 0.1, "seed": 1, "caseInsensitive": false}"#);
+        assert!(!exact.get_options().contains("caseInsensitive"));
+    }
+
+    #[test]
+    fn test_js_entries_snapshot() {
+        let mut table = js_table(r#"{"capacity": 64, "delta": 0.1, "seed": 1}"#);
+        table.insert("a".into(), "1".into()).unwrap();
+        table.insert("b".into(), "2".into()).unwrap();
+        let mut snapshot = table.entries_snapshot();
+        table.remove("a".into());
+        table.remove("b".into());
+        table.insert("c".into(), "3".into()).unwrap();
+        let mut seen = vec![snapshot.next_entry().unwrap(), snapshot.next_entry().unwrap()];
+        assert_eq!(snapshot.remaining(), 0);
+        assert_eq!(snapshot.next_entry(), None);
+        seen.sort();
+        assert_eq!(seen, [["a", "1"], ["b", "2"]]);
+    }
+
+    #[test]
+    fn test_js_entries_fail_fast() {
+        let mut table = js_table(r#"{"capacity": 64, "delta": 0.1, "seed": 1}"#);
+        for i in 0..5 {
+            table.insert(i.to_string(), "v".into()).unwrap();
+        }
+        let mut cursor = table.entries_cursor();
+        let mut seen = Vec::new();
+        while let Some(entry) = table.next_entry(&mut cursor).unwrap() {
+            seen.push(entry[0].clone());
+        }
+        seen.sort();
+        assert_eq!(seen, ["0", "1", "2", "3", "4"]);
+        assert_eq!(table.next_entry(&mut cursor).unwrap(), None);
+
+        let mut cursor = table.entries_cursor();
+        assert!(table.next_entry(&mut cursor).unwrap().is_some());
+        let before = table.table.generation();
+        table.remove("3".into());
+        assert_eq!(table.table.generation(), before + 1);
+        let err = table.next_entry(&mut cursor).unwrap_err();
+        assert_eq!(err.code(), "ERR_MODIFIED_DURING_ITERATION");
+        assert_eq!((err.expected, err.actual), (5, 6));
+    }
+
+    #[test]
+    fn test_js_entries_pages() {
+        let mut table = js_table(r#"{"capacity": 12000, "delta": 0.1, "seed": 1}"#);
+        for i in 0..10_000 {
+            table.insert(format!("k{}", i), i.to_string()).unwrap();
+        }
+        let mut seen = std::collections::HashMap::new();
+        let mut cursor = Some(0);
+        let mut pages = 0;
+        while let Some(at) = cursor {
+            let (items, next) = table.page(at as usize, 100).unwrap();
+            assert_eq!(items.len(), 100);
+            seen.extend(items);
+            cursor = next;
+            pages += 1;
+        }
+        assert_eq!(pages, 100);
+        assert_eq!(seen.len(), 10_000);
+        assert!(seen.iter().all(|(k, v)| k[1..] == *v));
+        let last = table.table.levels.iter().map(Vec::len).sum::<usize>() as u32;
+        assert_eq!(table.page(last as usize, 100).unwrap(), (vec![], None));
+
+        let mut small = js_table(r#"{"capacity": 16, "delta": 0.1, "seed": 1}"#);
+        small.insert("a\"".into(), "1".into()).unwrap();
+        small.insert("b".into(), "2".into()).unwrap();
+        let first = small.entries_page(0, 1, None).unwrap();
+        let (_, next) = small.page(0, 1).unwrap();
+        assert!(first.ends_with(&format!("]],\"nextCursor\":{},\"generation\":2}}", next.unwrap())));
+        let second = small.entries_page(next.unwrap(), 5, Some(2.0)).unwrap();
+        assert!(second.ends_with("\"nextCursor\":null,\"generation\":2}"));
+        assert!(format!("{}{}", first, second).contains(r#"["a\"","1"]"#));
+
+        // a zero limit would hand back the same cursor forever
+        assert_eq!(small.page(0, 0).unwrap_err().code(), "ERR_INVALID_ARGUMENT");
+        match small.entries_page(0, 0, Some(2.0)) {
+            Err(PageError::Invalid(err)) => assert_eq!(err.code(), "ERR_INVALID_ARGUMENT"),
+            other => panic!("expected ERR_INVALID_ARGUMENT, got {:?}", other),
+        }
+
+        small.remove("b".into());
+        match small.entries_page(next.unwrap(), 5, Some(2.0)) {
+            Err(PageError::Modified(err)) => assert_eq!((err.expected, err.actual), (2, 3)),
+            other => panic!("expected ModifiedDuringIteration, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_js_search_into() {
+        let mut table = js_table(r#"{"capacity": 64, "delta": 0.1, "seed": 1}"#);
+        table.insert("k".into(), "h\u{e9}llo".into()).unwrap();
+        table.insert("empty".into(), String::new()).unwrap();
+
+        let mut exact = [0u8; 6];
+        assert_eq!(table.search_into("k", &mut exact), 6);
+        assert_eq!(&exact, "h\u{e9}llo".as_bytes());
+
+        let mut large = [0xaa; 8];
+        assert_eq!(table.search_into("k", &mut large), 6);
+        assert_eq!(large[6..], [0xaa, 0xaa]);
+
+        let mut small = [0u8; 5];
+        assert_eq!(table.search_into("k", &mut small), -8);
+        assert_eq!(small, [0; 5]);
+
+        assert_eq!(table.search_into("empty", &mut []), 0);
+        assert_eq!(table.search_into("missing", &mut large), -1);
+    }
+
+    /// strings a JS caller can pass that stress the UTF-8 round trip; all well-formed UTF-16
+    const ADVERSARIAL: [&str; 12] = [
+        "\0",
+        "a\0b",
+        "\0\0\0",
+        "\u{1F600}",
+        "a\u{1F600}b\u{1F4A9}",
+        "\u{1D11E}\0\u{1D11E}",
+        "\u{10000}",
+        "\u{10FFFF}",
+        // the code points on either side of the surrogate range
+        "\u{D7FF}\u{E000}",
+        "\u{FFFD}",
+        "\u{FFFF}\u{10000}",
+        "\"\\\n\u{1F}",
+    ];
+
+    #[test]
+    fn test_js_adversarial_strings_round_trip() {
+        let mut options = vec!["", r#", "caseInsensitive": true"#, r#", "dedup": true"#];
+        if cfg!(feature = "compress") {
+            options.push(r#", "compressAbove": 8"#);
+        }
+        if cfg!(feature = "normalize") {
+            options.push(r#", "normalize": "nfc""#);
+        }
+        for extra in options {
+            let mut table = js_table(&format!(r#"{{"capacity": 64, "delta": 0.1, "seed": 1{}}}"#, extra));
+            let value = |key: &str| format!("{}|{}", key, key.repeat(20));
+            for key in ADVERSARIAL {
+                table.insert(key.into(), value(key)).unwrap();
+            }
+            for key in ADVERSARIAL {
+                assert_eq!(table.search(key.into()), Some(value(key)), "{:?} with{}", key, extra);
+                let mut buffer = vec![0u8; 512];
+                let len = table.search_into(key, &mut buffer);
+                assert_eq!(&buffer[..len as usize], value(key).as_bytes(), "{:?} with{}", key, extra);
+                assert!(table.get_detailed(key).contains(&json::quote(&value(key))));
+            }
+            let mut keys = table.keys();
+            keys.sort();
+            let mut expected: Vec<String> = ADVERSARIAL.iter().map(|k| k.to_string()).collect();
+            expected.sort();
+            assert_eq!(keys, expected, "with{}", extra);
+            let page = table.entries_page(0, 100, None).unwrap();
+            for key in ADVERSARIAL {
+                assert!(page.contains(&format!("[{},{}]", json::quote(key), json::quote(&value(key)))));
+            }
+
+            for key in ADVERSARIAL {
+                assert_eq!(table.remove(key.into()), Some(value(key)));
+            }
+            assert_eq!(table.table.num_inserts, 0);
+        }
+    }
+
+    #[test]
+    fn test_js_strict_unicode() {
+        let mut table = js_table(r#"{"capacity": 64, "delta": 0.1, "seed": 1, "strictUnicode": true}"#);
+        assert!(table.get_options().ends_with(r#""strictUnicode":true}"#));
+        for key in ADVERSARIAL.iter().filter(|k| !k.contains('\u{FFFD}')) {
+            table.insert(key.to_string(), key.to_string()).unwrap();
+            assert_eq!(table.search(key.to_string()).as_deref(), Some(*key));
+        }
+        // what "\uD800" arrives as
+        assert_eq!(table.insert("\u{FFFD}".into(), "v".into()).unwrap_err().code(), "ERR_LOSSY_STRING");
+        assert_eq!(table.insert("k".into(), "a\u{FFFD}".into()).unwrap_err().code(), "ERR_LOSSY_STRING");
+        assert_eq!(table.search("k".into()), None);
+    }
+
+    #[test]
+    fn test_js_full_table_throws() {
+        let mut table = js_table(r#"{"capacity": 8, "delta": 0.25, "seed": 1, "dedup": true}"#);
+        for i in 0..6 {
+            table.insert(i.to_string(), format!("v{}", i)).unwrap();
+        }
+        assert!(table.is_full());
+        let err = table.insert("new".into(), "fresh".into()).unwrap_err();
+        assert_eq!(err.code(), "ERR_TABLE_FULL");
+        assert!(err.message().contains("maximum allowed insertions"), "{}", err.message());
+        assert_eq!(table.insert_tagged("new".into(), "x".into(), 1.0).unwrap_err().code(), "ERR_TABLE_FULL");
+        let entry = table.entry("new".into()).unwrap();
+        assert_eq!(entry.insert(&mut table, "x".into()).unwrap_err().code(), "ERR_TABLE_FULL");
+        // the rejected values did not stay in the pool
+        assert_eq!(table.pool.as_ref().unwrap().distinct_values(), 6);
+
+        // still usable: reads, replacing present keys, and new keys once there is room again
+        assert_eq!(table.search("3".into()), Some("v3".into()));
+        assert_eq!(table.search("new".into()), None);
+        table.insert("3".into(), "w".into()).unwrap();
+        assert_eq!(table.remove("0".into()), Some("v0".into()));
+        table.insert("new".into(), "fresh".into()).unwrap();
+        assert_eq!(table.search("new".into()), Some("fresh".into()));
+        table.table.check_invariants().unwrap();
+
+        // a batch with more new keys than room inserts nothing
+        table.remove("1".into());
+        let mut batch = table.begin_batch();
+        for key in ["2", "a", "b"] {
+            batch.push(key, "batched").unwrap();
+        }
+        assert_eq!(batch.commit(&mut table).unwrap_err().code(), "ERR_TABLE_FULL");
+        assert_eq!(table.search("2".into()), Some("v2".into()));
+        assert_eq!(batch.size(), 3);
+    }
+
+    #[test]
+    fn test_js_constructors_throw_on_bad_arguments() {
+        for (capacity, delta) in [(0, 0.1), (8, 0.0), (8, 1.0), (8, f64::NAN)] {
+            let err = JsElasticHashTable::new(capacity, delta).err().expect("bad arguments accepted");
+            assert_eq!(err.code(), "ERR_INVALID_ARGUMENT");
+            assert!(crate::JsCounterTable::new(capacity, delta, None).is_err());
+            assert!(crate::JsElasticHashTableBytes::new(capacity, delta).is_err());
+        }
+        assert!(JsElasticHashTable::new(8, 0.1).is_ok());
+    }
+
+    #[test]
+    fn test_js_capacity_accessors() {
+        let mut table = js_table(r#"{"capacity": 64, "delta": 0.25, "seed": 1}"#);
+        assert_eq!((table.len(), table.capacity(), table.max_inserts()), (0, 64, 48));
+        assert!(table.is_empty());
+        while !table.is_full() {
+            let before = table.remaining_capacity();
+            table.insert(table.len().to_string(), "v".into()).unwrap();
+            assert_eq!(table.remaining_capacity(), before - 1);
+        }
+        assert_eq!(table.len(), 48);
+        assert_eq!(table.load_factor(), 0.75);
+        table.remove("3".into());
+        assert_eq!((table.len(), table.remaining_capacity()), (47, 1));
+        let occupied: Vec<usize> = table.table.level_stats().iter().map(|&(occupied, _)| occupied).collect();
+        let sizes = table.table.level_sizes();
+        let expected: Vec<String> = sizes.iter().zip(&occupied).map(|(s, o)| format!("[{},{}]", o, s)).collect();
+        assert_eq!(table.level_stats(), format!("[{}]", expected.join(",")));
+        assert_eq!(occupied.iter().sum::<usize>(), 47);
+    }
+
+    #[test]
+    fn test_js_resize() {
+        let mut table = js_table(r#"{"capacity": 64, "delta": 0.25, "seed": 1}"#);
+        while !table.is_full() {
+            table.insert(table.len().to_string(), "v".into()).unwrap();
+        }
+        table.resize(256).unwrap();
+        assert_eq!((table.capacity(), table.remaining_capacity()), (256, 192 - 48));
+        assert!(table.load_factor() < 0.25);
+        assert_eq!(table.search("47".into()), Some("v".into()));
+        let err = table.resize(60).unwrap_err();
+        assert_eq!(err.code(), "ERR_INVALID_ARGUMENT");
+        assert_eq!(err.message(), "60 slots cannot hold the table's 48 entries");
+        assert_eq!(table.resize(0).unwrap_err().code(), "ERR_INVALID_ARGUMENT");
+        table.resize(64).unwrap();
+        assert!(table.is_full());
+    }
+
+    #[test]
+    fn test_js_from_entries_checks_options() {
+        let table = JsElasticHashTable::from_entries(r#"{"capacity": 64, "delta": 0.25, "seed": 1}"#, vec![]).unwrap();
+        assert_eq!(table.len(), 0);
+        let err = JsElasticHashTable::from_entries(r#"{"capacity": 64, "delta": 2}"#, vec![]).err().unwrap();
+        assert_eq!((err.code().as_str(), err.index()), ("ERR_INVALID_ARGUMENT", None));
+    }
+
+    #[test]
+    fn test_js_insert_many_and_search_many() {
+        let mut table = js_table(r#"{"capacity": 64, "delta": 0.25, "seed": 1, "maxValueLength": 3}"#);
+        let keys: Vec<String> = (0..40).map(|i| format!("k{}", i)).collect();
+        let values: Vec<String> = (0..40).map(|i| i.to_string()).collect();
+        assert_eq!(table.insert_many(keys, values).unwrap(), 40);
+        let found = table.search_all(vec!["k3".into(), "nope".into(), "k39".into(), "k3".into()]);
+        assert_eq!(found, [Some("3".into()), None, Some("39".into()), Some("3".into())]);
+
+        let err = table.insert_many(vec!["a".into(), "b".into()], vec!["1".into()]).unwrap_err();
+        assert_eq!((err.code().as_str(), err.index()), ("ERR_INVALID_ARGUMENT", None));
+        assert_eq!(table.search("a".into()), None);
+
+        // the pair at index 1 breaks the rules: the one before it stays
+        let err = table.insert_many(vec!["a".into(), "b".into()], vec!["1".into(), "long".into()]).unwrap_err();
+        assert_eq!((err.code().as_str(), err.index()), ("ERR_INVALID_VALUE", Some(1)));
+        assert_eq!((table.search("a".into()), table.search("b".into())), (Some("1".into()), None));
+
+        // 7 slots left: the eighth new key fills the table
+        let more: Vec<String> = (0..10).map(|i| format!("m{}", i)).collect();
+        let err = table.insert_many(more.clone(), vec!["v".into(); 10]).unwrap_err();
+        assert_eq!((err.code().as_str(), err.index()), ("ERR_TABLE_FULL", Some(7)));
+        assert_eq!(table.search_all(more[6..8].to_vec()), [Some("v".into()), None]);
+        assert_eq!(table.len(), 48);
+    }
+
+    #[test]
+    fn test_js_append() {
+        let mut table = js_table(r#"{"capacity": 64, "delta": 0.25, "seed": 1, "maxValueLength": 4, "dedup": true}"#);
+        table.insert("a".into(), "x".into()).unwrap();
+        table.insert("b".into(), "x".into()).unwrap();
+        assert!(table.append("a".into(), "yz").unwrap());
+        assert_eq!((table.search("a".into()), table.search("b".into())), (Some("xyz".into()), Some("x".into())));
+        assert_eq!(table.distinct_value_count(), Some(2));
+        assert!(!table.append("missing".into(), "!").unwrap());
+        assert_eq!(table.append("a".into(), "12").unwrap_err().code(), "ERR_INVALID_VALUE");
+        assert_eq!((table.search("a".into()), table.len()), (Some("xyz".into()), 2));
+    }
+
+    #[test]
+    fn test_js_explain() {
+        let mut table = js_table(r#"{"capacity": 64, "delta": 0.1, "seed": 1, "caseInsensitive": true}"#);
+        table.insert("Key".into(), "v".into()).unwrap();
+        let before = table.table.probe_count();
+        let json = table.explain("KEY");
+        assert_eq!(json, table.table.explain(&table.rules.table_key("key")).to_json());
+        assert!(json.contains(r#""state":"match"}],"stop":"match"}],"found":{"level":"#));
+        assert!(table.explain("other").contains(r#""found":null"#));
+        assert_eq!(table.table.probe_count(), before);
+    }
+
+    #[test]
+    fn test_js_single_level() {
+        let mut table = js_table(r#"{"capacity": 64, "delta": 0.1, "seed": 1, "singleLevel": true}"#);
+        assert!(table.get_options().ends_with(r#""singleLevel":true}"#));
+        assert_eq!(JsElasticHashTable::with_options(&table.get_options()).unwrap().get_options(), table.get_options());
+        for i in 0..50 {
+            table.insert(i.to_string(), "v".into()).unwrap();
+        }
+        assert_eq!(table.remove("7".into()), Some("v".into()));
+        assert_eq!(table.search("8".into()), Some("v".into()));
+        assert_eq!(table.table.stats().levels, 1);
+        assert!(!js_table(r#"{"capacity": 64, "delta": 0.1, "seed": 1}"#).get_options().contains("singleLevel"));
+    }
+
+    #[test]
+    fn test_js_metadata() {
+        let mut plain = js_table(r#"{"capacity": 64, "delta": 0.1, "seed": 1}"#);
+        let mut tagged = js_table(r#"{"capacity": 64, "delta": 0.1, "seed": 1, "metadata": true}"#);
+        assert!(tagged.get_options().ends_with(r#""metadata":true}"#));
+        assert_eq!(JsElasticHashTable::with_options(&tagged.get_options()).unwrap().get_options(), tagged.get_options());
+        for table in [&mut plain, &mut tagged] {
+            table.insert_tagged("k".into(), "v".into(), 1_700_000_000_000.0).unwrap();
+            table.insert("plain".into(), "p".into()).unwrap();
+        }
+        assert_eq!(tagged.get_with_meta("k").as_deref(), Some(r#"{"value":"v","meta":1700000000000}"#));
+        assert_eq!(tagged.get_with_meta("plain").as_deref(), Some(r#"{"value":"p","meta":0}"#));
+        assert_eq!(tagged.get_with_meta("missing"), None);
+        assert_eq!(plain.get_with_meta("k"), None);
+
+        // with metadata off the slots are exactly as large as before; on, each carries one u64
+        let slot = std::mem::size_of::<Slot<JsKey, StoredValue>>();
+        assert_eq!(plain.memory_usage(), 64 * slot + "kvplainp".len());
+        assert_eq!(tagged.memory_usage(), plain.memory_usage() + 64 * 8);
+    }
+
+    #[test]
+    fn test_js_get_stats_fields() {
+        let mut table = js_table(r#"{"capacity": 64, "delta": 0.1, "seed": 1}"#);
+        table.insert("k".into(), "v".into()).unwrap();
+        let fields = json::flat_object(&table.get_stats()).unwrap();
+        let names: Vec<&str> = fields.iter().map(|(name, _)| name.as_str()).collect();
+        assert_eq!(names, STATS_FIELDS);
+        assert_eq!(table.get_stats(), table.table.stats_json());
+    }
+
+    #[test]
+    fn test_js_clear() {
+        let mut table = js_table(r#"{"capacity": 64, "delta": 0.25, "seed": 1, "dedup": true}"#);
+        for i in 0..table.max_inserts() {
+            table.insert(format!("k{}", i), (i % 3).to_string()).unwrap();
+        }
+        table.clear();
+        assert_eq!((table.len(), table.capacity(), table.distinct_value_count()), (0, 64, Some(0)));
+        assert_eq!(table.search("k1".into()), None);
+        for i in 0..table.max_inserts() {
+            table.insert(format!("n{}", i), "v".into()).unwrap();
+        }
+        assert!(table.is_full());
+        assert_eq!(table.distinct_value_count(), Some(1));
+    }
+
+    #[test]
+    fn test_js_probe_stats() {
+        let mut table = js_table(r#"{"capacity": 64, "delta": 0.1, "seed": 1}"#);
+        table.insert("k".into(), "v".into()).unwrap();
+        table.search("k".into());
+        assert_eq!(table.probe_stats(), table.table.probe_stats().to_json());
+        assert!(table.probe_stats().starts_with(r#"{"inserts":1,"searches":2,"levels":[{"insertProbes":1,"#));
+        table.reset_probe_stats();
+        assert!(table.probe_stats().starts_with(r#"{"inserts":0,"searches":0,"levels":[{"insertProbes":0,"#));
+        assert!(table.status_string().starts_with("Occupancies: [1, 0"));
+    }
+
+    #[test]
+    fn test_js_get_detailed() {
+        let mut table = js_table(r#"{"capacity": 64, "delta": 0.1, "seed": 1}"#);
+        table.insert("k".into(), "v\"1".into()).unwrap();
+        table.insert("empty".into(), String::new()).unwrap();
+        table.insert("gone".into(), "x".into()).unwrap();
+
+        for (key, value) in [("k", r#""v\"1""#), ("empty", r#""""#)] {
+            let (level, index) = table.table.find(&table.rules.table_key(key)).unwrap();
+            let found = format!(
+                r#"{{"found":true,"value":{},"slot":{{"level":{},"index":{}}},"probes":"#,
+                value, level, index
+            );
+            let detailed = table.get_detailed(key);
+            assert!(detailed.starts_with(&found), "{}", detailed);
+        }
+
+        let missing = table.get_detailed("missing");
+        assert!(missing.starts_with(r#"{"found":false,"probes":"#), "{}", missing);
+
+        // a removed key probes past its tombstone and is reported missing
+        table.remove("gone".into());
+        let gone = json::flat_object(&table.get_detailed("gone")).unwrap();
+        assert_eq!(gone[0], ("found".to_string(), "false".to_string()));
+        assert!(gone[1].1.parse::<usize>().unwrap() >= 1);
+    }
+
+    #[test]
+    fn test_js_insert_replaces_present_key() {
+        for extra in ["", r#", "dedup": true, "metadata": true"#] {
+            let mut table = js_table(&format!(r#"{{"capacity": 64, "delta": 0.1, "seed": 1{}}}"#, extra));
+            table.insert("a".into(), "1".into()).unwrap();
+            table.insert("a".into(), "2".into()).unwrap();
+            table.insert_tagged("a".into(), "3".into(), 7.0).unwrap();
+            assert_eq!(table.search("a".into()), Some("3".into()));
+            assert_eq!(table.keys(), ["a"]);
+            assert_eq!(table.table.num_inserts, 1);
+
+            let mut batch = table.begin_batch();
+            batch.push("a", "4").unwrap();
+            batch.push("b", "1").unwrap();
+            batch.push("b", "4").unwrap();
+            assert_eq!(batch.commit(&mut table).unwrap(), 3);
+            assert_eq!(table.search("a".into()), Some("4".into()));
+            assert_eq!(table.search("b".into()), Some("4".into()));
+            assert_eq!(table.table.num_inserts, 2);
+            // replaced values leave the pool: only "4" is still referenced
+            if table.pool.is_some() {
+                assert_eq!(table.distinct_value_count(), Some(1));
+            }
+        }
+    }
+
+    #[test]
+    fn test_js_dedup_values() {
+        let statuses: Vec<String> = (0..10).map(|i| format!("status-{:02}-{}", i, "x".repeat(40))).collect();
+        let options = r#"{"capacity": 131072, "delta": 0.1, "seed": 1}"#;
+        let mut plain = js_table(options);
+        let mut dedup = js_table(&options.replace('}', r#", "dedup": true}"#));
+        assert!(dedup.get_options().ends_with(r#""dedup":true}"#));
+        for i in 0..100_000 {
+            plain.insert(i.to_string(), statuses[i % 10].clone()).unwrap();
+            dedup.insert(i.to_string(), statuses[i % 10].clone()).unwrap();
+        }
+        assert_eq!(plain.distinct_value_count(), None);
+        assert_eq!(dedup.distinct_value_count(), Some(10));
+        assert_eq!(dedup.search("12345".into()), Some(statuses[5].clone()));
+
+        // the plain table holds 100k value copies, the deduplicated one 10 plus its pool
+        let value_bytes: usize = statuses.iter().map(String::len).sum();
+        let pool = dedup.pool.as_ref().unwrap().memory_usage();
+        assert!(pool < value_bytes + 1024);
+        assert_eq!(plain.memory_usage() - dedup.memory_usage(), value_bytes * 10_000 - pool);
+
+        // dropping every entry holding status 3 frees it from the pool
+        for i in (3..100_000).step_by(10) {
+            assert_eq!(dedup.remove(i.to_string()), Some(statuses[3].clone()));
+        }
+        assert_eq!(dedup.distinct_value_count(), Some(9));
+        dedup.insert("3".into(), statuses[3].clone()).unwrap();
+        assert_eq!(dedup.distinct_value_count(), Some(10));
+        assert_eq!(dedup.pop().map(|entry| entry.len()), Some(2));
+    }
+
+    /// a JSON document of roughly 3 KB, varying with i
+    fn json_blob(i: usize) -> String {
+        let items: Vec<String> = (0..40)
+            .map(|j| format!("{{\"id\":{},\"sku\":\"SKU-{:05}\",\"qty\":{},\"tags\":[\"new\",\"sale\"]}}", j, i * 40 + j, j % 7))
+            .collect();
+        format!("{{\"order\":{},\"customer\":\"user{}@example.com\",\"items\":[{}]}}", i, i, items.join(","))
+    }
+
+    #[cfg(feature = "compress")]
+    #[test]
+    fn test_js_compressed_values() {
+        let mut plain = js_table(r#"{"capacity": 64, "delta": 0.1, "seed": 1}"#);
+        let mut packed = js_table(r#"{"capacity": 64, "delta": 0.1, "seed": 1, "compressAbove": 256}"#);
+        assert!(packed.get_options().ends_with(r#""compressAbove":256}"#));
+        for i in 0..20 {
+            plain.insert(i.to_string(), json_blob(i)).unwrap();
+            packed.insert(i.to_string(), json_blob(i)).unwrap();
+        }
+        // empty, short and incompressible values are stored as given
+        let noise: String = (0..1000u32).map(|i| char::from(b'!' + (i.wrapping_mul(2_654_435_761) >> 25) as u8)).collect();
+        for (key, value) in [("empty", String::new()), ("short", "x".repeat(10)), ("noise", noise)] {
+            packed.insert(key.into(), value.clone()).unwrap();
+            assert_eq!(packed.search(key.into()), Some(value));
+        }
+
+        for i in 0..20 {
+            assert_eq!(packed.search(i.to_string()), Some(json_blob(i)));
+        }
+        assert!(packed.compression_ratio() > 2.0);
+        assert_eq!(plain.compression_ratio(), 1.0);
+        assert!(packed.memory_usage() < plain.memory_usage() / 2);
+
+        let update = packed.table.modify(&JsKey::Exact("3".into()), |v| {
+            *v = StoredValue::new(json_blob(4), Some(256));
+        });
+        assert_eq!(update, Some(()));
+        assert_eq!(packed.remove("3".into()), Some(json_blob(4)));
+    }
+
+    /// run with `cargo test --release --features compress -- --ignored --nocapture bench_compressed_values`
+    #[test]
+    #[ignore]
+    fn bench_compressed_values() {
+        let n = 2_000;
+        for options in [
+            r#"{"capacity": 4096, "delta": 0.1, "seed": 1}"#,
+            r#"{"capacity": 4096, "delta": 0.1, "seed": 1, "compressAbove": 512}"#,
+        ] {
+            let Ok(mut table) = JsElasticHashTable::with_options(options) else {
+                println!("{}: needs the compress feature", options);
+                continue;
+            };
+            for i in 0..n {
+                table.insert(i.to_string(), json_blob(i)).unwrap();
+            }
+            let start = std::time::Instant::now();
+            for i in 0..n {
+                assert!(table.search(i.to_string()).is_some());
+            }
+            println!(
+                "{}: {} KB, ratio {:.2}, {:?} per lookup",
+                options,
+                table.memory_usage() / 1024,
+                table.compression_ratio(),
+                start.elapsed() / n as u32
+            );
+        }
+    }
+
+    #[cfg(feature = "normalize")]
+    #[test]
+    fn test_js_nfc_keys() {
+        let mut table = js_table(r#"{"capacity": 64, "delta": 0.1, "seed": 1, "normalize": "nfc", "maxKeyLength": 5}"#);
+        assert!(table.get_options().ends_with(r#""maxKeyLength":5,"normalize":"nfc"}"#));
+
+        // the decomposed form is 6 bytes, but the limit applies to the stored 5-byte NFC key
+        table.insert("cafe\u{301}".into(), "1".into()).unwrap();
+        assert_eq!(table.search("caf\u{e9}".into()), Some("1".into()));
+        assert_eq!(table.search("cafe\u{301}".into()), Some("1".into()));
+        assert!(table.has("caf\u{e9}".into()));
+        assert_eq!(table.keys(), vec!["caf\u{e9}"]);
+
+        assert_eq!(table.remove("caf\u{e9}".into()), Some("1".into()));
+        assert!(!table.has("cafe\u{301}".into()));
+    }
+
+    #[test]
+    fn test_js_numbers_past_2_53() {
+        let mut table = js_table(r#"{"capacity": 64, "delta": 0.1, "seed": 1, "metadata": true}"#);
+        table.insert("k".into(), "v".into()).unwrap();
+        assert_eq!(table.hash_key("k"), table.table.hash_key(&table.rules.table_key("k")));
+
+        let err = table.insert_tagged("t".into(), "v".into(), 9_007_199_254_740_992.0).unwrap_err();
+        assert_eq!(err.code(), "ERR_INVALID_TAG");
+        assert_eq!(table.insert_tagged("t".into(), "v".into(), -1.0).unwrap_err().code(), "ERR_INVALID_TAG");
+        table.insert_tagged("t".into(), "v".into(), 9_007_199_254_740_991.0).unwrap();
+        assert_eq!(table.get_with_meta("t").as_deref(), Some(r#"{"value":"v","meta":9007199254740991}"#));
+
+        // 2^53 + 1 mutations: as an f64 it would round to 2^53 and match a stale generation
+        table.table.generation = (1 << 53) + 1;
+        assert!(table.get_stats().contains(r#""generation":"9007199254740993""#));
+        assert!(table.entries_page(0, 10, Some(9_007_199_254_740_992.0)).is_err());
+        let page = table.entries_page(0, 10, None).unwrap();
+        assert!(page.ends_with(r#""generation":"9007199254740993"}"#));
+    }
+
+    #[test]
+    fn test_js_entries_for_shard() {
+        let mut table = js_table(r#"{"capacity": 256, "delta": 0.1, "seed": 1}"#);
+        for i in 0..150 {
+            table.insert(format!("k{}", i), i.to_string()).unwrap();
+        }
+        for total in [1, 3, 4, 7] {
+            let mut keys = Vec::new();
+            for shard in 0..total {
+                keys.extend(table.shard(shard, total).into_iter().map(|(k, _)| k));
+            }
+            keys.sort();
+            let mut expected = table.keys();
+            expected.sort();
+            assert_eq!(keys, expected, "{} shards", total);
+        }
+
+        let mut sparse = js_table(r#"{"capacity": 64, "delta": 0.1, "seed": 1}"#);
+        sparse.insert("only".into(), "v".into()).unwrap();
+        let pages: Vec<String> = (0..8).map(|shard| sparse.entries_for_shard(shard, 8).unwrap()).collect();
+        assert_eq!(pages.iter().filter(|page| *page == "[]").count(), 7);
+        assert!(pages.contains(&r#"[["only","v"]]"#.to_string()));
+    }
+}
+
+#[cfg(all(test, target_arch = "wasm32"))]
+mod wasm_tests {
+    use super::*;
+    use wasm_bindgen_test::wasm_bindgen_test;
+
+    fn js_table(options: &str) -> JsElasticHashTable {
+        JsElasticHashTable::with_options(options).expect("invalid options")
+    }
+
+    fn pair(key: &str, value: &str) -> JsValue {
+        js_sys::Array::of2(&key.into(), &value.into()).into()
+    }
+
+    fn pair_of(key: u32, value: u32) -> JsValue {
+        js_sys::Array::of2(&key.into(), &value.into()).into()
+    }
+
+    fn code(result: Result<(), ValidationError>) -> Option<String> {
+        result.err().map(|err| err.code())
+    }
+
+    #[wasm_bindgen_test]
+    fn test_max_key_length_boundary() {
+        let mut table = js_table(r#"{"capacity": 64, "delta": 0.25, "seed": 1, "maxKeyLength": 4}"#);
+        assert_eq!(code(table.insert("".into(), "v".into())), None);
+        assert_eq!(code(table.insert("abcd".into(), "v".into())), None);
+        assert_eq!(code(table.insert("abcde".into(), "v".into())).as_deref(), Some("ERR_INVALID_KEY"));
+        // the limit is in UTF-8 bytes: two 2-byte characters fit, a third byte does not
+        assert_eq!(code(table.insert("\u{e9}\u{e9}".into(), "v".into())), None);
+        assert_eq!(code(table.insert("\u{e9}\u{e9}a".into(), "v".into())).as_deref(), Some("ERR_INVALID_KEY"));
+        assert!(!table.has("abcde".into()));
+        assert_eq!(table.len(), 3);
+    }
+
+    #[wasm_bindgen_test]
+    fn test_max_value_length_boundary() {
+        let mut table = js_table(r#"{"capacity": 64, "delta": 0.25, "seed": 1, "maxValueLength": 3}"#);
+        assert_eq!(code(table.insert("empty".into(), "".into())), None);
+        assert_eq!(code(table.insert("full".into(), "abc".into())), None);
+        assert_eq!(code(table.insert("over".into(), "abcd".into())).as_deref(), Some("ERR_INVALID_VALUE"));
+        assert_eq!(code(table.insert("full".into(), "abcd".into())).as_deref(), Some("ERR_INVALID_VALUE"));
+        assert_eq!(table.search("full".into()), Some("abc".into()));
+        assert!(!table.has("over".into()));
+    }
+
+    #[wasm_bindgen_test]
+    fn test_key_pattern() {
+        let mut table = js_table(r#"{"capacity": 64, "delta": 0.25, "seed": 1, "keyPattern": "a-z0-9_-"}"#);
+        assert_eq!(code(table.insert("a".into(), "v".into())), None);
+        assert_eq!(code(table.insert("user_42-z".into(), "v".into())), None);
+        assert_eq!(code(table.insert("User".into(), "v".into())).as_deref(), Some("ERR_INVALID_KEY"));
+        assert_eq!(code(table.insert("a b".into(), "v".into())).as_deref(), Some("ERR_INVALID_KEY"));
+        assert_eq!(code(table.insert("caf\u{e9}".into(), "v".into())).as_deref(), Some("ERR_INVALID_KEY"));
+        assert_eq!(table.len(), 2);
+    }
+
+    #[wasm_bindgen_test]
+    fn test_insert_many_reports_index() {
+        let mut table = js_table(r#"{"capacity": 64, "delta": 0.25, "seed": 1, "maxKeyLength": 2}"#);
+        let keys = vec!["a".into(), "ab".into(), "abc".into(), "b".into()];
+        let err = table.insert_many(keys, vec!["v".into(); 4]).unwrap_err();
+        assert_eq!((err.code().as_str(), err.index()), ("ERR_INVALID_KEY", Some(2)));
+        assert_eq!(table.len(), 2);
+    }
+
+    #[wasm_bindgen_test]
+    fn test_entries_is_an_array_of_pairs() {
+        let mut table = js_table(r#"{"capacity": 64, "delta": 0.25, "seed": 1}"#);
+        for i in 0..20 {
+            table.insert(format!("k{}", i), i.to_string()).unwrap();
+        }
+        table.remove("k3".into());
+        table.insert("k4".into(), "four".into()).unwrap();
+        let entries = table.entries();
+        assert!(js_sys::Array::is_array(&entries));
+        let mut seen: Vec<(String, String)> = entries
+            .iter()
+            .map(|entry| {
+                let pair = js_sys::Array::from(&entry);
+                (pair.get(0).as_string().unwrap(), pair.get(1).as_string().unwrap())
+            })
+            .collect();
+        let cursor_order: Vec<String> = seen.iter().map(|(k, _)| k.clone()).collect();
+        let mut cursor = table.entries_cursor();
+        let mut expected_order = Vec::new();
+        while let Some(entry) = table.next_entry(&mut cursor).unwrap() {
+            expected_order.push(entry[0].clone());
+        }
+        assert_eq!(cursor_order, expected_order);
+
+        seen.sort();
+        let mut expected: Vec<(String, String)> =
+            (0..20).filter(|&i| i != 3).map(|i| (format!("k{}", i), i.to_string())).collect();
+        expected[3].1 = "four".into();
+        expected.sort();
+        assert_eq!(seen, expected);
+        // the array is a copy
+        table.clear();
+        assert_eq!(entries.length(), 19);
+    }
+
+    #[wasm_bindgen_test]
+    fn test_from_entries() {
+        let options = r#"{"capacity": 64, "delta": 0.25, "seed": 1, "maxKeyLength": 3, "maxValueLength": 3}"#;
+        let table = JsElasticHashTable::from_entries(options, vec![pair("a", "1"), pair("abc", "123")]).unwrap();
+        assert_eq!((table.search("a".into()), table.search("abc".into())), (Some("1".into()), Some("123".into())));
+
+        let entries = vec![pair("a", "1"), pair("b", "2"), pair("abcd", "3")];
+        let err = JsElasticHashTable::from_entries(options, entries).err().unwrap();
+        assert_eq!((err.code().as_str(), err.index()), ("ERR_INVALID_KEY", Some(2)));
+        let err = JsElasticHashTable::from_entries(options, vec![pair("a", "1"), pair("b", "1234")]).err().unwrap();
+        assert_eq!((err.code().as_str(), err.index()), ("ERR_INVALID_VALUE", Some(1)));
+
+        // a string, a one-element array and a pair of numbers are not entries
+        let not_pairs = [JsValue::from_str("a"), js_sys::Array::of1(&"a".into()).into(), pair_of(1, 2)];
+        for entry in not_pairs {
+            let err = JsElasticHashTable::from_entries(options, vec![pair("x", "1"), entry]).err().unwrap();
+            assert_eq!((err.code().as_str(), err.index()), ("ERR_INVALID_ARGUMENT", Some(1)));
+        }
+    }
+}
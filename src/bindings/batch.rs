@@ -0,0 +1,223 @@
+//! JsInsertBatch: gather many inserts on the Rust side, then insert them with one call
+//!
+//! ```ignore
+//! const batch = table.beginBatch();
+//! for (const [key, value] of rows) batch.push(key, value);
+//! const inserted = batch.commit(table);
+//! ```
+//!
+//! Pushed keys and values are copied end to end into one text buffer. The table lends its buffers
+//! to beginBatch and gets them back, cleared, from commit, so a writer sending batch after batch
+//! of similar size reuses the same memory instead of allocating per batch. As with JsEntry, a
+//! batch cannot borrow its table across the wasm boundary, so commit takes the table as an argument.
+
+use wasm_bindgen::prelude::*;
+
+use std::collections::HashSet;
+
+use super::validate::{JsKey, Violation};
+use super::{JsElasticHashTable, ValidationError};
+
+/// text of the pushed entries, laid end to end
+#[derive(Debug, Default)]
+pub(crate) struct BatchBuffers {
+    text: String,
+    /// (key start, value start, value end) in text, one per entry
+    spans: Vec<(usize, usize, usize)>,
+}
+
+impl BatchBuffers {
+    fn push(&mut self, key: &str, value: &str) {
+        let start = self.text.len();
+        self.text.push_str(key);
+        let mid = self.text.len();
+        self.text.push_str(value);
+        self.spans.push((start, mid, self.text.len()));
+    }
+
+    fn entries(&self) -> impl Iterator<Item = (&str, &str)> + '_ {
+        self.spans.iter().map(|&(start, mid, end)| (&self.text[start..mid], &self.text[mid..end]))
+    }
+
+    /// empty, keeping the capacity
+    fn clear(&mut self) {
+        self.text.clear();
+        self.spans.clear();
+    }
+}
+
+/// inserts gathered by push and applied by commit; open until committed or aborted
+#[wasm_bindgen]
+pub struct JsInsertBatch {
+    table_id: u64,
+    /// None once committed or aborted
+    buffers: Option<BatchBuffers>,
+}
+
+#[wasm_bindgen]
+impl JsElasticHashTable {
+    /// start a JsInsertBatch for this table
+    #[wasm_bindgen(js_name = beginBatch)]
+    pub fn begin_batch(&mut self) -> JsInsertBatch {
+        JsInsertBatch {
+            table_id: self.id,
+            buffers: Some(std::mem::take(&mut self.batch_buffers)),
+        }
+    }
+}
+
+impl JsInsertBatch {
+    fn open(&mut self) -> Result<&mut BatchBuffers, ValidationError> {
+        self.buffers.as_mut().ok_or_else(|| ValidationError::new(&Violation::BatchClosed, None))
+    }
+}
+
+#[wasm_bindgen]
+impl JsInsertBatch {
+    /// add an entry; nothing reaches the table before commit. throws ERR_BATCH_CLOSED
+    #[wasm_bindgen]
+    pub fn push(&mut self, key: &str, value: &str) -> Result<(), ValidationError> {
+        self.open()?.push(key, value);
+        Ok(())
+    }
+
+    /// number of entries pushed so far, 0 once closed
+    #[wasm_bindgen(getter)]
+    pub fn size(&self) -> usize {
+        self.buffers.as_ref().map_or(0, |buffers| buffers.spans.len())
+    }
+
+    /// insert every pushed entry into table, in push order, and close the batch; returns the count
+    /// an entry whose key is present, or was pushed earlier, replaces its value like insert
+    /// the entries are checked against the input rules first: if one breaks them, nothing is inserted,
+    /// the batch stays open, and the ValidationError's index is the entry's position in the batch.
+    /// likewise ERR_TABLE_FULL, with no index, when the table has no room for the batch's new keys.
+    /// throws ERR_BATCH_CLOSED, or ERR_WRONG_TABLE when table is not the one that began the batch
+    #[wasm_bindgen]
+    pub fn commit(&mut self, table: &mut JsElasticHashTable) -> Result<usize, ValidationError> {
+        if table.id != self.table_id {
+            self.open()?;
+            return Err(ValidationError::new(&Violation::WrongTable, None));
+        }
+        let buffers = self.open()?;
+        let JsElasticHashTable { table, rules, pool, batch_buffers, .. } = table;
+        let mut keys = Vec::with_capacity(buffers.spans.len());
+        for (index, (key, value)) in buffers.entries().enumerate() {
+            let key = rules.table_key(key);
+            rules.check(key.as_str(), value).map_err(|v| ValidationError::new(&v, Some(index as u32)))?;
+            keys.push(key);
+        }
+        let fresh: HashSet<&JsKey> = keys.iter().filter(|key| table.find(*key).is_none()).collect();
+        let room = table.remaining_capacity();
+        if fresh.len() > room {
+            let reason = format!("the batch has {} new keys but the table has room for {}", fresh.len(), room);
+            return Err(ValidationError::new(&Violation::TableFull(reason), None));
+        }
+        // one at a time rather than through insert_batch, so values replaced by a present key leave the pool
+        for (index, (key, (_, value))) in keys.into_iter().zip(buffers.entries()).enumerate() {
+            let value = JsElasticHashTable::store_value(rules, pool, value.to_string());
+            match table.insert_or_update(key, value) {
+                Ok(old) => JsElasticHashTable::drop_replaced(pool, old),
+                Err(err) => return Err(JsElasticHashTable::table_full(pool, err, Some(index as u32))),
+            }
+        }
+        let count = buffers.spans.len();
+
+        let mut buffers = self.buffers.take().expect("batch is open");
+        buffers.clear();
+        *batch_buffers = buffers;
+        Ok(count)
+    }
+
+    /// drop the pushed entries and close the batch; throws ERR_BATCH_CLOSED
+    #[wasm_bindgen]
+    pub fn abort(&mut self) -> Result<(), ValidationError> {
+        self.open()?;
+        self.buffers = None;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::JsElasticHashTable;
+
+    fn js_table(options: &str) -> JsElasticHashTable {
+        JsElasticHashTable::with_options(options).expect("invalid options")
+    }
+
+    #[test]
+    fn test_batch_contents_land() {
+        let mut table = js_table(r#"{"capacity": 64, "delta": 0.1, "seed": 1, "caseInsensitive": true}"#);
+        let mut batch = table.begin_batch();
+        for i in 0..20 {
+            batch.push(&format!("Key{}", i), &i.to_string()).unwrap();
+        }
+        assert_eq!(batch.size(), 20);
+        assert_eq!(table.search("key3".into()), None);
+        assert_eq!(batch.commit(&mut table).unwrap(), 20);
+        for i in 0..20 {
+            assert_eq!(table.search(format!("KEY{}", i)), Some(i.to_string()));
+        }
+        assert_eq!(batch.size(), 0);
+        assert_eq!(table.begin_batch().commit(&mut table).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_closed_batch_throws() {
+        let mut table = js_table(r#"{"capacity": 64, "delta": 0.1, "seed": 1}"#);
+        let mut batch = table.begin_batch();
+        batch.push("a", "1").unwrap();
+        batch.commit(&mut table).unwrap();
+        assert_eq!(batch.push("b", "2").unwrap_err().code(), "ERR_BATCH_CLOSED");
+        assert_eq!(batch.commit(&mut table).unwrap_err().code(), "ERR_BATCH_CLOSED");
+        assert_eq!(batch.abort().unwrap_err().code(), "ERR_BATCH_CLOSED");
+
+        let mut batch = table.begin_batch();
+        batch.push("b", "2").unwrap();
+        batch.abort().unwrap();
+        assert_eq!(batch.commit(&mut table).unwrap_err().code(), "ERR_BATCH_CLOSED");
+        assert_eq!(table.search("b".into()), None);
+        assert_eq!(table.keys(), ["a"]);
+
+        let mut other = js_table(r#"{"capacity": 64, "delta": 0.1, "seed": 1}"#);
+        let mut batch = table.begin_batch();
+        batch.push("c", "3").unwrap();
+        assert_eq!(batch.commit(&mut other).unwrap_err().code(), "ERR_WRONG_TABLE");
+        assert_eq!(batch.commit(&mut table).unwrap(), 1);
+    }
+
+    #[test]
+    fn test_invalid_entry_inserts_nothing() {
+        let mut table = js_table(r#"{"capacity": 64, "delta": 0.1, "seed": 1, "maxValueLength": 3}"#);
+        let mut batch = table.begin_batch();
+        batch.push("a", "1").unwrap();
+        batch.push("b", "toolong").unwrap();
+        let err = batch.commit(&mut table).unwrap_err();
+        assert_eq!((err.code().as_str(), err.index()), ("ERR_INVALID_VALUE", Some(1)));
+        assert_eq!(table.search("a".into()), None);
+        assert_eq!(batch.size(), 2);
+        batch.abort().unwrap();
+    }
+
+    #[test]
+    fn test_buffers_are_reused_across_batches() {
+        let mut table = js_table(r#"{"capacity": 40000, "delta": 0.1, "seed": 1}"#);
+        let mut capacities = None;
+        for round in 0..50 {
+            let mut batch = table.begin_batch();
+            for i in 0..500 {
+                batch
+                    .push(&format!("k{:05}", round * 500 + i), "value")
+                    .unwrap();
+            }
+            assert_eq!(batch.commit(&mut table).unwrap(), 500);
+            let buffers = &table.batch_buffers;
+            assert!(buffers.text.is_empty() && buffers.spans.is_empty());
+            let now = (buffers.text.capacity(), buffers.spans.capacity());
+            assert_eq!(*capacities.get_or_insert(now), now, "round {}", round);
+        }
+        assert_eq!(table.table.num_inserts, 25000);
+        assert_eq!(table.search("k24999".into()), Some("value".into()));
+    }
+}
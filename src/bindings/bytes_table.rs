@@ -0,0 +1,185 @@
+//! JsElasticHashTableBytes: keys and values as Uint8Arrays, stored byte for byte
+//!
+//! The string tables receive JS strings as UTF-8, which is lossless for any well-formed string,
+//! NUL and astral characters included, but a JS string may also hold unpaired surrogates: those
+//! become U+FFFD on the way in, so "\uD800" and "\uDC00" would be the same key. Keys that are not
+//! valid UTF-16 belong in this table, encoded by the caller, e.g. as UTF-16 code units:
+//!
+//! ```ignore
+//! const units = (s) => new Uint8Array(Uint16Array.from({ length: s.length }, (_, i) => s.charCodeAt(i)).buffer);
+//! table.insert(units(key), units(value));
+//! ```
+
+use wasm_bindgen::prelude::*;
+
+use super::validate::Violation;
+use super::ValidationError;
+use crate::ElasticHashTable;
+
+/// ElasticHashTable<Vec<u8>, Vec<u8>> for JS
+#[wasm_bindgen]
+pub struct JsElasticHashTableBytes {
+    table: ElasticHashTable<Vec<u8>, Vec<u8>>,
+}
+
+#[wasm_bindgen]
+impl JsElasticHashTableBytes {
+    /// throws ERR_INVALID_ARGUMENT unless capacity is positive and delta between 0 and 1
+    #[wasm_bindgen(constructor)]
+    pub fn new(capacity: usize, delta: f64) -> Result<JsElasticHashTableBytes, ValidationError> {
+        ValidationError::check_dimensions(capacity, delta)?;
+        Ok(JsElasticHashTableBytes {
+            table: ElasticHashTable::new(capacity, delta),
+        })
+    }
+
+    /// insert, or replace the value if key is present; throws ERR_TABLE_FULL when key is new and there is no room
+    #[wasm_bindgen]
+    pub fn insert(&mut self, key: &[u8], value: &[u8]) -> Result<(), ValidationError> {
+        self.table
+            .insert(key.to_vec(), value.to_vec())
+            .map_err(|err| ValidationError::new(&Violation::from(err.error()), None))?;
+        Ok(())
+    }
+
+    #[wasm_bindgen]
+    pub fn search(&self, key: &[u8]) -> Option<Vec<u8>> {
+        self.table.get(key).cloned()
+    }
+
+    #[wasm_bindgen]
+    pub fn has(&self, key: &[u8]) -> bool {
+        self.table.get(key).is_some()
+    }
+
+    /// remove key, returning its value
+    #[wasm_bindgen]
+    pub fn remove(&mut self, key: &[u8]) -> Option<Vec<u8>> {
+        self.table.remove(key)
+    }
+
+    /// number of entries
+    #[wasm_bindgen(getter)]
+    pub fn size(&self) -> usize {
+        self.table.num_inserts
+    }
+
+    /// number of entries, as on JsElasticHashTable
+    #[wasm_bindgen(getter)]
+    pub fn len(&self) -> usize {
+        self.table.len()
+    }
+
+    #[wasm_bindgen(getter, js_name = isEmpty)]
+    pub fn is_empty(&self) -> bool {
+        self.table.is_empty()
+    }
+
+    /// total number of slots
+    #[wasm_bindgen(getter)]
+    pub fn capacity(&self) -> usize {
+        self.table.capacity()
+    }
+
+    /// entries the table accepts before insert throws
+    #[wasm_bindgen(getter, js_name = maxInserts)]
+    pub fn max_inserts(&self) -> usize {
+        self.table.max_inserts()
+    }
+
+    /// new keys the table still accepts
+    #[wasm_bindgen(getter, js_name = remainingCapacity)]
+    pub fn remaining_capacity(&self) -> usize {
+        self.table.remaining_capacity()
+    }
+
+    /// whether inserting a new key would throw
+    #[wasm_bindgen(getter, js_name = isFull)]
+    pub fn is_full(&self) -> bool {
+        self.table.is_full()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::JsElasticHashTableBytes;
+
+    /// UTF-16 code units, little-endian, as the doc example encodes them
+    fn units(s: &[u16]) -> Vec<u8> {
+        s.iter().flat_map(|u| u.to_le_bytes()).collect()
+    }
+
+    #[test]
+    fn test_unpaired_surrogates_stay_distinct() {
+        let mut table = JsElasticHashTableBytes::new(64, 0.1).unwrap();
+        let keys = [
+            units(&[0xD800]),
+            units(&[0xDC00]),
+            units(&[0xFFFD]),
+            units(&[0x61, 0xD83D]),
+            units(&[0xD83D, 0xDE00]),
+            units(&[0x0000]),
+            vec![],
+        ];
+        for (i, key) in keys.iter().enumerate() {
+            table.insert(key, &[i as u8, 0, 0xff]).unwrap();
+        }
+        assert_eq!(table.size(), keys.len());
+        for (i, key) in keys.iter().enumerate() {
+            assert_eq!(table.search(key), Some(vec![i as u8, 0, 0xff]));
+        }
+        assert!(!table.has(&units(&[0xDBFF])));
+        assert_eq!(table.remove(&units(&[0xD800])), Some(vec![0, 0, 0xff]));
+        assert_eq!(table.search(&units(&[0xDC00])), Some(vec![1, 0, 0xff]));
+    }
+
+    #[test]
+    fn test_random_binary_keys() {
+        let mut table = JsElasticHashTableBytes::new(8192, 0.1).unwrap();
+        let mut oracle = HashMap::new();
+        let mut state = 0x9e37_79b9_7f4a_7c15u64;
+        let mut next = move || {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state
+        };
+        while oracle.len() < 3000 {
+            let len = (next() % 40) as usize;
+            let key: Vec<u8> = (0..len).map(|_| next() as u8).collect();
+            let value = next().to_le_bytes().to_vec();
+            table.insert(&key, &value).unwrap();
+            oracle.insert(key, value);
+        }
+        assert_eq!((table.len(), table.size()), (oracle.len(), oracle.len()));
+        assert_eq!(table.remaining_capacity(), table.max_inserts() - oracle.len());
+        for (key, value) in &oracle {
+            assert_eq!(table.search(key).as_ref(), Some(value));
+        }
+        // longer than any inserted key
+        assert!(!table.has(&[0xab; 40]));
+    }
+
+    #[test]
+    fn test_empty_key_and_value() {
+        let mut table = JsElasticHashTableBytes::new(32, 0.1).unwrap();
+        assert!(table.is_empty());
+        assert_eq!(table.search(&[]), None);
+        table.insert(&[], &[]).unwrap();
+        assert_eq!(table.search(&[]), Some(vec![]));
+        assert!(table.has(&[]) && !table.has(&[0]));
+        table.insert(&[0], &[]).unwrap();
+        table.insert(&[], &[7]).unwrap();
+        assert_eq!((table.len(), table.search(&[])), (2, Some(vec![7])));
+        assert_eq!(table.remove(&[]), Some(vec![7]));
+        assert_eq!(table.search(&[0]), Some(vec![]));
+
+        while !table.is_full() {
+            table.insert(&table.len().to_le_bytes(), &[]).unwrap();
+        }
+        assert_eq!(table.insert(&[], &[]).unwrap_err().code(), "ERR_TABLE_FULL");
+        assert_eq!(table.capacity(), 32);
+    }
+}
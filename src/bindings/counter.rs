@@ -0,0 +1,186 @@
+//! JsCounterTable: an ElasticHashTable<String, u32> of counts for JS, so counting code gets numbers
+//! back instead of formatting and parsing String values on every call
+
+use wasm_bindgen::prelude::*;
+
+use super::validate::Violation;
+use super::ValidationError;
+use crate::raw_entry::RawEntry;
+use crate::{hasher, json, ElasticHashTable};
+
+/// counts by key; a key that was never counted, or was counted down to 0 and removed, reads as 0
+#[wasm_bindgen]
+pub struct JsCounterTable {
+    table: ElasticHashTable<String, u32>,
+    /// drop an entry when decrement brings it to 0
+    remove_at_zero: bool,
+}
+
+impl JsCounterTable {
+    fn with_seed(capacity: usize, delta: f64, remove_at_zero: bool, seed: u64) -> Self {
+        JsCounterTable {
+            table: ElasticHashTable::with_seed(capacity, delta, seed),
+            remove_at_zero,
+        }
+    }
+
+    /// the n highest counts, highest first; equal counts in key order
+    fn top(&self, n: usize) -> Vec<(&str, u32)> {
+        let mut counts: Vec<(&str, u32)> = self.table.iter().map(|(k, &c)| (k.as_str(), c)).collect();
+        let order = |a: &(&str, u32), b: &(&str, u32)| b.1.cmp(&a.1).then(a.0.cmp(b.0));
+        if n < counts.len() {
+            counts.select_nth_unstable_by(n, order);
+            counts.truncate(n);
+        }
+        counts.sort_unstable_by(order);
+        counts
+    }
+}
+
+#[wasm_bindgen]
+impl JsCounterTable {
+    /// removeAtZero, true when omitted, drops keys that decrement brings to 0
+    /// throws ERR_INVALID_ARGUMENT unless capacity is positive and delta between 0 and 1
+    #[wasm_bindgen(constructor)]
+    pub fn new(capacity: usize, delta: f64, remove_at_zero: Option<bool>) -> Result<JsCounterTable, ValidationError> {
+        ValidationError::check_dimensions(capacity, delta)?;
+        Ok(Self::with_seed(capacity, delta, remove_at_zero.unwrap_or(true), hasher::random_seed()))
+    }
+
+    /// add 1 to key's count and return the new count; counts stop at 2^32 - 1
+    /// the key is only copied into the table the first time it is counted;
+    /// throws ERR_TABLE_FULL when the key is new and there is no room
+    #[wasm_bindgen]
+    pub fn increment(&mut self, key: &str) -> Result<u32, ValidationError> {
+        match self.table.raw_entry().from_key(key) {
+            RawEntry::Occupied(mut entry) => {
+                let count = entry.get_mut();
+                *count = count.saturating_add(1);
+                Ok(*count)
+            }
+            RawEntry::Vacant(entry) => match entry.insert_with_owned_key(key.to_string(), 1) {
+                Ok(count) => Ok(*count),
+                Err(error) => Err(ValidationError::new(&Violation::from(&error), None)),
+            },
+        }
+    }
+
+    /// take 1 from key's count and return the new count; an absent key stays absent and returns 0
+    #[wasm_bindgen]
+    pub fn decrement(&mut self, key: &str) -> u32 {
+        let RawEntry::Occupied(mut entry) = self.table.raw_entry().from_key(key) else {
+            return 0;
+        };
+        let count = entry.get_mut();
+        *count = count.saturating_sub(1);
+        let count = *count;
+        if count == 0 && self.remove_at_zero {
+            entry.remove_entry();
+        }
+        count
+    }
+
+    /// key's count, 0 when absent
+    #[wasm_bindgen(js_name = getCount)]
+    pub fn get_count(&self, key: &str) -> u32 {
+        self.table.get(key).copied().unwrap_or(0)
+    }
+
+    /// the n highest counts as JSON, `[["key", count], ...]`, highest first and equal counts in key order
+    #[wasm_bindgen(js_name = topN)]
+    pub fn top_n(&self, n: usize) -> String {
+        let items: Vec<String> =
+            self.top(n).into_iter().map(|(k, c)| format!("[{},{}]", json::quote(k), c)).collect();
+        format!("[{}]", items.join(","))
+    }
+
+    /// number of keys
+    #[wasm_bindgen(getter)]
+    pub fn size(&self) -> usize {
+        self.table.num_inserts
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::JsCounterTable;
+
+    /// count up and down a generated event stream, checking every return against a HashMap
+    fn tally(remove_at_zero: bool) -> (JsCounterTable, HashMap<String, u32>) {
+        let mut counter = JsCounterTable::with_seed(1024, 0.1, remove_at_zero, 9);
+        let mut oracle: HashMap<String, u32> = HashMap::new();
+        let mut state = 9u64;
+        for _ in 0..20_000 {
+            state = state.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+            let key = format!("event{}", (state >> 33) % 300);
+            if (state >> 20) & 1 == 0 {
+                let expected = match oracle.get_mut(&key) {
+                    Some(count) => {
+                        *count = count.saturating_sub(1);
+                        let count = *count;
+                        if count == 0 && remove_at_zero {
+                            oracle.remove(&key);
+                        }
+                        count
+                    }
+                    None => 0,
+                };
+                assert_eq!(counter.decrement(&key), expected, "{}", key);
+            } else {
+                let count = oracle.entry(key.clone()).or_insert(0);
+                *count += 1;
+                assert_eq!(counter.increment(&key).unwrap(), *count, "{}", key);
+            }
+        }
+        (counter, oracle)
+    }
+
+    #[test]
+    fn test_counts_match_oracle() {
+        for remove_at_zero in [true, false] {
+            let (counter, oracle) = tally(remove_at_zero);
+            assert_eq!(counter.size(), oracle.len());
+            for i in 0..310 {
+                let key = format!("event{}", i);
+                assert_eq!(counter.get_count(&key), oracle.get(&key).copied().unwrap_or(0), "{}", key);
+            }
+            if remove_at_zero {
+                assert!(oracle.values().all(|&c| c > 0));
+            } else {
+                assert!(oracle.values().any(|&c| c == 0));
+            }
+            counter.table.check_invariants().unwrap();
+        }
+    }
+
+    #[test]
+    fn test_top_n() {
+        let (counter, oracle) = tally(true);
+        let mut expected: Vec<(&str, u32)> = oracle.iter().map(|(k, &c)| (k.as_str(), c)).collect();
+        expected.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)));
+        for n in [0, 1, 10, oracle.len(), oracle.len() + 5] {
+            assert_eq!(counter.top(n), expected[..n.min(expected.len())]);
+        }
+
+        let mut counter = JsCounterTable::with_seed(64, 0.1, true, 1);
+        for key in ["b", "a\"", "b", "c", "a\"", "b"] {
+            counter.increment(key).unwrap();
+        }
+        assert_eq!(counter.top_n(2), r#"[["b",3],["a\"",2]]"#);
+        assert_eq!(counter.top_n(0), "[]");
+    }
+
+    #[test]
+    fn test_remove_at_zero_off_keeps_zero_counts() {
+        let mut counter = JsCounterTable::with_seed(64, 0.1, false, 1);
+        assert_eq!(counter.increment("a").unwrap(), 1);
+        assert_eq!(counter.decrement("a"), 0);
+        assert_eq!(counter.decrement("a"), 0);
+        assert_eq!(counter.size(), 1);
+        assert_eq!(counter.top_n(5), r#"[["a",0]]"#);
+        assert_eq!(counter.decrement("missing"), 0);
+        assert_eq!(counter.size(), 1);
+    }
+}
@@ -0,0 +1,196 @@
+//! JsEntry: look a key up once, decide in JS, then insert or delete without probing again
+//!
+//! ```ignore
+//! const entry = table.entry("user:7");
+//! if (!entry.exists() && confirm("create user 7?")) {
+//!     entry.insert(table, "{}");   // goes to the slot the lookup found
+//! }
+//! ```
+//!
+//! A handle cannot borrow its table across the wasm boundary, so insert and delete take the table
+//! back as an argument and the handle pins the table's generation instead: once anything else has
+//! changed the table, or when given another table, they throw ERR_STALE_ENTRY.
+
+use wasm_bindgen::prelude::*;
+
+use super::validate::{JsKey, Violation};
+use super::{JsElasticHashTable, ValidationError};
+use crate::elastic::table::FreeSlot;
+use crate::ElasticHashError;
+
+/// what the lookup found
+enum Found {
+    /// the key's slot and its value at the time
+    Occupied { level: usize, idx: usize, value: String },
+    /// the slot insert will claim, or why the table has none
+    Vacant(Result<FreeSlot, ElasticHashError>),
+}
+
+/// a key looked up by JsElasticHashTable::entry; acts on its table at most once
+#[wasm_bindgen]
+pub struct JsEntry {
+    table_id: u64,
+    generation: u64,
+    key: JsKey,
+    found: Found,
+}
+
+#[wasm_bindgen]
+impl JsElasticHashTable {
+    /// look key up once, see JsEntry; throws ERR_INVALID_KEY if key breaks the input rules
+    #[wasm_bindgen]
+    pub fn entry(&self, key: String) -> Result<JsEntry, ValidationError> {
+        let key = self.rules.table_key(&key);
+        self.rules.check_key(key.as_str()).map_err(|v| ValidationError::new(&v, None))?;
+        let found = match self.table.find(&key) {
+            Some((level, idx)) => Found::Occupied {
+                level,
+                idx,
+                value: self.table.value_at(Some((level, idx))).expect("found slot is occupied").get().into_owned(),
+            },
+            None => Found::Vacant(self.table.locate_free(&key)),
+        };
+        Ok(JsEntry {
+            table_id: self.id,
+            generation: self.table.generation(),
+            key,
+            found,
+        })
+    }
+}
+
+impl JsEntry {
+    fn check(&self, table: &JsElasticHashTable) -> Result<(), ValidationError> {
+        if table.id != self.table_id || table.table.generation() != self.generation {
+            return Err(ValidationError::new(&Violation::StaleEntry, None));
+        }
+        Ok(())
+    }
+}
+
+#[wasm_bindgen]
+impl JsEntry {
+    /// whether the key was in the table at the lookup
+    #[wasm_bindgen]
+    pub fn exists(&self) -> bool {
+        matches!(self.found, Found::Occupied { .. })
+    }
+
+    /// the key's value at the lookup, or undefined
+    #[wasm_bindgen]
+    pub fn get(&self) -> Option<String> {
+        match &self.found {
+            Found::Occupied { value, .. } => Some(value.clone()),
+            Found::Vacant(_) => None,
+        }
+    }
+
+    /// store value under the key: into the free slot the lookup picked, or over the existing value
+    /// throws ERR_STALE_ENTRY, ERR_INVALID_VALUE if value breaks the input rules,
+    /// or ERR_TABLE_FULL if the key was absent and the table had no room
+    #[wasm_bindgen]
+    pub fn insert(&self, table: &mut JsElasticHashTable, value: String) -> Result<(), ValidationError> {
+        self.check(table)?;
+        if let Found::Vacant(Err(error)) = &self.found {
+            return Err(ValidationError::new(&Violation::from(error), None));
+        }
+        let JsElasticHashTable { table, rules, pool, .. } = table;
+        rules.check_value(&value).map_err(|v| ValidationError::new(&v, None))?;
+        let value = JsElasticHashTable::store_value(rules, pool, value);
+        match &self.found {
+            Found::Occupied { level, idx, .. } => {
+                if let (Some(old), Some(pool)) = (table.replace_slot(*level, *idx, value), pool) {
+                    pool.release(&old);
+                }
+            }
+            Found::Vacant(free) => {
+                let free = free.clone().expect("checked above");
+                table.place(free, self.key.clone(), value);
+            }
+        }
+        Ok(())
+    }
+
+    /// remove the key, returning its value, or undefined when it was absent; throws ERR_STALE_ENTRY
+    #[wasm_bindgen]
+    pub fn delete(&self, table: &mut JsElasticHashTable) -> Result<Option<String>, ValidationError> {
+        self.check(table)?;
+        match self.found {
+            Found::Occupied { level, idx, .. } => {
+                let (_, value) = table.table.take_slot(level, idx);
+                Ok(Some(table.release_value(value)))
+            }
+            Found::Vacant(_) => Ok(None),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::JsElasticHashTable;
+
+    fn js_table() -> JsElasticHashTable {
+        JsElasticHashTable::with_options(r#"{"capacity": 64, "delta": 0.1, "seed": 1}"#).expect("invalid options")
+    }
+
+    #[test]
+    fn test_entry_insert_and_delete() {
+        let mut table = js_table();
+        let entry = table.entry("a".into()).unwrap();
+        assert!(!entry.exists());
+        assert_eq!(entry.get(), None);
+        entry.insert(&mut table, "1".into()).unwrap();
+        assert_eq!(table.search("a".into()), Some("1".into()));
+
+        let entry = table.entry("a".into()).unwrap();
+        assert!(entry.exists());
+        assert_eq!(entry.get(), Some("1".into()));
+        entry.insert(&mut table, "2".into()).unwrap();
+        assert_eq!(table.search("a".into()), Some("2".into()));
+        assert_eq!(table.keys(), ["a"]);
+
+        let entry = table.entry("a".into()).unwrap();
+        assert_eq!(entry.delete(&mut table).unwrap(), Some("2".into()));
+        assert_eq!(table.search("a".into()), None);
+        assert_eq!(table.entry("a".into()).unwrap().delete(&mut table).unwrap(), None);
+    }
+
+    #[test]
+    fn test_entry_is_stale_after_any_other_change() {
+        let mut table = js_table();
+        let entry = table.entry("a".into()).unwrap();
+        table.insert("b".into(), "unrelated".into()).unwrap();
+        assert_eq!(entry.insert(&mut table, "1".into()).unwrap_err().code(), "ERR_STALE_ENTRY");
+        assert_eq!(entry.delete(&mut table).unwrap_err().code(), "ERR_STALE_ENTRY");
+        assert_eq!(table.search("a".into()), None);
+
+        // a handle acts once: its own insert moves the generation on
+        let entry = table.entry("a".into()).unwrap();
+        entry.insert(&mut table, "1".into()).unwrap();
+        assert_eq!(entry.insert(&mut table, "2".into()).unwrap_err().code(), "ERR_STALE_ENTRY");
+
+        let mut other = js_table();
+        let entry = table.entry("c".into()).unwrap();
+        assert_eq!(entry.insert(&mut other, "1".into()).unwrap_err().code(), "ERR_STALE_ENTRY");
+    }
+
+    #[test]
+    fn test_entry_insert_does_not_probe_again() {
+        let mut table = js_table();
+        for i in 0..30 {
+            table.insert(i.to_string(), "v".into()).unwrap();
+        }
+        let before = table.table.probe_count();
+        let entry = table.entry("new".into()).unwrap();
+        let lookup = table.table.probe_count() - before;
+        assert!(lookup > 0);
+        entry.insert(&mut table, "v".into()).unwrap();
+        assert_eq!(table.table.probe_count() - before, lookup);
+
+        let entry = table.entry("new".into()).unwrap();
+        let before = table.table.probe_count();
+        entry.insert(&mut table, "w".into()).unwrap();
+        assert_eq!(table.table.probe_count(), before);
+        assert_eq!(table.search("new".into()), Some("w".into()));
+    }
+}
@@ -0,0 +1,234 @@
+//! JsComparisonHarness: every operation runs against an ElasticHashTable and against a plain
+//! linear-probing table, with probes and time totalled per side, for demos comparing the two
+//!
+//! Both sides hash with hash_with_seed and the same seed, so the difference in probes comes from
+//! the probing strategy alone.
+
+use wasm_bindgen::prelude::*;
+
+use crate::elastic::table::check_dimensions;
+use crate::testing::now_ms;
+use crate::{hash_with_seed, hasher, ElasticHashTable};
+
+/// the baseline: one array, linear probing from the key's hash, no deletions
+struct LinearProbing {
+    slots: Vec<Option<(String, String)>>,
+    seed: u64,
+    len: usize,
+    /// slots inspected so far
+    probes: u64,
+}
+
+impl LinearProbing {
+    fn new(capacity: usize, seed: u64) -> Self {
+        LinearProbing {
+            slots: vec![None; capacity],
+            seed,
+            len: 0,
+            probes: 0,
+        }
+    }
+
+    /// Ok with the slot holding key, or Err with the empty slot ending its run; None when the table is full
+    fn position(&mut self, key: &str) -> Option<Result<usize, usize>> {
+        let size = self.slots.len();
+        let start = (hash_with_seed(key, self.seed) % size as u64) as usize;
+        for j in 0..size {
+            let idx = (start + j) % size;
+            self.probes += 1;
+            match &self.slots[idx] {
+                None => return Some(Err(idx)),
+                Some((k, _)) if k == key => return Some(Ok(idx)),
+                Some(_) => {}
+            }
+        }
+        None
+    }
+
+    /// insert or replace; false when key is new and the table is full
+    fn upsert(&mut self, key: String, value: String) -> bool {
+        match self.position(&key) {
+            Some(Ok(idx)) => self.slots[idx] = Some((key, value)),
+            Some(Err(idx)) => {
+                self.slots[idx] = Some((key, value));
+                self.len += 1;
+            }
+            None => return false,
+        }
+        true
+    }
+
+    fn search(&mut self, key: &str) -> Option<&String> {
+        match self.position(key)? {
+            Ok(idx) => self.slots[idx].as_ref().map(|(_, v)| v),
+            Err(_) => None,
+        }
+    }
+}
+
+/// what one side has spent so far
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+struct Totals {
+    probes: u64,
+    ms: f64,
+}
+
+impl Totals {
+    fn to_json(self) -> String {
+        format!("{{\"probes\":{},\"ms\":{}}}", super::numbers::json_int(self.probes), self.ms)
+    }
+}
+
+/// an elastic table and a linear-probing baseline fed the same inserts and searches
+#[wasm_bindgen]
+pub struct JsComparisonHarness {
+    elastic: ElasticHashTable<String, String>,
+    baseline: LinearProbing,
+    elastic_totals: Totals,
+    baseline_totals: Totals,
+    operations: u64,
+    /// searches where the two sides returned different values; always 0 unless one of them is broken
+    mismatches: u64,
+}
+
+impl JsComparisonHarness {
+    fn with_seed(capacity: usize, delta: f64, seed: u64) -> Self {
+        JsComparisonHarness {
+            elastic: ElasticHashTable::with_seed(capacity, delta, seed),
+            baseline: LinearProbing::new(capacity, seed),
+            elastic_totals: Totals::default(),
+            baseline_totals: Totals::default(),
+            operations: 0,
+            mismatches: 0,
+        }
+    }
+
+    /// insert or replace on both sides; false, with neither side changed, when the elastic table is full
+    fn upsert(&mut self, key: String, value: String) -> bool {
+        let (probes, start) = (self.elastic.probe_count(), now_ms());
+        let stored = match self.elastic.find(key.as_str()) {
+            Some((level, idx)) => {
+                self.elastic.replace_slot(level, idx, value.clone());
+                true
+            }
+            None => self.elastic.insert(key.clone(), value.clone()).is_ok(),
+        };
+        self.elastic_totals.ms += now_ms() - start;
+        self.elastic_totals.probes += self.elastic.probe_count() - probes;
+        if !stored {
+            return false;
+        }
+
+        let (probes, start) = (self.baseline.probes, now_ms());
+        let stored = self.baseline.upsert(key, value);
+        self.baseline_totals.ms += now_ms() - start;
+        self.baseline_totals.probes += self.baseline.probes - probes;
+        debug_assert!(stored, "the baseline has as many slots as the elastic table");
+        self.operations += 1;
+        true
+    }
+
+    /// key's value on each side
+    fn lookup(&mut self, key: &str) -> (Option<String>, Option<String>) {
+        let (probes, start) = (self.elastic.probe_count(), now_ms());
+        let elastic = self.elastic.get(key).cloned();
+        self.elastic_totals.ms += now_ms() - start;
+        self.elastic_totals.probes += self.elastic.probe_count() - probes;
+
+        let (probes, start) = (self.baseline.probes, now_ms());
+        let baseline = self.baseline.search(key).cloned();
+        self.baseline_totals.ms += now_ms() - start;
+        self.baseline_totals.probes += self.baseline.probes - probes;
+
+        self.operations += 1;
+        if elastic != baseline {
+            self.mismatches += 1;
+        }
+        (elastic, baseline)
+    }
+}
+
+#[wasm_bindgen]
+impl JsComparisonHarness {
+    /// throws unless capacity is positive and delta between 0 and 1
+    #[wasm_bindgen(constructor)]
+    pub fn new(capacity: usize, delta: f64) -> Result<JsComparisonHarness, JsValue> {
+        check_dimensions(capacity, delta).map_err(|e| JsValue::from_str(&e.to_string()))?;
+        Ok(Self::with_seed(capacity, delta, hasher::random_seed()))
+    }
+
+    /// insert or replace key on both tables; throws, changing neither, when the elastic table is full
+    #[wasm_bindgen]
+    pub fn insert(&mut self, key: String, value: String) -> Result<(), JsValue> {
+        if self.upsert(key, value) {
+            Ok(())
+        } else {
+            Err(JsValue::from_str("the elastic table is full"))
+        }
+    }
+
+    /// key's value, looked up on both tables
+    #[wasm_bindgen]
+    pub fn search(&mut self, key: &str) -> Option<String> {
+        self.lookup(key).0
+    }
+
+    /// totals so far as JSON:
+    /// `{"operations": number, "mismatches": number, "elastic": {"probes": number, "ms": number}, "baseline": {...}}`
+    /// probes count slots inspected; ms is wall time, as precise as the platform clock
+    #[wasm_bindgen(js_name = getComparison)]
+    pub fn get_comparison(&self) -> String {
+        format!(
+            "{{\"operations\":{},\"mismatches\":{},\"elastic\":{},\"baseline\":{}}}",
+            super::numbers::json_int(self.operations),
+            super::numbers::json_int(self.mismatches),
+            self.elastic_totals.to_json(),
+            self.baseline_totals.to_json()
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::JsComparisonHarness;
+
+    #[test]
+    fn test_both_sides_agree() {
+        let mut harness = JsComparisonHarness::with_seed(1024, 0.1, 5);
+        let mut state = 5u64;
+        for step in 0..3000u64 {
+            state = state.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+            let key = format!("k{}", (state >> 33) % 1200);
+            if step % 3 == 0 {
+                harness.upsert(key, step.to_string());
+            } else {
+                let (elastic, baseline) = harness.lookup(&key);
+                assert_eq!(elastic, baseline, "{}", key);
+            }
+        }
+        assert_eq!(harness.mismatches, 0);
+        assert_eq!(harness.elastic.num_inserts, harness.baseline.len);
+    }
+
+    #[test]
+    fn test_totals_accumulate() {
+        let mut harness = JsComparisonHarness::with_seed(64, 0.1, 1);
+        assert_eq!(
+            harness.get_comparison(),
+            r#"{"operations":0,"mismatches":0,"elastic":{"probes":0,"ms":0},"baseline":{"probes":0,"ms":0}}"#
+        );
+        assert!(harness.upsert("a".into(), "1".into()));
+        assert!(harness.upsert("a".into(), "2".into()));
+        assert_eq!(harness.search("a"), Some("2".into()));
+        assert_eq!(harness.search("b"), None);
+        assert_eq!(harness.operations, 4);
+        let (elastic, baseline) = (harness.elastic_totals, harness.baseline_totals);
+        assert!(elastic.probes >= 4 && baseline.probes >= 4);
+        assert!(elastic.ms >= 0.0 && baseline.ms >= 0.0);
+
+        harness.search("a");
+        assert!(harness.elastic_totals.probes > elastic.probes);
+        assert!(harness.baseline_totals.probes > baseline.probes);
+        assert!(harness.get_comparison().starts_with(r#"{"operations":5,"mismatches":0,"elastic":{"probes":"#));
+    }
+}
@@ -0,0 +1,95 @@
+//! JsElasticHashTableU64: an ElasticIntTable<String> for JS, taking keys as numbers or BigInts
+
+use wasm_bindgen::prelude::*;
+
+use super::numbers;
+use crate::elastic::table::check_dimensions;
+use crate::{hasher, ElasticIntTable};
+
+/// a key passed from JS, as a number or a BigInt
+fn js_key(key: &JsValue) -> Result<u64, JsValue> {
+    numbers::js_to_u64(key).ok_or_else(|| JsValue::from_str("keys must be non-negative integers below 2^53 or BigInts below 2^64"))
+}
+
+/// ElasticIntTable<String> for JS
+#[wasm_bindgen]
+pub struct JsElasticHashTableU64 {
+    table: ElasticIntTable<String>,
+}
+
+impl JsElasticHashTableU64 {
+    /// Err with the reason when key is new and the table has no room
+    fn insert_key(&mut self, key: u64, value: String) -> Result<(), String> {
+        self.table.insert(key, value).map(|_| ()).map_err(|err| err.reason())
+    }
+}
+
+#[wasm_bindgen]
+impl JsElasticHashTableU64 {
+    /// throws unless capacity is positive and delta between 0 and 1
+    #[wasm_bindgen(constructor)]
+    pub fn new(capacity: usize, delta: f64) -> Result<JsElasticHashTableU64, JsValue> {
+        check_dimensions(capacity, delta).map_err(|e| JsValue::from_str(&e.to_string()))?;
+        Ok(JsElasticHashTableU64 {
+            table: ElasticIntTable::with_hasher(capacity, delta, hasher::random_seed()),
+        })
+    }
+
+    /// throws unless key is a non-negative integer number below 2^53 or a BigInt below 2^64,
+    /// and when key is new and the table has no room
+    #[wasm_bindgen]
+    pub fn insert(&mut self, key: JsValue, value: String) -> Result<(), JsValue> {
+        self.insert_key(js_key(&key)?, value).map_err(|e| JsValue::from_str(&e))
+    }
+
+    #[wasm_bindgen]
+    pub fn search(&self, key: JsValue) -> Result<Option<String>, JsValue> {
+        Ok(self.table.get(&js_key(&key)?).cloned())
+    }
+
+    #[wasm_bindgen]
+    pub fn has(&self, key: JsValue) -> Result<bool, JsValue> {
+        Ok(self.table.get(&js_key(&key)?).is_some())
+    }
+
+    /// remove key, returning its value
+    #[wasm_bindgen]
+    pub fn remove(&mut self, key: JsValue) -> Result<Option<String>, JsValue> {
+        Ok(self.table.remove(&js_key(&key)?))
+    }
+
+    /// number of entries
+    #[wasm_bindgen(getter)]
+    pub fn size(&self) -> usize {
+        self.table.num_inserts
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::JsElasticHashTableU64;
+    use crate::bindings::numbers::number_to_u64 as number_key;
+
+    #[test]
+    fn test_number_keys() {
+        assert_eq!(number_key(0.0), Some(0));
+        assert_eq!(number_key(9_007_199_254_740_991.0), Some((1 << 53) - 1));
+        assert_eq!(number_key(9_007_199_254_740_992.0), None);
+        assert_eq!(number_key(-1.0), None);
+        assert_eq!(number_key(1.5), None);
+        assert_eq!(number_key(f64::NAN), None);
+
+        let mut table = JsElasticHashTableU64::new(64, 0.1).unwrap();
+        table.insert_key(7, "seven".into()).unwrap();
+        assert_eq!(table.table.get(&7).map(String::as_str), Some("seven"));
+        assert_eq!(table.size(), 1);
+
+        let mut small = JsElasticHashTableU64::new(4, 0.25).unwrap();
+        for key in 0..3 {
+            small.insert_key(key, "v".into()).unwrap();
+        }
+        assert!(small.insert_key(3, "v".into()).unwrap_err().contains("maximum allowed insertions"));
+        small.insert_key(0, "replaced".into()).unwrap();
+        assert_eq!(small.table.get(&0).map(String::as_str), Some("replaced"));
+    }
+}
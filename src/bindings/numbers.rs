@@ -0,0 +1,103 @@
+//! how integers cross into JS: a JS number holds integers exactly only up to 2^53 - 1, so every u64
+//! the bindings return goes through JsInt instead of an `as f64`, and every integer taken as a number
+//! through number_to_u64
+//!
+//! usize needs no care: it is 32 bits on wasm32, so counts, lengths and slot indexes are always exact.
+
+use wasm_bindgen::prelude::*;
+
+/// largest integer a JS number holds exactly, Number.MAX_SAFE_INTEGER
+pub(crate) const MAX_SAFE_INTEGER: u64 = (1 << 53) - 1;
+
+#[cfg(target_arch = "wasm32")]
+const _: () = assert!(usize::BITS <= 53);
+
+/// a u64 on its way to JS
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum JsInt {
+    Number(f64),
+    BigInt(u64),
+}
+
+impl JsInt {
+    /// a number up to MAX_SAFE_INTEGER, a BigInt beyond
+    pub(crate) fn new(n: u64) -> Self {
+        if n <= MAX_SAFE_INTEGER {
+            JsInt::Number(n as f64)
+        } else {
+            JsInt::BigInt(n)
+        }
+    }
+
+    /// as JSON text: a number up to MAX_SAFE_INTEGER, a decimal string beyond, since JSON.parse
+    /// has no BigInt; `BigInt(field)` is exact either way
+    pub(crate) fn to_json(self) -> String {
+        match self {
+            JsInt::Number(n) => (n as u64).to_string(),
+            JsInt::BigInt(n) => format!("\"{}\"", n),
+        }
+    }
+}
+
+impl From<JsInt> for JsValue {
+    fn from(n: JsInt) -> JsValue {
+        match n {
+            JsInt::Number(n) => JsValue::from_f64(n),
+            JsInt::BigInt(n) => JsValue::from(n),
+        }
+    }
+}
+
+/// JSON text for n, see JsInt::to_json
+pub(crate) fn json_int(n: u64) -> String {
+    JsInt::new(n).to_json()
+}
+
+/// a JS number as a u64: a non-negative integer up to MAX_SAFE_INTEGER; anything else may
+/// already have been rounded, so it is refused rather than guessed at
+pub(crate) fn number_to_u64(n: f64) -> Option<u64> {
+    (n.fract() == 0.0 && (0.0..=MAX_SAFE_INTEGER as f64).contains(&n)).then_some(n as u64)
+}
+
+/// a JS number or BigInt as a u64
+pub(crate) fn js_to_u64(value: &JsValue) -> Option<u64> {
+    if value.is_bigint() {
+        u64::try_from(value.clone()).ok()
+    } else {
+        value.as_f64().and_then(number_to_u64)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{json_int, number_to_u64, JsInt, MAX_SAFE_INTEGER};
+
+    const TWO_53: u64 = 1 << 53;
+
+    #[test]
+    fn test_u64_crosses_as_number_only_while_exact() {
+        assert_eq!(JsInt::new(0), JsInt::Number(0.0));
+        assert_eq!(JsInt::new(TWO_53 - 1), JsInt::Number(9_007_199_254_740_991.0));
+        assert_eq!(JsInt::new(TWO_53), JsInt::BigInt(TWO_53));
+        assert_eq!(JsInt::new(TWO_53 + 1), JsInt::BigInt(TWO_53 + 1));
+        assert_eq!(JsInt::new(u64::MAX), JsInt::BigInt(u64::MAX));
+
+        assert_eq!(json_int(TWO_53 - 1), "9007199254740991");
+        assert_eq!(json_int(TWO_53), "\"9007199254740992\"");
+        assert_eq!(json_int(TWO_53 + 1), "\"9007199254740993\"");
+        assert_eq!(json_int(u64::MAX), "\"18446744073709551615\"");
+    }
+
+    #[test]
+    fn test_numbers_convert_only_when_exact() {
+        assert_eq!(number_to_u64(0.0), Some(0));
+        assert_eq!(number_to_u64(MAX_SAFE_INTEGER as f64), Some(TWO_53 - 1));
+        // 2^53 + 1 is not a JS number: it rounds to 2^53, which is refused like every unsafe integer
+        assert_eq!(number_to_u64((TWO_53 + 1) as f64), None);
+        assert_eq!(number_to_u64(TWO_53 as f64), None);
+        assert_eq!(number_to_u64(-1.0), None);
+        assert_eq!(number_to_u64(1.5), None);
+        assert_eq!(number_to_u64(f64::NAN), None);
+        assert_eq!(number_to_u64(f64::INFINITY), None);
+    }
+}
@@ -0,0 +1,111 @@
+//! value deduplication for the JS binding: every distinct value is allocated once
+//! and shared by all entries holding it
+
+use std::rc::Rc;
+
+use super::stored::StoredValue;
+use crate::ElasticHashTable;
+
+/// capacity a pool starts with; it doubles through try_reserve as distinct values arrive
+const INITIAL_CAPACITY: usize = 16;
+
+/// distinct values with the number of entries referencing each
+#[derive(Clone)]
+pub(crate) struct ValuePool {
+    refs: ElasticHashTable<Rc<str>, u32>,
+}
+
+impl ValuePool {
+    pub(crate) fn new(delta: f64) -> Self {
+        ValuePool {
+            refs: ElasticHashTable::new(INITIAL_CAPACITY, delta),
+        }
+    }
+
+    /// a shared handle to value, adding a reference
+    pub(crate) fn intern(&mut self, value: String) -> StoredValue {
+        if let Some(shared) = self.refs.get_key(value.as_str()).cloned() {
+            self.refs.modify(value.as_str(), |refs| *refs += 1);
+            return StoredValue::Shared(shared);
+        }
+        let shared: Rc<str> = value.into();
+        self.refs.try_reserve(1).expect("value pool cannot grow");
+        self.refs.insert(shared.clone(), 1).expect("value pool is full");
+        StoredValue::Shared(shared)
+    }
+
+    /// add a reference to a value interned by another pool
+    pub(crate) fn adopt(&mut self, value: &StoredValue) {
+        if let StoredValue::Shared(shared) = value {
+            if self.refs.modify(shared, |refs| *refs += 1).is_none() {
+                self.refs.try_reserve(1).expect("value pool cannot grow");
+                self.refs.insert(shared.clone(), 1).expect("value pool is full");
+            }
+        }
+    }
+
+    /// drop a reference; the value is freed with its last one
+    pub(crate) fn release(&mut self, value: &StoredValue) {
+        if let StoredValue::Shared(shared) = value {
+            if self.refs.modify(shared, |refs| {
+                *refs -= 1;
+                *refs
+            }) == Some(0)
+            {
+                self.refs.remove(shared);
+            }
+        }
+    }
+
+    pub(crate) fn distinct_values(&self) -> usize {
+        self.refs.num_inserts
+    }
+
+    /// approximate bytes held: the pool's slot array plus one copy of every distinct value
+    pub(crate) fn memory_usage(&self) -> usize {
+        let slots = self.refs.capacity() * std::mem::size_of::<crate::elastic::Slot<Rc<str>, u32>>();
+        slots + self.refs.keys().map(|v| v.len()).sum::<usize>()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{StoredValue, ValuePool};
+
+    #[test]
+    fn test_refcounts() {
+        let mut pool = ValuePool::new(0.1);
+        let a = pool.intern("active".into());
+        let b = pool.intern("active".into());
+        let c = pool.intern("banned".into());
+        match (&a, &b) {
+            (StoredValue::Shared(x), StoredValue::Shared(y)) => assert!(std::rc::Rc::ptr_eq(x, y)),
+            _ => panic!("expected shared values"),
+        }
+        assert_eq!(pool.distinct_values(), 2);
+
+        pool.release(&a);
+        assert_eq!(pool.distinct_values(), 2);
+        pool.release(&b);
+        assert_eq!(pool.distinct_values(), 1);
+        assert_eq!(pool.refs.get("active"), None);
+
+        let mut other = ValuePool::new(0.1);
+        other.adopt(&c);
+        other.adopt(&c);
+        pool.release(&c);
+        assert_eq!((pool.distinct_values(), other.distinct_values()), (0, 1));
+        assert_eq!(other.refs.get("banned"), Some(&2));
+    }
+
+    #[test]
+    fn test_pool_grows() {
+        let mut pool = ValuePool::new(0.1);
+        let values: Vec<_> = (0..1000).map(|i| pool.intern(format!("status-{}", i))).collect();
+        assert_eq!(pool.distinct_values(), 1000);
+        for v in &values {
+            pool.release(v);
+        }
+        assert_eq!(pool.distinct_values(), 0);
+    }
+}
@@ -0,0 +1,348 @@
+//! JsTableRegistry: named tables owned in one place, so they can be counted, listed and saved together
+//!
+//! ```ignore
+//! const registry = new JsTableRegistry();
+//! registry.createTable("users", JSON.stringify({ capacity: 1024, delta: 0.1, seed: 1 }));
+//! const users = registry.get("users");
+//! users.insert("alice", "admin");
+//! localStorage.setItem("tables", btoa(String.fromCharCode(...registry.exportAll())));
+//! ```
+//!
+//! wasm-bindgen cannot hand JS a reference into a Rust-side map, so get returns a JsNamespace handle
+//! that reaches its table through the registry. A handle does not keep its table alive: once the table
+//! is dropped, or replaced by importAll, the handle throws ERR_NO_SUCH_TABLE.
+
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::rc::{Rc, Weak};
+
+use wasm_bindgen::prelude::*;
+
+use super::validate::Violation;
+use super::{JsElasticHashTable, ValidationError};
+use crate::{json, ByteCodec};
+
+/// start of exportAll's output
+const MAGIC: &[u8; 4] = b"EHTD";
+
+/// named JsElasticHashTables
+#[wasm_bindgen]
+#[derive(Default)]
+pub struct JsTableRegistry {
+    tables: BTreeMap<String, Rc<RefCell<JsElasticHashTable>>>,
+}
+
+/// a table of a JsTableRegistry, see JsTableRegistry.get
+#[wasm_bindgen]
+pub struct JsNamespace {
+    name: String,
+    table: Weak<RefCell<JsElasticHashTable>>,
+}
+
+fn no_such_table(name: &str) -> ValidationError {
+    ValidationError::new(&Violation::NoSuchTable(name.to_string()), None)
+}
+
+fn invalid_argument(reason: String) -> ValidationError {
+    ValidationError::new(&Violation::InvalidArgument(reason), None)
+}
+
+#[wasm_bindgen]
+impl JsTableRegistry {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> JsTableRegistry {
+        JsTableRegistry::default()
+    }
+
+    /// add an empty table built from options, JSON as for JsElasticHashTable.withOptions
+    /// throws ERR_TABLE_EXISTS when name is taken, ERR_INVALID_ARGUMENT for invalid options
+    #[wasm_bindgen(js_name = createTable)]
+    pub fn create_table(&mut self, name: String, options: &str) -> Result<(), ValidationError> {
+        if self.tables.contains_key(&name) {
+            return Err(ValidationError::new(&Violation::TableExists(name), None));
+        }
+        let table = JsElasticHashTable::from_options(options).map_err(invalid_argument)?;
+        self.tables.insert(name, Rc::new(RefCell::new(table)));
+        Ok(())
+    }
+
+    /// a handle on the table named name; throws ERR_NO_SUCH_TABLE
+    #[wasm_bindgen]
+    pub fn get(&self, name: String) -> Result<JsNamespace, ValidationError> {
+        let table = self.tables.get(&name).ok_or_else(|| no_such_table(&name))?;
+        Ok(JsNamespace { table: Rc::downgrade(table), name })
+    }
+
+    /// remove the table named name and everything in it; throws ERR_NO_SUCH_TABLE
+    #[wasm_bindgen]
+    pub fn drop(&mut self, name: &str) -> Result<(), ValidationError> {
+        self.tables.remove(name).map(|_| ()).ok_or_else(|| no_such_table(name))
+    }
+
+    /// number of tables
+    #[wasm_bindgen(getter)]
+    pub fn size(&self) -> usize {
+        self.tables.len()
+    }
+
+    /// every table as JSON, in name order: `[{"name": string, "size": number, "capacity": number,
+    /// "bytes": number}]`, bytes as memoryUsage
+    #[wasm_bindgen(js_name = listTables)]
+    pub fn list_tables(&self) -> String {
+        let tables: Vec<String> = self
+            .tables
+            .iter()
+            .map(|(name, table)| {
+                let table = table.borrow();
+                format!(
+                    "{{\"name\":{},\"size\":{},\"capacity\":{},\"bytes\":{}}}",
+                    json::quote(name),
+                    table.len(),
+                    table.capacity(),
+                    table.memory_usage()
+                )
+            })
+            .collect();
+        format!("[{}]", tables.join(","))
+    }
+
+    /// the memoryUsage of every table, summed
+    #[wasm_bindgen(js_name = totalMemoryUsage)]
+    pub fn total_memory_usage(&self) -> usize {
+        self.tables.values().map(|table| table.borrow().memory_usage()).sum()
+    }
+
+    /// every table, for importAll: a directory of (name, getOptions(), toBytes()) in name order
+    #[wasm_bindgen(js_name = exportAll)]
+    pub fn export_all(&self) -> Vec<u8> {
+        let mut out = MAGIC.to_vec();
+        self.tables.len().encode(&mut out);
+        for (name, table) in &self.tables {
+            let table = table.borrow();
+            name.encode(&mut out);
+            table.get_options().encode(&mut out);
+            table.to_bytes().encode(&mut out);
+        }
+        out
+    }
+
+    /// add every table of exportAll's output, restored as by JsElasticHashTable.fromBytes; all or nothing:
+    /// throws ERR_TABLE_EXISTS, adding none, when a name is taken, ERR_INVALID_ARGUMENT for bytes that are
+    /// not exportAll's. returns the names of the tables that were re-inserted rather than restored slot
+    /// for slot, see SnapshotImport
+    #[wasm_bindgen(js_name = importAll)]
+    pub fn import_all(&mut self, bytes: &[u8]) -> Result<Vec<String>, ValidationError> {
+        let tables = Self::read_directory(bytes).map_err(invalid_argument)?;
+        if let Some((name, ..)) = tables.iter().find(|(name, ..)| self.tables.contains_key(name)) {
+            return Err(ValidationError::new(&Violation::TableExists(name.clone()), None));
+        }
+        let mut imported = Vec::with_capacity(tables.len());
+        for (name, options, bytes) in &tables {
+            let import = JsElasticHashTable::import_bytes(bytes, Some(options))
+                .map_err(|e| invalid_argument(format!("table {}: {}", json::quote(name), e)))?;
+            imported.push((name.clone(), import));
+        }
+        let mut reinserted = Vec::new();
+        for (name, import) in imported {
+            if import.warning().is_some() {
+                reinserted.push(name.clone());
+            }
+            self.tables.insert(name, Rc::new(RefCell::new(import.into_table())));
+        }
+        Ok(reinserted)
+    }
+}
+
+impl JsTableRegistry {
+    /// (name, options, table bytes) of every table in exportAll's output
+    fn read_directory(bytes: &[u8]) -> Result<Vec<(String, String, Vec<u8>)>, String> {
+        let malformed = || "not a registry export, or a truncated one".to_string();
+        let mut input = bytes.strip_prefix(MAGIC).ok_or_else(malformed)?;
+        let input = &mut input;
+        let count = usize::decode(input).ok_or_else(malformed)?;
+        let mut tables: Vec<(String, String, Vec<u8>)> = Vec::with_capacity(count.min(input.len()));
+        for _ in 0..count {
+            let name = String::decode(input).ok_or_else(malformed)?;
+            let options = String::decode(input).ok_or_else(malformed)?;
+            let table = Vec::decode(input).ok_or_else(malformed)?;
+            if tables.last().is_some_and(|(last, ..)| *last >= name) {
+                return Err(format!("table {} is out of order or repeated", json::quote(&name)));
+            }
+            tables.push((name, options, table));
+        }
+        if !input.is_empty() {
+            return Err(malformed());
+        }
+        Ok(tables)
+    }
+}
+
+impl JsNamespace {
+    fn with<R>(&self, f: impl FnOnce(&mut JsElasticHashTable) -> R) -> Result<R, ValidationError> {
+        let table = self.table.upgrade().ok_or_else(|| no_such_table(&self.name))?;
+        let mut table = table.borrow_mut();
+        Ok(f(&mut table))
+    }
+}
+
+/// the common table operations, as on JsElasticHashTable; every method throws ERR_NO_SUCH_TABLE once the
+/// table left its registry
+#[wasm_bindgen]
+impl JsNamespace {
+    #[wasm_bindgen(getter)]
+    pub fn name(&self) -> String {
+        self.name.clone()
+    }
+
+    #[wasm_bindgen]
+    pub fn insert(&self, key: String, value: String) -> Result<(), ValidationError> {
+        self.with(|table| table.insert(key, value))?
+    }
+
+    #[wasm_bindgen]
+    pub fn search(&self, key: String) -> Result<Option<String>, ValidationError> {
+        self.with(|table| table.search(key))
+    }
+
+    #[wasm_bindgen]
+    pub fn has(&self, key: String) -> Result<bool, ValidationError> {
+        self.with(|table| table.has(key))
+    }
+
+    #[wasm_bindgen]
+    pub fn remove(&self, key: String) -> Result<Option<String>, ValidationError> {
+        self.with(|table| table.remove(key))
+    }
+
+    #[wasm_bindgen]
+    pub fn keys(&self) -> Result<Vec<String>, ValidationError> {
+        self.with(|table| table.keys())
+    }
+
+    #[wasm_bindgen]
+    pub fn len(&self) -> Result<usize, ValidationError> {
+        self.with(|table| table.len())
+    }
+
+    #[wasm_bindgen(js_name = isEmpty)]
+    pub fn is_empty(&self) -> Result<bool, ValidationError> {
+        self.with(|table| table.is_empty())
+    }
+
+    #[wasm_bindgen]
+    pub fn capacity(&self) -> Result<usize, ValidationError> {
+        self.with(|table| table.capacity())
+    }
+
+    #[wasm_bindgen(js_name = memoryUsage)]
+    pub fn memory_usage(&self) -> Result<usize, ValidationError> {
+        self.with(|table| table.memory_usage())
+    }
+
+    #[wasm_bindgen(js_name = getOptions)]
+    pub fn get_options(&self) -> Result<String, ValidationError> {
+        self.with(|table| table.get_options())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::JsTableRegistry;
+
+    fn options(capacity: usize, extra: &str) -> String {
+        format!(r#"{{"capacity": {}, "delta": 0.1, "seed": 3{}}}"#, capacity, extra)
+    }
+
+    /// three namespaces holding the same keys with different values
+    fn registry() -> JsTableRegistry {
+        let mut registry = JsTableRegistry::new();
+        registry.create_table("users".into(), &options(256, "")).unwrap();
+        registry.create_table("orders".into(), &options(1024, r#", "caseInsensitive": true"#)).unwrap();
+        registry.create_table("tags".into(), &options(64, r#", "dedup": true"#)).unwrap();
+        for (name, count) in [("users", 150), ("orders", 700), ("tags", 40)] {
+            let table = registry.get(name.into()).unwrap();
+            for i in 0..count {
+                table.insert(format!("Key{}", i), format!("{}:{}", name, i % 9)).unwrap();
+            }
+        }
+        registry
+    }
+
+    #[test]
+    fn test_namespaces_are_isolated() {
+        let mut registry = registry();
+        let (users, orders) = (registry.get("users".into()).unwrap(), registry.get("orders".into()).unwrap());
+        assert_eq!(users.search("Key1".into()).unwrap(), Some("users:1".into()));
+        assert_eq!(orders.search("KEY1".into()).unwrap(), Some("orders:1".into()));
+        assert_eq!(users.search("KEY1".into()).unwrap(), None);
+        assert_eq!(users.search("Key500".into()).unwrap(), None);
+        users.remove("Key2".into()).unwrap();
+        assert!(!users.has("Key2".into()).unwrap() && orders.has("Key2".into()).unwrap());
+        assert_eq!((users.len().unwrap(), orders.len().unwrap()), (149, 700));
+
+        let list = registry.list_tables();
+        assert!(list.starts_with(r#"[{"name":"orders","size":700,"capacity":1024,"bytes":"#), "{}", list);
+        assert!(list.contains(r#"{"name":"users","size":149,"capacity":256,"bytes":"#), "{}", list);
+        let total: usize = ["users", "orders", "tags"]
+            .iter()
+            .map(|name| registry.get(name.to_string()).unwrap().memory_usage().unwrap())
+            .sum();
+        assert_eq!(registry.total_memory_usage(), total);
+
+        registry.drop("users").unwrap();
+        assert_eq!(users.search("Key1".into()).unwrap_err().code(), "ERR_NO_SUCH_TABLE");
+        assert_eq!(registry.size(), 2);
+        assert!(registry.total_memory_usage() < total);
+        // a new table under the old name is not reachable through the old handle
+        registry.create_table("users".into(), &options(64, "")).unwrap();
+        assert_eq!(users.len().unwrap_err().code(), "ERR_NO_SUCH_TABLE");
+        assert!(registry.get("users".into()).unwrap().is_empty().unwrap());
+    }
+
+    #[test]
+    fn test_name_errors() {
+        let mut registry = registry();
+        let err = registry.create_table("users".into(), &options(64, "")).unwrap_err();
+        assert_eq!(err.code(), "ERR_TABLE_EXISTS");
+        assert_eq!(err.message(), "a table named \"users\" already exists");
+        assert_eq!(registry.get("nope".into()).err().unwrap().code(), "ERR_NO_SUCH_TABLE");
+        assert_eq!(registry.drop("nope").unwrap_err().code(), "ERR_NO_SUCH_TABLE");
+        let err = registry.create_table("bad".into(), &options(0, "")).unwrap_err();
+        assert_eq!(err.code(), "ERR_INVALID_ARGUMENT");
+        assert_eq!(registry.size(), 3);
+    }
+
+    #[test]
+    fn test_export_import_roundtrip() {
+        let registry = registry();
+        let bytes = registry.export_all();
+        let mut restored = JsTableRegistry::new();
+        assert!(restored.import_all(&bytes).unwrap().is_empty());
+        assert_eq!(restored.list_tables(), registry.list_tables());
+        for name in ["users", "orders", "tags"] {
+            let (before, after) = (registry.get(name.into()).unwrap(), restored.get(name.into()).unwrap());
+            assert_eq!(after.get_options().unwrap(), before.get_options().unwrap());
+            let mut keys = before.keys().unwrap();
+            keys.sort();
+            let mut restored_keys = after.keys().unwrap();
+            restored_keys.sort();
+            assert_eq!(restored_keys, keys);
+            for key in keys {
+                assert_eq!(after.search(key.clone()).unwrap(), before.search(key).unwrap());
+            }
+        }
+        assert_eq!(restored.export_all(), bytes);
+        let orders = restored.get("orders".into()).unwrap();
+        assert_eq!(orders.search("key699".into()).unwrap(), Some("orders:6".into()));
+
+        // a colliding name adds nothing
+        let mut partial = JsTableRegistry::new();
+        partial.create_table("tags".into(), &options(64, "")).unwrap();
+        assert_eq!(partial.import_all(&bytes).unwrap_err().code(), "ERR_TABLE_EXISTS");
+        assert_eq!(partial.size(), 1);
+
+        for bad in [&bytes[..bytes.len() - 1], &bytes[1..], b"EHTD\x01"] {
+            assert_eq!(JsTableRegistry::new().import_all(bad).unwrap_err().code(), "ERR_INVALID_ARGUMENT");
+        }
+    }
+}
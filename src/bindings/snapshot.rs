@@ -0,0 +1,328 @@
+//! layout snapshots of a JsElasticHashTable as JSON, see ElasticHashTable::layout_snapshot
+//!
+//! ```ignore
+//! localStorage.setItem("table", table.exportSnapshot());
+//! // later, possibly after an upgrade
+//! const restored = JsElasticHashTable.fromSnapshot(localStorage.getItem("table"));
+//! if (restored.warning) console.warn(restored.warning);
+//! const table = restored.intoTable();
+//! ```
+//!
+//! toBytes and fromBytes do the same in the binary encoding of ElasticHashTable::to_bytes, which a native
+//! `ElasticHashTable<String, String>` writes too: a table built ahead of time loads in the browser slot for slot.
+
+use wasm_bindgen::prelude::*;
+
+use super::pool::ValuePool;
+use super::stored::StoredValue;
+use super::validate::JsKey;
+use super::JsElasticHashTable;
+use super::validate::KeyRules;
+use crate::json::{self, Value};
+use crate::{ElasticHashTable, ImportReport, LayoutSnapshot, SlotId};
+
+/// the result of fromSnapshot: the rebuilt table and how it was rebuilt
+#[wasm_bindgen]
+pub struct SnapshotImport {
+    table: JsElasticHashTable,
+    report: ImportReport,
+}
+
+#[wasm_bindgen]
+impl SnapshotImport {
+    /// "layout-preserving" when every entry is back in its slot, "reinserted" when the snapshot came from a
+    /// build that places keys differently and its entries were inserted afresh
+    #[wasm_bindgen(getter)]
+    pub fn mode(&self) -> String {
+        self.report.mode.as_str().to_string()
+    }
+
+    /// why the entries were re-inserted, undefined in layout-preserving mode
+    #[wasm_bindgen(getter)]
+    pub fn warning(&self) -> Option<String> {
+        self.report.warning.clone()
+    }
+
+    /// the rebuilt table; the SnapshotImport is consumed
+    #[wasm_bindgen(js_name = intoTable)]
+    pub fn into_table(self) -> JsElasticHashTable {
+        self.table
+    }
+}
+
+#[wasm_bindgen]
+impl JsElasticHashTable {
+    /// every slot as JSON, for fromSnapshot: `{"layoutVersion": number, "hasher": string, "options": getOptions(),
+    /// "levels": [size], "entries": [[level, index, key, value]], "tombstones": [[level, index]]}`;
+    /// insertTagged's tags are not included
+    #[wasm_bindgen(js_name = exportSnapshot)]
+    pub fn export_snapshot(&self) -> String {
+        let snapshot = self.table.layout_snapshot();
+        let sizes: Vec<String> = snapshot.level_sizes.iter().map(usize::to_string).collect();
+        let entries: Vec<String> = snapshot
+            .entries
+            .iter()
+            .map(|(id, k, v)| {
+                format!("[{},{},{},{}]", id.level, id.index, json::quote(k.as_str()), json::quote(&v.get()))
+            })
+            .collect();
+        let tombstones: Vec<String> =
+            snapshot.tombstones.iter().map(|id| format!("[{},{}]", id.level, id.index)).collect();
+        format!(
+            "{{\"layoutVersion\":{},\"hasher\":{},\"options\":{},\"levels\":[{}],\"entries\":[{}],\
+             \"tombstones\":[{}]}}",
+            snapshot.layout_version,
+            json::quote(&snapshot.hasher),
+            self.get_options(),
+            sizes.join(","),
+            entries.join(","),
+            tombstones.join(",")
+        )
+    }
+
+    /// rebuild a table from exportSnapshot's JSON, with the options and input rules it was built with
+    /// the layout is kept only when the snapshot's layoutVersion matches this build's LAYOUT_VERSION;
+    /// otherwise the entries are re-inserted and the result's warning says so. see SnapshotImport
+    #[wasm_bindgen(js_name = fromSnapshot)]
+    pub fn from_snapshot(json: &str) -> Result<SnapshotImport, JsValue> {
+        Self::import_snapshot(json).map_err(|e| JsValue::from_str(&e))
+    }
+
+    /// every slot in the binary encoding of ElasticHashTable::to_bytes, for fromBytes; the input rules are
+    /// not included, pass getOptions() to fromBytes for them
+    #[wasm_bindgen(js_name = toBytes)]
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let LayoutSnapshot { layout_version, hasher, options, level_sizes, entries, tombstones } =
+            self.table.layout_snapshot();
+        let entries = entries.into_iter().map(|(id, k, v)| (id, k.into_string(), v.get().into_owned())).collect();
+        LayoutSnapshot { layout_version, hasher, options, level_sizes, entries, tombstones }.to_bytes()
+    }
+
+    /// rebuild a table from toBytes, or from the to_bytes of a native `ElasticHashTable<String, String>`;
+    /// the dimensions come from the bytes, the input rules and metadata from options, a getOptions() JSON
+    /// string, when given. layout is kept as with fromSnapshot, see SnapshotImport
+    #[wasm_bindgen(js_name = fromBytes)]
+    pub fn from_bytes(bytes: &[u8], options: Option<String>) -> Result<SnapshotImport, JsValue> {
+        Self::import_bytes(bytes, options.as_deref()).map_err(|e| JsValue::from_str(&e))
+    }
+}
+
+impl JsElasticHashTable {
+    fn import_snapshot(json: &str) -> Result<SnapshotImport, String> {
+        let Value::Object(fields) = json::value(json)? else {
+            return Err("expected a snapshot object".into());
+        };
+        let field = |name: &str| {
+            fields
+                .iter()
+                .find(|(field, _)| field == name)
+                .map(|(_, value)| value)
+                .ok_or_else(|| format!("snapshot has no {}", name))
+        };
+        let Value::Object(options) = field("options")? else {
+            return Err("snapshot options must be an object".into());
+        };
+        let options = options
+            .iter()
+            .map(|(name, value)| match value {
+                Value::String(s) | Value::Number(s) => Ok((name.clone(), s.clone())),
+                Value::Bool(b) => Ok((name.clone(), b.to_string())),
+                _ => Err(format!("invalid value for {}", name)),
+            })
+            .collect::<Result<Vec<_>, String>>()?;
+        let (options, rules, metadata, _) = Self::parse_options(&options)?;
+
+        let mut entries = Vec::new();
+        for entry in array("entries", field("entries")?)? {
+            match array("entries", entry)? {
+                [level, index, Value::String(k), Value::String(v)] => {
+                    let id = SlotId { level: number("level", level)?, index: number("index", index)? };
+                    entries.push((id, k.clone(), v.clone()));
+                }
+                _ => return Err("a snapshot entry must be [level, index, key, value]".into()),
+            }
+        }
+        let mut tombstones = Vec::new();
+        for tombstone in array("tombstones", field("tombstones")?)? {
+            match array("tombstones", tombstone)? {
+                [level, index] => {
+                    tombstones.push(SlotId { level: number("level", level)?, index: number("index", index)? })
+                }
+                _ => return Err("a snapshot tombstone must be [level, index]".into()),
+            }
+        }
+        let Value::String(hasher) = field("hasher")? else {
+            return Err("snapshot hasher must be a string".into());
+        };
+        let snapshot = LayoutSnapshot {
+            layout_version: number("layoutVersion", field("layoutVersion")?)?,
+            hasher: hasher.clone(),
+            options,
+            level_sizes: array("levels", field("levels")?)?
+                .iter()
+                .map(|size| number("levels", size))
+                .collect::<Result<_, _>>()?,
+            entries,
+            tombstones,
+        };
+        Self::restore(snapshot, rules, metadata)
+    }
+
+    pub(super) fn import_bytes(bytes: &[u8], options: Option<&str>) -> Result<SnapshotImport, String> {
+        let snapshot = LayoutSnapshot::from_bytes(bytes)?;
+        let (rules, metadata) = match options {
+            Some(options) => {
+                let (_, rules, metadata, _) = Self::parse_options(&json::flat_object(options)?)?;
+                (rules, metadata)
+            }
+            None => (KeyRules::default(), false),
+        };
+        Self::restore(snapshot, rules, metadata)
+    }
+
+    /// the table a snapshot of plain keys and values describes, with rules applied to them
+    fn restore(
+        snapshot: LayoutSnapshot<String, String>,
+        rules: KeyRules,
+        metadata: bool,
+    ) -> Result<SnapshotImport, String> {
+        let LayoutSnapshot { layout_version, hasher, options, level_sizes, entries, tombstones } = snapshot;
+        let mut pool = rules.dedup().then(|| ValuePool::new(options.delta));
+        let entries = entries
+            .into_iter()
+            .map(|(id, k, v)| (id, rules.table_key(&k), Self::store_value(&rules, &mut pool, v)))
+            .collect();
+        let snapshot: LayoutSnapshot<JsKey, StoredValue> =
+            LayoutSnapshot { layout_version, hasher, options, level_sizes, entries, tombstones };
+        let (table, report) = ElasticHashTable::from_layout_snapshot(snapshot)?;
+        let table = if metadata { table.with_metadata(None) } else { table };
+        let mut table = Self::from_parts(table, rules);
+        table.pool = pool;
+        Ok(SnapshotImport { table, report })
+    }
+}
+
+fn array<'v>(name: &str, value: &'v Value) -> Result<&'v [Value], String> {
+    match value {
+        Value::Array(items) => Ok(items),
+        _ => Err(format!("snapshot {} must be an array", name)),
+    }
+}
+
+fn number<T: std::str::FromStr>(name: &str, value: &Value) -> Result<T, String> {
+    match value {
+        Value::Number(raw) => json::parse(name, raw),
+        _ => Err(format!("snapshot {} must be a number", name)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::bindings::validate::JsKey;
+    use crate::{ElasticHashTable, ImportMode, JsElasticHashTable, LAYOUT_VERSION};
+
+    /// a table with tombstones, rules and shared values
+    fn churned() -> JsElasticHashTable {
+        let options = r#"{"capacity": 256, "delta": 0.1, "seed": 4, "caseInsensitive": true, "dedup": true}"#;
+        let mut table = JsElasticHashTable::with_options(options).expect("invalid options");
+        for i in 0..200 {
+            table.insert(format!("Key\"{}", i), format!("value\n{}", i % 7)).unwrap();
+        }
+        for i in (0..200).step_by(3) {
+            table.remove(format!("key\"{}", i));
+        }
+        table
+    }
+
+    #[test]
+    fn test_snapshot_roundtrip_keeps_layout() {
+        let table = churned();
+        let json = table.export_snapshot();
+        assert!(json.starts_with(&format!("{{\"layoutVersion\":{},\"hasher\":\"fnv1a-splitmix64\"", LAYOUT_VERSION)));
+        let import = JsElasticHashTable::import_snapshot(&json).unwrap();
+        assert_eq!((import.mode().as_str(), import.warning()), ("layout-preserving", None));
+        let restored = import.into_table();
+        assert_eq!(restored.get_options(), table.get_options());
+        assert_eq!(restored.export_snapshot(), json);
+        assert_eq!(restored.search("KEY\"1".into()), Some("value\n1".into()));
+        assert_eq!(restored.search("key\"3".into()), None);
+        assert_eq!(restored.pool.as_ref().unwrap().distinct_values(), 7);
+        restored.table.check_invariants().unwrap();
+    }
+
+    #[test]
+    fn test_old_snapshot_is_reinserted() {
+        let table = churned();
+        let json = table.export_snapshot().replacen(
+            &format!("\"layoutVersion\":{}", LAYOUT_VERSION),
+            &format!("\"layoutVersion\":{}", LAYOUT_VERSION + 7),
+            1,
+        );
+        let import = JsElasticHashTable::import_snapshot(&json).unwrap();
+        assert_eq!(import.report.mode, ImportMode::Reinserted);
+        assert_eq!(import.mode(), "reinserted");
+        assert!(import.warning().unwrap().contains(&format!("layout version {}", LAYOUT_VERSION + 7)));
+        let restored = import.into_table();
+        assert_eq!(restored.table.len(), table.table.len());
+        assert_eq!(restored.search("key\"200".into()), None);
+        assert_eq!(restored.search("KEY\"199".into()), Some("value\n3".into()));
+    }
+
+    #[test]
+    fn test_bad_snapshots_are_rejected() {
+        let json = churned().export_snapshot();
+        for bad in [
+            "[]".to_string(),
+            json.replace("\"tombstones\"", "\"graves\""),
+            json.replacen("[0,", "[\"0\",", 1),
+            json.replacen("\"levels\":[", "\"levels\":[1,", 1),
+            json.replace("\"capacity\":256", "\"capacity\":0"),
+        ] {
+            assert!(JsElasticHashTable::import_snapshot(&bad).is_err(), "{}", bad);
+        }
+    }
+
+    #[test]
+    fn test_bytes_roundtrip_keeps_layout() {
+        let table = churned();
+        let bytes = table.to_bytes();
+        let import = JsElasticHashTable::import_bytes(&bytes, Some(&table.get_options())).unwrap();
+        assert_eq!((import.mode().as_str(), import.warning()), ("layout-preserving", None));
+        let restored = import.into_table();
+        assert_eq!(restored.get_options(), table.get_options());
+        assert_eq!(restored.export_snapshot(), table.export_snapshot());
+        assert_eq!(restored.to_bytes(), bytes);
+        assert_eq!(restored.search("KEY\"1".into()), Some("value\n1".into()));
+        assert_eq!(restored.pool.as_ref().unwrap().distinct_values(), 7);
+
+        // without options the rules are the defaults: keys match exactly as stored
+        let plain = JsElasticHashTable::import_bytes(&bytes, None).unwrap().into_table();
+        assert_eq!(plain.search("Key\"1".into()), Some("value\n1".into()));
+        assert_eq!(plain.search("KEY\"1".into()), None);
+
+        assert!(JsElasticHashTable::import_bytes(&bytes[..bytes.len() - 1], None).is_err());
+        assert!(JsElasticHashTable::import_bytes(&bytes, Some("{}")).is_err());
+    }
+
+    #[test]
+    fn test_native_table_loads_slot_for_slot() {
+        let mut native = ElasticHashTable::with_seed(4096, 0.1, 9);
+        for i in 0..3500 {
+            native.insert(format!("user:{}", i), format!("{{\"id\":{}}}", i)).expect("Insertion failed");
+        }
+        for i in (0..3500).step_by(5) {
+            native.remove(&format!("user:{}", i));
+        }
+        let bytes = native.to_bytes();
+        let import = JsElasticHashTable::import_bytes(&bytes, None).unwrap();
+        assert_eq!(import.report.mode, ImportMode::LayoutPreserving);
+        let table = import.into_table();
+        assert_eq!((table.table.len(), table.table.capacity()), (native.len(), native.capacity()));
+        for (key, value) in native.iter() {
+            assert_eq!(table.table.find(&JsKey::Exact(key.clone())), native.find(key));
+            assert_eq!(table.search(key.clone()).as_ref(), Some(value));
+        }
+        assert!(table.table.displaced.is_empty());
+        assert_eq!(table.to_bytes(), bytes);
+    }
+}
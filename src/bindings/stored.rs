@@ -0,0 +1,103 @@
+//! values as the JS binding stores them: plain, compressed above a size threshold,
+//! or shared through a ValuePool
+
+use std::borrow::Cow;
+use std::rc::Rc;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) enum StoredValue {
+    Plain(String),
+    /// an LZ4 block of a value of len bytes
+    #[cfg(feature = "compress")]
+    Compressed { block: Box<[u8]>, len: usize },
+    /// a value interned in the table's ValuePool
+    Shared(Rc<str>),
+}
+
+impl StoredValue {
+    /// store value, compressed if it is longer than compress_above bytes and compression saves space
+    pub(crate) fn new(value: String, compress_above: Option<usize>) -> Self {
+        #[cfg(feature = "compress")]
+        if compress_above.is_some_and(|limit| value.len() > limit) {
+            let block = crate::compress::compress(value.as_bytes());
+            if block.len() < value.len() {
+                return StoredValue::Compressed {
+                    block: block.into_boxed_slice(),
+                    len: value.len(),
+                };
+            }
+        }
+        #[cfg(not(feature = "compress"))]
+        let _ = compress_above;
+        StoredValue::Plain(value)
+    }
+
+    pub(crate) fn get(&self) -> Cow<'_, str> {
+        match self {
+            StoredValue::Plain(s) => Cow::Borrowed(s),
+            StoredValue::Shared(s) => Cow::Borrowed(s),
+            #[cfg(feature = "compress")]
+            StoredValue::Compressed { block, len } => {
+                let bytes = crate::compress::decompress(block, *len).expect("corrupt compressed value");
+                Cow::Owned(String::from_utf8(bytes).expect("compressed value is not UTF-8"))
+            }
+        }
+    }
+
+    pub(crate) fn into_string(self) -> String {
+        match self {
+            StoredValue::Plain(s) => s,
+            other => other.get().into_owned(),
+        }
+    }
+
+    /// bytes of the value as stored
+    pub(crate) fn stored_len(&self) -> usize {
+        match self {
+            StoredValue::Plain(s) => s.len(),
+            StoredValue::Shared(s) => s.len(),
+            #[cfg(feature = "compress")]
+            StoredValue::Compressed { block, .. } => block.len(),
+        }
+    }
+
+    /// heap bytes owned by this entry alone; shared values are accounted to their pool
+    pub(crate) fn owned_len(&self) -> usize {
+        match self {
+            StoredValue::Shared(_) => 0,
+            other => other.stored_len(),
+        }
+    }
+
+    /// bytes of the value as inserted
+    pub(crate) fn len(&self) -> usize {
+        match self {
+            StoredValue::Plain(s) => s.len(),
+            StoredValue::Shared(s) => s.len(),
+            #[cfg(feature = "compress")]
+            StoredValue::Compressed { len, .. } => *len,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::StoredValue;
+
+    #[test]
+    fn test_roundtrip() {
+        let blob = "{\"k\":\"v\"},".repeat(100);
+        for value in ["".to_string(), "x".to_string(), blob.clone()] {
+            let stored = StoredValue::new(value.clone(), Some(0));
+            assert_eq!(stored.get(), value);
+            assert_eq!(stored.len(), value.len());
+            assert_eq!(stored.clone().into_string(), value);
+        }
+
+        let stored = StoredValue::new(blob.clone(), Some(64));
+        assert_eq!(stored.stored_len() < blob.len(), cfg!(feature = "compress"));
+        // at or below the threshold values stay plain
+        assert_eq!(StoredValue::new(blob.clone(), Some(blob.len())), StoredValue::Plain(blob.clone()));
+        assert_eq!(StoredValue::new(blob.clone(), None), StoredValue::Plain(blob));
+    }
+}
@@ -0,0 +1,466 @@
+//! input rules the JS binding applies before a key or value reaches the table,
+//! configured through the `maxKeyLength`, `maxValueLength`, `keyPattern`, `normalize`, `caseInsensitive` and
+//! `strictUnicode` options;
+//! also carries `compressAbove` and `dedup`, which pick how values are stored
+
+use std::borrow::Cow;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+
+use wasm_bindgen::prelude::*;
+
+use crate::elastic::table::check_dimensions;
+use crate::{json, CaseInsensitive, ElasticHashError};
+
+/// limits on keys and values; lengths are counted in UTF-8 bytes
+#[derive(Clone, Debug, Default, PartialEq)]
+pub(crate) struct KeyRules {
+    max_key_length: Option<usize>,
+    max_value_length: Option<usize>,
+    key_pattern: Option<BytePattern>,
+    /// store and look up keys in NFC; needs the `normalize` feature
+    nfc: bool,
+    /// match keys case-insensitively, see CaseInsensitive
+    case_insensitive: bool,
+    /// compress values longer than this many bytes; needs the `compress` feature
+    compress_above: Option<usize>,
+    /// store each distinct value once, see ValuePool
+    dedup: bool,
+    /// reject keys and values containing U+FFFD, see Violation::Replacement
+    strict_unicode: bool,
+}
+
+/// key type of the JS binding; all keys of one table use the same variant
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) enum JsKey {
+    Exact(String),
+    CaseInsensitive(CaseInsensitive),
+}
+
+/// hashes like the key alone, without the variant, so an exact-key table places keys where an
+/// ElasticHashTable<String, _> with the same seed does and fromBytes can keep its layout
+impl Hash for JsKey {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        match self {
+            JsKey::Exact(k) => k.hash(state),
+            JsKey::CaseInsensitive(k) => k.hash(state),
+        }
+    }
+}
+
+impl JsKey {
+    pub(crate) fn as_str(&self) -> &str {
+        match self {
+            JsKey::Exact(k) => k,
+            JsKey::CaseInsensitive(k) => k.as_str(),
+        }
+    }
+
+    pub(crate) fn into_string(self) -> String {
+        match self {
+            JsKey::Exact(k) => k,
+            JsKey::CaseInsensitive(k) => k.into_inner(),
+        }
+    }
+}
+
+/// the set of bytes a key may consist of
+/// written as single ASCII characters and ranges, e.g. `a-zA-Z0-9_.-`; a '-' that is first or last is literal.
+/// bytes outside ASCII never match.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct BytePattern {
+    source: String,
+    allowed: [bool; 128],
+}
+
+impl BytePattern {
+    pub(crate) fn parse(source: &str) -> Result<Self, String> {
+        if source.is_empty() || !source.is_ascii() {
+            return Err("keyPattern must be a non-empty ASCII character set".into());
+        }
+        let bytes = source.as_bytes();
+        let mut allowed = [false; 128];
+        let mut i = 0;
+        while i < bytes.len() {
+            if i + 2 < bytes.len() && bytes[i + 1] == b'-' {
+                let (lo, hi) = (bytes[i], bytes[i + 2]);
+                if lo > hi {
+                    return Err(format!("invalid range in keyPattern: {}-{}", lo as char, hi as char));
+                }
+                allowed[lo as usize..=hi as usize].iter_mut().for_each(|a| *a = true);
+                i += 3;
+            } else {
+                allowed[bytes[i] as usize] = true;
+                i += 1;
+            }
+        }
+        Ok(BytePattern {
+            source: source.to_string(),
+            allowed,
+        })
+    }
+
+    /// offset of the first byte of s outside the set
+    fn mismatch(&self, s: &str) -> Option<usize> {
+        s.bytes().position(|b| !self.allowed.get(b as usize).copied().unwrap_or(false))
+    }
+}
+
+/// why an input was rejected
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) enum Violation {
+    KeyTooLong { len: usize, max: usize },
+    ValueTooLong { len: usize, max: usize },
+    /// the key byte at offset is not in keyPattern
+    KeyPattern { offset: usize },
+    /// an insertTagged tag that is not an integer a JS number holds exactly
+    Tag,
+    /// U+FFFD at byte offset of the key, or of the value; with strictUnicode, since it may be an
+    /// unpaired surrogate that wasm-bindgen replaced on the way in
+    Replacement { in_value: bool, offset: usize },
+    /// a JsEntry used on another table, or after its table changed
+    StaleEntry,
+    /// a JsInsertBatch used after commit or abort
+    BatchClosed,
+    /// a JsInsertBatch committed to another table than the one that began it
+    WrongTable,
+    /// an insert of a new key found no room, for the reason the table gave
+    TableFull(String),
+    /// a constructor argument the table cannot be built with
+    InvalidArgument(String),
+    /// a JsTableRegistry already holds a table of this name
+    TableExists(String),
+    /// a JsTableRegistry holds no table of this name, or a JsNamespace outlived its table
+    NoSuchTable(String),
+}
+
+impl Violation {
+    pub(crate) fn code(&self) -> &'static str {
+        match self {
+            Violation::KeyTooLong { .. } | Violation::KeyPattern { .. } => "ERR_INVALID_KEY",
+            Violation::ValueTooLong { .. } => "ERR_INVALID_VALUE",
+            Violation::Tag => "ERR_INVALID_TAG",
+            Violation::Replacement { .. } => "ERR_LOSSY_STRING",
+            Violation::StaleEntry => "ERR_STALE_ENTRY",
+            Violation::BatchClosed => "ERR_BATCH_CLOSED",
+            Violation::WrongTable => "ERR_WRONG_TABLE",
+            Violation::TableFull(_) => "ERR_TABLE_FULL",
+            Violation::InvalidArgument(_) => "ERR_INVALID_ARGUMENT",
+            Violation::TableExists(_) => "ERR_TABLE_EXISTS",
+            Violation::NoSuchTable(_) => "ERR_NO_SUCH_TABLE",
+        }
+    }
+}
+
+/// ERR_TABLE_FULL for an insert that found no room, ERR_INVALID_ARGUMENT for bad dimensions or levels
+impl From<&ElasticHashError> for Violation {
+    fn from(error: &ElasticHashError) -> Self {
+        match error {
+            ElasticHashError::InvalidCapacity
+            | ElasticHashError::InvalidDelta(_)
+            | ElasticHashError::TooManyLevels { .. }
+            | ElasticHashError::InvalidLayout(_)
+            | ElasticHashError::CapacityTooSmall { .. } => Violation::InvalidArgument(error.to_string()),
+            _ => Violation::TableFull(error.to_string()),
+        }
+    }
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Violation::KeyTooLong { len, max } => write!(f, "key is {} bytes long, the limit is {}", len, max),
+            Violation::ValueTooLong { len, max } => write!(f, "value is {} bytes long, the limit is {}", len, max),
+            Violation::KeyPattern { offset } => write!(f, "key byte {} is not allowed by keyPattern", offset),
+            Violation::Tag => f.write_str("tag must be an integer from 0 to 2^53 - 1"),
+            Violation::Replacement { in_value, offset } => write!(
+                f,
+                "{} byte {} is U+FFFD, which may be an unpaired surrogate replaced on the way into wasm; \
+                 use JsElasticHashTableBytes for strings that are not valid UTF-16",
+                if *in_value { "value" } else { "key" },
+                offset
+            ),
+            Violation::StaleEntry => f.write_str("entry is from another table, or the table changed since the lookup"),
+            Violation::BatchClosed => f.write_str("batch was already committed or aborted"),
+            Violation::WrongTable => f.write_str("batch was begun on another table"),
+            Violation::TableFull(reason) | Violation::InvalidArgument(reason) => f.write_str(reason),
+            Violation::TableExists(name) => write!(f, "a table named {} already exists", json::quote(name)),
+            Violation::NoSuchTable(name) => write!(f, "no table named {}", json::quote(name)),
+        }
+    }
+}
+
+impl KeyRules {
+    /// option names read by from_fields
+    pub(crate) const FIELDS: [&'static str; 8] = [
+        "maxKeyLength",
+        "maxValueLength",
+        "keyPattern",
+        "normalize",
+        "caseInsensitive",
+        "compressAbove",
+        "dedup",
+        "strictUnicode",
+    ];
+
+    /// pick the rule options out of a parsed options object, ignoring every other field
+    pub(crate) fn from_fields(fields: &[(String, String)]) -> Result<Self, String> {
+        let mut rules = KeyRules::default();
+        for (name, value) in fields {
+            match name.as_str() {
+                "maxKeyLength" => rules.max_key_length = Some(json::parse(name, value)?),
+                "maxValueLength" => rules.max_value_length = Some(json::parse(name, value)?),
+                "keyPattern" => rules.key_pattern = Some(BytePattern::parse(value)?),
+                "normalize" => {
+                    rules.nfc = match value.as_str() {
+                        "nfc" if cfg!(feature = "normalize") => true,
+                        "nfc" => return Err("normalize: \"nfc\" needs the normalize feature".into()),
+                        "none" => false,
+                        _ => return Err(format!("invalid value for normalize: {}", value)),
+                    }
+                }
+                "caseInsensitive" => rules.case_insensitive = json::parse(name, value)?,
+                "compressAbove" if cfg!(feature = "compress") => rules.compress_above = Some(json::parse(name, value)?),
+                "compressAbove" => return Err("compressAbove needs the compress feature".into()),
+                "dedup" => rules.dedup = json::parse(name, value)?,
+                "strictUnicode" => rules.strict_unicode = json::parse(name, value)?,
+                _ => {}
+            }
+        }
+        if rules.dedup && rules.compress_above.is_some() {
+            return Err("dedup and compressAbove cannot be combined".into());
+        }
+        Ok(rules)
+    }
+
+    /// the rules as `"name":value` JSON fragments, in the form from_fields reads
+    pub(crate) fn json_fields(&self) -> Vec<String> {
+        let mut fields = Vec::new();
+        if let Some(max) = self.max_key_length {
+            fields.push(format!("\"maxKeyLength\":{}", max));
+        }
+        if let Some(max) = self.max_value_length {
+            fields.push(format!("\"maxValueLength\":{}", max));
+        }
+        if let Some(pattern) = &self.key_pattern {
+            fields.push(format!("\"keyPattern\":{}", json::quote(&pattern.source)));
+        }
+        if self.nfc {
+            fields.push("\"normalize\":\"nfc\"".into());
+        }
+        if self.case_insensitive {
+            fields.push("\"caseInsensitive\":true".into());
+        }
+        if let Some(limit) = self.compress_above {
+            fields.push(format!("\"compressAbove\":{}", limit));
+        }
+        if self.dedup {
+            fields.push("\"dedup\":true".into());
+        }
+        if self.strict_unicode {
+            fields.push("\"strictUnicode\":true".into());
+        }
+        fields
+    }
+
+    pub(crate) fn compress_above(&self) -> Option<usize> {
+        self.compress_above
+    }
+
+    pub(crate) fn dedup(&self) -> bool {
+        self.dedup
+    }
+
+    /// the table key for key: normalized, and case-insensitive if so configured
+    pub(crate) fn table_key(&self, key: &str) -> JsKey {
+        let key = self.canonical_key(key).into_owned();
+        if self.case_insensitive {
+            JsKey::CaseInsensitive(CaseInsensitive::new(key))
+        } else {
+            JsKey::Exact(key)
+        }
+    }
+
+    /// key in the form it is stored and looked up under
+    pub(crate) fn canonical_key<'k>(&self, key: &'k str) -> Cow<'k, str> {
+        #[cfg(feature = "normalize")]
+        if self.nfc {
+            return crate::normalize::nfc(key);
+        }
+        Cow::Borrowed(key)
+    }
+
+    pub(crate) fn check_key(&self, key: &str) -> Result<(), Violation> {
+        if let Some(max) = self.max_key_length {
+            if key.len() > max {
+                return Err(Violation::KeyTooLong { len: key.len(), max });
+            }
+        }
+        if let Some(offset) = self.key_pattern.as_ref().and_then(|p| p.mismatch(key)) {
+            return Err(Violation::KeyPattern { offset });
+        }
+        self.check_replacement(key, false)
+    }
+
+    pub(crate) fn check_value(&self, value: &str) -> Result<(), Violation> {
+        match self.max_value_length {
+            Some(max) if value.len() > max => Err(Violation::ValueTooLong { len: value.len(), max }),
+            _ => self.check_replacement(value, true),
+        }
+    }
+
+    fn check_replacement(&self, s: &str, in_value: bool) -> Result<(), Violation> {
+        match s.find('\u{FFFD}') {
+            Some(offset) if self.strict_unicode => Err(Violation::Replacement { in_value, offset }),
+            _ => Ok(()),
+        }
+    }
+
+    pub(crate) fn check(&self, key: &str, value: &str) -> Result<(), Violation> {
+        self.check_key(key)?;
+        self.check_value(value)
+    }
+}
+
+/// error thrown to JS for a rejected key, value or argument, or an insert that found no room
+#[wasm_bindgen]
+#[derive(Clone, Debug)]
+pub struct ValidationError {
+    code: String,
+    message: String,
+    index: Option<u32>,
+}
+
+#[wasm_bindgen]
+impl ValidationError {
+    /// "ERR_INVALID_KEY", "ERR_INVALID_VALUE", "ERR_LOSSY_STRING" (with strictUnicode),
+    /// "ERR_TABLE_FULL" when an insert of a new key finds no room, "ERR_INVALID_ARGUMENT" from constructors,
+    /// or from insertTagged "ERR_INVALID_TAG", or from JsEntry "ERR_STALE_ENTRY",
+    /// or from JsInsertBatch "ERR_BATCH_CLOSED" or "ERR_WRONG_TABLE",
+    /// or from JsTableRegistry and JsNamespace "ERR_TABLE_EXISTS" or "ERR_NO_SUCH_TABLE"
+    #[wasm_bindgen(getter)]
+    pub fn code(&self) -> String {
+        self.code.clone()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn message(&self) -> String {
+        self.message.clone()
+    }
+
+    /// position of the offending element in a batch call, undefined for single inserts
+    #[wasm_bindgen(getter)]
+    pub fn index(&self) -> Option<u32> {
+        self.index
+    }
+}
+
+impl ValidationError {
+    pub(crate) fn new(violation: &Violation, index: Option<u32>) -> Self {
+        ValidationError {
+            code: violation.code().to_string(),
+            message: violation.to_string(),
+            index,
+        }
+    }
+
+    /// ERR_INVALID_ARGUMENT where building a table of capacity slots with delta would panic
+    pub(crate) fn check_dimensions(capacity: usize, delta: f64) -> Result<(), Self> {
+        check_dimensions(capacity, delta).map_err(|e| ValidationError::new(&Violation::from(&e), None))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rules(json: &str) -> KeyRules {
+        KeyRules::from_fields(&json::flat_object(json).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_length_limits_at_boundaries() {
+        let rules = rules(r#"{"maxKeyLength": 4, "maxValueLength": 0}"#);
+        assert_eq!(rules.check("abcd", ""), Ok(()));
+        assert_eq!(rules.check("abcde", ""), Err(Violation::KeyTooLong { len: 5, max: 4 }));
+        assert_eq!(rules.check("", "x"), Err(Violation::ValueTooLong { len: 1, max: 0 }));
+        // lengths are UTF-8 bytes: "éé" is 4 bytes, "ééé" is 6
+        assert_eq!(rules.check("éé", ""), Ok(()));
+        assert_eq!(rules.check("ééé", ""), Err(Violation::KeyTooLong { len: 6, max: 4 }));
+        assert_eq!(rules.check("abcde", "").unwrap_err().code(), "ERR_INVALID_KEY");
+        assert_eq!(rules.check("", "x").unwrap_err().code(), "ERR_INVALID_VALUE");
+    }
+
+    #[test]
+    fn test_key_pattern() {
+        let rules = rules(r#"{"keyPattern": "a-z0-9_-"}"#);
+        assert_eq!(rules.check("user_42-x", "anything\n"), Ok(()));
+        assert_eq!(rules.check("User", ""), Err(Violation::KeyPattern { offset: 0 }));
+        assert_eq!(rules.check("ab\tc", ""), Err(Violation::KeyPattern { offset: 2 }));
+        assert_eq!(rules.check("né", ""), Err(Violation::KeyPattern { offset: 1 }));
+
+        assert!(BytePattern::parse("").is_err());
+        assert!(BytePattern::parse("z-a").is_err());
+        assert!(BytePattern::parse("é").is_err());
+        assert!(BytePattern::parse("-").unwrap().mismatch("--").is_none());
+    }
+
+    #[test]
+    fn test_normalize_option() {
+        let plain = rules(r#"{"normalize": "none"}"#);
+        assert_eq!(plain.canonical_key("e\u{301}"), "e\u{301}");
+        assert!(KeyRules::from_fields(&json::flat_object(r#"{"normalize": "nfd"}"#).unwrap()).is_err());
+        let nfc = KeyRules::from_fields(&json::flat_object(r#"{"normalize": "nfc"}"#).unwrap());
+        if cfg!(feature = "normalize") {
+            assert_eq!(nfc.unwrap().canonical_key("e\u{301}"), "\u{e9}");
+        } else {
+            assert!(nfc.is_err());
+        }
+    }
+
+    #[test]
+    fn test_dedup_option() {
+        assert!(rules(r#"{"dedup": true}"#).dedup());
+        assert!(KeyRules::from_fields(&json::flat_object(r#"{"dedup": 1}"#).unwrap()).is_err());
+        let both = json::flat_object(r#"{"dedup": true, "compressAbove": 10}"#).unwrap();
+        assert!(KeyRules::from_fields(&both).is_err());
+    }
+
+    #[test]
+    fn test_strict_unicode() {
+        let lenient = rules("{}");
+        assert_eq!(lenient.check("a\u{FFFD}", "\u{FFFD}"), Ok(()));
+        let strict = rules(r#"{"strictUnicode": true}"#);
+        assert_eq!(strict.check("\0\u{1F600}\u{10FFFF}", "\u{D7FF}\u{E000}\0"), Ok(()));
+        assert_eq!(
+            strict.check("a\u{FFFD}", ""),
+            Err(Violation::Replacement { in_value: false, offset: 1 })
+        );
+        assert_eq!(
+            strict.check("a", "\u{1F600}\u{FFFD}"),
+            Err(Violation::Replacement { in_value: true, offset: 4 })
+        );
+        assert_eq!(strict.check("\u{FFFD}", "").unwrap_err().code(), "ERR_LOSSY_STRING");
+        assert!(strict.check("\u{FFFD}", "").unwrap_err().to_string().contains("JsElasticHashTableBytes"));
+    }
+
+    #[test]
+    fn test_rules_roundtrip_through_json() {
+        let original = rules(r#"{"maxKeyLength": 8, "keyPattern": "a-z\"", "strictUnicode": true, "capacity": 10}"#);
+        let json = format!("{{{}}}", original.json_fields().join(","));
+        assert_eq!(rules(&json), original);
+        assert_eq!(rules("{}"), KeyRules::default());
+    }
+
+    #[test]
+    fn test_table_errors_carry_codes() {
+        let err = ValidationError::check_dimensions(16, 1.0).unwrap_err();
+        assert_eq!(err.code(), "ERR_INVALID_ARGUMENT");
+        assert_eq!(err.message(), "delta must be between 0 and 1.");
+        assert_eq!(ValidationError::check_dimensions(0, 0.5).unwrap_err().code(), "ERR_INVALID_ARGUMENT");
+        for error in [ElasticHashError::MaxInsertsReached { max: 9 }, ElasticHashError::TableSaturated] {
+            let err = ValidationError::new(&Violation::from(&error), Some(2));
+            assert_eq!((err.code(), err.message(), err.index()), ("ERR_TABLE_FULL".into(), error.to_string(), Some(2)));
+        }
+    }
+}
@@ -0,0 +1,219 @@
+//! JsElasticHashTableValues: string keys with any JS value, stored as is
+//!
+//! JsElasticHashTable keeps strings, so other values need JSON.stringify on the way in and JSON.parse
+//! on the way out, which is slow and loses Maps, Sets, typed arrays and functions. This table holds a
+//! handle to the JS value itself: search returns the same value that was inserted, so an object comes
+//! back `===` to the one stored and changes made through either are seen by both. Primitives compare
+//! by value as usual. Nothing is copied, and the table keeps its values alive until they are replaced
+//! or removed.
+//!
+//! ```ignore
+//! const table = new JsElasticHashTableValues(1024, 0.1);
+//! const bytes = new Uint8Array([1, 2, 3]);
+//! table.insert("bytes", bytes);
+//! table.search("bytes") === bytes; // true
+//! table.search("missing"); // undefined
+//! ```
+
+use wasm_bindgen::prelude::*;
+
+use super::validate::Violation;
+use super::ValidationError;
+use crate::ElasticHashTable;
+
+/// ElasticHashTable<String, JsValue> for JS
+/// JsValue is not Send, which wasm-bindgen types need not be
+#[wasm_bindgen]
+pub struct JsElasticHashTableValues {
+    table: ElasticHashTable<String, JsValue>,
+}
+
+#[wasm_bindgen]
+impl JsElasticHashTableValues {
+    /// throws ERR_INVALID_ARGUMENT unless capacity is positive and delta between 0 and 1
+    #[wasm_bindgen(constructor)]
+    pub fn new(capacity: usize, delta: f64) -> Result<JsElasticHashTableValues, ValidationError> {
+        ValidationError::check_dimensions(capacity, delta)?;
+        Ok(JsElasticHashTableValues {
+            table: ElasticHashTable::new(capacity, delta),
+        })
+    }
+
+    /// insert, or replace the value if key is present; throws ERR_TABLE_FULL when key is new and there is no room
+    #[wasm_bindgen]
+    pub fn insert(&mut self, key: String, value: JsValue) -> Result<(), ValidationError> {
+        self.table
+            .insert(key, value)
+            .map_err(|err| ValidationError::new(&Violation::from(err.error()), None))?;
+        Ok(())
+    }
+
+    /// the stored value itself, not a copy; undefined when key is absent
+    #[wasm_bindgen]
+    pub fn search(&self, key: &str) -> JsValue {
+        self.table.get(key).cloned().unwrap_or(JsValue::UNDEFINED)
+    }
+
+    /// whether key is present, also when its value is undefined
+    #[wasm_bindgen]
+    pub fn has(&self, key: &str) -> bool {
+        self.table.contains_key(key)
+    }
+
+    /// remove key, returning its value; undefined when key was absent
+    #[wasm_bindgen]
+    pub fn remove(&mut self, key: &str) -> JsValue {
+        self.table.remove(key).unwrap_or(JsValue::UNDEFINED)
+    }
+
+    /// remove every entry, releasing the values, and keep the capacity
+    #[wasm_bindgen]
+    pub fn clear(&mut self) {
+        self.table.clear();
+    }
+
+    /// number of entries, as on JsElasticHashTable
+    #[wasm_bindgen(getter)]
+    pub fn len(&self) -> usize {
+        self.table.len()
+    }
+
+    #[wasm_bindgen(getter, js_name = isEmpty)]
+    pub fn is_empty(&self) -> bool {
+        self.table.is_empty()
+    }
+
+    /// total number of slots
+    #[wasm_bindgen(getter)]
+    pub fn capacity(&self) -> usize {
+        self.table.capacity()
+    }
+
+    /// entries the table accepts before insert throws
+    #[wasm_bindgen(getter, js_name = maxInserts)]
+    pub fn max_inserts(&self) -> usize {
+        self.table.max_inserts()
+    }
+
+    /// new keys the table still accepts
+    #[wasm_bindgen(getter, js_name = remainingCapacity)]
+    pub fn remaining_capacity(&self) -> usize {
+        self.table.remaining_capacity()
+    }
+
+    /// whether inserting a new key would throw
+    #[wasm_bindgen(getter, js_name = isFull)]
+    pub fn is_full(&self) -> bool {
+        self.table.is_full()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use wasm_bindgen::JsValue;
+
+    use super::JsElasticHashTableValues;
+
+    // natively only the constant JsValues exist, and neither cloning nor comparing them works
+    // outside wasm, so these tests stick to moving values in and out; wasm_tests checks identity
+
+    #[test]
+    fn test_values_move_in_and_out() {
+        let mut table = JsElasticHashTableValues::new(64, 0.1).unwrap();
+        assert!(table.is_empty());
+        table.insert("null".to_string(), JsValue::NULL).unwrap();
+        table.insert("undefined".to_string(), JsValue::UNDEFINED).unwrap();
+        table.insert("flag".to_string(), JsValue::TRUE).unwrap();
+        table.insert("flag".to_string(), JsValue::FALSE).unwrap();
+        assert_eq!(table.len(), 3);
+        // a stored undefined is still present
+        assert!(table.has("undefined") && table.has("flag") && !table.has("missing"));
+
+        table.remove("flag");
+        assert!(!table.has("flag"));
+        assert_eq!((table.len(), table.remaining_capacity()), (2, table.max_inserts() - 2));
+        table.clear();
+        assert!(table.is_empty() && !table.has("null"));
+        assert_eq!(table.capacity(), 64);
+    }
+
+    #[test]
+    fn test_errors_carry_codes() {
+        let err = JsElasticHashTableValues::new(0, 0.1).err().map(|e| e.code());
+        assert_eq!(err.as_deref(), Some("ERR_INVALID_ARGUMENT"));
+        let mut table = JsElasticHashTableValues::new(32, 0.1).unwrap();
+        while !table.is_full() {
+            table.insert(table.len().to_string(), JsValue::NULL).unwrap();
+        }
+        let err = table.insert("new".to_string(), JsValue::NULL).unwrap_err();
+        assert_eq!(err.code(), "ERR_TABLE_FULL");
+        // replacing needs no room
+        table.insert("0".to_string(), JsValue::TRUE).unwrap();
+    }
+}
+
+#[cfg(all(test, target_arch = "wasm32"))]
+mod wasm_tests {
+    use js_sys::{Object, Reflect, Uint8Array};
+    use wasm_bindgen::{JsCast, JsValue};
+    use wasm_bindgen_test::wasm_bindgen_test;
+
+    use super::JsElasticHashTableValues;
+
+    #[wasm_bindgen_test]
+    fn test_numbers_come_back_as_stored() {
+        let mut table = JsElasticHashTableValues::new(64, 0.1).unwrap();
+        for (key, n) in [("int", 42.0), ("float", 1.5), ("negative zero", -0.0), ("nan", f64::NAN)] {
+            table.insert(key.to_string(), JsValue::from_f64(n)).unwrap();
+        }
+        // Object.is tells -0 from 0 and matches NaN with itself
+        assert!(Object::is(&table.search("int"), &JsValue::from_f64(42.0)));
+        assert!(Object::is(&table.search("float"), &JsValue::from_f64(1.5)));
+        assert!(Object::is(&table.search("negative zero"), &JsValue::from_f64(-0.0)));
+        assert!(!Object::is(&table.search("negative zero"), &JsValue::from_f64(0.0)));
+        assert!(Object::is(&table.search("nan"), &JsValue::from_f64(f64::NAN)));
+        assert!(table.search("int").as_f64().is_some());
+        assert!(table.search("missing").is_undefined());
+    }
+
+    #[wasm_bindgen_test]
+    fn test_objects_come_back_identical() {
+        let mut table = JsElasticHashTableValues::new(64, 0.1).unwrap();
+        let object = Object::new();
+        Reflect::set(&object, &"name".into(), &"first".into()).unwrap();
+        table.insert("object".to_string(), object.clone().into()).unwrap();
+
+        let found = table.search("object");
+        assert!(Object::is(&found, &object));
+        assert!(found.is_object());
+        // the table holds the object, not a copy: a change through either handle shows in the other
+        Reflect::set(&object, &"name".into(), &"second".into()).unwrap();
+        assert_eq!(Reflect::get(&table.search("object"), &"name".into()).unwrap(), "second");
+
+        // an equal but distinct object is not the stored one
+        let twin = Object::new();
+        Reflect::set(&twin, &"name".into(), &"second".into()).unwrap();
+        assert!(!Object::is(&table.search("object"), &twin));
+        assert!(Object::is(&table.remove("object"), &object));
+        assert!(table.search("object").is_undefined());
+    }
+
+    #[wasm_bindgen_test]
+    fn test_typed_arrays_come_back_identical() {
+        let mut table = JsElasticHashTableValues::new(64, 0.1).unwrap();
+        let bytes = Uint8Array::from(&[1u8, 2, 3][..]);
+        table.insert("bytes".to_string(), bytes.clone().into()).unwrap();
+
+        let found = table.search("bytes");
+        assert!(Object::is(&found, &bytes));
+        assert!(found.is_instance_of::<Uint8Array>());
+        bytes.set_index(0, 9);
+        assert_eq!(Uint8Array::from(table.search("bytes")).to_vec(), [9, 2, 3]);
+
+        // replacing stores the new handle
+        let other = Uint8Array::from(&[1u8, 2, 3][..]);
+        table.insert("bytes".to_string(), other.clone().into()).unwrap();
+        assert!(Object::is(&table.search("bytes"), &other));
+        assert!(!Object::is(&table.search("bytes"), &bytes));
+    }
+}
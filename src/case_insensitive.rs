@@ -0,0 +1,135 @@
+//! case-insensitive string keys that remember their original casing
+//!
+//! ```ignore
+//! let mut headers = ElasticHashTable::new(64, 0.1);
+//! headers.insert(CaseInsensitive::from("Content-Type"), "text/html")?;
+//! assert_eq!(headers.search(&CaseInsensitive::from("content-type")), Some(&"text/html"));
+//! assert_eq!(headers.keys().next().unwrap().as_str(), "Content-Type");
+//! ```
+
+use std::fmt;
+use std::hash::{Hash, Hasher};
+
+/// a String key compared and hashed case-folded
+/// folding is ASCII-only by default; with the `normalize` feature every character is
+/// mapped through its Unicode lowercase when that is a single character
+#[derive(Clone, Debug, Default)]
+pub struct CaseInsensitive(String);
+
+impl CaseInsensitive {
+    pub fn new(key: String) -> Self {
+        CaseInsensitive(key)
+    }
+
+    /// the key as it was created
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    pub fn into_inner(self) -> String {
+        self.0
+    }
+
+    fn folded(&self) -> impl Iterator<Item = char> + '_ {
+        self.0.chars().map(fold)
+    }
+}
+
+#[cfg(not(feature = "normalize"))]
+fn fold(c: char) -> char {
+    c.to_ascii_lowercase()
+}
+
+#[cfg(feature = "normalize")]
+fn fold(c: char) -> char {
+    let mut lower = c.to_lowercase();
+    match (lower.next(), lower.next()) {
+        (Some(l), None) => l,
+        _ => c,
+    }
+}
+
+impl PartialEq for CaseInsensitive {
+    fn eq(&self, other: &Self) -> bool {
+        self.folded().eq(other.folded())
+    }
+}
+
+impl Eq for CaseInsensitive {}
+
+/// hashes like the str of the folded key, so an already lowercase key hashes like its plain String
+impl Hash for CaseInsensitive {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        let mut buf = [0u8; 4];
+        for c in self.folded() {
+            state.write(c.encode_utf8(&mut buf).as_bytes());
+        }
+        state.write_u8(0xff);
+    }
+}
+
+impl fmt::Display for CaseInsensitive {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl From<String> for CaseInsensitive {
+    fn from(key: String) -> Self {
+        CaseInsensitive(key)
+    }
+}
+
+impl From<&str> for CaseInsensitive {
+    fn from(key: &str) -> Self {
+        CaseInsensitive(key.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::CaseInsensitive;
+    use crate::{hash_with_seed, ElasticHashTable};
+
+    fn key(s: &str) -> CaseInsensitive {
+        CaseInsensitive::from(s)
+    }
+
+    #[test]
+    fn test_fold_eq_and_hash() {
+        assert_eq!(key("Content-Type"), key("CONTENT-type"));
+        assert_ne!(key("Content-Type"), key("Content-Typo"));
+        assert_eq!(hash_with_seed(&key("Content-Type"), 3), hash_with_seed("content-type", 3));
+        assert_eq!(hash_with_seed(&key("ABC"), 3), hash_with_seed(&key("abc"), 3));
+    }
+
+    #[test]
+    fn test_non_ascii_fold() {
+        // under the normalize feature non-ASCII letters fold too
+        assert_eq!(key("ÉTÉ") == key("été"), cfg!(feature = "normalize"));
+    }
+
+    #[test]
+    fn test_case_insensitive_table_keeps_original_casing() {
+        let mut table = ElasticHashTable::deterministic(64, 0.1);
+        table.insert(key("Foo"), 1).expect("Insertion failed");
+        table.insert(key("bar"), 2).expect("Insertion failed");
+
+        assert_eq!(table.get(&key("FOO")), Some(&1));
+        assert_eq!(table.get(&key("BaR")), Some(&2));
+        let (stored, value) = table.get_key_value(&key("fOO")).unwrap();
+        assert_eq!((stored.as_str(), *value), ("Foo", 1));
+
+        // writing through "FOO" updates the entry stored under "Foo" in place
+        let slot = table.probe(&key("Foo")).0;
+        assert_eq!(table.modify(&key("FOO"), |v| *v = 10), Some(()));
+        assert_eq!(table.probe(&key("FOO")).0, slot);
+        let mut keys: Vec<_> = table.keys().map(CaseInsensitive::as_str).collect();
+        keys.sort();
+        assert_eq!(keys, vec!["Foo", "bar"]);
+
+        assert_eq!(table.remove(&key("BAR")), Some(2));
+        assert_eq!(table.get(&key("bar")), None);
+        assert_eq!(table.get(&key("foo")), Some(&10));
+    }
+}
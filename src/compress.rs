@@ -0,0 +1,168 @@
+//! LZ4 block compression for large values, enabled by the `compress` feature
+//!
+//! Each sequence is a token (literal length << 4 | match length - 4), the literals,
+//! a little-endian u16 offset and the match; lengths of 15 or more continue in extra bytes.
+//! The last sequence has literals only. No frame or checksum: the caller keeps the original length.
+
+const MIN_MATCH: usize = 4;
+/// the last match must start at least this far from the end of the input
+const MF_LIMIT: usize = 12;
+/// the input always ends with at least this many literals
+const LAST_LITERALS: usize = 5;
+const MAX_OFFSET: usize = 0xffff;
+const HASH_BITS: u32 = 12;
+
+fn hash4(bytes: &[u8]) -> usize {
+    let v = u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+    (v.wrapping_mul(2_654_435_761) >> (32 - HASH_BITS)) as usize
+}
+
+fn push_length(out: &mut Vec<u8>, mut len: usize) {
+    while len >= 255 {
+        out.push(255);
+        len -= 255;
+    }
+    out.push(len as u8);
+}
+
+fn push_sequence(out: &mut Vec<u8>, literals: &[u8], matched: Option<(usize, usize)>) {
+    let match_code = matched.map_or(0, |(_, len)| len - MIN_MATCH);
+    out.push(((literals.len().min(15) as u8) << 4) | match_code.min(15) as u8);
+    if literals.len() >= 15 {
+        push_length(out, literals.len() - 15);
+    }
+    out.extend_from_slice(literals);
+    if let Some((offset, _)) = matched {
+        out.extend_from_slice(&(offset as u16).to_le_bytes());
+        if match_code >= 15 {
+            push_length(out, match_code - 15);
+        }
+    }
+}
+
+pub(crate) fn compress(input: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(input.len() / 2 + 16);
+    let mut table = vec![usize::MAX; 1 << HASH_BITS];
+    let mut anchor = 0;
+    let mut pos = 0;
+    while input.len() >= MF_LIMIT && pos <= input.len() - MF_LIMIT {
+        let h = hash4(&input[pos..]);
+        let candidate = table[h];
+        table[h] = pos;
+        if candidate == usize::MAX
+            || pos - candidate > MAX_OFFSET
+            || input[candidate..candidate + MIN_MATCH] != input[pos..pos + MIN_MATCH]
+        {
+            pos += 1;
+            continue;
+        }
+        let limit = input.len() - LAST_LITERALS;
+        let mut len = MIN_MATCH;
+        while pos + len < limit && input[candidate + len] == input[pos + len] {
+            len += 1;
+        }
+        push_sequence(&mut out, &input[anchor..pos], Some((pos - candidate, len)));
+        pos += len;
+        anchor = pos;
+    }
+    push_sequence(&mut out, &input[anchor..], None);
+    out
+}
+
+fn read_length(input: &[u8], i: &mut usize, mut len: usize) -> Option<usize> {
+    if len == 15 {
+        loop {
+            let b = *input.get(*i)?;
+            *i += 1;
+            len += b as usize;
+            if b != 255 {
+                break;
+            }
+        }
+    }
+    Some(len)
+}
+
+/// None when input is not a valid block decompressing to exactly len bytes
+/// len comes from an untrusted header, so the buffer is sized for at most what input can expand to:
+/// every input byte adds at most 255 to a match length
+pub(crate) fn decompress(input: &[u8], len: usize) -> Option<Vec<u8>> {
+    let mut out = Vec::with_capacity(len.min(input.len().saturating_mul(255)));
+    let mut i = 0;
+    loop {
+        let token = *input.get(i)?;
+        i += 1;
+        let literals = read_length(input, &mut i, (token >> 4) as usize)?;
+        out.extend_from_slice(input.get(i..i + literals)?);
+        i += literals;
+        if i == input.len() {
+            break;
+        }
+        let offset = u16::from_le_bytes([*input.get(i)?, *input.get(i + 1)?]) as usize;
+        i += 2;
+        let matched = read_length(input, &mut i, (token & 15) as usize)? + MIN_MATCH;
+        if offset == 0 || offset > out.len() || out.len() + matched > len {
+            return None;
+        }
+        // the match may overlap the bytes it produces, so copy byte by byte
+        let start = out.len() - offset;
+        for k in 0..matched {
+            out.push(out[start + k]);
+        }
+    }
+    (out.len() == len).then_some(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{compress, decompress};
+
+    fn roundtrip(input: &[u8]) -> usize {
+        let packed = compress(input);
+        assert_eq!(decompress(&packed, input.len()).as_deref(), Some(input));
+        packed.len()
+    }
+
+    #[test]
+    fn test_roundtrip() {
+        assert_eq!(roundtrip(b""), 1);
+        roundtrip(b"short");
+        roundtrip(b"abcdabcdabcdabcd");
+        assert!(roundtrip(&[b'a'; 10_000]) < 100);
+
+        let json: String = (0..200)
+            .map(|i| format!("{{\"id\":{},\"name\":\"user{}\",\"active\":true}},", i, i))
+            .collect();
+        assert!(roundtrip(json.as_bytes()) < json.len() / 3);
+
+        // incompressible input grows by at most a few bytes
+        let mut x = 0x2545_f491_4f6c_dd1du64;
+        let noise: Vec<u8> = (0..5_000)
+            .map(|_| {
+                x ^= x << 13;
+                x ^= x >> 7;
+                x ^= x << 17;
+                x as u8
+            })
+            .collect();
+        assert!(roundtrip(&noise) <= noise.len() + noise.len() / 255 + 16);
+    }
+
+    #[test]
+    fn test_decompress_rejects_bad_input() {
+        let packed = compress(b"hello hello hello hello");
+        assert!(decompress(&packed, 22).is_none());
+        assert!(decompress(&packed[..packed.len() - 1], 23).is_none());
+        assert!(decompress(&[], 0).is_none());
+        // a match pointing before the start of the output
+        assert!(decompress(&[0x10, b'a', 5, 0, 0x00], 10).is_none());
+    }
+
+    #[test]
+    fn test_decompress_huge_declared_length() {
+        // allocating the declared length up front would abort
+        let packed = compress(b"hello");
+        assert!(decompress(&packed, usize::MAX).is_none());
+        assert!(decompress(&packed, isize::MAX as usize).is_none());
+    }
+}
@@ -0,0 +1,37 @@
+//! the elastic hashing algorithm, free of any JS or wasm dependency
+
+mod budget;
+mod bytes;
+mod clear;
+mod collect;
+mod error;
+mod explain;
+mod layout;
+mod pressure;
+mod probe;
+mod probe_stats;
+#[cfg(feature = "serde")]
+mod serialize;
+mod snapshot;
+pub mod stats;
+pub(crate) mod table;
+mod transform;
+mod tuning;
+
+pub use budget::ProbeBudgetExceeded;
+pub use bytes::ByteCodec;
+pub use clear::Drain;
+pub use collect::DEFAULT_DELTA;
+pub use error::ElasticHashError;
+pub use explain::{ExplainReport, LevelStop, LevelWalk};
+pub use layout::{ImportMode, ImportReport, LayoutSnapshot, LAYOUT_VERSION};
+pub use pressure::PressureCallback;
+pub use probe::ProbeState;
+pub use probe_stats::{LevelProbeStats, ProbeStats};
+pub use snapshot::SnapshotIter;
+pub(crate) use table::Slot;
+pub use table::{
+    ElasticHashTable, ExtractIf, InsertError, IntoIter, Iter, IterMut, Keys, LevelGeometry, LevelIter, RebalanceReport,
+    SlotError, SlotId, SlotView, TableOptions, TryReserveError, Values, ValuesMut, MAX_LEVELS, MIN_LEVEL_SIZE,
+};
+pub use tuning::{COST_RATIO, CURVE_BUCKETS};
@@ -0,0 +1,171 @@
+//! probe budgets: searches and inserts that give up after a fixed number of slot inspections,
+//! for callers that would rather retry later than stall on an exhaustive scan of a nearly full level
+
+use std::hash::Hash;
+use std::sync::atomic::Ordering;
+
+use super::error::ElasticHashError;
+use super::probe::{NoSlot, ProbeState};
+use super::table::{ElasticHashTable, InsertError};
+use crate::{Equivalent, TableHasher};
+
+/// a bounded operation inspected max_probes slots without reaching an answer
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ProbeBudgetExceeded {
+    pub max_probes: usize,
+}
+
+impl std::fmt::Display for ProbeBudgetExceeded {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "probe budget of {} slots exceeded", self.max_probes)
+    }
+}
+
+impl std::error::Error for ProbeBudgetExceeded {}
+
+impl<K, V, S> ElasticHashTable<K, V, S>
+where
+    K: Hash + Eq,
+    S: TableHasher,
+{
+    /// search, inspecting at most max_probes slots; every slot inspected counts towards probe_count
+    pub fn search_bounded<Q>(&self, key: &Q, max_probes: usize) -> Result<Option<&V>, ProbeBudgetExceeded>
+    where
+        Q: ?Sized + Hash + Equivalent<K>,
+    {
+        let mut budget = max_probes;
+        match self.find_within(key, &mut budget) {
+            Some(pos) => Ok(self.value_at(pos)),
+            None => Err(ProbeBudgetExceeded { max_probes }),
+        }
+    }
+
+    /// insert, inspecting at most max_probes slots between looking key up and finding it a free slot,
+    /// the exhaustive scans of full levels included; once the budget runs out the insert fails with
+    /// probe_budget_exceeded set, handing key and value back and leaving the table as it was
+    pub fn insert_bounded(&mut self, key: K, value: V, max_probes: usize) -> Result<(usize, usize), InsertError<K, V>> {
+        let mut budget = max_probes;
+        let exceeded = ProbeBudgetExceeded { max_probes };
+        match self.find_within(&key, &mut budget) {
+            None => return Err(InsertError::new(key, value, ElasticHashError::ProbeBudgetExceeded(exceeded))),
+            Some(Some((level, idx))) => {
+                self.replace_slot(level, idx, value);
+                return Ok((level, idx));
+            }
+            Some(None) => {}
+        }
+        match self.locate_free_within(&key, &mut budget) {
+            Ok(free) => Ok(self.place(free, key, value)),
+            Err(NoSlot::Full(error)) => Err(InsertError::new(key, value, error)),
+            Err(NoSlot::Budget) => Err(InsertError::new(key, value, ElasticHashError::ProbeBudgetExceeded(exceeded))),
+        }
+    }
+
+    /// find, spending one unit of budget per slot inspected; None when the budget runs out first
+    fn find_within<Q>(&self, key: &Q, budget: &mut usize) -> Option<Option<(usize, usize)>>
+    where
+        Q: ?Sized + Hash + Equivalent<K>,
+    {
+        self.probe_stats.searches.fetch_add(1, Ordering::Relaxed);
+        for step in self.probe_walk(S::hash_one(key, self.seed), key) {
+            if *budget == 0 {
+                return None;
+            }
+            *budget -= 1;
+            self.count_probes(1);
+            self.count_level(step.level, |l| &l.search_probes);
+            if step.state == ProbeState::Match {
+                return Some(Some((step.level, step.idx)));
+            }
+        }
+        Some(None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ProbeBudgetExceeded;
+    use crate::{ElasticHashTable, LevelGeometry};
+
+    /// a table filled to its last few free slots
+    fn nearly_full(geometry: LevelGeometry) -> ElasticHashTable<u64, u64> {
+        let mut table = ElasticHashTable::with_geometry(1024, 0.1, 3, geometry).unwrap();
+        let room = table.max_inserts() - 4;
+        for key in 0..room as u64 {
+            table.insert(key, key).expect("Insertion failed");
+        }
+        table
+    }
+
+    #[test]
+    fn test_budget_caps_probes_across_levels() {
+        for geometry in [LevelGeometry::Halving, LevelGeometry::Single, LevelGeometry::Custom(vec![256; 4])] {
+            let mut table = nearly_full(geometry);
+            let (len, generation) = (table.len(), table.generation());
+            let mut bailed = 0;
+            for key in 5000..5200u64 {
+                for budget in [0, 1, 3, 8] {
+                    let before = table.probe_count();
+                    let result = table.insert_bounded(key, 7, budget);
+                    assert!(table.probe_count() - before <= budget as u64, "budget {}", budget);
+                    let err = match result {
+                        Ok((level, idx)) => {
+                            assert_eq!(table.find(&key), Some((level, idx)));
+                            assert_eq!(table.remove(&key), Some(7));
+                            continue;
+                        }
+                        Err(err) => err,
+                    };
+                    assert_eq!(err.probe_budget_exceeded(), Some(&ProbeBudgetExceeded { max_probes: budget }));
+                    assert_eq!(err.into_parts(), (key, 7));
+                    assert_eq!(table.get(&key), None);
+                    bailed += 1;
+                }
+            }
+            assert!(bailed > 0);
+            table.check_invariants().unwrap();
+            assert_eq!(table.len(), len);
+            // the successful inserts and their removals are the only mutations
+            assert_eq!((table.generation() - generation) % 2, 0);
+
+            // with room to spare an unbounded retry succeeds
+            table.insert_bounded(9999, 1, usize::MAX).expect("Insertion failed");
+            assert_eq!(table.get(&9999), Some(&1));
+        }
+    }
+
+    #[test]
+    fn test_search_bounded() {
+        let table = nearly_full(LevelGeometry::Halving);
+        for key in (0..1000u64).step_by(37).chain(5000..5050) {
+            let (found, probes) = table.probe(&key);
+            let before = table.probe_count();
+            assert_eq!(table.search_bounded(&key, probes), Ok(table.value_at(found)));
+            if probes > 0 {
+                assert_eq!(table.search_bounded(&key, probes - 1), Err(ProbeBudgetExceeded { max_probes: probes - 1 }));
+            }
+            assert!(table.probe_count() - before <= 2 * probes as u64);
+        }
+        assert_eq!(ProbeBudgetExceeded { max_probes: 4 }.to_string(), "probe budget of 4 slots exceeded");
+    }
+
+    #[test]
+    fn test_bounded_replace_and_full_table() {
+        let mut table = ElasticHashTable::with_seed(16, 0.5, 1);
+        table.insert(1u32, 1u32).expect("Insertion failed");
+        assert!(table.insert_bounded(1, 2, 0).unwrap_err().probe_budget_exceeded().is_some());
+        assert_eq!(table.get(&1), Some(&1));
+        table.insert_bounded(1, 2, 64).expect("Insertion failed");
+        assert_eq!(table.get(&1), Some(&2));
+        assert_eq!(table.len(), 1);
+
+        let mut key = 2;
+        while !table.is_full() {
+            table.insert(key, key).expect("Insertion failed");
+            key += 1;
+        }
+        let err = table.insert_bounded(key, key, usize::MAX).unwrap_err();
+        assert_eq!(err.probe_budget_exceeded(), None);
+        assert_eq!(err.reason(), "Hash table is full (maximum allowed insertions reached).");
+    }
+}
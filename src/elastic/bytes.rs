@@ -0,0 +1,312 @@
+//! a compact binary encoding of layout snapshots, so a table built natively can be shipped to the wasm build
+//! and restored slot for slot instead of re-inserted
+//!
+//! Layout: the magic bytes `EHTB`, then the layout version, hasher id, capacity, delta (f64 bits, little-endian),
+//! seed (little-endian u64), level sizes, entries as (level, index, key, value) and tombstones as (level, index).
+//! Integers are LEB128 varints, counts precede lists, and strings and byte strings are length-prefixed.
+
+use std::hash::Hash;
+
+use super::layout::{ImportReport, LayoutSnapshot};
+use super::table::{ElasticHashTable, SlotId, TableOptions};
+use crate::TableHasher;
+
+const MAGIC: &[u8; 4] = b"EHTB";
+
+/// how a key or value type is written by to_bytes and read back by from_bytes
+pub trait ByteCodec: Sized {
+    fn encode(&self, out: &mut Vec<u8>);
+
+    /// read one value off the front of input, advancing it; None on malformed input
+    fn decode(input: &mut &[u8]) -> Option<Self>;
+}
+
+fn put_varint(out: &mut Vec<u8>, mut n: u64) {
+    while n >= 0x80 {
+        out.push(n as u8 | 0x80);
+        n >>= 7;
+    }
+    out.push(n as u8);
+}
+
+fn get_varint(input: &mut &[u8]) -> Option<u64> {
+    let mut n = 0u64;
+    for shift in (0..64).step_by(7) {
+        let (&byte, rest) = input.split_first()?;
+        *input = rest;
+        n |= u64::from(byte & 0x7f).checked_shl(shift)?;
+        if byte & 0x80 == 0 {
+            return Some(n);
+        }
+    }
+    None
+}
+
+fn take<'a>(input: &mut &'a [u8], len: usize) -> Option<&'a [u8]> {
+    if input.len() < len {
+        return None;
+    }
+    let (head, rest) = input.split_at(len);
+    *input = rest;
+    Some(head)
+}
+
+impl ByteCodec for u64 {
+    fn encode(&self, out: &mut Vec<u8>) {
+        put_varint(out, *self);
+    }
+
+    fn decode(input: &mut &[u8]) -> Option<Self> {
+        get_varint(input)
+    }
+}
+
+impl ByteCodec for u32 {
+    fn encode(&self, out: &mut Vec<u8>) {
+        put_varint(out, u64::from(*self));
+    }
+
+    fn decode(input: &mut &[u8]) -> Option<Self> {
+        get_varint(input)?.try_into().ok()
+    }
+}
+
+impl ByteCodec for usize {
+    fn encode(&self, out: &mut Vec<u8>) {
+        put_varint(out, *self as u64);
+    }
+
+    fn decode(input: &mut &[u8]) -> Option<Self> {
+        get_varint(input)?.try_into().ok()
+    }
+}
+
+/// zigzag, so small negative numbers stay short
+impl ByteCodec for i64 {
+    fn encode(&self, out: &mut Vec<u8>) {
+        put_varint(out, ((*self << 1) ^ (*self >> 63)) as u64);
+    }
+
+    fn decode(input: &mut &[u8]) -> Option<Self> {
+        let n = get_varint(input)?;
+        Some((n >> 1) as i64 ^ -((n & 1) as i64))
+    }
+}
+
+impl ByteCodec for i32 {
+    fn encode(&self, out: &mut Vec<u8>) {
+        i64::from(*self).encode(out);
+    }
+
+    fn decode(input: &mut &[u8]) -> Option<Self> {
+        i64::decode(input)?.try_into().ok()
+    }
+}
+
+impl ByteCodec for Vec<u8> {
+    fn encode(&self, out: &mut Vec<u8>) {
+        self.len().encode(out);
+        out.extend_from_slice(self);
+    }
+
+    fn decode(input: &mut &[u8]) -> Option<Self> {
+        let len = usize::decode(input)?;
+        take(input, len).map(<[u8]>::to_vec)
+    }
+}
+
+impl ByteCodec for String {
+    fn encode(&self, out: &mut Vec<u8>) {
+        self.len().encode(out);
+        out.extend_from_slice(self.as_bytes());
+    }
+
+    fn decode(input: &mut &[u8]) -> Option<Self> {
+        String::from_utf8(Vec::decode(input)?).ok()
+    }
+}
+
+impl ByteCodec for SlotId {
+    fn encode(&self, out: &mut Vec<u8>) {
+        self.level.encode(out);
+        self.index.encode(out);
+    }
+
+    fn decode(input: &mut &[u8]) -> Option<Self> {
+        Some(SlotId { level: usize::decode(input)?, index: usize::decode(input)? })
+    }
+}
+
+/// a count read from input, capped so a corrupt count cannot reserve more than the input could hold
+fn count(input: &mut &[u8]) -> Option<(usize, usize)> {
+    let count = usize::decode(input)?;
+    Some((count, count.min(input.len())))
+}
+
+impl<K: ByteCodec, V: ByteCodec> LayoutSnapshot<K, V> {
+    /// the binary encoding described in the module docs
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = MAGIC.to_vec();
+        self.layout_version.encode(&mut out);
+        self.hasher.encode(&mut out);
+        self.options.capacity.encode(&mut out);
+        out.extend_from_slice(&self.options.delta.to_bits().to_le_bytes());
+        out.extend_from_slice(&self.options.seed.to_le_bytes());
+        self.level_sizes.len().encode(&mut out);
+        for size in &self.level_sizes {
+            size.encode(&mut out);
+        }
+        self.entries.len().encode(&mut out);
+        for (id, key, value) in &self.entries {
+            id.encode(&mut out);
+            key.encode(&mut out);
+            value.encode(&mut out);
+        }
+        self.tombstones.len().encode(&mut out);
+        for id in &self.tombstones {
+            id.encode(&mut out);
+        }
+        out
+    }
+
+    /// read what to_bytes wrote; fails on anything else, trailing bytes included
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, String> {
+        Self::decode_all(bytes).ok_or_else(|| "not a table snapshot, or a truncated one".to_string())
+    }
+
+    fn decode_all(bytes: &[u8]) -> Option<Self> {
+        let mut input = bytes.strip_prefix(MAGIC)?;
+        let input = &mut input;
+        let layout_version = u32::decode(input)?;
+        let hasher = String::decode(input)?;
+        let capacity = usize::decode(input)?;
+        let delta = f64::from_bits(u64::from_le_bytes(take(input, 8)?.try_into().ok()?));
+        let seed = u64::from_le_bytes(take(input, 8)?.try_into().ok()?);
+        let (levels, reserve) = count(input)?;
+        let mut level_sizes = Vec::with_capacity(reserve);
+        for _ in 0..levels {
+            level_sizes.push(usize::decode(input)?);
+        }
+        let (entries, reserve) = count(input)?;
+        let mut decoded = Vec::with_capacity(reserve);
+        for _ in 0..entries {
+            decoded.push((SlotId::decode(input)?, K::decode(input)?, V::decode(input)?));
+        }
+        let (tombstones, reserve) = count(input)?;
+        let mut dead = Vec::with_capacity(reserve);
+        for _ in 0..tombstones {
+            dead.push(SlotId::decode(input)?);
+        }
+        if !input.is_empty() {
+            return None;
+        }
+        Some(LayoutSnapshot {
+            layout_version,
+            hasher,
+            options: TableOptions { capacity, delta, seed },
+            level_sizes,
+            entries: decoded,
+            tombstones: dead,
+        })
+    }
+}
+
+impl<K, V, S> ElasticHashTable<K, V, S>
+where
+    K: Hash + Eq + Clone + ByteCodec,
+    V: Clone + ByteCodec,
+    S: TableHasher,
+{
+    /// layout_snapshot in the binary encoding
+    pub fn to_bytes(&self) -> Vec<u8> {
+        self.layout_snapshot().to_bytes()
+    }
+
+    /// from_layout_snapshot of what to_bytes wrote
+    pub fn from_bytes(bytes: &[u8]) -> Result<(Self, ImportReport), String> {
+        Self::from_layout_snapshot(LayoutSnapshot::from_bytes(bytes)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{get_varint, put_varint, ByteCodec};
+    use crate::{ElasticHashTable, ImportMode, LayoutSnapshot};
+
+    #[test]
+    fn test_codecs_roundtrip() {
+        let mut out = Vec::new();
+        let numbers = [0, 1, 127, 128, 300, u64::MAX >> 1, u64::MAX];
+        for n in numbers {
+            put_varint(&mut out, n);
+        }
+        let ints = [0i64, -1, 1, -64, 64, i64::MIN, i64::MAX];
+        for i in ints {
+            i.encode(&mut out);
+        }
+        "héllo".to_string().encode(&mut out);
+        vec![0u8, 255].encode(&mut out);
+        let input = &mut &out[..];
+        for n in numbers {
+            assert_eq!(get_varint(input), Some(n));
+        }
+        for i in ints {
+            assert_eq!(i64::decode(input), Some(i));
+        }
+        assert_eq!(String::decode(input), Some("héllo".to_string()));
+        assert_eq!(Vec::decode(input), Some(vec![0u8, 255]));
+        assert!(input.is_empty());
+
+        assert_eq!(get_varint(&mut &[0x80u8; 11][..]), None);
+        assert_eq!(String::decode(&mut &[2u8, 0xff, 0xfe][..]), None);
+        assert_eq!(u32::decode(&mut &[0xff, 0xff, 0xff, 0xff, 0x1f][..]), None);
+    }
+
+    #[test]
+    fn test_table_roundtrip() {
+        let mut table = ElasticHashTable::with_seed(8192, 0.1, 77);
+        for i in 0..7000u32 {
+            table.insert(format!("key{}", i), format!("value{}", i * 3)).expect("Insertion failed");
+        }
+        for i in (0..7000).step_by(11) {
+            table.remove(&format!("key{}", i));
+        }
+        let bytes = table.to_bytes();
+        let (restored, report) = ElasticHashTable::<String, String>::from_bytes(&bytes).unwrap();
+        assert_eq!(report.mode, ImportMode::LayoutPreserving);
+        assert_eq!((restored.len(), restored.capacity()), (table.len(), table.capacity()));
+        assert_eq!(restored.level_stats(), table.level_stats());
+        for (key, value) in table.iter() {
+            assert_eq!(restored.find(key), table.find(key));
+            assert_eq!(restored.get(key), Some(value));
+        }
+        assert_eq!(restored.to_bytes(), bytes);
+        restored.check_invariants().unwrap();
+
+        let ints: ElasticHashTable<u64, i32> = ElasticHashTable::with_seed(64, 0.1, 1);
+        let (empty, _) = ElasticHashTable::<u64, i32>::from_bytes(&ints.to_bytes()).unwrap();
+        assert!(empty.is_empty());
+    }
+
+    #[test]
+    fn test_bad_bytes_are_rejected() {
+        let mut table = ElasticHashTable::with_seed(64, 0.1, 1);
+        for i in 0..40u64 {
+            table.insert(i, vec![i as u8; 3]).expect("Insertion failed");
+        }
+        let bytes = table.to_bytes();
+        for len in 0..bytes.len() {
+            assert!(LayoutSnapshot::<u64, Vec<u8>>::from_bytes(&bytes[..len]).is_err(), "{}", len);
+        }
+        let mut trailing = bytes.clone();
+        trailing.push(0);
+        assert!(LayoutSnapshot::<u64, Vec<u8>>::from_bytes(&trailing).is_err());
+        let mut magic = bytes;
+        magic[0] = b'X';
+        assert!(LayoutSnapshot::<u64, Vec<u8>>::from_bytes(&magic).is_err());
+        // a huge count in a short input must fail, not allocate
+        assert!(LayoutSnapshot::<u64, u64>::from_bytes(b"EHTB\x01\x00\x01\x9a\x99\x99\x99\x99\x99\xb9\x3f\
+            \x00\x00\x00\x00\x00\x00\x00\x00\x01\x01\xff\xff\xff\xff\xff\xff\xff\xff\x7f")
+        .is_err());
+    }
+}
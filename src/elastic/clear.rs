@@ -0,0 +1,171 @@
+//! emptying a table in place: clear and drain keep every level's allocation, so refilling
+//! a table of the same size allocates nothing for its slots
+
+use std::hash::Hash;
+
+use super::table::{ElasticHashTable, Slot};
+use crate::hasher::StableHash;
+use crate::TableHasher;
+
+/// iterator returned by ElasticHashTable::drain
+/// the table is empty once it is dropped, whether or not every entry was taken
+pub struct Drain<'a, K, V, S = StableHash>
+where
+    K: Hash + Eq,
+    S: TableHasher,
+{
+    table: &'a mut ElasticHashTable<K, V, S>,
+    level: usize,
+    idx: usize,
+}
+
+impl<K, V, S> ElasticHashTable<K, V, S>
+where
+    K: Hash + Eq,
+    S: TableHasher,
+{
+    /// remove every entry, keeping capacity, settings and the level allocations
+    /// the observer is told of each removal, as with remove
+    pub fn clear(&mut self) {
+        drop(self.drain());
+    }
+
+    /// remove and yield every entry in slot order, keeping the level allocations, e.g. to move
+    /// the entries into a table of another size
+    pub fn drain(&mut self) -> Drain<'_, K, V, S> {
+        Drain { table: self, level: 0, idx: 0 }
+    }
+
+    /// the bookkeeping of an empty table, once every slot is Empty
+    fn reset_emptied(&mut self) {
+        self.occupancies.fill(0);
+        self.num_inserts = 0;
+        self.free_cursors.fill(0);
+        self.pop_cursor = (0, 0);
+        self.displaced.clear();
+        if let Some(meta) = &mut self.meta {
+            meta.tags.iter_mut().for_each(|tags| tags.fill(0));
+        }
+        self.generation += 1;
+        self.update_pressure();
+    }
+}
+
+impl<K, V, S> Iterator for Drain<'_, K, V, S>
+where
+    K: Hash + Eq,
+    S: TableHasher,
+{
+    type Item = (K, V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while self.level < self.table.levels.len() {
+            let (level, idx) = (self.level, self.idx);
+            if idx >= self.table.levels[level].len() {
+                self.level += 1;
+                self.idx = 0;
+                continue;
+            }
+            self.idx += 1;
+            // tombstones go too, so refilling starts from empty levels
+            if let Slot::Occupied(k, v) = std::mem::replace(&mut self.table.levels[level][idx], Slot::Empty) {
+                self.table.occupancies[level] -= 1;
+                self.table.num_inserts -= 1;
+                if let Some(observer) = &mut self.table.observer {
+                    observer.on_remove(&k, &v);
+                }
+                return Some((k, v));
+            }
+        }
+        None
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.table.num_inserts, Some(self.table.num_inserts))
+    }
+}
+
+impl<K, V, S> ExactSizeIterator for Drain<'_, K, V, S>
+where
+    K: Hash + Eq,
+    S: TableHasher,
+{
+}
+
+impl<K, V, S> std::iter::FusedIterator for Drain<'_, K, V, S>
+where
+    K: Hash + Eq,
+    S: TableHasher,
+{
+}
+
+impl<K, V, S> Drop for Drain<'_, K, V, S>
+where
+    K: Hash + Eq,
+    S: TableHasher,
+{
+    fn drop(&mut self) {
+        self.for_each(drop);
+        self.table.reset_emptied();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use crate::ElasticHashTable;
+
+    fn filled(seed: u64) -> ElasticHashTable<u64, String> {
+        let mut table = ElasticHashTable::with_seed(512, 0.1, seed);
+        for key in 0..table.max_inserts() as u64 {
+            table.insert(key, key.to_string()).expect("Insertion failed");
+        }
+        for key in (0..100).step_by(3) {
+            table.remove(&key);
+        }
+        table
+    }
+
+    #[test]
+    fn test_clear_then_refill() {
+        let mut table = filled(1);
+        let (capacity, levels) = (table.capacity(), table.level_sizes());
+        let generation = table.generation();
+        table.clear();
+        assert_eq!((table.len(), table.capacity(), table.level_sizes()), (0, capacity, levels));
+        assert!(table.generation() > generation);
+        assert!((0..table.max_inserts() as u64).all(|key| !table.contains_key(&key)));
+        assert!(table.displaced.is_empty() && table.occupancies.iter().all(|&n| n == 0));
+        table.check_invariants().unwrap();
+
+        for key in 1000..1000 + table.max_inserts() as u64 {
+            table.insert(key, String::new()).expect("Insertion failed");
+        }
+        assert!(table.is_full());
+        assert_eq!(table.capacity(), capacity);
+        table.check_invariants().unwrap();
+    }
+
+    #[test]
+    fn test_drain_yields_every_entry() {
+        let mut table = filled(2);
+        let expected: HashSet<(u64, String)> = table.iter().map(|(k, v)| (*k, v.clone())).collect();
+        let drain = table.drain();
+        assert_eq!(drain.len(), expected.len());
+        let drained: Vec<(u64, String)> = drain.collect();
+        assert_eq!(drained.len(), expected.len());
+        assert_eq!(drained.into_iter().collect::<HashSet<_>>(), expected);
+        assert!(table.is_empty());
+        table.check_invariants().unwrap();
+
+        // a partly consumed drain still empties the table
+        let mut table = filled(3);
+        let mut bigger = ElasticHashTable::with_seed(2048, 0.1, 3);
+        bigger.extend(table.drain().take(10));
+        assert_eq!((bigger.len(), table.len()), (10, 0));
+        assert_eq!(table.iter().count(), 0);
+        table.insert(7, "seven".to_string()).expect("Insertion failed");
+        assert_eq!(table.get(&7).map(String::as_str), Some("seven"));
+    }
+}
@@ -0,0 +1,163 @@
+//! building a table from a collection: sized once for its entries, so the caller need not pick a capacity
+
+use std::collections::HashMap;
+use std::hash::Hash;
+
+use super::table::{capacity_for, check_dimensions, ElasticHashTable, InsertError};
+
+/// delta of the tables FromIterator and From<HashMap> build
+pub const DEFAULT_DELTA: f64 = 0.1;
+
+impl<K, V> ElasticHashTable<K, V>
+where
+    K: Hash + Eq,
+{
+    /// like new, with the smallest capacity whose max_inserts is at least n
+    /// panics unless delta is between 0 and 1, as new does
+    pub fn with_expected_items(n: usize, delta: f64) -> Self {
+        if let Err(e) = check_dimensions(1, delta) {
+            panic!("{}", e);
+        }
+        Self::new(capacity_for(n, delta), delta)
+    }
+
+    /// a table of every entry, later ones replacing earlier ones with the same key, sized by the upper bound
+    /// of the iterator's size_hint; an iterator without one is collected first to count its entries
+    /// fails at the first entry that does not fit, which takes an iterator yielding more than its hint
+    pub fn try_from_iter<I>(entries: I, delta: f64) -> Result<Self, InsertError<K, V>>
+    where
+        I: IntoIterator<Item = (K, V)>,
+    {
+        let entries = entries.into_iter();
+        if let (_, Some(n)) = entries.size_hint() {
+            let mut table = Self::with_expected_items(n, delta);
+            table.insert_batch(entries)?;
+            return Ok(table);
+        }
+        let entries: Vec<_> = entries.collect();
+        let mut table = Self::with_expected_items(entries.len(), delta);
+        table.insert_batch(entries)?;
+        Ok(table)
+    }
+}
+
+/// try_from_iter with DEFAULT_DELTA
+/// panics at the first entry that does not fit, as Extend does
+impl<K, V> FromIterator<(K, V)> for ElasticHashTable<K, V>
+where
+    K: Hash + Eq,
+{
+    fn from_iter<I: IntoIterator<Item = (K, V)>>(entries: I) -> Self {
+        match Self::try_from_iter(entries, DEFAULT_DELTA) {
+            Ok(table) => table,
+            Err(err) => panic!("from_iter: {}", err.reason()),
+        }
+    }
+}
+
+/// every entry of map, in a table with DEFAULT_DELTA sized for exactly map.len() entries
+impl<K, V, H> From<HashMap<K, V, H>> for ElasticHashTable<K, V>
+where
+    K: Hash + Eq,
+{
+    fn from(map: HashMap<K, V, H>) -> Self {
+        map.into_iter().collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::DEFAULT_DELTA;
+    use crate::elastic::table::{capacity_for, max_inserts};
+    use crate::ElasticHashTable;
+
+    #[test]
+    fn test_hash_map_round_trip() {
+        let map: HashMap<u64, String> = (0..5_000).map(|k| (k * 7, format!("v{}", k))).collect();
+        let table = ElasticHashTable::from(map.clone());
+        assert_eq!(table.len(), 5_000);
+        assert_eq!(table.delta(), DEFAULT_DELTA);
+        assert_eq!(table.capacity(), capacity_for(5_000, DEFAULT_DELTA));
+        assert!(table.max_inserts() >= 5_000);
+        for (key, value) in &map {
+            assert_eq!(table.get(key), Some(value));
+        }
+        assert_eq!(table.get(&1), None);
+        table.check_invariants().unwrap();
+
+        let back: HashMap<u64, String> = table.into_iter().collect();
+        assert_eq!(back, map);
+    }
+
+    #[test]
+    fn test_expected_items_fit_in_the_smallest_capacity() {
+        let deltas = [0.001, 0.01, 0.05, 0.1, 0.25, 1.0 / 3.0, 0.5, 0.7, 0.9, 0.99];
+        let counts = (0..300).chain([997, 1000, 1024, 4097, 65_536, 1_000_003]);
+        for n in counts {
+            for delta in deltas {
+                let capacity = capacity_for(n, delta);
+                assert!(max_inserts(capacity, delta) >= n, "n {} delta {}", n, delta);
+                assert!(capacity == 1 || max_inserts(capacity - 1, delta) < n, "n {} delta {}", n, delta);
+            }
+        }
+        for delta in deltas {
+            for n in [0, 1, 2, 3, 10, 100] {
+                let table: ElasticHashTable<u32, u32> = ElasticHashTable::with_expected_items(n, delta);
+                assert!(table.max_inserts() >= n);
+            }
+        }
+        // the floor in max_inserts leaves room for one entry in a single slot whatever delta is
+        assert_eq!(capacity_for(1, 0.9), 1);
+        assert_eq!(capacity_for(2, 0.5), 3);
+    }
+
+    #[test]
+    fn test_try_from_iter() {
+        let pairs = vec![(1, "a"), (2, "b"), (1, "c")];
+        let table = ElasticHashTable::try_from_iter(pairs, 0.2).unwrap();
+        assert_eq!((table.len(), table.get(&1)), (2, Some(&"c")));
+
+        // no upper bound to size by
+        let mut next = 0;
+        let unbounded = std::iter::from_fn(|| {
+            next += 1;
+            (next <= 1000).then_some((next, next))
+        });
+        let table = ElasticHashTable::try_from_iter(unbounded, 0.1).unwrap();
+        assert_eq!(table.len(), 1000);
+        assert!((1..=1000).all(|k| table.get(&k) == Some(&k)));
+
+        let collected: ElasticHashTable<u32, u32> = (0..100).map(|k| (k, k * 2)).collect();
+        assert_eq!((collected.len(), collected.get(&99)), (100, Some(&198)));
+        let empty: ElasticHashTable<u32, u32> = std::iter::empty().collect();
+        assert!(empty.is_empty() && empty.capacity() == 1);
+    }
+
+    /// yields count entries while claiming at most one
+    struct Understated {
+        count: u32,
+    }
+
+    impl Iterator for Understated {
+        type Item = (u32, u32);
+
+        fn next(&mut self) -> Option<(u32, u32)> {
+            self.count = self.count.checked_sub(1)?;
+            Some((self.count, self.count))
+        }
+
+        fn size_hint(&self) -> (usize, Option<usize>) {
+            (0, Some(1))
+        }
+    }
+
+    #[test]
+    fn test_more_entries_than_the_hint_fail() {
+        let err = ElasticHashTable::try_from_iter(Understated { count: 50 }, 0.1).err().expect("table overfilled");
+        assert!(err.error().is_full());
+        let result = std::panic::catch_unwind(|| Understated { count: 50 }.collect::<ElasticHashTable<u32, u32>>());
+        assert!(result.is_err());
+    }
+}
@@ -0,0 +1,129 @@
+//! ElasticHashError: why a table could not be built or an insert found no room
+
+use std::fmt;
+
+use super::budget::ProbeBudgetExceeded;
+use super::table::TryReserveError;
+
+/// error returned by insert, by resize, and by the constructors that check their arguments
+#[derive(Clone, Debug, PartialEq)]
+pub enum ElasticHashError {
+    /// the table holds max entries, as many as delta allows
+    MaxInsertsReached { max: usize },
+    /// there was room by count, but no level had a free slot left
+    TableSaturated,
+    /// capacity is zero
+    InvalidCapacity,
+    /// delta is not strictly between 0 and 1
+    InvalidDelta(f64),
+    /// an insert_bounded ran out of its budget first
+    ProbeBudgetExceeded(ProbeBudgetExceeded),
+    /// a table that grows on full was full and could not grow, or resize could not allocate its new table
+    GrowthFailed(TryReserveError),
+    /// resize was asked for requested slots, which hold fewer than the needed entries the table has
+    CapacityTooSmall { needed: usize, requested: usize },
+    /// a custom geometry or a from_layout layout has more levels than MAX_LEVELS
+    TooManyLevels { levels: usize, max: usize },
+    /// a custom geometry or a from_layout layout is unusable for another reason, given as text
+    InvalidLayout(String),
+}
+
+impl ElasticHashError {
+    /// whether the table had no room for a new key, however it found out
+    pub fn is_full(&self) -> bool {
+        matches!(
+            self,
+            ElasticHashError::MaxInsertsReached { .. }
+                | ElasticHashError::TableSaturated
+                | ElasticHashError::GrowthFailed(_)
+        )
+    }
+}
+
+impl fmt::Display for ElasticHashError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ElasticHashError::MaxInsertsReached { .. } => {
+                f.write_str("Hash table is full (maximum allowed insertions reached).")
+            }
+            ElasticHashError::TableSaturated => f.write_str("Insertion failed in all levels; hash table is full."),
+            ElasticHashError::InvalidCapacity => f.write_str("Capacity must be positive."),
+            ElasticHashError::InvalidDelta(_) => f.write_str("delta must be between 0 and 1."),
+            ElasticHashError::ProbeBudgetExceeded(exceeded) => exceeded.fmt(f),
+            ElasticHashError::GrowthFailed(e) => write!(f, "hash table is full and could not grow: {}", e),
+            ElasticHashError::TooManyLevels { levels, max } => {
+                write!(f, "{} levels, more than MAX_LEVELS ({})", levels, max)
+            }
+            ElasticHashError::InvalidLayout(reason) => f.write_str(reason),
+            ElasticHashError::CapacityTooSmall { needed, requested } => {
+                write!(f, "{} slots cannot hold the table's {} entries", requested, needed)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ElasticHashError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ElasticHashError::ProbeBudgetExceeded(exceeded) => Some(exceeded),
+            ElasticHashError::GrowthFailed(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+/// lets callers that report errors as strings keep using `?`
+impl From<ElasticHashError> for String {
+    fn from(err: ElasticHashError) -> String {
+        err.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ElasticHashError;
+    use crate::elastic::table::check_dimensions;
+    use crate::{ElasticHashTable, LevelGeometry};
+
+    #[test]
+    fn test_full_table_is_max_inserts_reached() {
+        let mut table = ElasticHashTable::with_seed(32, 0.1, 1);
+        let mut key = 0;
+        while !table.is_full() {
+            table.insert(key, key).expect("Insertion failed");
+            key += 1;
+        }
+        let err = table.insert(key, key).unwrap_err();
+        assert_eq!(err.error(), &ElasticHashError::MaxInsertsReached { max: 29 });
+        assert!(err.error().is_full());
+        assert_eq!(err.reason(), "Hash table is full (maximum allowed insertions reached).");
+    }
+
+    #[test]
+    fn test_saturated_probing_is_table_saturated() {
+        // allow one more entry than there are slots, so the count never stops the table before probing does
+        let mut table = ElasticHashTable::with_seed(64, 0.1, 5);
+        table.max_inserts = table.capacity() + 1;
+        let mut key = 0;
+        let err = loop {
+            if let Err(err) = table.insert(key, key) {
+                break err;
+            }
+            key += 1;
+        };
+        assert_eq!(err.error(), &ElasticHashError::TableSaturated);
+        assert!(err.error().is_full() && table.len() < table.max_inserts());
+        assert_eq!(err.reason(), "Insertion failed in all levels; hash table is full.");
+    }
+
+    #[test]
+    fn test_dimensions_and_strings() {
+        let err = ElasticHashTable::<u32, u32>::with_geometry(0, 0.1, 1, LevelGeometry::Single).err();
+        assert_eq!(err, Some(ElasticHashError::InvalidCapacity));
+        assert_eq!(err.map(|e| e.to_string()).as_deref(), Some("Capacity must be positive."));
+        assert_eq!(check_dimensions(8, 1.5), Err(ElasticHashError::InvalidDelta(1.5)));
+        assert_eq!(check_dimensions(0, 0.5), Err(ElasticHashError::InvalidCapacity));
+        let reason: String = ElasticHashError::TableSaturated.into();
+        assert_eq!(reason, "Insertion failed in all levels; hash table is full.");
+    }
+}
@@ -0,0 +1,238 @@
+//! explain: the walk a search for a key takes, slot by slot, for debugging slow lookups and for visualizers
+
+use std::hash::Hash;
+
+use super::probe::ProbeState;
+use super::table::{ElasticHashTable, SlotId};
+use crate::{json, Equivalent, TableHasher};
+
+/// why the walk left a level
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LevelStop {
+    /// the key was found
+    Match,
+    /// an Empty slot ended the level's sequence
+    Empty,
+    /// probe_bound probes, or the whole level, were inspected
+    Bound,
+}
+
+impl LevelStop {
+    fn as_str(self) -> &'static str {
+        match self {
+            LevelStop::Match => "match",
+            LevelStop::Empty => "empty",
+            LevelStop::Bound => "bound",
+        }
+    }
+}
+
+/// the walk through one level
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LevelWalk {
+    pub level: usize,
+    /// number of slots in the level
+    pub size: usize,
+    /// first slot of the key's probe sequence in the level
+    pub home: usize,
+    /// (index, state) of every probed slot, in probe order
+    pub probes: Vec<(usize, ProbeState)>,
+    pub stop: LevelStop,
+}
+
+/// what a search for a key inspects, see ElasticHashTable::explain
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ExplainReport {
+    /// the key's hash_key
+    pub key_hash: u64,
+    /// slots the displaced index lists for the hash, checked before any level
+    pub displaced: Vec<(SlotId, ProbeState)>,
+    /// the levels the walk reached, level 0 first; none when a displaced slot matched
+    pub levels: Vec<LevelWalk>,
+    /// where the key is, search's answer
+    pub found: Option<SlotId>,
+}
+
+impl ExplainReport {
+    /// slots inspected in total, what the search adds to probe_count
+    pub fn probes(&self) -> usize {
+        self.displaced.len() + self.levels.iter().map(|level| level.probes.len()).sum::<usize>()
+    }
+
+    /// as a JSON object:
+    /// `{"hash": "decimal u64", "displaced": [{"level", "index", "state"}], "levels": [{"level", "size", "home",
+    /// "probes": [{"index", "state"}], "stop"}], "found": {"level", "index"} | null, "probes": number}`
+    /// with states "empty", "tombstone", "occupied" (by another key) or "match", and stops "match",
+    /// "empty" or "bound"; the hash is a string since it does not fit a JS number
+    pub fn to_json(&self) -> String {
+        let slot = |id: SlotId| format!("\"level\":{},\"index\":{}", id.level, id.index);
+        let state = |state: ProbeState| json::quote(state.as_str());
+        let displaced: Vec<String> =
+            self.displaced.iter().map(|&(id, s)| format!("{{{},\"state\":{}}}", slot(id), state(s))).collect();
+        let levels: Vec<String> = self
+            .levels
+            .iter()
+            .map(|walk| {
+                let probe = |&(idx, s): &(usize, ProbeState)| format!("{{\"index\":{},\"state\":{}}}", idx, state(s));
+                let probes: Vec<String> = walk.probes.iter().map(probe).collect();
+                format!(
+                    "{{\"level\":{},\"size\":{},\"home\":{},\"probes\":[{}],\"stop\":{}}}",
+                    walk.level,
+                    walk.size,
+                    walk.home,
+                    probes.join(","),
+                    json::quote(walk.stop.as_str())
+                )
+            })
+            .collect();
+        let found = self.found.map_or("null".to_string(), |id| format!("{{{}}}", slot(id)));
+        format!(
+            "{{\"hash\":\"{}\",\"displaced\":[{}],\"levels\":[{}],\"found\":{},\"probes\":{}}}",
+            self.key_hash,
+            displaced.join(","),
+            levels.join(","),
+            found,
+            self.probes()
+        )
+    }
+}
+
+impl<K, V, S> ElasticHashTable<K, V, S>
+where
+    K: Hash + Eq,
+    S: TableHasher,
+{
+    /// the walk search takes for key, slot by slot, and where it ends; the same walk as search,
+    /// but probe_count is left alone
+    pub fn explain<Q>(&self, key: &Q) -> ExplainReport
+    where
+        Q: ?Sized + Hash + Equivalent<K>,
+    {
+        let key_hash = S::hash_one(key, self.seed);
+        let mut report = ExplainReport {
+            key_hash,
+            displaced: Vec::new(),
+            levels: Vec::new(),
+            found: None,
+        };
+        for step in self.probe_walk(key_hash, key) {
+            let slot = SlotId::from((step.level, step.idx));
+            if step.state == ProbeState::Match {
+                report.found = Some(slot);
+            }
+            if step.j.is_none() {
+                report.displaced.push((slot, step.state));
+                continue;
+            }
+            if report.levels.last().map(|walk| walk.level) != Some(step.level) {
+                report.levels.push(LevelWalk {
+                    level: step.level,
+                    size: self.levels[step.level].len(),
+                    home: step.idx,
+                    probes: Vec::new(),
+                    stop: LevelStop::Bound,
+                });
+            }
+            let walk = report.levels.last_mut().expect("level just pushed");
+            walk.probes.push((step.idx, step.state));
+            match step.state {
+                ProbeState::Match => walk.stop = LevelStop::Match,
+                ProbeState::Empty => walk.stop = LevelStop::Empty,
+                _ => {}
+            }
+        }
+        report
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{LevelStop, ProbeState};
+    use crate::{ElasticHashTable, IdentityHash, LevelGeometry, SlotId, SlotView, TableHasher};
+
+    /// check a report against search and the slots it names; returns the number of displaced slots it lists
+    fn check<S: TableHasher>(table: &ElasticHashTable<u64, u64, S>, key: u64) -> usize {
+        let report = table.explain(&key);
+        let (found, probes) = table.probe(&key);
+        assert_eq!(report.found, found.map(SlotId::from), "key {}", key);
+        assert_eq!(report.probes(), probes, "key {}", key);
+        assert_eq!(report.key_hash, table.hash_key(&key));
+        for walk in &report.levels {
+            assert_eq!(table.home_slot(&key, walk.level), Ok(walk.home));
+            assert_eq!(walk.probes[0].0, walk.home);
+            for &(idx, state) in &walk.probes {
+                let view = table.get_slot(SlotId::from((walk.level, idx))).unwrap();
+                let expected = match view {
+                    SlotView::Empty => ProbeState::Empty,
+                    SlotView::Tombstone => ProbeState::Tombstone,
+                    SlotView::Occupied(k, _) if *k == key => ProbeState::Match,
+                    SlotView::Occupied(..) => ProbeState::Other,
+                };
+                assert_eq!(state, expected);
+            }
+        }
+        for (i, walk) in report.levels.iter().enumerate() {
+            assert_eq!(walk.level, i);
+            let last = walk.probes.last().unwrap().1;
+            match walk.stop {
+                LevelStop::Match => assert_eq!(last, ProbeState::Match),
+                LevelStop::Empty => assert_eq!(last, ProbeState::Empty),
+                LevelStop::Bound => assert_eq!(walk.probes.len(), table.probe_bound().min(walk.size)),
+            }
+        }
+        report.displaced.len()
+    }
+
+    /// a random insert/remove workload near capacity, explaining keys along the way; returns the displaced
+    /// slots the reports listed
+    fn matrix<S: TableHasher>(geometry: LevelGeometry, seed: u64) -> usize {
+        let mut table = ElasticHashTable::<u64, u64, S>::with_geometry(1024, 0.1, seed, geometry).unwrap();
+        let (mut state, mut displaced) = (seed, 0);
+        for op in 0..6000u64 {
+            state = state.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+            let key = (state >> 33) % 1200;
+            if (state >> 20) % 3 == 0 {
+                table.remove(&key);
+            } else {
+                let _ = table.insert(key, op);
+            }
+            if op % 7 == 0 {
+                displaced += check(&table, key) + check(&table, key + 5000);
+            }
+        }
+        displaced + (0..1200).map(|key| check(&table, key)).sum::<usize>()
+    }
+
+    #[test]
+    fn test_explain_matches_search() {
+        let mut displaced = 0;
+        for seed in 1..=3 {
+            for geometry in [LevelGeometry::Halving, LevelGeometry::Single, LevelGeometry::Custom(vec![256; 4])] {
+                displaced += matrix::<crate::StableHash>(geometry.clone(), seed);
+                displaced += matrix::<IdentityHash>(geometry, seed);
+            }
+        }
+        assert!(displaced > 0, "the workload never reached the displaced index");
+    }
+
+    #[test]
+    fn test_explain_leaves_probe_count_alone() {
+        let mut table = ElasticHashTable::with_seed(64, 0.1, 1);
+        table.insert(1u64, 1u64).expect("Insertion failed");
+        let before = table.probe_count();
+        let report = table.explain(&1);
+        assert_eq!(table.probe_count(), before);
+        assert_eq!(report.levels.last().unwrap().stop, LevelStop::Match);
+        let json = report.to_json();
+        let start = format!("{{\"hash\":\"{}\",\"displaced\":[],\"levels\":[{{\"level\":0,", report.key_hash);
+        assert!(json.starts_with(&start));
+        let found = report.found.unwrap();
+        assert!(json.ends_with(&format!(
+            "\"stop\":\"match\"}}],\"found\":{{\"level\":{},\"index\":{}}},\"probes\":{}}}",
+            found.level,
+            found.index,
+            report.probes()
+        )));
+        assert!(table.explain(&2).to_json().contains("\"found\":null"));
+    }
+}
@@ -0,0 +1,286 @@
+//! layout snapshots: a table's slots copied out so an identical table can be rebuilt later, possibly by
+//! another release of the crate, and the LAYOUT_VERSION that says whether that release places keys alike
+
+use std::hash::Hash;
+
+use super::table::{
+    check_dimensions, max_inserts, ElasticHashTable, FreeSlot, LevelGeometry, Slot, SlotId, TableOptions,
+};
+use crate::TableHasher;
+
+/// version of the rules deciding where a key lands: hashing, level sizes and probing
+/// any change that moves an entry to another slot must bump it, so snapshots taken before the change are
+/// re-inserted instead of restored slot for slot; test_golden_placements fails until the bump is made
+pub const LAYOUT_VERSION: u32 = 2;
+
+/// every slot of a table, see ElasticHashTable::layout_snapshot
+#[derive(Clone, Debug, PartialEq)]
+pub struct LayoutSnapshot<K, V> {
+    /// LAYOUT_VERSION of the crate that took the snapshot
+    pub layout_version: u32,
+    /// hasher_id of the table
+    pub hasher: String,
+    pub options: TableOptions,
+    /// size of every level, level 0 first; they sum to options.capacity
+    pub level_sizes: Vec<usize>,
+    /// occupied slots in (level, index) order
+    pub entries: Vec<(SlotId, K, V)>,
+    pub tombstones: Vec<SlotId>,
+}
+
+/// how from_layout_snapshot rebuilt a table
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ImportMode {
+    /// every entry is in the slot it had when the snapshot was taken
+    LayoutPreserving,
+    /// the snapshot's placements could not be trusted, so its entries were inserted afresh
+    Reinserted,
+}
+
+impl ImportMode {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ImportMode::LayoutPreserving => "layout-preserving",
+            ImportMode::Reinserted => "reinserted",
+        }
+    }
+}
+
+/// what from_layout_snapshot did
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ImportReport {
+    pub mode: ImportMode,
+    /// why the layout was not preserved, None when it was
+    pub warning: Option<String>,
+}
+
+impl<K, V, S> ElasticHashTable<K, V, S>
+where
+    K: Hash + Eq,
+    S: TableHasher,
+{
+    /// copy every slot out; metadata, observers and counters are not part of a snapshot
+    pub fn layout_snapshot(&self) -> LayoutSnapshot<K, V>
+    where
+        K: Clone,
+        V: Clone,
+    {
+        let mut entries = Vec::with_capacity(self.num_inserts);
+        let mut tombstones = Vec::new();
+        for (level, slots) in self.levels.iter().enumerate() {
+            for (index, slot) in slots.iter().enumerate() {
+                match slot {
+                    Slot::Occupied(k, v) => entries.push((SlotId { level, index }, k.clone(), v.clone())),
+                    Slot::Tombstone => tombstones.push(SlotId { level, index }),
+                    Slot::Empty => {}
+                }
+            }
+        }
+        LayoutSnapshot {
+            layout_version: LAYOUT_VERSION,
+            hasher: S::ID.to_string(),
+            options: self.options(),
+            level_sizes: self.levels.iter().map(Vec::len).collect(),
+            entries,
+            tombstones,
+        }
+    }
+
+    /// rebuild the table a snapshot was taken of
+    /// when the snapshot's layout version and hasher match this build's, every entry and tombstone goes back
+    /// into its slot; otherwise the entries are inserted in snapshot order into an empty table of the same
+    /// geometry, and the report's warning says why. fails on a snapshot no table could have produced
+    pub fn from_layout_snapshot(snapshot: LayoutSnapshot<K, V>) -> Result<(Self, ImportReport), String> {
+        let LayoutSnapshot { layout_version, hasher, options, level_sizes, entries, tombstones } = snapshot;
+        check_dimensions(options.capacity, options.delta)?;
+        if level_sizes.iter().sum::<usize>() != options.capacity {
+            return Err(format!("level sizes do not sum to the capacity {}", options.capacity));
+        }
+        if entries.len() > max_inserts(options.capacity, options.delta) {
+            return Err(format!("snapshot holds {} entries, more than the table allows", entries.len()));
+        }
+        let geometry = [LevelGeometry::Halving, LevelGeometry::Single]
+            .into_iter()
+            .find(|geometry| geometry.sizes(options.capacity) == level_sizes)
+            .unwrap_or(LevelGeometry::Custom(level_sizes));
+        let mut table = Self::with_geometry(options.capacity, options.delta, options.seed, geometry)?;
+
+        let warning = if layout_version != LAYOUT_VERSION {
+            Some(format!(
+                "snapshot has layout version {} but this build places keys by version {}; entries were re-inserted",
+                layout_version, LAYOUT_VERSION
+            ))
+        } else if hasher != S::ID {
+            Some(format!(
+                "snapshot was hashed with {} but the table hashes with {}; entries were re-inserted",
+                hasher,
+                S::ID
+            ))
+        } else {
+            None
+        };
+        if let Some(warning) = warning {
+            for (_, key, value) in entries {
+                if table.insert_or_update(key, value)?.is_some() {
+                    return Err("snapshot holds a duplicate key".into());
+                }
+            }
+            let report = ImportReport { mode: ImportMode::Reinserted, warning: Some(warning) };
+            return Ok((table, report));
+        }
+
+        let claim = |table: &Self, id: SlotId| match table.levels.get(id.level).and_then(|level| level.get(id.index)) {
+            Some(Slot::Empty) => Ok(()),
+            Some(_) => Err(format!("slot ({}, {}) is listed twice", id.level, id.index)),
+            None => Err(format!("slot ({}, {}) is outside the table", id.level, id.index)),
+        };
+        for id in tombstones {
+            claim(&table, id)?;
+            table.levels[id.level][id.index] = Slot::Tombstone;
+        }
+        for (id, key, value) in entries {
+            claim(&table, id)?;
+            let free = FreeSlot { level: id.level, idx: id.index, displaced: false };
+            table.store(free, Slot::Occupied(key, value));
+        }
+        // entries the walk cannot reach were placed by a fallback scan and belong in the displaced index
+        let mut displaced = Vec::new();
+        for (level, slots) in table.levels.iter().enumerate() {
+            for (idx, slot) in slots.iter().enumerate() {
+                let Slot::Occupied(key, _) = slot else {
+                    continue;
+                };
+                match table.find(key) {
+                    Some(pos) if pos == (level, idx) => {}
+                    Some(_) => return Err("snapshot holds a duplicate key".into()),
+                    None => displaced.push((table.hash_key(key), (level, idx))),
+                }
+            }
+        }
+        for (hash, pos) in displaced {
+            table.displaced.entry(hash).or_default().push(pos);
+        }
+        *table.probes.get_mut() = 0;
+        table.reset_probe_stats();
+        let report = ImportReport { mode: ImportMode::LayoutPreserving, warning: None };
+        Ok((table, report))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ImportMode, LAYOUT_VERSION};
+    use crate::{ElasticHashTable, IdentityHash, LevelGeometry, SlotId};
+
+    /// the slot of every key, in key order
+    fn placements<S: crate::TableHasher>(table: &ElasticHashTable<u64, u64, S>, keys: u64) -> Vec<(usize, usize)> {
+        (0..keys).map(|key| table.find(&key).expect("key is present")).collect()
+    }
+
+    #[test]
+    fn test_golden_placements() {
+        // if this fails, a change moved keys to other slots: bump LAYOUT_VERSION, then update the slots
+        assert_eq!(LAYOUT_VERSION, 2);
+        let mut table = ElasticHashTable::with_seed(32, 0.1, 0x5eed);
+        let keys = table.max_inserts() as u64;
+        for key in 0..keys {
+            table.insert(key, key).expect("Insertion failed");
+        }
+        assert_eq!(placements(&table, keys), GOLDEN_HALVING);
+
+        let mut table = ElasticHashTable::<u64, u64>::single_level_with_hasher(32, 0.1, 0x5eed);
+        for key in 0..keys {
+            table.insert(key, key).expect("Insertion failed");
+        }
+        assert_eq!(placements(&table, keys), GOLDEN_SINGLE);
+    }
+
+    const GOLDEN_HALVING: [(usize, usize); 29] = [
+        (0, 11), (0, 4), (1, 5), (0, 2), (0, 10), (0, 6), (0, 9), (0, 3), (0, 0), (0, 13), (0, 12), (1, 3), (0, 14),
+        (1, 6), (0, 15), (0, 7), (0, 8), (0, 5), (1, 0), (0, 1), (1, 4), (2, 1), (1, 1), (2, 2), (1, 7), (2, 3),
+        (1, 2), (3, 1), (3, 0),
+    ];
+    const GOLDEN_SINGLE: [(usize, usize); 29] = [
+        (0, 27), (0, 4), (0, 28), (0, 18), (0, 26), (0, 22), (0, 9), (0, 19), (0, 16), (0, 25), (0, 29), (0, 10),
+        (0, 13), (0, 2), (0, 31), (0, 7), (0, 8), (0, 5), (0, 11), (0, 14), (0, 24), (0, 0), (0, 20), (0, 12),
+        (0, 30), (0, 23), (0, 3), (0, 1), (0, 6),
+    ];
+
+    /// a table of the given geometry after random inserts and removals, with tombstones and displaced entries
+    fn churned<S: crate::TableHasher>(geometry: LevelGeometry) -> ElasticHashTable<u64, u64, S> {
+        let mut table = ElasticHashTable::with_geometry(512, 0.1, 11, geometry).unwrap();
+        let mut state = 11u64;
+        for op in 0..4000 {
+            state = state.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+            let key = (state >> 33) % 600;
+            if (state >> 20).is_multiple_of(3) {
+                table.remove(&key);
+            } else {
+                let _ = table.insert(key, op);
+            }
+        }
+        table
+    }
+
+    /// every slot, so two tables can be compared slot for slot
+    fn slots<S: crate::TableHasher>(table: &ElasticHashTable<u64, u64, S>) -> Vec<String> {
+        table.levels.iter().flatten().map(|slot| format!("{:?}", slot)).collect()
+    }
+
+    #[test]
+    fn test_snapshot_restores_layout() {
+        let mut displaced = 0;
+        for geometry in [LevelGeometry::Halving, LevelGeometry::Single, LevelGeometry::Custom(vec![128; 4])] {
+            let table = churned::<IdentityHash>(geometry.clone());
+            let snapshot = table.layout_snapshot();
+            assert!(!snapshot.tombstones.is_empty());
+            let (restored, report) =
+                ElasticHashTable::<u64, u64, IdentityHash>::from_layout_snapshot(snapshot).unwrap();
+            assert_eq!((report.mode, report.warning), (ImportMode::LayoutPreserving, None));
+            assert_eq!(slots(&restored), slots(&table));
+            assert_eq!(restored.geometry(), &geometry);
+            assert_eq!(restored.displaced, table.displaced);
+            restored.check_invariants().unwrap();
+            displaced += table.displaced.len();
+        }
+        assert!(displaced > 0, "no snapshot held a displaced entry");
+    }
+
+    #[test]
+    fn test_mismatched_snapshot_is_reinserted() {
+        let table = churned::<crate::StableHash>(LevelGeometry::Halving);
+        let mut snapshot = table.layout_snapshot();
+        snapshot.layout_version = LAYOUT_VERSION + 1;
+        let (restored, report) = ElasticHashTable::<u64, u64>::from_layout_snapshot(snapshot).unwrap();
+        assert_eq!(report.mode, ImportMode::Reinserted);
+        assert!(report.warning.unwrap().contains(&format!("layout version {}", LAYOUT_VERSION + 1)));
+        assert_eq!(restored.len(), table.len());
+        for (key, value) in table.iter() {
+            assert_eq!(restored.get(key), Some(value));
+        }
+        restored.check_invariants().unwrap();
+
+        // the hasher is part of the layout too
+        let (other, report) = ElasticHashTable::<u64, u64, IdentityHash>::from_layout_snapshot(table.layout_snapshot())
+            .unwrap();
+        assert_eq!(report.mode, ImportMode::Reinserted);
+        assert_eq!(other.len(), table.len());
+    }
+
+    #[test]
+    fn test_snapshot_is_validated() {
+        let table = churned::<crate::StableHash>(LevelGeometry::Halving);
+        let import = |edit: &dyn Fn(&mut super::LayoutSnapshot<u64, u64>)| {
+            let mut snapshot = table.layout_snapshot();
+            edit(&mut snapshot);
+            ElasticHashTable::<u64, u64>::from_layout_snapshot(snapshot).map(|_| ())
+        };
+        assert!(import(&|_| {}).is_ok());
+        assert!(import(&|s| s.level_sizes[0] += 1).is_err());
+        assert!(import(&|s| s.entries[0].0 = SlotId { level: 99, index: 0 }).is_err());
+        assert!(import(&|s| s.entries[1].0 = s.entries[0].0).is_err());
+        assert!(import(&|s| s.tombstones.push(s.entries[0].0)).is_err());
+        assert!(import(&|s| s.entries[1].1 = s.entries[0].1).is_err());
+        assert!(import(&|s| s.options.delta = 2.0).is_err());
+    }
+}
@@ -0,0 +1,140 @@
+//! insert pressure: a callback fired as the load factor crosses given thresholds, so a streaming
+//! writer can slow its producers down without polling remaining_capacity between inserts
+
+use std::hash::Hash;
+
+use super::table::ElasticHashTable;
+use crate::TableHasher;
+
+/// receives the load factor and the remaining capacity right after the insert that crossed a threshold
+/// Send and Sync, so the table holding it is
+pub type PressureCallback = Box<dyn FnMut(f64, usize) + Send + Sync>;
+
+/// thresholds of a pressure callback and which of them may fire
+pub(crate) struct Pressure {
+    thresholds: Vec<f64>,
+    /// armed[i] while the load factor is below thresholds[i]
+    armed: Vec<bool>,
+    callback: PressureCallback,
+}
+
+impl<K, V, S> ElasticHashTable<K, V, S>
+where
+    K: Hash + Eq,
+    S: TableHasher,
+{
+    /// call callback(load_factor, remaining_capacity) when an insert takes the load factor from below a
+    /// threshold to or above it, at most once per threshold per crossing; an insert crossing several fires
+    /// once for each, lowest first. a threshold fires again only after removals or growth have taken the
+    /// load factor back below it. thresholds the table already meets do not fire until such a dip.
+    /// thresholds must lie in (0, 1]; replaces any previous pressure callback
+    pub fn set_pressure_callback(&mut self, thresholds: &[f64], callback: PressureCallback) -> Result<(), String> {
+        if let Some(t) = thresholds.iter().find(|t| !(0.0 < **t && **t <= 1.0)) {
+            return Err(format!("pressure threshold {} is not in (0, 1]", t));
+        }
+        let mut thresholds = thresholds.to_vec();
+        thresholds.sort_by(f64::total_cmp);
+        thresholds.dedup();
+        let load_factor = self.load_factor();
+        let armed = thresholds.iter().map(|&t| load_factor < t).collect();
+        self.pressure = Some(Pressure { thresholds, armed, callback });
+        Ok(())
+    }
+
+    /// stop calling the pressure callback
+    pub fn clear_pressure_callback(&mut self) {
+        self.pressure = None;
+    }
+
+    /// fire the thresholds the load factor has reached and re-arm the ones it is below;
+    /// run after every change to the entry count or the capacity
+    pub(crate) fn update_pressure(&mut self) {
+        let (load_factor, remaining) = (self.load_factor(), self.remaining_capacity());
+        let Some(pressure) = &mut self.pressure else {
+            return;
+        };
+        for (&threshold, armed) in pressure.thresholds.iter().zip(&mut pressure.armed) {
+            if load_factor < threshold {
+                *armed = true;
+            } else if *armed {
+                *armed = false;
+                (pressure.callback)(load_factor, remaining);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use crate::ElasticHashTable;
+
+    type Fired = Arc<Mutex<Vec<(usize, f64, usize)>>>;
+
+    /// a table of 100 slots whose callback records (len, load factor, remaining capacity)
+    fn watched(thresholds: &[f64]) -> (ElasticHashTable<u32, u32>, Fired) {
+        let mut table = ElasticHashTable::with_seed(100, 0.1, 1);
+        let fired = Fired::default();
+        let log = fired.clone();
+        let record = move |lf: f64, remaining| log.lock().unwrap().push(((lf * 100.0).round() as usize, lf, remaining));
+        table.set_pressure_callback(thresholds, Box::new(record)).unwrap();
+        (table, fired)
+    }
+
+    #[test]
+    fn test_each_threshold_fires_once() {
+        let (mut table, fired) = watched(&[0.8, 0.5, 0.8]);
+        for i in 0..90u32 {
+            table.insert(i, i).expect("Insertion failed");
+            // replacing a present key changes nothing
+            table.insert(i, i + 1).expect("Insertion failed");
+        }
+        assert_eq!(*fired.lock().unwrap(), [(50, 0.5, 40), (80, 0.8, 10)]);
+    }
+
+    #[test]
+    fn test_thresholds_rearm_below() {
+        let (mut table, fired) = watched(&[0.5]);
+        for i in 0..50u32 {
+            table.insert(i, i).expect("Insertion failed");
+        }
+        assert_eq!(fired.lock().unwrap().len(), 1);
+        table.remove(&0);
+        table.insert(0, 0).expect("Insertion failed");
+        table.insert(100, 0).expect("Insertion failed");
+        assert_eq!(fired.lock().unwrap().len(), 2);
+
+        // growth halves the load factor, re-arming the threshold for the bigger table
+        table.try_reserve(60).expect("reserve failed");
+        assert!(table.load_factor() < 0.5);
+        let mut key = 200;
+        while table.load_factor() < 0.5 {
+            assert_eq!(fired.lock().unwrap().len(), 2);
+            table.insert(key, 0).expect("Insertion failed");
+            key += 1;
+        }
+        assert_eq!(fired.lock().unwrap().len(), 3);
+        assert_eq!(fired.lock().unwrap()[2].2, table.remaining_capacity());
+    }
+
+    #[test]
+    fn test_batch_inserts_fire_too() {
+        let (mut table, fired) = watched(&[0.25, 0.3]);
+        table.insert_batch((0..40u32).map(|i| (i, i))).expect("Insertion failed");
+        assert_eq!(fired.lock().unwrap().iter().map(|f| f.0).collect::<Vec<_>>(), [25, 30]);
+
+        // thresholds already met when the callback is set wait for a dip
+        let mut full = ElasticHashTable::with_seed(100, 0.1, 1);
+        full.insert_batch((0..60u32).map(|i| (i, i))).expect("Insertion failed");
+        let count = Arc::new(Mutex::new(0));
+        let counter = count.clone();
+        full.set_pressure_callback(&[0.5], Box::new(move |_, _| *counter.lock().unwrap() += 1)).unwrap();
+        full.insert(60, 0).expect("Insertion failed");
+        assert_eq!(*count.lock().unwrap(), 0);
+        full.clear_pressure_callback();
+        assert!(full.set_pressure_callback(&[0.0], Box::new(|_, _| {})).is_err());
+        assert!(full.set_pressure_callback(&[1.5], Box::new(|_, _| {})).is_err());
+        assert!(full.set_pressure_callback(&[f64::NAN], Box::new(|_, _| {})).is_err());
+    }
+}
@@ -0,0 +1,602 @@
+//! where a key may live: the per-level probe sequences, the fallback scan and free-slot selection
+
+use std::cmp;
+use std::hash::Hash;
+use std::sync::atomic::Ordering;
+
+use super::error::ElasticHashError;
+use super::table::{ElasticHashTable, FreeSlot, Slot, SlotError, THRESHOLD};
+use crate::hasher::{self, TableHasher};
+use crate::Equivalent;
+
+/// per-level hash derived from a key's hash_key
+pub(crate) fn level_hash(key_hash: u64, level: usize) -> u64 {
+    let h = key_hash ^ (level as u64).wrapping_mul(0x9e37_79b9_7f4a_7c15);
+    hasher::mix64(h)
+}
+
+/// index of the j-th probe in a level of size slots, for a full-width level hash
+/// reduced in u64, so wasm32 and 64-bit hosts agree and nothing overflows
+pub(crate) fn probe_index(level_hash: u64, j: usize, size: usize) -> usize {
+    let size = size as u64;
+    let step = (j as u64 * j as u64) % size;
+    ((level_hash % size + step) % size) as usize
+}
+
+/// why locate_free_within has no slot to offer
+pub(crate) enum NoSlot {
+    /// the table has no room, for the reason given
+    Full(ElasticHashError),
+    /// the probe budget ran out first
+    Budget,
+}
+
+/// what a probe found in a slot
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ProbeState {
+    /// never used: the key is not further along this level's sequence
+    Empty,
+    /// held an entry once; the walk goes on past it
+    Tombstone,
+    /// holds another key
+    Other,
+    /// holds the key
+    Match,
+}
+
+impl ProbeState {
+    /// lowercase name, as in explain's JSON
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ProbeState::Empty => "empty",
+            ProbeState::Tombstone => "tombstone",
+            ProbeState::Other => "occupied",
+            ProbeState::Match => "match",
+        }
+    }
+}
+
+/// one slot inspected by a ProbeWalk
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) struct ProbeStep {
+    pub(crate) level: usize,
+    pub(crate) idx: usize,
+    /// 0-based position in the level's probe sequence, None for a slot from the displaced index
+    pub(crate) j: Option<usize>,
+    pub(crate) state: ProbeState,
+}
+
+/// the search walk as an iterator: first the key's slots in the displaced index, then in each level
+/// the probe sequence up to probe_bound, leaving a level at its first Empty slot; ends after a Match
+pub(crate) struct ProbeWalk<'a, K, V, Q: ?Sized> {
+    levels: &'a [Vec<Slot<K, V>>],
+    key: &'a Q,
+    key_hash: u64,
+    /// displaced places not yet inspected
+    displaced: &'a [(usize, usize)],
+    bound: usize,
+    level: usize,
+    /// level_hash of the current level
+    level_hash: u64,
+    /// next position in the current level's sequence
+    j: usize,
+    done: bool,
+}
+
+impl<K, V, Q: ?Sized + Equivalent<K>> ProbeWalk<'_, K, V, Q> {
+    fn state(&self, level: usize, idx: usize) -> ProbeState {
+        match &self.levels[level][idx] {
+            Slot::Empty => ProbeState::Empty,
+            Slot::Tombstone => ProbeState::Tombstone,
+            Slot::Occupied(k, _) if self.key.equivalent(k) => ProbeState::Match,
+            Slot::Occupied(..) => ProbeState::Other,
+        }
+    }
+
+    fn next_level(&mut self) {
+        self.level += 1;
+        self.level_hash = level_hash(self.key_hash, self.level);
+        self.j = 0;
+    }
+}
+
+impl<K, V, Q: ?Sized + Equivalent<K>> Iterator for ProbeWalk<'_, K, V, Q> {
+    type Item = ProbeStep;
+
+    fn next(&mut self) -> Option<ProbeStep> {
+        if self.done {
+            return None;
+        }
+        let step = if let Some((&(level, idx), rest)) = self.displaced.split_first() {
+            self.displaced = rest;
+            ProbeStep { level, idx, j: None, state: self.state(level, idx) }
+        } else {
+            loop {
+                let size = self.levels.get(self.level)?.len();
+                if self.j < cmp::min(self.bound, size) {
+                    break;
+                }
+                self.next_level();
+            }
+            let (level, j) = (self.level, self.j);
+            let idx = probe_index(self.level_hash, j, self.levels[level].len());
+            let state = self.state(level, idx);
+            match state {
+                ProbeState::Empty => self.next_level(),
+                _ => self.j += 1,
+            }
+            ProbeStep { level, idx, j: Some(j), state }
+        };
+        self.done = step.state == ProbeState::Match;
+        Some(step)
+    }
+}
+
+impl<K, V, S> ElasticHashTable<K, V, S>
+where
+    K: Hash + Eq,
+    S: TableHasher,
+{
+    /// per-level hash value: the key hash mixed with the level
+    pub(crate) fn hash<Q>(&self, key: &Q, level: usize) -> u64
+    where
+        K: std::borrow::Borrow<Q>,
+        Q: ?Sized + Hash,
+    {
+        level_hash(self.hash_key(key), level)
+    }
+
+    /// quadratic probe function: return the index of the j-th probe
+    pub(crate) fn quad_probe<Q>(&self, key: &Q, level: usize, j: usize, table_size: usize) -> usize
+    where
+        K: std::borrow::Borrow<Q>,
+        Q: ?Sized + Hash,
+    {
+        probe_index(self.hash(key, level), j, table_size)
+    }
+
+    /// calculate the free ratio of the specified level: free/size
+    pub(crate) fn level_load(&self, level: usize) -> f64 {
+        let size = self.levels[level].len() as f64;
+        let occ = self.occupancies[level] as f64;
+        let free = size - occ;
+        free / size
+    }
+
+    /// any free slot of level, scanning from where the previous scan stopped, one unit of budget per slot
+    /// scanned; as a level fills the cursor only moves forward, so filling it completely costs O(size) overall
+    fn any_free_within(&self, level: usize, budget: &mut usize) -> Result<Option<FreeSlot>, NoSlot> {
+        let slots = &self.levels[level];
+        if self.occupancies[level] == slots.len() {
+            return Ok(None);
+        }
+        self.count_level(level, |l| &l.full_scans);
+        let start = self.free_cursors[level] % slots.len();
+        for (scanned, idx) in (start..slots.len()).chain(0..start).enumerate() {
+            if scanned == *budget {
+                *budget = 0;
+                return Err(NoSlot::Budget);
+            }
+            if slots[idx].is_free() {
+                *budget -= scanned + 1;
+                return Ok(Some(FreeSlot { level, idx, displaced: true }));
+            }
+        }
+        Ok(None)
+    }
+
+    /// number of probe-sequence slots the exhaustive fallbacks try before taking any free slot
+    pub(crate) fn fallback_probes(&self) -> usize {
+        cmp::max(1, (self.c * (1.0 / self.delta).log2()).ceil() as usize)
+    }
+
+    /// insert never looks further than this into a probe sequence (apart from displaced entries),
+    /// so neither does search
+    pub(crate) fn probe_bound(&self) -> usize {
+        cmp::max(self.fallback_probes(), self.c.ceil() as usize)
+    }
+
+    /// find the slot insert should claim for key
+    /// according to the strategy described in the paper:
+    /// - for non-last levels, first calculate the load of the current level, then calculate the probe_limit based on the load,
+    ///   then decide which strategy to use based on the state of the next level (load_next and 0.25 threshold).
+    /// - for the last level, scan the entire level.
+    ///
+    /// the exhaustive scans (Case 3 and the last level) try a few probes, then take any free slot of the level;
+    /// such entries are recorded as displaced so search can still find them. when every strategy misses,
+    /// the levels are scanned in order, so an insert only fails for lack of room once num_inserts reaches
+    /// max_inserts or every slot is taken
+    pub(crate) fn locate_free(&self, key: &K) -> Result<FreeSlot, ElasticHashError> {
+        let mut unbounded = usize::MAX;
+        match self.locate_free_within(key, &mut unbounded) {
+            Ok(free) => Ok(free),
+            Err(NoSlot::Full(reason)) => Err(reason),
+            Err(NoSlot::Budget) => unreachable!("an unbounded search has no budget to exceed"),
+        }
+    }
+
+    /// locate_free, inspecting at most budget slots, probes and scanned slots alike; what is left of
+    /// budget stays in it
+    pub(crate) fn locate_free_within(&self, key: &K, budget: &mut usize) -> Result<FreeSlot, NoSlot> {
+        if self.num_inserts >= self.max_inserts {
+            return Err(NoSlot::Full(ElasticHashError::MaxInsertsReached { max: self.max_inserts }));
+        }
+        self.probe_stats.inserts.fetch_add(1, Ordering::Relaxed);
+        for i in 0..self.levels.len() - 1 {
+            let level_size = self.levels[i].len();
+            let load = self.level_load(i);
+
+            // non-last level: calculate the load of the next level
+            let next_load = self.level_load(i + 1);
+            if load > (self.delta / 2.0) && next_load > THRESHOLD {
+                // calculate probe_limit, simulate f(ε)=c×min(log₂(1/ε), log₂(1/δ))
+                let log_inv_load = if load > 0.0 { (1.0 / load).log2() } else { 0.0 };
+                let log_inv_delta = (1.0 / self.delta).log2();
+                let probe_limit = cmp::max(
+                    1,
+                    (self.c * log_inv_load.min(log_inv_delta)).ceil() as usize,
+                );
+                // Case 1: try limited probes in the current level
+                for j in 0..probe_limit {
+                    let idx = self.quad_probe(key, i, j, level_size);
+                    self.spend(budget, i)?;
+                    if self.levels[i][idx].is_free() {
+                        return Ok(FreeSlot { level: i, idx, displaced: false });
+                    }
+                }
+                // if insertion fails in the current level, try a fixed number of probes in the next level (here using the ceiling of c)
+                let next_size = self.levels[i + 1].len();
+                for j in 0..self.c.ceil() as usize {
+                    let idx = self.quad_probe(key, i + 1, j, next_size);
+                    self.spend(budget, i + 1)?;
+                    if self.levels[i + 1][idx].is_free() {
+                        return Ok(FreeSlot { level: i + 1, idx, displaced: false });
+                    }
+                }
+            } else if load <= (self.delta / 2.0) {
+                // Case 2: current level has too few empty slots, skip and try the next level
+                self.count_level(i, |l| &l.fall_throughs);
+                continue;
+            } else if next_load <= THRESHOLD {
+                // Case 3: next level is full, must find a slot in the current level
+                for j in 0..cmp::min(self.fallback_probes(), level_size) {
+                    let idx = self.quad_probe(key, i, j, level_size);
+                    self.spend(budget, i)?;
+                    if self.levels[i][idx].is_free() {
+                        return Ok(FreeSlot { level: i, idx, displaced: false });
+                    }
+                }
+                if let Some(free) = self.any_free_within(i, budget)? {
+                    return Ok(free);
+                }
+            }
+            self.count_level(i, |l| &l.fall_throughs);
+        }
+        // last level: any free slot will do
+        let last = self.levels.len() - 1;
+        let last_level_size = self.levels[last].len();
+        for j in 0..cmp::min(self.fallback_probes(), last_level_size) {
+            let idx = self.quad_probe(key, last, j, last_level_size);
+            self.spend(budget, last)?;
+            if self.levels[last][idx].is_free() {
+                return Ok(FreeSlot { level: last, idx, displaced: false });
+            }
+        }
+        if let Some(free) = self.any_free_within(last, budget)? {
+            return Ok(free);
+        }
+        // with the last level full, a level Case 2 skipped, or one only Case 1 probed, may still have room;
+        // the count allows another entry, so take the first free slot of any level
+        for level in 0..last {
+            if let Some(free) = self.any_free_within(level, budget)? {
+                return Ok(free);
+            }
+        }
+        Err(NoSlot::Full(ElasticHashError::TableSaturated))
+    }
+
+    /// locate the (level, index) holding key
+    pub(crate) fn find<Q>(&self, key: &Q) -> Option<(usize, usize)>
+    where
+        Q: ?Sized + Hash + Equivalent<K>,
+    {
+        self.probe(key).0
+    }
+
+    /// walk the probe sequences like find, also returning the number of slots inspected
+    pub(crate) fn probe<Q>(&self, key: &Q) -> (Option<(usize, usize)>, usize)
+    where
+        Q: ?Sized + Hash + Equivalent<K>,
+    {
+        self.probe_hashed(S::hash_one(key, self.seed), key)
+    }
+
+    /// probe for key, whose hash_key is key_hash
+    pub(crate) fn probe_hashed<Q>(&self, key_hash: u64, key: &Q) -> (Option<(usize, usize)>, usize)
+    where
+        Q: ?Sized + Equivalent<K>,
+    {
+        let (pos, probes) = self.walk(key_hash, key);
+        self.count_probes(probes);
+        (pos, probes)
+    }
+
+    fn walk<Q>(&self, key_hash: u64, key: &Q) -> (Option<(usize, usize)>, usize)
+    where
+        Q: ?Sized + Equivalent<K>,
+    {
+        let mut probes = 0;
+        self.probe_stats.searches.fetch_add(1, Ordering::Relaxed);
+        for step in self.probe_walk(key_hash, key) {
+            probes += 1;
+            self.count_level(step.level, |l| &l.search_probes);
+            if step.state == ProbeState::Match {
+                return (Some((step.level, step.idx)), probes);
+            }
+        }
+        (None, probes)
+    }
+
+    /// the slots a search for key, whose hash_key is key_hash, inspects, in order; see ProbeWalk
+    pub(crate) fn probe_walk<'a, Q>(&'a self, key_hash: u64, key: &'a Q) -> ProbeWalk<'a, K, V, Q>
+    where
+        Q: ?Sized + Equivalent<K>,
+    {
+        // the displaced index is a cheap hash lookup, while walking full levels is not
+        let displaced = if self.displaced.is_empty() {
+            &[][..]
+        } else {
+            self.displaced.get(&key_hash).map_or(&[][..], |places| &places[..])
+        };
+        ProbeWalk {
+            levels: &self.levels,
+            key,
+            key_hash,
+            displaced,
+            bound: self.probe_bound(),
+            level: 0,
+            level_hash: level_hash(key_hash, 0),
+            j: 0,
+            done: false,
+        }
+    }
+
+    /// count one insert probe into level against budget, probe_count and the level's counters
+    fn spend(&self, budget: &mut usize, level: usize) -> Result<(), NoSlot> {
+        if *budget == 0 {
+            return Err(NoSlot::Budget);
+        }
+        *budget -= 1;
+        self.count_probes(1);
+        self.count_level(level, |l| &l.insert_probes);
+        Ok(())
+    }
+
+    /// add n slot inspections to probe_count
+    pub(crate) fn count_probes(&self, n: usize) {
+        self.probes.fetch_add(n as u64, Ordering::Relaxed);
+    }
+
+    /// number of probes insert spent in the level it finally used to reach idx
+    pub(crate) fn probe_position<Q>(&self, key: &Q, level: usize, idx: usize) -> usize
+    where
+        K: std::borrow::Borrow<Q>,
+        Q: ?Sized + Hash,
+    {
+        let level_size = self.levels[level].len();
+        (0..level_size)
+            .position(|j| self.quad_probe(key, level, j, level_size) == idx)
+            .map_or(level_size, |j| j + 1)
+    }
+
+    /// first slot of key's probe sequence in level
+    pub fn home_slot<Q>(&self, key: &Q, level: usize) -> Result<usize, SlotError>
+    where
+        K: std::borrow::Borrow<Q>,
+        Q: ?Sized + Hash,
+    {
+        let size = self
+            .levels
+            .get(level)
+            .ok_or(SlotError::LevelOutOfRange {
+                level,
+                levels: self.levels.len(),
+            })?
+            .len();
+        Ok(self.quad_probe(key, level, 0, size))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// key whose probe sequences the layout tests below are built around
+    const PROBED_KEY: u32 = 7;
+
+    /// the distinct slots of PROBED_KEY's probe sequence in a level of the given size, in probe order
+    fn reachable(level: usize, size: usize) -> Vec<usize> {
+        let scratch = ElasticHashTable::<u32, u32>::deterministic(16, 0.1);
+        let mut slots = Vec::new();
+        for j in 0..size {
+            let idx = scratch.quad_probe(&PROBED_KEY, level, j, size);
+            if !slots.contains(&idx) {
+                slots.push(idx);
+            }
+        }
+        slots
+    }
+
+    /// a level of the given size with every slot occupied except those in free
+    fn level_with_free(size: usize, free: &[usize], next_key: &mut u32) -> Vec<Option<(u32, u32)>> {
+        (0..size)
+            .map(|idx| {
+                if free.contains(&idx) {
+                    None
+                } else {
+                    *next_key += 1;
+                    Some((*next_key, 0))
+                }
+            })
+            .collect()
+    }
+
+    /// the slots of a level of the given size that PROBED_KEY can never reach
+    fn unreachable(level: usize, size: usize) -> Vec<usize> {
+        let reachable = reachable(level, size);
+        (0..size).filter(|idx| !reachable.contains(idx)).collect()
+    }
+
+    #[test]
+    fn test_case_3_scans_current_level_when_next_is_full() {
+        // level 1 is completely full, so level 0 has to be scanned past the limited probes of Case 1
+        let mut next_key = 100;
+        let target = reachable(0, 8)[2];
+        let level0 = level_with_free(8, &[target, unreachable(0, 8)[0]], &mut next_key);
+        let level1 = level_with_free(4, &[], &mut next_key);
+        let mut table = ElasticHashTable::from_layout(vec![level0, level1], 0.1).expect("valid layout");
+
+        assert_eq!(table.insert(PROBED_KEY, 1), Ok((0, target)));
+        assert_eq!(table.get(&PROBED_KEY), Some(&1));
+    }
+
+    #[test]
+    fn test_last_level_full_scan() {
+        // level 0 has no free slots left, so insert falls through to scanning the last level
+        let mut next_key = 100;
+        let target = *reachable(1, 8).last().unwrap();
+        let level0 = level_with_free(8, &[], &mut next_key);
+        let level1 = level_with_free(8, &[target, unreachable(1, 8)[0]], &mut next_key);
+        let mut table = ElasticHashTable::from_layout(vec![level0, level1], 0.1).expect("valid layout");
+
+        assert_eq!(table.insert(PROBED_KEY, 1), Ok((1, target)));
+        assert_eq!(table.get(&PROBED_KEY), Some(&1));
+    }
+
+    #[test]
+    fn test_insert_uses_free_slots_off_the_probe_sequence() {
+        // quadratic probing modulo 8 only reaches 3 slots, so the free slots of the last level are never probed;
+        // the free-slot fallback still places the key there and search finds it through the displaced index
+        let mut next_key = 100;
+        let free = unreachable(1, 8);
+        let level0 = level_with_free(8, &[], &mut next_key);
+        let level1 = level_with_free(8, &free[..2], &mut next_key);
+        let mut table = ElasticHashTable::from_layout(vec![level0, level1], 0.1).expect("valid layout");
+
+        assert_eq!(table.insert(PROBED_KEY, 1), Ok((1, free[0])));
+        assert_eq!(table.get(&PROBED_KEY), Some(&1));
+        assert_eq!(table.occupancies, vec![8, 7]);
+        assert_eq!(table.displaced.len(), 1);
+
+        assert_eq!(table.remove(&PROBED_KEY), Some(1));
+        assert!(table.displaced.is_empty());
+        assert_eq!(table.get(&PROBED_KEY), None);
+    }
+
+    #[test]
+    fn test_insert_falls_back_to_earlier_levels() {
+        // with delta above 1/2, Case 2 skips a level whose free fraction lies between THRESHOLD and delta / 2,
+        // which is exactly when the level before it only tried Case 1's limited probes; with the last level
+        // full, every strategy misses while level 0 is nearly empty, so the fallback scans the levels in order
+        let mut next_key = 100;
+        let level0 = level_with_free(40, &[], &mut next_key)
+            .into_iter()
+            .enumerate()
+            .map(|(idx, slot)| slot.filter(|_| reachable(0, 40)[..3].contains(&idx)))
+            .collect();
+        let probed = &reachable(1, 10)[..4];
+        let free: Vec<usize> = (0..10).filter(|idx| !probed.contains(idx)).take(3).collect();
+        let level1 = level_with_free(10, &free, &mut next_key);
+        let last = level_with_free(1, &[], &mut next_key);
+        let mut table = ElasticHashTable::from_layout(vec![level0, level1, last], 0.7).expect("valid layout");
+        assert_eq!((table.len(), table.max_inserts()), (11, 16));
+
+        let (level, idx) = table.insert(PROBED_KEY, 1).expect("Insertion failed");
+        assert_eq!(level, 0);
+        assert!(!reachable(0, 40)[..3].contains(&idx));
+        assert_eq!(table.get(&PROBED_KEY), Some(&1));
+        assert_eq!(table.displaced.len(), 1);
+    }
+
+    #[test]
+    fn test_insert_never_fails_before_max_inserts() {
+        let mut state = 0x2545_f491_4f6c_dd1du64;
+        let mut next = move || {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state
+        };
+        for round in 0..400 {
+            let capacity = 8 + (next() % 193) as usize;
+            let delta = [0.05, 0.1, 0.2, 0.5, 0.7, 0.9][round % 6];
+            let mut table = ElasticHashTable::with_seed(capacity, delta, next());
+            let keys: Vec<u64> = (0..table.max_inserts()).map(|_| next()).collect();
+            for (i, &key) in keys.iter().enumerate() {
+                let inserted = table.insert(key, key);
+                assert!(inserted.is_ok(), "capacity {} delta {} entry {}: {:?}", capacity, delta, i, inserted);
+            }
+            assert!(table.is_full());
+            // refilling after removals leaves tombstones in the probe sequences
+            for &key in keys.iter().step_by(3) {
+                table.remove(&key);
+            }
+            for key in keys.iter().step_by(3).map(|key| key ^ 1) {
+                table.insert(key, key).expect("Insertion failed");
+            }
+            assert!(keys.iter().skip(1).step_by(3).all(|key| table.get(key) == Some(key)));
+            table.check_invariants().unwrap();
+        }
+        // the regression case of a 10-slot table
+        let mut table = ElasticHashTable::with_seed(10, 0.1, 1);
+        for key in 0..table.max_inserts() as u32 {
+            table.insert(key, key).expect("Insertion failed");
+        }
+        assert_eq!(table.len(), 9);
+    }
+
+    #[test]
+    fn test_probe_index_uses_the_full_hash() {
+        // hashes that agree in their low 32 bits still start at different slots
+        let size = 1_000_003;
+        assert_ne!(probe_index(1 << 40, 0, size), probe_index(0, 0, size));
+        assert_eq!(probe_index(u64::MAX, 0, size), (u64::MAX % size as u64) as usize);
+        assert!(probe_index(u64::MAX, 100_000, size) < size);
+        assert_eq!(probe_index(u64::MAX, 3, 8), ((u64::MAX % 8 + 9) % 8) as usize);
+    }
+
+    #[test]
+    fn test_nearly_full_small_table_finds_every_key() {
+        // placement in the last level itself is pinned down by test_last_level_full_scan
+        let mut used = std::collections::BTreeSet::new();
+        for seed in 0..200 {
+            let mut table = ElasticHashTable::with_seed(16, 0.1, seed);
+            for i in 0..table.max_inserts as u32 {
+                used.insert(table.insert(i, i).expect("Insertion failed").0);
+            }
+            for i in 0..table.max_inserts as u32 {
+                assert_eq!(table.get(&i), Some(&i), "seed {} key {}", seed, i);
+            }
+            table.check_invariants().unwrap();
+        }
+        assert!(used.len() >= 3, "levels used: {:?}", used);
+    }
+
+    #[test]
+    fn test_search_probes_are_bounded_by_the_probe_limit() {
+        let mut table = ElasticHashTable::with_seed(4096, 0.1, 2);
+        for i in 0..3600u32 {
+            table.insert(i, i).expect("Insertion failed");
+        }
+        // a miss walks at most probe_bound slots per level (plus the displaced entries sharing its hash),
+        // never a whole level
+        let per_level = table.levels.len() * table.probe_bound();
+        let misses: usize = (10_000..11_000u32).map(|k| table.probe(&k).1).sum();
+        assert!(misses / 1000 <= per_level, "{} per miss", misses / 1000);
+        for k in 0..3600u32 {
+            let displaced = table.displaced.get(&table.hash_key(&k)).map_or(0, Vec::len);
+            assert!(table.probe(&k).1 <= per_level + displaced);
+        }
+        assert!(per_level < table.levels[0].len() / 10);
+    }
+}
@@ -0,0 +1,295 @@
+//! per-level probe counters, for measuring how the bounded-probe insertion strategy behaves:
+//! where inserts spend their probes, how often they fall through to the next level or scan a whole one,
+//! and what searches cost in each level
+
+use std::hash::Hash;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use super::table::ElasticHashTable;
+use crate::TableHasher;
+
+/// probe counters of one level
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct LevelProbeStats {
+    /// slots inserts inspected in this level along probe sequences
+    pub insert_probes: u64,
+    /// inserts that looked at this level, or skipped it as too full, and went on to the next one
+    pub fall_throughs: u64,
+    /// scans of the whole level for any free slot, by Case 3 or, in the last level, the final fallback
+    pub full_scans: u64,
+    /// slots searches inspected in this level
+    pub search_probes: u64,
+}
+
+/// probe counters since the table was built, grew or had them reset, see ElasticHashTable::probe_stats
+/// insert_probes and search_probes over all levels add up to the probes probe_count counted in that time
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ProbeStats {
+    /// level 0 first, one for each level of the table
+    pub levels: Vec<LevelProbeStats>,
+    /// free-slot lookups, one per insert of a new key; an insert into a full table makes none
+    pub inserts: u64,
+    /// key lookups, whether they found the key or not; every insert makes one too
+    pub searches: u64,
+}
+
+impl ProbeStats {
+    /// the counters of every level added up
+    pub fn total(&self) -> LevelProbeStats {
+        self.levels.iter().fold(LevelProbeStats::default(), |sum, level| LevelProbeStats {
+            insert_probes: sum.insert_probes + level.insert_probes,
+            fall_throughs: sum.fall_throughs + level.fall_throughs,
+            full_scans: sum.full_scans + level.full_scans,
+            search_probes: sum.search_probes + level.search_probes,
+        })
+    }
+
+    /// probes per free-slot lookup, 0 before the first
+    pub fn avg_insert_probes(&self) -> f64 {
+        per(self.total().insert_probes, self.inserts)
+    }
+
+    /// probes per key lookup, 0 before the first
+    pub fn avg_search_probes(&self) -> f64 {
+        per(self.total().search_probes, self.searches)
+    }
+
+    /// compact JSON object: {"inserts":..,"searches":..,"levels":[{"insertProbes":..,"fallThroughs":..,
+    /// "fullScans":..,"searchProbes":..},..]}
+    pub fn to_json(&self) -> String {
+        self.to_json_with(|n| n.to_string())
+    }
+
+    /// to_json with every counter written by int, as TableStats::to_json_with
+    pub(crate) fn to_json_with(&self, int: impl Fn(u64) -> String) -> String {
+        let levels: Vec<String> = self
+            .levels
+            .iter()
+            .map(|l| {
+                format!(
+                    "{{\"insertProbes\":{},\"fallThroughs\":{},\"fullScans\":{},\"searchProbes\":{}}}",
+                    int(l.insert_probes),
+                    int(l.fall_throughs),
+                    int(l.full_scans),
+                    int(l.search_probes)
+                )
+            })
+            .collect();
+        format!(
+            "{{\"inserts\":{},\"searches\":{},\"levels\":[{}]}}",
+            int(self.inserts),
+            int(self.searches),
+            levels.join(",")
+        )
+    }
+}
+
+/// LevelProbeStats as atomics
+#[derive(Default)]
+pub(crate) struct LevelCounters {
+    pub(crate) insert_probes: AtomicU64,
+    pub(crate) fall_throughs: AtomicU64,
+    pub(crate) full_scans: AtomicU64,
+    pub(crate) search_probes: AtomicU64,
+}
+
+/// the table's ProbeStats as atomics, so lookups count through &self without a borrow held across the
+/// caller's Equivalent impl, and without making the table !Sync; Relaxed throughout, as they order nothing
+pub(crate) struct ProbeCounters {
+    levels: Vec<LevelCounters>,
+    pub(crate) inserts: AtomicU64,
+    pub(crate) searches: AtomicU64,
+}
+
+impl ProbeCounters {
+    /// zeroed counters for a table of levels levels, allocated up front so probing never allocates
+    pub(crate) fn new(levels: usize) -> Self {
+        ProbeCounters {
+            levels: std::iter::repeat_with(LevelCounters::default).take(levels).collect(),
+            inserts: AtomicU64::new(0),
+            searches: AtomicU64::new(0),
+        }
+    }
+
+    /// the counters as they are now
+    pub(crate) fn snapshot(&self) -> ProbeStats {
+        let load = |counter: &AtomicU64| counter.load(Ordering::Relaxed);
+        ProbeStats {
+            levels: self
+                .levels
+                .iter()
+                .map(|l| LevelProbeStats {
+                    insert_probes: load(&l.insert_probes),
+                    fall_throughs: load(&l.fall_throughs),
+                    full_scans: load(&l.full_scans),
+                    search_probes: load(&l.search_probes),
+                })
+                .collect(),
+            inserts: load(&self.inserts),
+            searches: load(&self.searches),
+        }
+    }
+}
+
+impl Clone for ProbeCounters {
+    fn clone(&self) -> Self {
+        let stats = self.snapshot();
+        let counters = ProbeCounters::new(stats.levels.len());
+        for (to, from) in counters.levels.iter().zip(&stats.levels) {
+            to.insert_probes.store(from.insert_probes, Ordering::Relaxed);
+            to.fall_throughs.store(from.fall_throughs, Ordering::Relaxed);
+            to.full_scans.store(from.full_scans, Ordering::Relaxed);
+            to.search_probes.store(from.search_probes, Ordering::Relaxed);
+        }
+        counters.inserts.store(stats.inserts, Ordering::Relaxed);
+        counters.searches.store(stats.searches, Ordering::Relaxed);
+        counters
+    }
+}
+
+fn per(total: u64, count: u64) -> f64 {
+    if count == 0 {
+        0.0
+    } else {
+        total as f64 / count as f64
+    }
+}
+
+impl<K, V, S> ElasticHashTable<K, V, S>
+where
+    K: Hash + Eq,
+    S: TableHasher,
+{
+    /// the probe counters; growing or rehashing starts them over, as the levels they describe are rebuilt
+    pub fn probe_stats(&self) -> ProbeStats {
+        self.probe_stats.snapshot()
+    }
+
+    /// zero the probe counters, e.g. between the phases of a benchmark; probe_count is left as it is
+    pub fn reset_probe_stats(&mut self) {
+        self.probe_stats = ProbeCounters::new(self.probe_stats.levels.len());
+    }
+
+    /// add one to the counter of level that counter picks
+    pub(crate) fn count_level(&self, level: usize, counter: impl FnOnce(&LevelCounters) -> &AtomicU64) {
+        counter(&self.probe_stats.levels[level]).fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+    use std::hash::{Hash, Hasher};
+
+    use super::{LevelProbeStats, ProbeStats};
+    use crate::{json, ElasticHashTable, Equivalent, LevelGeometry};
+
+    /// a lookup of key that reads the probe stats of the table it searches from inside equivalent
+    struct Peeking<'a> {
+        key: u64,
+        table: &'a ElasticHashTable<u64, u64>,
+        searches_seen: Cell<u64>,
+    }
+
+    impl Hash for Peeking<'_> {
+        fn hash<H: Hasher>(&self, state: &mut H) {
+            self.key.hash(state);
+        }
+    }
+
+    impl Equivalent<u64> for Peeking<'_> {
+        fn equivalent(&self, key: &u64) -> bool {
+            self.searches_seen.set(self.table.probe_stats().searches);
+            self.key == *key
+        }
+    }
+
+    #[test]
+    fn test_stats_readable_during_lookups() {
+        let mut table: ElasticHashTable<u64, u64> = ElasticHashTable::with_seed(64, 0.1, 1);
+        for key in 0..40 {
+            table.insert(key, key).expect("Insertion failed");
+        }
+        let peek = Peeking { key: 7, table: &table, searches_seen: Cell::new(0) };
+        assert_eq!(table.get(&peek), Some(&7));
+        // 40 inserts searched once each, and this lookup was counted before it compared keys
+        assert_eq!(peek.searches_seen.get(), 41);
+
+        // lookups count through &self, so threads can share the table
+        fn assert_sync<T: Sync>(_: &T) {}
+        assert_sync(&table);
+        std::thread::scope(|s| {
+            for _ in 0..4 {
+                s.spawn(|| assert!((0..40).all(|key| table.contains_key(&key))));
+            }
+        });
+        let stats = table.probe_stats();
+        assert_eq!(stats.searches, 41 + 4 * 40);
+        assert_eq!(stats.total().insert_probes + stats.total().search_probes, table.probe_count());
+    }
+
+    #[test]
+    fn test_probe_stats_at_ninety_percent() {
+        let mut table: ElasticHashTable<u64, u64> = ElasticHashTable::with_seed(10_000, 0.1, 7);
+        for key in 0..9_000 {
+            table.insert(key, key).expect("Insertion failed");
+        }
+        assert!(table.is_full());
+        let stats = table.probe_stats();
+        assert_eq!((stats.inserts, stats.searches), (9_000, 9_000));
+        let avg = stats.avg_insert_probes();
+        assert!(avg > 1.0 && avg < 4.0, "average insert probes {}", avg);
+        let total = stats.total();
+        assert!(total.fall_throughs > 0);
+        assert_eq!(stats.levels.len(), table.level_sizes().len());
+        // every probe probe_count saw is in exactly one level's counters
+        assert_eq!(total.insert_probes + total.search_probes, table.probe_count());
+
+        for key in 0..9_000 {
+            assert!(table.contains_key(&key));
+        }
+        let searched = table.probe_stats();
+        assert_eq!(searched.searches, 18_000);
+        assert!(searched.total().search_probes > total.search_probes);
+        assert!(searched.avg_search_probes() >= 1.0);
+
+        table.reset_probe_stats();
+        let levels = vec![LevelProbeStats::default(); table.level_sizes().len()];
+        assert_eq!(table.probe_stats(), ProbeStats { levels, ..Default::default() });
+        assert!(table.probe_count() > 0);
+    }
+
+    #[test]
+    fn test_full_scans_and_fall_throughs_near_saturation() {
+        let mut table: ElasticHashTable<u64, u64> =
+            ElasticHashTable::with_geometry(1024, 0.02, 3, LevelGeometry::Halving).unwrap();
+        let mut empty = table.probe_stats();
+        for key in 0..table.max_inserts() as u64 {
+            table.insert(key, key).expect("Insertion failed");
+            if key == 100 {
+                empty = table.probe_stats();
+            }
+        }
+        // a nearly empty table places everything in level 0
+        assert_eq!(empty.total().fall_throughs, 0);
+        let stats = table.probe_stats();
+        assert!(stats.total().fall_throughs > 0);
+        assert_eq!(stats.levels[stats.levels.len() - 1].fall_throughs, 0);
+
+        // a single level is scanned once its probe sequences stop finding room
+        let mut single: ElasticHashTable<u64, u64> =
+            ElasticHashTable::with_geometry(256, 0.01, 3, LevelGeometry::Single).unwrap();
+        for key in 0..single.max_inserts() as u64 {
+            single.insert(key, key).expect("Insertion failed");
+        }
+        let scans = single.probe_stats().levels[0].full_scans;
+        assert!(scans > 0 && scans as usize == single.displaced.values().map(Vec::len).sum::<usize>());
+
+        let fields = stats.to_json();
+        assert!(fields.starts_with(&format!("{{\"inserts\":{},\"searches\":", stats.inserts)));
+        let level0 = &fields[fields.find('[').unwrap() + 1..=fields.find('}').unwrap()];
+        let level0 = json::flat_object(level0).unwrap();
+        assert_eq!(level0[0], ("insertProbes".to_string(), stats.levels[0].insert_probes.to_string()));
+        assert_eq!(level0[1], ("fallThroughs".to_string(), stats.levels[0].fall_throughs.to_string()));
+    }
+}
@@ -0,0 +1,163 @@
+//! serde Serialize and Deserialize for ElasticHashTable, enabled by the `serde` feature
+//!
+//! A table is written as its layout: the layout version and hasher, capacity, delta and seed, the level sizes,
+//! per-level occupancies and the entry count, then every entry with its (level, index) and the tombstones.
+//! Deserializing puts every entry back in its slot, as from_layout_snapshot does, so the probe structure
+//! is the one that was serialized; a table whose layout cannot be restored exactly is an error, never
+//! re-inserted. Metadata, observers, growth and tuning settings are not serialized.
+
+use std::hash::Hash;
+
+use serde::de::Error as _;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use super::layout::{ImportMode, LayoutSnapshot, LAYOUT_VERSION};
+use super::table::{ElasticHashTable, Slot, SlotId, TableOptions};
+use crate::TableHasher;
+
+/// the serialized form; K and V are references when serializing
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SerializedTable<K, V> {
+    layout_version: u32,
+    hasher: String,
+    capacity: usize,
+    delta: f64,
+    seed: u64,
+    level_sizes: Vec<usize>,
+    occupancies: Vec<usize>,
+    len: usize,
+    /// (level, index, key, value) in (level, index) order
+    entries: Vec<(usize, usize, K, V)>,
+    tombstones: Vec<(usize, usize)>,
+}
+
+impl<K, V, S> Serialize for ElasticHashTable<K, V, S>
+where
+    K: Serialize,
+    V: Serialize,
+    S: TableHasher,
+{
+    fn serialize<Z: Serializer>(&self, serializer: Z) -> Result<Z::Ok, Z::Error> {
+        let mut entries = Vec::with_capacity(self.num_inserts);
+        let mut tombstones = Vec::new();
+        for (level, slots) in self.levels.iter().enumerate() {
+            for (index, slot) in slots.iter().enumerate() {
+                match slot {
+                    Slot::Occupied(k, v) => entries.push((level, index, k, v)),
+                    Slot::Tombstone => tombstones.push((level, index)),
+                    Slot::Empty => {}
+                }
+            }
+        }
+        let level_sizes: Vec<usize> = self.levels.iter().map(Vec::len).collect();
+        SerializedTable {
+            layout_version: LAYOUT_VERSION,
+            hasher: S::ID.to_string(),
+            capacity: level_sizes.iter().sum(),
+            delta: self.delta,
+            seed: self.seed,
+            level_sizes,
+            occupancies: self.occupancies.clone(),
+            len: self.num_inserts,
+            entries,
+            tombstones,
+        }
+        .serialize(serializer)
+    }
+}
+
+impl<'de, K, V, S> Deserialize<'de> for ElasticHashTable<K, V, S>
+where
+    K: Hash + Eq + Deserialize<'de>,
+    V: Deserialize<'de>,
+    S: TableHasher,
+{
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let serialized = SerializedTable::<K, V>::deserialize(deserializer)?;
+        let (occupancies, len) = (serialized.occupancies, serialized.len);
+        if len != serialized.entries.len() {
+            return Err(D::Error::custom(format!("len is {} but there are {} entries", len, serialized.entries.len())));
+        }
+        let snapshot = LayoutSnapshot {
+            layout_version: serialized.layout_version,
+            hasher: serialized.hasher,
+            options: TableOptions { capacity: serialized.capacity, delta: serialized.delta, seed: serialized.seed },
+            level_sizes: serialized.level_sizes,
+            entries: serialized
+                .entries
+                .into_iter()
+                .map(|(level, index, k, v)| (SlotId { level, index }, k, v))
+                .collect(),
+            tombstones: serialized.tombstones.into_iter().map(SlotId::from).collect(),
+        };
+        let (table, report) = Self::from_layout_snapshot(snapshot).map_err(D::Error::custom)?;
+        if report.mode != ImportMode::LayoutPreserving {
+            return Err(D::Error::custom(report.warning.unwrap_or_default()));
+        }
+        if table.occupancies != occupancies {
+            return Err(D::Error::custom(format!(
+                "occupancies are {:?} but the entries fill {:?}",
+                occupancies, table.occupancies
+            )));
+        }
+        Ok(table)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{ElasticHashTable, IdentityHash, StableHash};
+
+    fn table(len: u64) -> ElasticHashTable<u64, String> {
+        let mut table = ElasticHashTable::with_seed(4096, 0.1, 7);
+        for key in 0..len {
+            table.insert(key, format!("value {}", key)).expect("Insertion failed");
+        }
+        table
+    }
+
+    #[test]
+    fn test_serde_roundtrip_keeps_layout() {
+        let mut table = table(3000);
+        for key in (0..3000).step_by(7) {
+            table.remove(&key);
+        }
+        table.insert_or_update(1, "updated".to_string()).expect("Insertion failed");
+        let json = serde_json::to_string(&table).unwrap();
+        let restored: ElasticHashTable<u64, String> = serde_json::from_str(&json).unwrap();
+
+        assert_eq!((restored.len(), restored.capacity()), (table.len(), table.capacity()));
+        assert_eq!((restored.delta, restored.seed), (table.delta, table.seed));
+        assert_eq!(restored.occupancies, table.occupancies);
+        for key in 0..3000 {
+            assert_eq!(restored.get(&key), table.get(&key), "{}", key);
+            assert_eq!(restored.find(&key), table.find(&key), "{}", key);
+        }
+        assert_eq!(restored.layout_snapshot(), table.layout_snapshot());
+        restored.check_invariants().unwrap();
+        // serializing again gives the same document
+        assert_eq!(serde_json::to_string(&restored).unwrap(), json);
+    }
+
+    #[test]
+    fn test_serde_rejects_inconsistent_tables() {
+        let json = serde_json::to_string(&table(10)).unwrap();
+        // the error from reading json with from replaced by to
+        let edit = |from: &str, to: &str| -> String {
+            assert!(json.contains(from), "{}", from);
+            let edited = json.replacen(from, to, 1);
+            let err = serde_json::from_str::<ElasticHashTable<u64, String>>(&edited).err();
+            err.expect("the edited table was accepted").to_string()
+        };
+        assert!(edit("\"len\":10", "\"len\":11").contains("len is 11"));
+        assert!(edit("\"occupancies\":[", "\"occupancies\":[1,").contains("occupancies"));
+        assert!(edit("\"capacity\":4096", "\"capacity\":4097").contains("do not sum"));
+        assert!(edit("\"layoutVersion\":", "\"layoutVersion\":9").contains("layout version"));
+
+        // a table hashed one way cannot be read as one hashed another way
+        let err = serde_json::from_str::<ElasticHashTable<u64, String, IdentityHash>>(&json).err().unwrap();
+        assert!(err.to_string().contains("hashes with"));
+        assert!(serde_json::from_str::<ElasticHashTable<u64, String, StableHash>>(&json).is_ok());
+    }
+}
@@ -0,0 +1,75 @@
+//! point-in-time copies of a table's entries
+
+use std::hash::Hash;
+
+use super::table::ElasticHashTable;
+use crate::TableHasher;
+
+/// owning iterator over a copy of the entries, returned by ElasticHashTable::iter_snapshot
+pub struct SnapshotIter<K, V> {
+    entries: std::vec::IntoIter<(K, V)>,
+}
+
+impl<K, V> Iterator for SnapshotIter<K, V> {
+    type Item = (K, V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.entries.next()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.entries.size_hint()
+    }
+}
+
+impl<K, V> ExactSizeIterator for SnapshotIter<K, V> {}
+
+impl<K, V> std::iter::FusedIterator for SnapshotIter<K, V> {}
+
+impl<K, V, S> ElasticHashTable<K, V, S>
+where
+    K: Hash + Eq,
+    S: TableHasher,
+{
+    /// iterate over a copy of the entries taken now, so the table can be mutated while iterating
+    /// costs O(n) memory up front: every (K, V) pair is cloned into one buffer (slots are not copied)
+    pub fn iter_snapshot(&self) -> SnapshotIter<K, V>
+    where
+        K: Clone,
+        V: Clone,
+    {
+        SnapshotIter {
+            entries: self.iter().map(|(k, v)| (k.clone(), v.clone())).collect::<Vec<_>>().into_iter(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ElasticHashTable;
+
+    #[test]
+    fn test_iter_snapshot_ignores_mutations() {
+        let mut table = ElasticHashTable::deterministic(64, 0.1);
+        for i in 0..20 {
+            table.insert(i, i * 10).expect("Insertion failed");
+        }
+        let mut original: Vec<_> = table.iter().map(|(k, v)| (*k, *v)).collect();
+        let snapshot = table.iter_snapshot();
+        assert_eq!(snapshot.len(), 20);
+
+        let mut seen = Vec::new();
+        for (k, v) in snapshot {
+            // expire what we see, and add entries the snapshot must not yield
+            assert!(table.remove(&k).is_some());
+            table.insert(k + 100, v).expect("Insertion failed");
+            table.modify(&((k + 1) % 20), |v| *v += 1);
+            seen.push((k, v));
+        }
+        original.sort();
+        seen.sort();
+        assert_eq!(seen, original);
+        assert_eq!(table.get(&5), None);
+        assert_eq!(table.get(&105), Some(&50));
+    }
+}
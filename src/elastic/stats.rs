@@ -0,0 +1,308 @@
+//! table statistics: the one-line summary, and the counters as a struct and as the JSON object
+//! both stats_json and the JS getStats return
+
+use std::hash::Hash;
+use std::sync::atomic::Ordering;
+
+use super::table::ElasticHashTable;
+use crate::TableHasher;
+
+/// names of the stats_json fields, in output order; stable, since scrapers key on them
+pub const STATS_FIELDS: [&str; 10] = [
+    "len",
+    "capacity",
+    "maxInserts",
+    "loadFactor",
+    "levels",
+    "rehashes",
+    "generation",
+    "delta",
+    "recommendedDelta",
+    "previousDelta",
+];
+
+/// counters describing a table, see ElasticHashTable::stats
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TableStats {
+    /// entries stored
+    pub len: usize,
+    /// total number of slots
+    pub capacity: usize,
+    /// entries the table accepts before insert fails
+    pub max_inserts: usize,
+    /// len / capacity
+    pub load_factor: f64,
+    pub levels: usize,
+    pub rehashes: usize,
+    pub generation: u64,
+    pub delta: f64,
+    /// see ElasticHashTable::recommended_delta; null in JSON when None
+    pub recommended_delta: Option<f64>,
+    /// delta before the last tuning adjustment, see ElasticHashTable::previous_delta; null in JSON when None
+    pub previous_delta: Option<f64>,
+}
+
+impl TableStats {
+    /// compact JSON object with the STATS_FIELDS names
+    pub fn to_json(&self) -> String {
+        self.to_json_with(|n| n.to_string())
+    }
+
+    /// to_json with every integer field written by int, e.g. as a string when a JS number would round it
+    pub(crate) fn to_json_with(self, int: impl Fn(u64) -> String) -> String {
+        let values = [
+            int(self.len as u64),
+            int(self.capacity as u64),
+            int(self.max_inserts as u64),
+            self.load_factor.to_string(),
+            int(self.levels as u64),
+            int(self.rehashes as u64),
+            int(self.generation),
+            self.delta.to_string(),
+            self.recommended_delta.map_or("null".to_string(), |d| d.to_string()),
+            self.previous_delta.map_or("null".to_string(), |d| d.to_string()),
+        ];
+        let fields: Vec<String> = STATS_FIELDS
+            .iter()
+            .zip(values)
+            .map(|(name, value)| format!("\"{}\":{}", name, value))
+            .collect();
+        format!("{{{}}}", fields.join(","))
+    }
+}
+
+impl<K, V, S> ElasticHashTable<K, V, S>
+where
+    K: Hash + Eq,
+    S: TableHasher,
+{
+    /// current counters; reads only per-level bookkeeping, never the entries
+    pub fn stats(&self) -> TableStats {
+        TableStats {
+            len: self.num_inserts,
+            capacity: self.capacity(),
+            max_inserts: self.max_inserts,
+            load_factor: self.load_factor(),
+            levels: self.levels.len(),
+            rehashes: self.rehashes(),
+            generation: self.generation(),
+            delta: self.delta,
+            recommended_delta: self.recommended_delta(),
+            previous_delta: self.previous_delta(),
+        }
+    }
+
+    /// number of slots in each level, level 0 first; a custom LevelGeometry's sizes at its capacity
+    pub fn level_sizes(&self) -> Vec<usize> {
+        self.levels.iter().map(Vec::len).collect()
+    }
+
+    /// number of stored entries
+    pub fn len(&self) -> usize {
+        self.num_inserts
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.num_inserts == 0
+    }
+
+    /// entries the table accepts before insert fails, (1 - delta) of the capacity
+    pub fn max_inserts(&self) -> usize {
+        self.max_inserts
+    }
+
+    /// new keys the table still accepts before insert fails; replacing a present key needs none
+    pub fn remaining_capacity(&self) -> usize {
+        self.max_inserts.saturating_sub(self.num_inserts)
+    }
+
+    /// whether inserting a new key would fail with "maximum allowed insertions reached"
+    pub fn is_full(&self) -> bool {
+        self.remaining_capacity() == 0
+    }
+
+    /// len / capacity
+    pub fn load_factor(&self) -> f64 {
+        self.num_inserts as f64 / self.capacity() as f64
+    }
+
+    /// (occupied, size) of each level, level 0 first
+    pub fn level_stats(&self) -> Vec<(usize, usize)> {
+        self.occupancies.iter().zip(&self.levels).map(|(&occupied, level)| (occupied, level.len())).collect()
+    }
+
+    /// stats() as a JSON object, without needing serde; same shape as the JS getStats()
+    pub fn stats_json(&self) -> String {
+        self.stats().to_json()
+    }
+
+    /// slots inspected along probe sequences by every lookup and insert so far
+    pub fn probe_count(&self) -> u64 {
+        self.probes.load(Ordering::Relaxed)
+    }
+
+    /// status_string on stdout; under wasm, where stdout goes nowhere, use status_string
+    pub fn print_status(&self) {
+        print!("{}", self.status_string());
+    }
+
+    /// occupancies, entry counts and free slots per level, one item per line
+    pub fn status_string(&self) -> String {
+        let mut status = format!(
+            "Occupancies: {:?}\nNum inserts: {}\nMax inserts: {}\n",
+            self.occupancies, self.num_inserts, self.max_inserts
+        );
+        for i in 0..self.levels.len() {
+            let free = self.levels[i].len() - self.occupancies[i];
+            status += &format!("Level {}: {}/{}\n", i, free, self.levels[i].len());
+        }
+        status
+    }
+
+    /// one-line summary for logs, e.g. `elastic{n=10234/20000 lf=0.51 levels=7}`
+    /// only touches per-level counters, never the entries themselves
+    pub fn summary(&self) -> String {
+        let capacity = self.capacity();
+        format!(
+            "elastic{{n={}/{} lf={:.2} levels={}}}",
+            self.num_inserts,
+            capacity,
+            self.num_inserts as f64 / capacity as f64,
+            self.levels.len()
+        )
+    }
+}
+
+impl<K, V, S> std::fmt::Display for ElasticHashTable<K, V, S>
+where
+    K: Hash + Eq,
+    S: TableHasher,
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.summary())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::STATS_FIELDS;
+    use crate::json;
+    use crate::ElasticHashTable;
+
+    #[test]
+    fn test_stats_json_matches_struct() {
+        let mut table: ElasticHashTable<u32, u32> = ElasticHashTable::with_seed(64, 0.1, 1);
+        for i in 0..20 {
+            table.insert(i, i).expect("Insertion failed");
+        }
+        table.remove(&3);
+        let stats = table.stats();
+        assert_eq!(stats.len, 19);
+
+        let fields = json::flat_object(&table.stats_json()).unwrap();
+        let names: Vec<&str> = fields.iter().map(|(name, _)| name.as_str()).collect();
+        assert_eq!(names, STATS_FIELDS);
+        let value = |name: &str| fields.iter().find(|(n, _)| n == name).unwrap().1.clone();
+        assert_eq!(value("len"), "19");
+        assert_eq!(value("capacity"), stats.capacity.to_string());
+        assert_eq!(value("maxInserts"), stats.max_inserts.to_string());
+        assert_eq!(value("loadFactor").parse::<f64>().unwrap(), stats.load_factor);
+        assert_eq!(value("levels"), stats.levels.to_string());
+        assert_eq!(value("rehashes"), "0");
+        assert_eq!(value("generation"), "21");
+        assert_eq!(value("delta"), "0.1");
+        assert_eq!((value("recommendedDelta"), value("previousDelta")), ("null".into(), "null".into()));
+    }
+
+    #[test]
+    fn test_stats_json_parses_with_serde_json() {
+        let mut table: ElasticHashTable<u32, u32> = ElasticHashTable::with_seed(100, 0.25, 1);
+        for i in 0..60 {
+            table.insert(i, i).expect("Insertion failed");
+        }
+        let stats = table.stats();
+        let parsed: serde_json::Value = serde_json::from_str(&table.stats_json()).unwrap();
+        let object = parsed.as_object().unwrap();
+        assert_eq!(object.keys().map(String::as_str).collect::<Vec<_>>(), STATS_FIELDS);
+
+        let number = |name: &str| object[name].as_f64().unwrap();
+        assert_eq!(object["len"].as_u64(), Some(60));
+        assert_eq!(number("capacity"), stats.capacity as f64);
+        assert_eq!(number("maxInserts"), stats.max_inserts as f64);
+        assert_eq!(number("loadFactor"), stats.load_factor);
+        assert_eq!(number("levels"), stats.levels as f64);
+        assert_eq!(number("rehashes"), stats.rehashes as f64);
+        assert_eq!(number("generation"), stats.generation as f64);
+        assert_eq!(number("delta"), 0.25);
+        assert!(object["recommendedDelta"].is_null() && object["previousDelta"].is_null());
+    }
+
+    #[test]
+    fn test_summary() {
+        let mut table = ElasticHashTable::deterministic(200, 0.1);
+        assert_eq!(table.summary(), "elastic{n=0/200 lf=0.00 levels=7}");
+
+        for i in 0..103 {
+            table.insert(i, i).expect("Insertion failed");
+        }
+        assert_eq!(table.summary(), "elastic{n=103/200 lf=0.52 levels=7}");
+        assert_eq!(table.to_string(), table.summary());
+    }
+
+    #[test]
+    fn test_status_string() {
+        let mut table: ElasticHashTable<u32, u32> =
+            ElasticHashTable::with_geometry(8, 0.25, 1, crate::LevelGeometry::Custom(vec![4, 4])).unwrap();
+        for i in 0..3 {
+            table.insert(i, i).expect("Insertion failed");
+        }
+        let status = table.status_string();
+        let lines: Vec<&str> = status.lines().collect();
+        assert_eq!(lines.len(), 5);
+        assert_eq!(lines[1..3], ["Num inserts: 3", "Max inserts: 6"]);
+        assert_eq!(lines[0], format!("Occupancies: {:?}", table.occupancies));
+        assert_eq!(lines[3], format!("Level 0: {}/4", 4 - table.occupancies[0]));
+    }
+
+    #[test]
+    fn test_probe_count() {
+        let mut table = ElasticHashTable::with_seed(64, 0.1, 1);
+        assert_eq!(table.probe_count(), 0);
+        table.insert(1, 1).expect("Insertion failed");
+        let after_insert = table.probe_count();
+        assert!(after_insert > 0);
+        let (_, probes) = table.probe(&1);
+        assert_eq!(table.probe_count(), after_insert + probes as u64);
+        assert_eq!(table.clone().probe_count(), table.probe_count());
+    }
+
+    #[test]
+    fn test_counters_track_inserts_and_removes() {
+        let mut table = ElasticHashTable::with_seed(100, 0.1, 1);
+        assert_eq!((table.len(), table.capacity(), table.max_inserts()), (0, 100, 90));
+        assert!(table.is_empty() && !table.is_full());
+        for i in 0..90u32 {
+            assert_eq!(table.remaining_capacity(), 90 - i as usize);
+            table.insert(i, i).expect("Insertion failed");
+            assert_eq!(table.len(), i as usize + 1);
+            assert_eq!(table.level_stats().iter().map(|(occupied, _)| occupied).sum::<usize>(), table.len());
+        }
+        assert!(table.is_full());
+        assert_eq!(table.remaining_capacity(), 0);
+        assert_eq!(table.load_factor(), 0.9);
+        assert!(table.insert(1000, 0).is_err());
+        table.insert(5, 50).expect("replacing needs no room");
+        assert_eq!(table.len(), 90);
+
+        for i in (0..90u32).step_by(2) {
+            table.remove(&i);
+        }
+        assert_eq!((table.len(), table.remaining_capacity()), (45, 45));
+        assert!(!table.is_full());
+        let levels = table.level_stats();
+        assert_eq!(levels.iter().map(|&(_, size)| size).collect::<Vec<_>>(), table.level_sizes());
+        assert_eq!(levels.iter().map(|&(occupied, _)| occupied).sum::<usize>(), 45);
+        assert!(levels.iter().all(|&(occupied, size)| occupied <= size));
+        assert_eq!(table.stats().load_factor, table.load_factor());
+    }
+}
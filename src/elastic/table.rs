@@ -0,0 +1,2980 @@
+//! the elastic hash table: slots, levels, insertion, removal, growth and iteration
+
+use std::cmp;
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::marker::PhantomData;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use super::budget::ProbeBudgetExceeded;
+use super::error::ElasticHashError;
+use super::probe_stats::ProbeCounters;
+use super::pressure::Pressure;
+use super::tuning::DeltaTuning;
+use crate::hasher::{self, StableHash, TableHasher};
+use crate::{json, meta, Equivalent, TableObserver};
+
+
+/// state of a single slot
+/// removed entries leave a Tombstone behind so that probe chains running through the slot stay intact
+#[derive(Debug)]
+pub(crate) enum Slot<K, V> {
+    Empty,
+    Tombstone,
+    Occupied(K, V),
+}
+
+impl<K: Clone, V: Clone> Clone for Slot<K, V> {
+    fn clone(&self) -> Self {
+        match self {
+            Slot::Empty => Slot::Empty,
+            Slot::Tombstone => Slot::Tombstone,
+            Slot::Occupied(k, v) => Slot::Occupied(k.clone(), v.clone()),
+        }
+    }
+
+    /// reuse the key and value allocations when both slots are occupied
+    fn clone_from(&mut self, source: &Self) {
+        match (self, source) {
+            (Slot::Occupied(k, v), Slot::Occupied(sk, sv)) => {
+                k.clone_from(sk);
+                v.clone_from(sv);
+            }
+            (this, source) => *this = source.clone(),
+        }
+    }
+}
+
+impl<K, V> Slot<K, V> {
+    /// a slot insert may claim: never used, or freed by remove
+    pub(crate) fn is_free(&self) -> bool {
+        !matches!(self, Slot::Occupied(..))
+    }
+}
+
+/// a slot insert may claim, as picked by locate_free
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct FreeSlot {
+    pub(crate) level: usize,
+    pub(crate) idx: usize,
+    /// not on the key's probe sequence, so it must be recorded in the displaced index
+    pub(crate) displaced: bool,
+}
+
+/// location of a slot: level and index within the level, as returned by insert
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct SlotId {
+    pub level: usize,
+    pub index: usize,
+}
+
+impl From<(usize, usize)> for SlotId {
+    fn from((level, index): (usize, usize)) -> Self {
+        SlotId { level, index }
+    }
+}
+
+/// read-only view of a slot, see ElasticHashTable::get_slot
+#[derive(Debug, PartialEq, Eq)]
+pub enum SlotView<'a, K, V> {
+    Empty,
+    Tombstone,
+    Occupied(&'a K, &'a V),
+}
+
+/// a slot location that does not exist in the table
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SlotError {
+    LevelOutOfRange { level: usize, levels: usize },
+    IndexOutOfRange { id: SlotId, level_size: usize },
+}
+
+impl std::fmt::Display for SlotError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SlotError::LevelOutOfRange { level, levels } => {
+                write!(f, "level {} out of range; table has {} levels", level, levels)
+            }
+            SlotError::IndexOutOfRange { id, level_size } => write!(
+                f,
+                "slot {} out of range; level {} has {} slots",
+                id.index, id.level, level_size
+            ),
+        }
+    }
+}
+
+impl std::error::Error for SlotError {}
+
+/// iterator over the occupied slots of a single level, see ElasticHashTable::level_iter
+pub struct LevelIter<'a, K, V> {
+    slots: std::iter::Enumerate<std::slice::Iter<'a, Slot<K, V>>>,
+    /// occupied slots not yielded yet
+    remaining: usize,
+}
+
+impl<'a, K, V> LevelIter<'a, K, V> {
+    fn new(levels: &'a [Vec<Slot<K, V>>], occupancies: &[usize], level: usize) -> Option<Self> {
+        let slots = levels.get(level)?;
+        Some(LevelIter {
+            slots: slots.iter().enumerate(),
+            remaining: occupancies[level],
+        })
+    }
+}
+
+impl<'a, K, V> Iterator for LevelIter<'a, K, V> {
+    type Item = (usize, &'a K, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.remaining == 0 {
+            return None;
+        }
+        for (idx, slot) in self.slots.by_ref() {
+            if let Slot::Occupied(k, v) = slot {
+                self.remaining -= 1;
+                return Some((idx, k, v));
+            }
+        }
+        None
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<K, V> ExactSizeIterator for LevelIter<'_, K, V> {}
+
+impl<K, V> std::iter::FusedIterator for LevelIter<'_, K, V> {}
+
+/// iterator over all entries as (&key, &value), level by level, see ElasticHashTable::iter
+pub struct Iter<'a, K, V> {
+    levels: &'a [Vec<Slot<K, V>>],
+    occupancies: &'a [usize],
+    /// level the current iterator walks
+    level: usize,
+    current: Option<LevelIter<'a, K, V>>,
+    /// entries not yielded yet
+    remaining: usize,
+}
+
+impl<'a, K, V> Iterator for Iter<'a, K, V> {
+    type Item = (&'a K, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while self.remaining > 0 {
+            if let Some((_, k, v)) = self.current.as_mut()?.next() {
+                self.remaining -= 1;
+                return Some((k, v));
+            }
+            self.level += 1;
+            self.current = LevelIter::new(self.levels, self.occupancies, self.level);
+        }
+        None
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<K, V> ExactSizeIterator for Iter<'_, K, V> {}
+
+impl<K, V> std::iter::FusedIterator for Iter<'_, K, V> {}
+
+/// iterator over all keys, see ElasticHashTable::keys
+pub struct Keys<'a, K, V> {
+    inner: Iter<'a, K, V>,
+}
+
+impl<'a, K, V> Iterator for Keys<'a, K, V> {
+    type Item = &'a K;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner.next().map(|(k, _)| k)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl<K, V> ExactSizeIterator for Keys<'_, K, V> {}
+
+impl<K, V> std::iter::FusedIterator for Keys<'_, K, V> {}
+
+/// iterator over all values, see ElasticHashTable::values
+pub struct Values<'a, K, V> {
+    inner: Iter<'a, K, V>,
+}
+
+impl<'a, K, V> Iterator for Values<'a, K, V> {
+    type Item = &'a V;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner.next().map(|(_, v)| v)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl<K, V> ExactSizeIterator for Values<'_, K, V> {}
+
+impl<K, V> std::iter::FusedIterator for Values<'_, K, V> {}
+
+/// iterator over all entries with mutable values, see ElasticHashTable::iter_mut
+pub struct IterMut<'a, K, V> {
+    slots: std::iter::Flatten<std::slice::IterMut<'a, Vec<Slot<K, V>>>>,
+    /// entries not yielded yet
+    remaining: usize,
+}
+
+impl<'a, K, V> Iterator for IterMut<'a, K, V> {
+    type Item = (&'a K, &'a mut V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.remaining == 0 {
+            return None;
+        }
+        for slot in self.slots.by_ref() {
+            if let Slot::Occupied(k, v) = slot {
+                self.remaining -= 1;
+                return Some((k, v));
+            }
+        }
+        None
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<K, V> ExactSizeIterator for IterMut<'_, K, V> {}
+
+impl<K, V> std::iter::FusedIterator for IterMut<'_, K, V> {}
+
+/// iterator over all mutable values, see ElasticHashTable::values_mut
+pub struct ValuesMut<'a, K, V> {
+    inner: IterMut<'a, K, V>,
+}
+
+impl<'a, K, V> Iterator for ValuesMut<'a, K, V> {
+    type Item = &'a mut V;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner.next().map(|(_, v)| v)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl<K, V> ExactSizeIterator for ValuesMut<'_, K, V> {}
+
+impl<K, V> std::iter::FusedIterator for ValuesMut<'_, K, V> {}
+
+/// owning iterator over all entries, in iter's order; from ElasticHashTable::into_iter
+pub struct IntoIter<K, V> {
+    slots: std::iter::Flatten<std::vec::IntoIter<Vec<Slot<K, V>>>>,
+    /// entries not yielded yet
+    remaining: usize,
+}
+
+impl<K, V> Iterator for IntoIter<K, V> {
+    type Item = (K, V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.remaining == 0 {
+            return None;
+        }
+        for slot in self.slots.by_ref() {
+            if let Slot::Occupied(k, v) = slot {
+                self.remaining -= 1;
+                return Some((k, v));
+            }
+        }
+        None
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<K, V> ExactSizeIterator for IntoIter<K, V> {}
+
+impl<K, V> std::iter::FusedIterator for IntoIter<K, V> {}
+
+/// moves every entry out, e.g. to migrate them into a larger table; the observer is not told
+impl<K, V, S> IntoIterator for ElasticHashTable<K, V, S> {
+    type Item = (K, V);
+    type IntoIter = IntoIter<K, V>;
+
+    fn into_iter(self) -> IntoIter<K, V> {
+        IntoIter {
+            slots: self.levels.into_iter().flatten(),
+            remaining: self.num_inserts,
+        }
+    }
+}
+
+impl<'a, K, V, S> IntoIterator for &'a ElasticHashTable<K, V, S>
+where
+    K: Hash + Eq,
+    S: TableHasher,
+{
+    type Item = (&'a K, &'a V);
+    type IntoIter = Iter<'a, K, V>;
+
+    fn into_iter(self) -> Iter<'a, K, V> {
+        self.iter()
+    }
+}
+
+impl<'a, K, V, S> IntoIterator for &'a mut ElasticHashTable<K, V, S>
+where
+    K: Hash + Eq,
+    S: TableHasher,
+{
+    type Item = (&'a K, &'a mut V);
+    type IntoIter = IterMut<'a, K, V>;
+
+    fn into_iter(self) -> IterMut<'a, K, V> {
+        self.iter_mut()
+    }
+}
+
+/// insert every entry, like insert_batch
+/// panics at the first entry that finds no free slot; use insert_batch to handle a full table
+impl<K, V, S> Extend<(K, V)> for ElasticHashTable<K, V, S>
+where
+    K: Hash + Eq,
+    S: TableHasher,
+{
+    fn extend<I: IntoIterator<Item = (K, V)>>(&mut self, entries: I) {
+        if let Err(err) = self.insert_batch(entries) {
+            panic!("extend: {}", err.reason());
+        }
+    }
+}
+
+
+/// draining iterator returned by ElasticHashTable::extract_if
+/// entries are removed one at a time as the iterator advances; dropping it stops the extraction
+pub struct ExtractIf<'a, K, V, F, S = StableHash> {
+    table: &'a mut ElasticHashTable<K, V, S>,
+    pred: F,
+    level: usize,
+    idx: usize,
+}
+
+impl<K, V, F, S> Iterator for ExtractIf<'_, K, V, F, S>
+where
+    K: Hash + Eq,
+    S: TableHasher,
+    F: FnMut(&K, &mut V) -> bool,
+{
+    type Item = (K, V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while self.level < self.table.levels.len() {
+            let (level, idx) = (self.level, self.idx);
+            if idx >= self.table.levels[level].len() {
+                self.level += 1;
+                self.idx = 0;
+                continue;
+            }
+            self.idx += 1;
+            if let Slot::Occupied(k, v) = &mut self.table.levels[level][idx] {
+                if (self.pred)(k, v) {
+                    return Some(self.table.take_slot(level, idx));
+                }
+            }
+        }
+        None
+    }
+
+    /// at most every live entry is still to be extracted
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        if self.level >= self.table.levels.len() {
+            return (0, Some(0));
+        }
+        (0, Some(self.table.num_inserts))
+    }
+}
+
+impl<K, V, F, S> std::iter::FusedIterator for ExtractIf<'_, K, V, F, S>
+where
+    K: Hash + Eq,
+    S: TableHasher,
+    F: FnMut(&K, &mut V) -> bool,
+{
+}
+
+/// why new() would panic for capacity and delta, if it would
+pub(crate) fn check_dimensions(capacity: usize, delta: f64) -> Result<(), ElasticHashError> {
+    if capacity == 0 {
+        return Err(ElasticHashError::InvalidCapacity);
+    }
+    if !(0.0 < delta && delta < 1.0) {
+        return Err(ElasticHashError::InvalidDelta(delta));
+    }
+    Ok(())
+}
+
+/// max inserts = capacity - floor(delta * capacity)
+pub(crate) fn max_inserts(capacity: usize, delta: f64) -> usize {
+    capacity - (delta * capacity as f64).floor() as usize
+}
+
+/// the smallest capacity, at least 1, whose max_inserts is at least items
+pub(crate) fn capacity_for(items: usize, delta: f64) -> usize {
+    // max_inserts is at least capacity × (1 - delta), so this is enough but for float error
+    let mut high = cmp::max(1, (items as f64 / (1.0 - delta)).ceil() as usize);
+    while max_inserts(high, delta) < items {
+        high += 1;
+    }
+    // the floor in max_inserts may leave room for items in fewer slots; max_inserts never shrinks
+    // as capacity grows, so search below high
+    let mut low = 1;
+    while low < high {
+        let mid = low + (high - low) / 2;
+        if max_inserts(mid, delta) >= items {
+            high = mid;
+        } else {
+            low = mid + 1;
+        }
+    }
+    high
+}
+
+/// most levels a table may have; a lookup walks every level, so their number bounds its cost
+pub const MAX_LEVELS: usize = 64;
+
+/// sizes of the levels of a table with the given total capacity, each about half the previous one
+/// number of levels: about log₂(capacity), at least 1 level, so never more than MAX_LEVELS
+pub(crate) fn level_sizes(capacity: usize) -> Vec<usize> {
+    let mut sizes = Vec::new();
+    let mut remaining = capacity;
+    let mut cap = remaining;
+    while remaining > 0 {
+        cap = std::cmp::min(remaining, (cap as f64 / 2.0).ceil() as usize);
+        sizes.push(cap);
+        remaining -= cap;
+    }
+    sizes
+}
+
+/// the (level, index) an insert stored its value at, with the value it replaced there
+pub(crate) type Upserted<V> = ((usize, usize), Option<V>);
+
+/// smallest level a custom geometry may have
+pub const MIN_LEVEL_SIZE: usize = 1;
+
+/// how a table's capacity is split into levels
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub enum LevelGeometry {
+    /// each level about half the size of the one before, level 0 taking half the capacity
+    #[default]
+    Halving,
+    /// one level of the full capacity, see ElasticHashTable::single_level
+    Single,
+    /// explicit level sizes, level 0 first, e.g. a small level 0 as a hot cache before larger ones;
+    /// growth multiplies every level by the same factor
+    Custom(Vec<usize>),
+}
+
+impl LevelGeometry {
+    /// check that the geometry can build a table of capacity slots
+    pub fn validate(&self, capacity: usize) -> Result<(), ElasticHashError> {
+        let LevelGeometry::Custom(sizes) = self else {
+            return Ok(());
+        };
+        if sizes.is_empty() {
+            return Err(ElasticHashError::InvalidLayout("a custom geometry needs at least one level".into()));
+        }
+        if sizes.len() > MAX_LEVELS {
+            return Err(ElasticHashError::TooManyLevels { levels: sizes.len(), max: MAX_LEVELS });
+        }
+        if let Some(level) = sizes.iter().position(|&size| size < MIN_LEVEL_SIZE) {
+            let reason = format!("level {} is smaller than {} slots", level, MIN_LEVEL_SIZE);
+            return Err(ElasticHashError::InvalidLayout(reason));
+        }
+        let total = sizes.iter().try_fold(0usize, |sum, &size| sum.checked_add(size));
+        if total != Some(capacity) {
+            let reason = format!("level sizes add up to {:?}, not the capacity {}", total, capacity);
+            return Err(ElasticHashError::InvalidLayout(reason));
+        }
+        Ok(())
+    }
+
+    /// level sizes for capacity slots; a custom geometry is used verbatim at its own capacity and
+    /// scaled proportionally at any other, falling back to Halving below one slot per level
+    pub(crate) fn sizes(&self, capacity: usize) -> Vec<usize> {
+        match self {
+            LevelGeometry::Halving => level_sizes(capacity),
+            LevelGeometry::Single => vec![capacity],
+            LevelGeometry::Custom(sizes) if capacity < sizes.len() * MIN_LEVEL_SIZE => level_sizes(capacity),
+            LevelGeometry::Custom(sizes) => {
+                let total: usize = sizes.iter().sum();
+                let mut scaled: Vec<usize> = sizes
+                    .iter()
+                    .map(|&size| cmp::max(MIN_LEVEL_SIZE, (size as u128 * capacity as u128 / total as u128) as usize))
+                    .collect();
+                // rounding leaves the sum slightly off; settle the difference on the largest level
+                let largest = (0..scaled.len()).max_by_key(|&i| scaled[i]).unwrap_or(0);
+                let sum: usize = scaled.iter().sum();
+                scaled[largest] = scaled[largest] + capacity - sum;
+                scaled
+            }
+        }
+    }
+}
+
+/// everything needed to recreate a table's configuration, see ElasticHashTable::options
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TableOptions {
+    /// total number of slots
+    pub capacity: usize,
+    pub delta: f64,
+    pub seed: u64,
+}
+
+impl TableOptions {
+    /// parse the JSON object produced by to_json; missing fields are an error
+    pub fn from_json(s: &str) -> Result<Self, String> {
+        Self::from_fields(&json::flat_object(s)?, &[])
+    }
+
+    /// like from_json on an already split object; names in extra belong to the caller and are skipped
+    pub(crate) fn from_fields(fields: &[(String, String)], extra: &[&str]) -> Result<Self, String> {
+        let (mut capacity, mut delta, mut seed) = (None, None, None);
+        for (name, value) in fields {
+            match name.as_str() {
+                "capacity" => capacity = Some(json::parse(name, value)?),
+                "delta" => delta = Some(json::parse(name, value)?),
+                "seed" => seed = Some(json::parse(name, value)?),
+                // read-only parameters echoed by to_json
+                "c" | "threshold" | "hasher" => {}
+                name if extra.contains(&name) => {}
+                _ => return Err(format!("unknown table option: {}", name)),
+            }
+        }
+        Ok(TableOptions {
+            capacity: capacity.ok_or("missing table option: capacity")?,
+            delta: delta.ok_or("missing table option: delta")?,
+            seed: seed.ok_or("missing table option: seed")?,
+        })
+    }
+
+    /// options plus the fixed parameters c, threshold and hasher, which from_json accepts and ignores
+    pub fn to_json(&self) -> String {
+        format!(
+            "{{\"capacity\":{},\"delta\":{},\"seed\":{},\"c\":{},\"threshold\":{},\"hasher\":\"{}\"}}",
+            self.capacity, self.delta, self.seed, C, THRESHOLD, StableHash::ID
+        )
+    }
+}
+
+/// average search probe counts around ElasticHashTable::rebalance
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RebalanceReport {
+    pub probes_before: f64,
+    pub probes_after: f64,
+}
+
+/// error returned by ElasticHashTable::try_reserve; the table is left unchanged
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum TryReserveError {
+    /// the required capacity does not fit in usize
+    CapacityOverflow,
+    /// allocating a level of the given number of slots failed
+    AllocError { slots: usize },
+    /// an existing entry found no slot in the new levels
+    RehashFailed,
+}
+
+impl std::fmt::Display for TryReserveError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TryReserveError::CapacityOverflow => write!(f, "capacity overflow"),
+            TryReserveError::AllocError { slots } => write!(f, "failed to allocate a level of {} slots", slots),
+            TryReserveError::RehashFailed => write!(f, "an entry could not be placed in the grown table"),
+        }
+    }
+}
+
+impl std::error::Error for TryReserveError {}
+
+/// error returned by ElasticHashTable::insert; hands the key and value back so a caller can
+/// retry elsewhere without cloning them up front
+#[derive(Clone, PartialEq)]
+pub struct InsertError<K, V> {
+    key: K,
+    value: V,
+    error: ElasticHashError,
+}
+
+impl<K, V> InsertError<K, V> {
+    pub(crate) fn new(key: K, value: V, error: ElasticHashError) -> Self {
+        InsertError { key, value, error }
+    }
+
+    pub fn key(&self) -> &K {
+        &self.key
+    }
+
+    pub fn value(&self) -> &V {
+        &self.value
+    }
+
+    /// why the insert failed
+    pub fn error(&self) -> &ElasticHashError {
+        &self.error
+    }
+
+    /// why the insert failed, as a message
+    pub fn reason(&self) -> String {
+        self.error.to_string()
+    }
+
+    /// the budget an insert_bounded ran out of, None when the table had no room
+    pub fn probe_budget_exceeded(&self) -> Option<&ProbeBudgetExceeded> {
+        match &self.error {
+            ElasticHashError::ProbeBudgetExceeded(exceeded) => Some(exceeded),
+            _ => None,
+        }
+    }
+
+    /// the key and value that were not inserted
+    pub fn into_parts(self) -> (K, V) {
+        (self.key, self.value)
+    }
+
+    pub(crate) fn map_value<W>(self, f: impl FnOnce(V) -> W) -> InsertError<K, W> {
+        InsertError {
+            key: self.key,
+            value: f(self.value),
+            error: self.error,
+        }
+    }
+}
+
+// no K: Debug or V: Debug bound, so expect() works for any table
+impl<K, V> std::fmt::Debug for InsertError<K, V> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("InsertError").field("error", &self.error).finish_non_exhaustive()
+    }
+}
+
+impl<K, V> std::fmt::Display for InsertError<K, V> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.error.fmt(f)
+    }
+}
+
+impl<K, V> std::error::Error for InsertError<K, V> {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&self.error)
+    }
+}
+
+/// lets callers that report errors as strings keep using `?`
+impl<K, V> From<InsertError<K, V>> for String {
+    fn from(err: InsertError<K, V>) -> String {
+        err.error.to_string()
+    }
+}
+
+/// the error alone, dropping the key and value
+impl<K, V> From<InsertError<K, V>> for ElasticHashError {
+    fn from(err: InsertError<K, V>) -> ElasticHashError {
+        err.error
+    }
+}
+
+#[cfg(test)]
+thread_local! {
+    /// number of level allocations try_reserve may still make before they start failing; None never fails
+    static ALLOC_BUDGET: std::cell::Cell<Option<usize>> = const { std::cell::Cell::new(None) };
+}
+
+/// allocate an empty level without aborting on allocation failure
+pub(crate) fn try_alloc_level<K, V>(size: usize) -> Result<Vec<Slot<K, V>>, TryReserveError> {
+    #[cfg(test)]
+    if let Some(budget) = ALLOC_BUDGET.with(|b| b.get()) {
+        if budget == 0 {
+            return Err(TryReserveError::AllocError { slots: size });
+        }
+        ALLOC_BUDGET.with(|b| b.set(Some(budget - 1)));
+    }
+    let mut level = Vec::new();
+    level
+        .try_reserve_exact(size)
+        .map_err(|_| TryReserveError::AllocError { slots: size })?;
+    level.resize_with(size, || Slot::Empty);
+    Ok(level)
+}
+
+/// S picks the hash function, see TableHasher; the default StableHash suits any key
+///
+/// Iteration order is canonical: iter, keys, values, iter_hash_range and iter_snapshot, and the JS
+/// entries, nextEntry, entriesPage, entriesSnapshot and keys, yield entries in ascending
+/// (level, slot index) order, the order of the positions insert returns. The same seed and the same
+/// operations therefore always iterate identically. Any change to how slots are stored must keep
+/// this logical order, whatever the physical one.
+pub struct ElasticHashTable<K, V, S = StableHash> {
+    pub(crate) delta: f64,
+    pub(crate) max_inserts: usize,
+    pub(crate) num_inserts: usize,
+    pub(crate) levels: Vec<Vec<Slot<K, V>>>,
+    pub(crate) occupancies: Vec<usize>,
+    pub(crate) c: f64,
+    /// (level, index) where the next pop starts scanning
+    pub(crate) pop_cursor: (usize, usize),
+    pub(crate) seed: u64,
+    /// number of rehash_with_seed calls
+    pub(crate) rehashes: usize,
+    /// number of mutations so far, see generation
+    pub(crate) generation: u64,
+    /// per level, where the scan for any free slot resumes
+    pub(crate) free_cursors: Vec<usize>,
+    /// entries placed off their probe sequence by the free-slot fallback, by key hash
+    pub(crate) displaced: HashMap<u64, Vec<(usize, usize)>>,
+    /// see set_observer
+    pub(crate) observer: Option<Box<dyn TableObserver<K, V> + Send + Sync>>,
+    /// see set_pressure_callback
+    pub(crate) pressure: Option<Pressure>,
+    /// per-slot metadata, see with_metadata; None costs nothing per slot
+    pub(crate) meta: Option<meta::Metadata>,
+    /// slots inspected by lookups and inserts so far, see probe_count
+    /// lookups count through &self; an atomic rather than a Cell keeps that from making the table !Sync
+    pub(crate) probes: AtomicU64,
+    /// per-level probe counters, see probe_stats
+    pub(crate) probe_stats: ProbeCounters,
+    /// how levels are sized, see LevelGeometry; kept through growth and rehashing
+    pub(crate) geometry: LevelGeometry,
+    /// double the capacity instead of failing an insert into a full table, see set_grow_on_full
+    pub(crate) grow_on_full: bool,
+    /// insert cost curve and delta bounds, see enable_delta_analysis and set_delta_tuning
+    pub(crate) tuning: Option<Box<DeltaTuning>>,
+    pub(crate) hasher: PhantomData<S>,
+}
+
+/// clones start without an observer or pressure callback
+impl<K: Clone, V: Clone, S> Clone for ElasticHashTable<K, V, S> {
+    fn clone(&self) -> Self {
+        Self {
+            delta: self.delta,
+            max_inserts: self.max_inserts,
+            num_inserts: self.num_inserts,
+            levels: self.levels.clone(),
+            occupancies: self.occupancies.clone(),
+            c: self.c,
+            pop_cursor: self.pop_cursor,
+            seed: self.seed,
+            rehashes: self.rehashes,
+            generation: self.generation,
+            free_cursors: self.free_cursors.clone(),
+            displaced: self.displaced.clone(),
+            observer: None,
+            pressure: None,
+            meta: self.meta.clone(),
+            probes: AtomicU64::new(self.probes.load(Ordering::Relaxed)),
+            probe_stats: self.probe_stats.clone(),
+            geometry: self.geometry.clone(),
+            grow_on_full: self.grow_on_full,
+            tuning: self.tuning.clone(),
+            hasher: PhantomData,
+        }
+    }
+
+    /// overwrite self with a copy of source, keeping the existing level buffers
+    /// levels only reallocate where source's level is larger than the one already there;
+    /// self keeps its observer, which is not told about the overwrite
+    fn clone_from(&mut self, source: &Self) {
+        self.delta = source.delta;
+        self.max_inserts = source.max_inserts;
+        self.num_inserts = source.num_inserts;
+        self.levels.clone_from(&source.levels);
+        self.occupancies.clone_from(&source.occupancies);
+        self.c = source.c;
+        self.pop_cursor = source.pop_cursor;
+        self.seed = source.seed;
+        self.rehashes = source.rehashes;
+        self.generation = source.generation;
+        self.free_cursors.clone_from(&source.free_cursors);
+        self.displaced.clone_from(&source.displaced);
+        self.meta.clone_from(&source.meta);
+        *self.probes.get_mut() = source.probes.load(Ordering::Relaxed);
+        self.probe_stats.clone_from(&source.probe_stats);
+        self.geometry.clone_from(&source.geometry);
+        self.grow_on_full = source.grow_on_full;
+        self.tuning.clone_from(&source.tuning);
+    }
+}
+
+pub(crate) const THRESHOLD: f64 = 0.25;
+/// seed used by deterministic() and from_layout()
+pub(crate) const DEFAULT_SEED: u64 = 0;
+/// constant c of the probe limit f(ε)=c×min(log₂(1/ε), log₂(1/δ))
+pub(crate) const C: f64 = 4.0;
+
+impl<K, V> ElasticHashTable<K, V>
+where
+    K: Hash + Eq,
+{
+    /// capacity: total capacity
+    /// delta: ratio of empty slots
+    /// every table gets its own random seed, so nobody can precompute keys that collide in it (HashDoS);
+    /// use with_seed or deterministic when layouts must be reproducible.
+    /// on wasm32-unknown-unknown without the wasm feature there is no random source and new is deterministic
+    pub fn new(capacity: usize, delta: f64) -> Self {
+        Self::with_seed(capacity, delta, hasher::random_seed())
+    }
+
+    /// like new, with the fixed default seed: the same inserts always give the same layout,
+    /// but anyone who knows the crate can craft colliding keys
+    pub fn deterministic(capacity: usize, delta: f64) -> Self {
+        Self::with_seed(capacity, delta, DEFAULT_SEED)
+    }
+
+    /// like new, growing instead of failing when an insert finds the table full, see set_grow_on_full
+    pub fn with_growth(capacity: usize, delta: f64) -> Self {
+        let mut table = Self::new(capacity, delta);
+        table.set_grow_on_full(true);
+        table
+    }
+
+    /// like new, hashing keys with the given seed (see hash_with_seed)
+    pub fn with_seed(capacity: usize, delta: f64, seed: u64) -> Self {
+        Self::with_hasher(capacity, delta, seed)
+    }
+
+    /// adopt an explicit slot layout, so tests can set up occupancy patterns the public API cannot reach
+    /// occupancies and the insert count are recomputed from the layout;
+    /// max inserts follow new() for the total number of slots
+    #[cfg(any(test, feature = "test-util"))]
+    pub fn from_layout(levels: Vec<Vec<Option<(K, V)>>>, delta: f64) -> Result<Self, ElasticHashError> {
+        if levels.is_empty() || levels.iter().any(|level| level.is_empty()) {
+            let reason = "Layout must have at least one level and no empty levels.".into();
+            return Err(ElasticHashError::InvalidLayout(reason));
+        }
+        if levels.len() > MAX_LEVELS {
+            return Err(ElasticHashError::TooManyLevels { levels: levels.len(), max: MAX_LEVELS });
+        }
+        let capacity: usize = levels.iter().map(|level| level.len()).sum();
+        check_dimensions(capacity, delta)?;
+        let max_inserts = max_inserts(capacity, delta);
+
+        let mut seen = std::collections::HashSet::new();
+        for (k, _) in levels.iter().flatten().flatten() {
+            if !seen.insert(k) {
+                return Err(ElasticHashError::InvalidLayout("Layout contains a duplicate key.".into()));
+            }
+        }
+        let num_inserts = seen.len();
+        if num_inserts > max_inserts {
+            return Err(ElasticHashError::InvalidLayout(format!(
+                "Layout holds {} entries but at most {} are allowed.",
+                num_inserts, max_inserts
+            )));
+        }
+
+        let occupancies = levels.iter().map(|level| level.iter().flatten().count()).collect();
+        let num_levels = levels.len();
+        let sizes: Vec<usize> = levels.iter().map(Vec::len).collect();
+        let geometry = if sizes == level_sizes(capacity) { LevelGeometry::Halving } else { LevelGeometry::Custom(sizes) };
+        let levels = levels
+            .into_iter()
+            .map(|level| {
+                level
+                    .into_iter()
+                    .map(|slot| match slot {
+                        Some((k, v)) => Slot::Occupied(k, v),
+                        None => Slot::Empty,
+                    })
+                    .collect()
+            })
+            .collect();
+
+        Ok(Self {
+            delta,
+            max_inserts,
+            num_inserts,
+            levels,
+            occupancies,
+            c: C,
+            pop_cursor: (0, 0),
+            seed: DEFAULT_SEED,
+            rehashes: 0,
+            generation: 0,
+            free_cursors: vec![0; num_levels],
+            displaced: HashMap::new(),
+            observer: None,
+            pressure: None,
+            meta: None,
+            probes: AtomicU64::new(0),
+            probe_stats: ProbeCounters::new(num_levels),
+            geometry,
+            grow_on_full: false,
+            tuning: None,
+            hasher: PhantomData,
+        })
+    }
+
+    pub fn with_options(options: TableOptions) -> Self {
+        Self::with_seed(options.capacity, options.delta, options.seed)
+    }
+
+    /// a probe playground: one level of the full capacity instead of halving levels, so insert, search
+    /// and remove run plain bounded quadratic probing (with the same any-free-slot fallback as the last
+    /// level of an elastic table); stats, probe counts and everything else work as usual
+    pub fn single_level(capacity: usize, delta: f64) -> Self {
+        Self::single_level_with_hasher(capacity, delta, hasher::random_seed())
+    }
+}
+
+
+impl<K, V, S> ElasticHashTable<K, V, S>
+where
+    K: Hash + Eq,
+    S: TableHasher,
+{
+    pub fn with_hasher(capacity: usize, delta: f64, seed: u64) -> Self {
+        Self::build(capacity, delta, seed, LevelGeometry::Halving)
+    }
+
+    /// like single_level, hashing with S and the given seed
+    pub fn single_level_with_hasher(capacity: usize, delta: f64, seed: u64) -> Self {
+        Self::build(capacity, delta, seed, LevelGeometry::Single)
+    }
+
+    /// like with_hasher, splitting the capacity into levels as geometry says
+    /// fails where new would panic, or when a custom geometry has an empty level or its sizes do not add up
+    /// to capacity
+    pub fn with_geometry(
+        capacity: usize,
+        delta: f64,
+        seed: u64,
+        geometry: LevelGeometry,
+    ) -> Result<Self, ElasticHashError> {
+        check_dimensions(capacity, delta)?;
+        geometry.validate(capacity)?;
+        Ok(Self::build(capacity, delta, seed, geometry))
+    }
+
+    fn build(capacity: usize, delta: f64, seed: u64, geometry: LevelGeometry) -> Self {
+        if let Err(e) = check_dimensions(capacity, delta) {
+            panic!("{}", e);
+        }
+        let max_inserts = max_inserts(capacity, delta);
+        let levels: Vec<_> = geometry
+            .sizes(capacity)
+            .into_iter()
+            .map(|size| std::iter::repeat_with(|| Slot::Empty).take(size).collect())
+            .collect();
+
+        let occupancies = vec![0; levels.len()];
+        let occupancies_len = occupancies.len();
+        let c = C;
+
+        Self {
+            delta,
+            max_inserts,
+            num_inserts: 0,
+            levels,
+            occupancies,
+            c,
+            pop_cursor: (0, 0),
+            seed,
+            rehashes: 0,
+            generation: 0,
+            free_cursors: vec![0; occupancies_len],
+            displaced: HashMap::new(),
+            observer: None,
+            pressure: None,
+            meta: None,
+            probes: AtomicU64::new(0),
+            probe_stats: ProbeCounters::new(occupancies_len),
+            geometry,
+            grow_on_full: false,
+            tuning: None,
+            hasher: PhantomData,
+        }
+    }
+
+    /// make room for at least additional more inserts, growing the table if needed
+    /// new levels are allocated fallibly and every entry is moved over;
+    /// on any failure the table is left exactly as it was
+    pub fn try_reserve(&mut self, additional: usize) -> Result<(), TryReserveError> {
+        let needed = self
+            .num_inserts
+            .checked_add(additional)
+            .ok_or(TryReserveError::CapacityOverflow)?;
+        if needed <= self.max_inserts {
+            return Ok(());
+        }
+        let mut capacity = self.capacity().checked_mul(2).ok_or(TryReserveError::CapacityOverflow)?;
+        while max_inserts(capacity, self.delta) < needed {
+            capacity = capacity.checked_mul(2).ok_or(TryReserveError::CapacityOverflow)?;
+        }
+        self.rebuild(capacity, self.seed)
+    }
+
+    /// move every entry into a table of new_capacity slots, keeping seed and geometry and dropping tombstones
+    /// fails, leaving the table as it was, when new_capacity has no room for the current entries
+    pub fn resize(&mut self, new_capacity: usize) -> Result<(), ElasticHashError> {
+        check_dimensions(new_capacity, self.delta)?;
+        if max_inserts(new_capacity, self.delta) < self.num_inserts {
+            return Err(ElasticHashError::CapacityTooSmall { needed: self.num_inserts, requested: new_capacity });
+        }
+        self.rebuild(new_capacity, self.seed).map_err(ElasticHashError::GrowthFailed)
+    }
+
+    /// when grow is set, an insert of a new key into a full table doubles the capacity (as try_reserve(1)
+    /// would) and goes ahead instead of failing; insert_bounded never grows the table
+    pub fn set_grow_on_full(&mut self, grow: bool) {
+        self.grow_on_full = grow;
+    }
+
+    /// whether inserts grow a full table, see set_grow_on_full
+    pub fn grows_on_full(&self) -> bool {
+        self.grow_on_full
+    }
+
+    /// locate_free, first growing a full table that grows on full
+    pub(crate) fn locate_free_or_grow(&mut self, key: &K) -> Result<FreeSlot, ElasticHashError> {
+        if self.grow_on_full && self.num_inserts >= self.max_inserts {
+            self.grow_for_insert()?;
+        }
+        let (load_factor, before) = (self.load_factor(), *self.probes.get_mut());
+        let free = self.locate_free(key)?;
+        if let Some(tuning) = &mut self.tuning {
+            tuning.record(load_factor, *self.probes.get_mut() - before);
+        }
+        Ok(free)
+    }
+
+    /// double the capacity of a full table, moving delta toward its recommendation first when it is tuned;
+    /// on failure delta is restored along with the table
+    fn grow_for_insert(&mut self) -> Result<(), ElasticHashError> {
+        let delta = self.delta;
+        let previous = self.previous_delta();
+        let tuned = self.tuned_delta().filter(|&tuned| tuned != delta);
+        if let (Some(tuned), Some(tuning)) = (tuned, &mut self.tuning) {
+            tuning.previous_delta = Some(delta);
+            self.delta = tuned;
+        }
+        self.try_reserve(1).map_err(|e| {
+            self.delta = delta;
+            if let Some(tuning) = &mut self.tuning {
+                tuning.previous_delta = previous;
+            }
+            ElasticHashError::GrowthFailed(e)
+        })
+    }
+
+    /// re-place every entry under a new seed, keeping capacity and configuration
+    /// the new levels are built before the old ones are dropped; on failure the table is left as it was
+    pub fn rehash_with_seed(&mut self, new_seed: u64) -> Result<(), TryReserveError> {
+        self.rebuild(self.capacity(), new_seed)?;
+        self.rehashes += 1;
+        Ok(())
+    }
+
+    /// rehash_with_seed with a fresh random seed
+    pub fn rehash_random(&mut self) -> Result<(), TryReserveError> {
+        self.rehash_with_seed(hasher::random_seed())
+    }
+
+    /// number of successful rehash_with_seed calls
+    pub fn rehashes(&self) -> usize {
+        self.rehashes
+    }
+
+    /// rebuild the levels at the current capacity and seed, clearing all tombstones;
+    /// entries are re-inserted in order of their home slot in level 0, which keeps early probe chains short
+    pub fn rebalance(&mut self) -> Result<RebalanceReport, TryReserveError> {
+        let probes_before = self.average_probes();
+        let mut order: Vec<_> = (0..self.levels.len())
+            .flat_map(|level| {
+                self.level_iter(level)
+                    .expect("level in range")
+                    .map(move |(idx, k, _)| (level, idx, k))
+            })
+            .map(|(level, idx, k)| (self.quad_probe(k, 0, 0, self.levels[0].len()), level, idx))
+            .collect();
+        order.sort_unstable();
+        let order = order.into_iter().map(|(_, level, idx)| (level, idx)).collect();
+        self.rebuild_in_order(self.capacity(), self.seed, order)?;
+        Ok(RebalanceReport {
+            probes_before,
+            probes_after: self.average_probes(),
+        })
+    }
+
+    /// average number of slots a successful search inspects
+    pub(crate) fn average_probes(&self) -> f64 {
+        if self.num_inserts == 0 {
+            return 0.0;
+        }
+        let total: usize = self.keys().map(|k| self.probe(k).1).sum();
+        total as f64 / self.num_inserts as f64
+    }
+
+    /// move every entry into freshly allocated levels of the given capacity, hashed with seed
+    pub(crate) fn rebuild(&mut self, capacity: usize, seed: u64) -> Result<(), TryReserveError> {
+        let order = (0..self.levels.len())
+            .flat_map(|level| {
+                self.level_iter(level)
+                    .expect("level in range")
+                    .map(move |(idx, _, _)| (level, idx))
+            })
+            .collect();
+        self.rebuild_in_order(capacity, seed, order)
+    }
+
+    /// rebuild, re-inserting the entries at the given (level, index) positions in that order
+    pub(crate) fn rebuild_in_order(
+        &mut self,
+        capacity: usize,
+        seed: u64,
+        order: Vec<(usize, usize)>,
+    ) -> Result<(), TryReserveError> {
+        let sizes = self.geometry.sizes(capacity);
+        let mut levels = Vec::new();
+        levels
+            .try_reserve_exact(sizes.len())
+            .map_err(|_| TryReserveError::AllocError { slots: sizes.len() })?;
+        for size in sizes {
+            levels.push(try_alloc_level(size)?);
+        }
+        let num_levels = levels.len();
+        let mut rebuilt = Self {
+            delta: self.delta,
+            max_inserts: max_inserts(capacity, self.delta),
+            num_inserts: 0,
+            occupancies: vec![0; levels.len()],
+            levels,
+            c: self.c,
+            pop_cursor: (0, 0),
+            seed,
+            rehashes: self.rehashes,
+            generation: self.generation,
+            free_cursors: vec![0; num_levels],
+            displaced: HashMap::new(),
+            observer: None,
+            pressure: None,
+            meta: None,
+            probes: AtomicU64::new(0),
+            probe_stats: ProbeCounters::new(num_levels),
+            geometry: self.geometry.clone(),
+            grow_on_full: self.grow_on_full,
+            tuning: None,
+            hasher: PhantomData,
+        };
+        rebuilt.meta = self
+            .meta
+            .as_ref()
+            .map(|meta| meta::Metadata::new(rebuilt.levels.iter().map(Vec::len), meta.clock.clone()));
+
+        // move entries over one by one, remembering where each came from so a failure can undo the moves
+        let mut moved = Vec::with_capacity(order.len());
+        for (level, idx) in order {
+            let Slot::Occupied(k, _) = &self.levels[level][idx] else { continue };
+            match rebuilt.locate_free(k) {
+                Ok(free) => {
+                    let entry = std::mem::replace(&mut self.levels[level][idx], Slot::Empty);
+                    rebuilt.store(free, entry);
+                    if let (Some(old), Some(new)) = (&self.meta, &mut rebuilt.meta) {
+                        new.tags[free.level][free.idx] = old.tags[level][idx];
+                    }
+                    moved.push(((level, idx), (free.level, free.idx)));
+                }
+                Err(_) => {
+                    for ((level, idx), (new_level, new_idx)) in moved {
+                        let entry = std::mem::replace(&mut rebuilt.levels[new_level][new_idx], Slot::Empty);
+                        self.levels[level][idx] = entry;
+                    }
+                    return Err(TryReserveError::RehashFailed);
+                }
+            }
+        }
+        let old_capacity = self.capacity();
+        rebuilt.observer = self.observer.take();
+        rebuilt.pressure = self.pressure.take();
+        rebuilt.tuning = self.tuning.take();
+        rebuilt.generation += 1;
+        *self = rebuilt;
+        if let Some(observer) = &mut self.observer {
+            observer.on_rebuild(old_capacity, capacity);
+        }
+        self.update_pressure();
+        Ok(())
+    }
+
+    /// total number of slots, the sum of the level sizes
+    pub fn capacity(&self) -> usize {
+        self.levels.iter().map(|level| level.len()).sum()
+    }
+
+    /// the stable hash of key under this table's seed, see hash_with_seed
+    pub fn hash_key<Q>(&self, key: &Q) -> u64
+    where
+        K: std::borrow::Borrow<Q>,
+        Q: ?Sized + Hash,
+    {
+        S::hash_one(key, self.seed)
+    }
+
+    /// the seed the table hashes keys with
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// ratio of slots kept free
+    pub fn delta(&self) -> f64 {
+        self.delta
+    }
+
+    /// constant c of the probe limit
+    pub fn c(&self) -> f64 {
+        self.c
+    }
+
+    /// free ratio of the next level below which insert stops spilling into it
+    pub fn threshold(&self) -> f64 {
+        THRESHOLD
+    }
+
+    /// name of the hash algorithm
+    pub fn hasher_id(&self) -> &'static str {
+        S::ID
+    }
+
+    /// the construction parameters, enough to build an identically behaving empty table
+    /// (through with_geometry instead of with_options unless geometry is Halving)
+    pub fn options(&self) -> TableOptions {
+        TableOptions {
+            capacity: self.capacity(),
+            delta: self.delta,
+            seed: self.seed,
+        }
+    }
+
+    /// how the table sizes its levels
+    pub fn geometry(&self) -> &LevelGeometry {
+        &self.geometry
+    }
+
+    /// whether the table was built by single_level
+    pub fn is_single_level(&self) -> bool {
+        self.geometry == LevelGeometry::Single
+    }
+
+    /// insert (key, value), or replace the value if key is already present
+    /// a replacement stays in the key's slot, whose position is returned, and keeps the stored key;
+    /// it succeeds even when the table is full and does not count towards num_inserts
+    pub fn insert(&mut self, key: K, value: V) -> Result<(usize, usize), InsertError<K, V>> {
+        self.insert_with(key, || value).map_err(|err| err.map_value(|make| make()))
+    }
+
+    /// insert like insert, returning the value key held before, or None if it was absent
+    pub fn insert_or_update(&mut self, key: K, value: V) -> Result<Option<V>, InsertError<K, V>> {
+        self.upsert_with(key, || value)
+            .map(|(_, old)| old)
+            .map_err(|err| err.map_value(|make| make()))
+    }
+
+    /// insert every entry in order, returning how many were inserted or replaced
+    /// stops at the first entry that finds no free slot and returns it in the error;
+    /// the entries before it stay inserted and the ones after it are dropped
+    pub fn insert_batch<I>(&mut self, entries: I) -> Result<usize, InsertError<K, V>>
+    where
+        I: IntoIterator<Item = (K, V)>,
+    {
+        let mut count = 0;
+        for (key, value) in entries {
+            self.insert(key, value)?;
+            count += 1;
+        }
+        Ok(count)
+    }
+
+    /// insert key with the value built by make, replacing the value if key is present like insert
+    /// make is only called once a free slot has been found, so a failed insert never builds the value
+    /// and hands back the key with make unused
+    pub fn insert_with<F>(&mut self, key: K, make: F) -> Result<(usize, usize), InsertError<K, F>>
+    where
+        F: FnOnce() -> V,
+    {
+        self.upsert_with(key, make).map(|(pos, _)| pos)
+    }
+
+    /// store make's value under key: in the key's slot if present, returning the value it replaces,
+    /// otherwise in the slot locate_free picks
+    pub(crate) fn upsert_with<F>(&mut self, key: K, make: F) -> Result<Upserted<V>, InsertError<K, F>>
+    where
+        F: FnOnce() -> V,
+    {
+        if let Some((level, idx)) = self.find(&key) {
+            let value = make();
+            let old = self.replace_slot(level, idx, value);
+            return Ok(((level, idx), old));
+        }
+        let free = match self.locate_free_or_grow(&key) {
+            Ok(free) => free,
+            Err(error) => return Err(InsertError::new(key, make, error)),
+        };
+        Ok((self.place(free, key, make()), None))
+    }
+
+    /// insert key at the slot locate_free picked for it, which must still be free
+    pub(crate) fn place(&mut self, free: FreeSlot, key: K, value: V) -> (usize, usize) {
+        self.store(free, Slot::Occupied(key, value));
+        if let Some(meta) = &mut self.meta {
+            meta.tags[free.level][free.idx] = meta.stamp();
+        }
+        self.generation += 1;
+        if let Some(observer) = &mut self.observer {
+            if let Slot::Occupied(k, v) = &self.levels[free.level][free.idx] {
+                observer.on_insert(k, v, SlotId::from((free.level, free.idx)));
+            }
+        }
+        self.update_pressure();
+        (free.level, free.idx)
+    }
+
+    /// report every mutation to observer from now on, replacing any previous observer
+    /// observer must be Send and Sync so the table stays so; share state with it through Arc<Mutex<_>>
+    pub fn set_observer(&mut self, observer: Box<dyn TableObserver<K, V> + Send + Sync>) {
+        self.observer = Some(observer);
+    }
+
+    /// number of mutations so far: every insert, modify, key replacement, removal and rebuild counts once,
+    /// failed operations and reads do not; values changed through handed-out references are not counted
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    /// stop reporting mutations, returning the observer
+    pub fn take_observer(&mut self) -> Option<Box<dyn TableObserver<K, V> + Send + Sync>> {
+        self.observer.take()
+    }
+
+    /// put an occupied slot at the place locate_free picked
+    pub(crate) fn store(&mut self, free: FreeSlot, slot: Slot<K, V>) {
+        if free.displaced {
+            if let Slot::Occupied(k, _) = &slot {
+                let hash = self.hash_key(k);
+                self.displaced.entry(hash).or_default().push((free.level, free.idx));
+            }
+            self.free_cursors[free.level] = free.idx + 1;
+        }
+        self.levels[free.level][free.idx] = slot;
+        self.occupancies[free.level] += 1;
+        self.num_inserts += 1;
+    }
+
+    /// the value stored under key
+    /// insert always claims the first free slot of the key's probe sequence in whichever level it picks,
+    /// so every level is walked along the same sequence: an Empty slot ends the walk for that level,
+    /// a Tombstone does not (the key may have been placed past it before the removal).
+    /// insert never probes past probe_bound, so the walk stops there as well;
+    /// keys the exhaustive fallbacks placed off their sequence are looked up in the displaced index first.
+    pub fn get<Q>(&self, key: &Q) -> Option<&V>
+    where
+        Q: ?Sized + Hash + Equivalent<K>,
+    {
+        self.value_at(self.find(key))
+    }
+
+    /// the value stored under key, see get
+    #[deprecated(note = "renamed to get")]
+    pub fn search<Q>(&self, key: &Q) -> Option<&V>
+    where
+        Q: ?Sized + Hash + Equivalent<K>,
+    {
+        self.get(key)
+    }
+
+    /// the value stored under key, for in-place mutation; found like get, so counters and the key's slot
+    /// stay as they are. like iter_mut, a change through it is not reported to the observer and does not
+    /// count as a generation; use modify for that
+    pub fn get_mut<Q>(&mut self, key: &Q) -> Option<&mut V>
+    where
+        Q: ?Sized + Hash + Equivalent<K>,
+    {
+        let (level, idx) = self.find(key)?;
+        match &mut self.levels[level][idx] {
+            Slot::Occupied(_, v) => Some(v),
+            _ => unreachable!("find returned a slot that is not occupied"),
+        }
+    }
+
+    /// whether key is present, without touching its value
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        Q: ?Sized + Hash + Equivalent<K>,
+    {
+        self.find(key).is_some()
+    }
+
+    /// the value in the slot at pos, as returned by find
+    pub(crate) fn value_at(&self, pos: Option<(usize, usize)>) -> Option<&V> {
+        pos.map(|(level, idx)| match &self.levels[level][idx] {
+            Slot::Occupied(_, v) => v,
+            _ => unreachable!("find returned a slot that is not occupied"),
+        })
+    }
+
+    /// the stored key equal to key
+    pub fn get_key<Q>(&self, key: &Q) -> Option<&K>
+    where
+        Q: ?Sized + Hash + Equivalent<K>,
+    {
+        self.find(key).map(|(level, idx)| match &self.levels[level][idx] {
+            Slot::Occupied(k, _) => k,
+            _ => unreachable!("find returned a slot that is not occupied"),
+        })
+    }
+
+    /// the stored key equal to key together with its value
+    pub fn get_key_value<Q>(&self, key: &Q) -> Option<(&K, &V)>
+    where
+        Q: ?Sized + Hash + Equivalent<K>,
+    {
+        self.find(key).map(|(level, idx)| match &self.levels[level][idx] {
+            Slot::Occupied(k, v) => (k, v),
+            _ => unreachable!("find returned a slot that is not occupied"),
+        })
+    }
+
+    /// swap the stored key equal to new_key for new_key, keeping the value, and return the old key
+    /// the lookup goes through new_key's own hash, so a key that is not equal (or does not hash the same)
+    /// is rejected with None and the table is left untouched
+    pub fn replace_key(&mut self, new_key: K) -> Option<K> {
+        let (level, idx) = self.find(&new_key)?;
+        match &mut self.levels[level][idx] {
+            Slot::Occupied(k, _) => {
+                self.generation += 1;
+                Some(std::mem::replace(k, new_key))
+            }
+            _ => unreachable!("find returned a slot that is not occupied"),
+        }
+    }
+
+    /// remove key and return its value
+    /// the slot becomes a Tombstone, which insert may reuse but search walks past
+    pub fn remove<Q>(&mut self, key: &Q) -> Option<V>
+    where
+        Q: ?Sized + Hash + Equivalent<K>,
+    {
+        let (level, idx) = self.find(key)?;
+        Some(self.take_slot(level, idx).1)
+    }
+
+    /// remove and return an arbitrary entry
+    /// scanning resumes where the previous pop stopped, so draining the table walks it only once
+    pub fn pop(&mut self) -> Option<(K, V)> {
+        if self.num_inserts == 0 {
+            return None;
+        }
+        let (mut level, mut idx) = self.pop_cursor;
+        loop {
+            if level >= self.levels.len() {
+                level = 0;
+                idx = 0;
+            } else if idx >= self.levels[level].len() {
+                level += 1;
+                idx = 0;
+            } else if let Slot::Occupied(..) = self.levels[level][idx] {
+                self.pop_cursor = (level, idx + 1);
+                return Some(self.take_slot(level, idx));
+            } else {
+                idx += 1;
+            }
+        }
+    }
+
+    /// iterate over all entries in ascending (level, slot index) order
+    ///
+    /// ```
+    /// use hashing_wasm::ElasticHashTable;
+    ///
+    /// let mut table = ElasticHashTable::with_seed(64, 0.1, 7);
+    /// let mut placed = Vec::new();
+    /// for key in 0..20 {
+    ///     let slot = table.insert(key, key * key).unwrap();
+    ///     placed.push((slot, key));
+    /// }
+    /// placed.sort();
+    /// let by_slot: Vec<i32> = placed.into_iter().map(|(_, key)| key).collect();
+    /// assert_eq!(table.keys().copied().collect::<Vec<_>>(), by_slot);
+    /// ```
+    pub fn iter(&self) -> Iter<'_, K, V> {
+        Iter {
+            levels: &self.levels,
+            occupancies: &self.occupancies,
+            level: 0,
+            current: self.level_iter(0),
+            remaining: self.num_inserts,
+        }
+    }
+
+    /// keys in iter's order
+    pub fn keys(&self) -> Keys<'_, K, V> {
+        Keys { inner: self.iter() }
+    }
+
+    /// the entries whose hash_key starts with the given bits: its top `bits` bits equal prefix
+    /// the 2^bits prefixes split the table into shards that hold every entry exactly once,
+    /// and a shard's content depends only on the seed and the keys, not on where they sit;
+    /// bits is at most 64, and bits == 0 yields every entry
+    pub fn iter_hash_range(&self, prefix: u64, bits: u32) -> impl Iterator<Item = (&K, &V)> + '_ {
+        assert!(bits <= 64, "a hash has 64 bits, not {}", bits);
+        self.iter()
+            .filter(move |(k, _)| self.hash_key(*k).checked_shr(64 - bits).unwrap_or(0) == prefix)
+    }
+
+    /// values in iter's order
+    pub fn values(&self) -> Values<'_, K, V> {
+        Values { inner: self.iter() }
+    }
+
+    /// iter with mutable values; like every handed-out reference, changes through it are not
+    /// reported to the observer and do not count as a generation
+    pub fn iter_mut(&mut self) -> IterMut<'_, K, V> {
+        IterMut {
+            slots: self.levels.iter_mut().flatten(),
+            remaining: self.num_inserts,
+        }
+    }
+
+    /// mutable values in iter's order, see iter_mut
+    pub fn values_mut(&mut self) -> ValuesMut<'_, K, V> {
+        ValuesMut { inner: self.iter_mut() }
+    }
+
+    /// lazily remove and yield every entry for which pred returns true
+    /// pred may also modify the values it keeps
+    pub fn extract_if<F>(&mut self, pred: F) -> ExtractIf<'_, K, V, F, S>
+    where
+        F: FnMut(&K, &mut V) -> bool,
+    {
+        ExtractIf {
+            table: self,
+            pred,
+            level: 0,
+            idx: 0,
+        }
+    }
+
+    /// move every entry for which pred returns true into a new table, sized for them, with the same delta,
+    /// seed, geometry, growth and tuning settings; with metadata on, the new table keeps it too, with each
+    /// entry's tag. the observer and the pressure callback stay with self, as they do on clone: the new table
+    /// starts without them
+    pub fn split_off<F>(&mut self, mut pred: F) -> Self
+    where
+        F: FnMut(&K, &V) -> bool,
+    {
+        let mut entries = Vec::new();
+        for level in 0..self.levels.len() {
+            for idx in 0..self.levels[level].len() {
+                if matches!(&self.levels[level][idx], Slot::Occupied(k, v) if pred(k, v)) {
+                    let tag = self.meta.as_ref().map_or(0, |meta| meta.tags[level][idx]);
+                    let (k, v) = self.take_slot(level, idx);
+                    entries.push((k, v, tag));
+                }
+            }
+        }
+        let capacity = capacity_for(entries.len(), self.delta);
+        let mut split = Self::build(capacity, self.delta, self.seed, self.geometry.clone());
+        split.meta = self
+            .meta
+            .as_ref()
+            .map(|meta| meta::Metadata::new(split.levels.iter().map(Vec::len), meta.clock.clone()));
+        split.grow_on_full = self.grow_on_full;
+        split.tuning = self.tuning.clone();
+        // insert only fails past max_inserts, which capacity_for leaves room for
+        for (k, v, tag) in entries {
+            let placed = split.insert_tagged(k, v, tag).is_ok();
+            assert!(placed, "split_off: a table sized for its entries was full");
+        }
+        split
+    }
+
+    /// check the internal bookkeeping: per-level occupancies, the insert count, the displaced index,
+    /// and that every stored key is found where it is stored
+    pub fn check_invariants(&self) -> Result<(), String> {
+        for (level, slots) in self.levels.iter().enumerate() {
+            let occupied = slots.iter().filter(|slot| matches!(slot, Slot::Occupied(..))).count();
+            if occupied != self.occupancies[level] {
+                return Err(format!(
+                    "level {} holds {} entries but its occupancy is {}",
+                    level, occupied, self.occupancies[level]
+                ));
+            }
+        }
+        let total: usize = self.occupancies.iter().sum();
+        if total != self.num_inserts {
+            return Err(format!("occupancies sum to {} but num_inserts is {}", total, self.num_inserts));
+        }
+        if self.num_inserts > self.max_inserts {
+            return Err(format!("{} entries exceed max_inserts {}", self.num_inserts, self.max_inserts));
+        }
+        for (&hash, places) in &self.displaced {
+            for &(level, idx) in places {
+                match self.levels.get(level).and_then(|slots| slots.get(idx)) {
+                    Some(Slot::Occupied(k, _)) if self.hash_key(k) == hash => {}
+                    _ => return Err(format!("displaced index entry at level {} slot {} is stale", level, idx)),
+                }
+            }
+        }
+        if self.free_cursors.len() != self.levels.len() {
+            return Err("free cursors do not match the levels".into());
+        }
+        for level in 0..self.levels.len() {
+            for (idx, k, _) in self.level_iter(level).expect("level in range") {
+                if self.find(k) != Some((level, idx)) {
+                    return Err(format!("entry at level {} slot {} is not found by search", level, idx));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// turn an occupied slot into a Tombstone and return its entry
+    pub(crate) fn take_slot(&mut self, level: usize, idx: usize) -> (K, V) {
+        match std::mem::replace(&mut self.levels[level][idx], Slot::Tombstone) {
+            Slot::Occupied(k, v) => {
+                self.occupancies[level] -= 1;
+                self.num_inserts -= 1;
+                if let Some(meta) = &mut self.meta {
+                    meta.tags[level][idx] = 0;
+                }
+                if !self.displaced.is_empty() {
+                    let hash = self.hash_key(&k);
+                    if let Some(places) = self.displaced.get_mut(&hash) {
+                        places.retain(|&place| place != (level, idx));
+                        if places.is_empty() {
+                            self.displaced.remove(&hash);
+                        }
+                    }
+                }
+                self.generation += 1;
+                if let Some(observer) = &mut self.observer {
+                    observer.on_remove(&k, &v);
+                }
+                self.update_pressure();
+                (k, v)
+            }
+            _ => unreachable!("take_slot called on a slot that is not occupied"),
+        }
+    }
+
+    /// apply f to the value stored under key and return its result, or None if key is absent
+    pub fn modify<Q, F, R>(&mut self, key: &Q, f: F) -> Option<R>
+    where
+        Q: ?Sized + Hash + Equivalent<K>,
+        F: FnOnce(&mut V) -> R,
+    {
+        let (level, idx) = self.find(key)?;
+        self.modify_slot(level, idx, f)
+    }
+
+    /// apply f to the value stored under key, inserting default first if key is absent
+    /// the default is stored before f runs, so a panicking f leaves the table consistent
+    pub fn modify_or_insert<F, R>(&mut self, key: K, default: V, f: F) -> Result<R, ElasticHashError>
+    where
+        F: FnOnce(&mut V) -> R,
+    {
+        let (level, idx) = match self.find(&key) {
+            Some(pos) => pos,
+            None => {
+                let free = self.locate_free_or_grow(&key)?;
+                self.place(free, key, default)
+            }
+        };
+        Ok(self
+            .modify_slot(level, idx, f)
+            .expect("slot located by find or insert is occupied"))
+    }
+
+    /// apply f to the value in an occupied slot; the observer is told with on_modify, which gets only the
+    /// value after, so the value from before need not be cloned
+    pub(crate) fn modify_slot<F, R>(&mut self, level: usize, idx: usize, f: F) -> Option<R>
+    where
+        F: FnOnce(&mut V) -> R,
+    {
+        if !self.touch_slot(level, idx) {
+            return None;
+        }
+        let Slot::Occupied(k, v) = &mut self.levels[level][idx] else {
+            unreachable!("touch_slot checked the slot is occupied");
+        };
+        let result = f(v);
+        if let Some(observer) = &mut self.observer {
+            observer.on_modify(k, v);
+        }
+        Some(result)
+    }
+
+    /// put value into an occupied slot and return the value it replaces, which is moved out, so the
+    /// observer's on_replace sees both
+    pub(crate) fn replace_slot(&mut self, level: usize, idx: usize, value: V) -> Option<V> {
+        if !self.touch_slot(level, idx) {
+            return None;
+        }
+        let Slot::Occupied(k, v) = &mut self.levels[level][idx] else {
+            unreachable!("touch_slot checked the slot is occupied");
+        };
+        let old = std::mem::replace(v, value);
+        if let Some(observer) = &mut self.observer {
+            observer.on_replace(k, &old, v);
+        }
+        Some(old)
+    }
+
+    /// count a change of the value in a slot, if it is occupied
+    fn touch_slot(&mut self, level: usize, idx: usize) -> bool {
+        if !matches!(self.levels[level][idx], Slot::Occupied(..)) {
+            return false;
+        }
+        self.generation += 1;
+        // a replacement is restamped from the clock; tags without a clock stay as set
+        if let Some(meta::Metadata { tags, clock: Some(clock) }) = &mut self.meta {
+            tags[level][idx] = clock.now();
+        }
+        true
+    }
+
+    /// look at the content of a slot, e.g. the one returned by insert
+    pub fn get_slot(&self, id: SlotId) -> Result<SlotView<'_, K, V>, SlotError> {
+        let level = self.levels.get(id.level).ok_or(SlotError::LevelOutOfRange {
+            level: id.level,
+            levels: self.levels.len(),
+        })?;
+        let slot = level.get(id.index).ok_or(SlotError::IndexOutOfRange {
+            id,
+            level_size: level.len(),
+        })?;
+        Ok(match slot {
+            Slot::Empty => SlotView::Empty,
+            Slot::Tombstone => SlotView::Tombstone,
+            Slot::Occupied(k, v) => SlotView::Occupied(k, v),
+        })
+    }
+
+    /// iterate over the occupied slots of one level as (slot index, key, value)
+    /// returns None when level is out of range
+    pub fn level_iter(&self, level: usize) -> Option<LevelIter<'_, K, V>> {
+        LevelIter::new(&self.levels, &self.occupancies, level)
+    }
+
+    /// overwrite the value stored in an occupied slot, bypassing all bookkeeping
+    /// only used by tests to simulate a corrupted table
+    #[cfg(test)]
+    pub(crate) fn corrupt_slot(&mut self, level: usize, idx: usize, value: V) {
+        if let Slot::Occupied(_, v) = &mut self.levels[level][idx] {
+            *v = value;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hash_with_seed;
+    use log::LevelFilter;
+
+    fn init() {
+        let _ = env_logger::builder()
+            .filter_level(LevelFilter::Debug)
+            .is_test(true)
+            .try_init();
+    }
+
+    #[test]
+    fn test_elastic_hash_table() {
+        init();
+        let n = 10000;
+        let delta = 0.01;
+        let mut table = ElasticHashTable::deterministic(n, delta);
+
+        for i in 0..(n as f64 * (1.0 - delta)) as usize {
+            table.insert(i, i << 1).expect("Insertion failed");
+        }
+        table.print_status();
+
+        // test search
+        for i in 0..(n as f64 * (1.0 - delta)) as usize {
+            let res = table.get(&i);
+            assert!(res.is_some(), "Key {} not found", i);
+            assert_eq!(res.unwrap(), &(i << 1));
+        }
+    }
+
+    #[test]
+    fn test_small_elastic_hash_table() {
+        init();
+        let n = 10;
+        let delta = 0.1;
+        let mut table = ElasticHashTable::deterministic(n, delta);
+
+        for i in 0..9 {
+            let res = table.insert(i, i).expect("Insertion failed");
+            println!("{:?}", res);
+        }
+        table.print_status();
+
+        for i in 0..9 {
+            let res = table.get(&i);
+            assert!(res.is_some(), "Key {} not found", i);
+            assert_eq!(res.unwrap(), &i);
+        }
+    }
+
+    #[test]
+    fn test_level_iter() {
+        let mut table = ElasticHashTable::deterministic(500, 0.1);
+        for i in 0..400 {
+            table.insert(i, i * 2).expect("Insertion failed");
+        }
+        for i in (0..400).step_by(5) {
+            table.remove(&i);
+        }
+
+        let mut total = 0;
+        for level in 0..table.levels.len() {
+            let iter = table.level_iter(level).expect("level in range");
+            assert_eq!(iter.len(), table.occupancies[level]);
+            for (idx, k, v) in iter {
+                assert_eq!(*v, k * 2);
+                assert_eq!(table.find(k), Some((level, idx)));
+                total += 1;
+            }
+        }
+        assert_eq!(total, table.num_inserts);
+        assert!(table.level_iter(table.levels.len()).is_none());
+    }
+
+    #[test]
+    fn test_get_slot() {
+        let mut table = ElasticHashTable::deterministic(64, 0.1);
+        let id = SlotId::from(table.insert("a", 1).expect("Insertion failed"));
+        assert_eq!(table.get_slot(id), Ok(SlotView::Occupied(&"a", &1)));
+
+        let home = table.home_slot("a", id.level).expect("level in range");
+        let untouched = SlotId { level: id.level, index: (home + 1) % table.levels[id.level].len() };
+        assert_eq!(table.get_slot(untouched), Ok(SlotView::Empty));
+
+        assert_eq!(table.remove("a"), Some(1));
+        assert_eq!(table.get_slot(id), Ok(SlotView::Tombstone));
+
+        let levels = table.levels.len();
+        assert_eq!(
+            table.get_slot(SlotId { level: levels, index: 0 }),
+            Err(SlotError::LevelOutOfRange { level: levels, levels })
+        );
+        let bad = SlotId { level: 0, index: table.levels[0].len() };
+        assert_eq!(
+            table.get_slot(bad),
+            Err(SlotError::IndexOutOfRange { id: bad, level_size: table.levels[0].len() })
+        );
+        assert!(table.home_slot("a", levels).is_err());
+    }
+
+    #[test]
+    fn test_modify() {
+        let mut table = ElasticHashTable::deterministic(64, 0.1);
+        table.insert("a", 1).expect("Insertion failed");
+
+        assert_eq!(table.modify("a", |v| { *v += 10; *v * 2 }), Some(22));
+        assert_eq!(table.get("a"), Some(&11));
+        assert_eq!(table.modify("b", |v| *v += 1), None);
+        assert_eq!(table.get("b"), None);
+
+        assert_eq!(table.modify_or_insert("a", 0, |v| { *v += 1; *v }), Ok(12));
+        assert_eq!(table.modify_or_insert("b", 5, |v| { *v += 1; *v }), Ok(6));
+        assert_eq!(table.get("b"), Some(&6));
+        assert_eq!(table.num_inserts, 2);
+    }
+
+    #[test]
+    fn test_modify_panic_keeps_counters() {
+        let mut table = ElasticHashTable::deterministic(64, 0.1);
+        table.insert(1, 1).expect("Insertion failed");
+
+        let res = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            table.modify(&1, |_| panic!("boom"));
+        }));
+        assert!(res.is_err());
+        let res = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            let _ = table.modify_or_insert(2, 2, |_| panic!("boom"));
+        }));
+        assert!(res.is_err());
+
+        assert_eq!(table.num_inserts, 2);
+        assert_eq!(table.occupancies.iter().sum::<usize>(), 2);
+        assert_eq!(table.get(&1), Some(&1));
+        assert_eq!(table.get(&2), Some(&2));
+    }
+
+    #[test]
+    fn test_remove_keeps_probe_chains() {
+        let mut table = ElasticHashTable::with_seed(64, 0.1, 3);
+        for i in 0..56u32 {
+            table.insert(i, i * 10).expect("Insertion failed");
+        }
+        // a key that collided on insert: its home slot in the level it landed in holds another key
+        let (collided, holder) = (0..56u32)
+            .find_map(|k| {
+                let (level, idx) = table.find(&k)?;
+                let home = table.home_slot(&k, level).ok()?;
+                match &table.levels[level][home] {
+                    Slot::Occupied(other, _) if home != idx => Some((k, *other)),
+                    _ => None,
+                }
+            })
+            .expect("a full table has collisions");
+
+        // the emptied slot does not cut the chain the collided key was placed along
+        assert_eq!(table.remove(&holder), Some(holder * 10));
+        assert_eq!(table.get(&holder), None);
+        assert_eq!(table.remove(&holder), None);
+        assert_eq!(table.get(&collided), Some(&(collided * 10)));
+        assert_eq!(table.num_inserts, 55);
+        table.check_invariants().unwrap();
+
+        table.insert(holder, 1).expect("Insertion failed");
+        assert_eq!(table.get(&holder), Some(&1));
+        assert_eq!(table.get(&collided), Some(&(collided * 10)));
+        for i in (0..56u32).filter(|&i| i != holder) {
+            assert_eq!(table.get(&i), Some(&(i * 10)));
+        }
+        table.check_invariants().unwrap();
+    }
+
+    #[test]
+    fn test_single_level() {
+        let mut table = ElasticHashTable::<u32, u32>::single_level_with_hasher(200, 0.1, 4);
+        assert!(table.is_single_level());
+        assert_eq!(table.levels.len(), 1);
+        assert_eq!(table.stats().levels, 1);
+        assert_eq!(table.stats().capacity, 200);
+        for i in 0..180u32 {
+            table.insert(i, i).expect("Insertion failed");
+        }
+        for i in (0..180u32).step_by(3) {
+            assert_eq!(table.remove(&i), Some(i));
+        }
+        for i in 0..180u32 {
+            assert_eq!(table.get(&i), (i % 3 != 0).then_some(&i));
+        }
+        assert!(table.probe_count() > 0);
+        table.check_invariants().unwrap();
+
+        // growth, rehashing and split_off keep the single level
+        table.try_reserve(300).expect("reserve failed");
+        assert_eq!((table.levels.len(), table.capacity()), (1, 800));
+        table.rehash_with_seed(5).expect("rehash failed");
+        let split = table.split_off(|k, _| k % 2 == 0);
+        for t in [&table, &split, &table.clone()] {
+            assert!(t.is_single_level());
+            assert_eq!(t.levels.len(), 1);
+            t.check_invariants().unwrap();
+        }
+        assert_eq!(table.num_inserts + split.num_inserts, 120);
+        assert!(!ElasticHashTable::<u32, u32>::with_seed(200, 0.1, 4).is_single_level());
+    }
+
+    #[test]
+    fn test_custom_geometry() {
+        let inverted = vec![64, 128, 256, 576];
+        let mut table =
+            ElasticHashTable::<u32, u32>::with_geometry(1024, 0.1, 3, LevelGeometry::Custom(inverted.clone())).unwrap();
+        assert_eq!(table.level_sizes(), inverted);
+        assert_eq!((table.stats().levels, table.stats().capacity), (4, 1024));
+        for i in 0..900u32 {
+            table.insert(i, i).expect("Insertion failed");
+        }
+        for i in (0..900u32).step_by(4) {
+            assert_eq!(table.remove(&i), Some(i));
+        }
+        for i in 0..900u32 {
+            assert_eq!(table.get(&i), (i % 4 != 0).then_some(&i));
+        }
+        table.check_invariants().unwrap();
+
+        // growth scales every level by the same factor; rehashing, clones and split_off keep the geometry
+        table.try_reserve(1000).expect("reserve failed");
+        let grown = table.level_sizes();
+        assert_eq!(grown.iter().sum::<usize>(), table.capacity());
+        let factor = table.capacity() as f64 / 1024.0;
+        for (size, was) in grown.iter().zip(&inverted) {
+            assert!((*size as f64 - *was as f64 * factor).abs() <= 1.0, "{:?}", grown);
+        }
+        table.rehash_with_seed(5).expect("rehash failed");
+        assert_eq!(table.level_sizes(), grown);
+        let split = table.split_off(|k, _| k % 2 == 0);
+        // sized once for its entries, even with level 0 the smallest
+        assert_eq!(split.capacity(), capacity_for(split.len(), 0.1));
+        for t in [&table, &split, &table.clone()] {
+            assert_eq!(t.geometry(), &LevelGeometry::Custom(inverted.clone()));
+            t.check_invariants().unwrap();
+        }
+        assert_eq!(table.num_inserts + split.num_inserts, 675);
+
+        let uniform = ElasticHashTable::<u32, u32>::with_geometry(1024, 0.1, 3, LevelGeometry::Custom(vec![256; 4]));
+        assert_eq!(uniform.unwrap().level_sizes(), [256; 4]);
+        assert_eq!(ElasticHashTable::<u32, u32>::with_seed(1024, 0.1, 3).geometry(), &LevelGeometry::Halving);
+    }
+
+    #[test]
+    fn test_custom_geometry_is_validated() {
+        let build = |sizes: Vec<usize>| {
+            ElasticHashTable::<u32, u32>::with_geometry(100, 0.1, 1, LevelGeometry::Custom(sizes)).map(|_| ())
+        };
+        assert!(build(vec![]).is_err());
+        assert!(build(vec![50, 49]).is_err());
+        assert!(build(vec![50, 51]).is_err());
+        assert!(build(vec![100, 0]).is_err());
+        assert!(build(vec![usize::MAX, 101]).is_err());
+        assert!(build(vec![1, 99]).is_ok());
+        assert!(build(vec![100]).is_ok());
+    }
+
+    #[test]
+    fn test_level_count_is_capped() {
+        for capacity in [1, 2, 3, 1 << 20, (1 << 20) + 1, usize::MAX / 2, usize::MAX] {
+            let sizes = level_sizes(capacity);
+            assert!(sizes.len() <= MAX_LEVELS, "{} levels for {}", sizes.len(), capacity);
+            assert_eq!(sizes.iter().sum::<usize>(), capacity);
+            assert!(sizes.iter().all(|&size| size >= MIN_LEVEL_SIZE));
+        }
+        assert_eq!(level_sizes(usize::MAX).len(), MAX_LEVELS);
+
+        let at_cap =
+            ElasticHashTable::<u32, u32>::with_geometry(64, 0.1, 1, LevelGeometry::Custom(vec![1; 64])).unwrap();
+        assert_eq!(at_cap.stats().levels, MAX_LEVELS);
+        let err = ElasticHashTable::<u32, u32>::with_geometry(65, 0.1, 1, LevelGeometry::Custom(vec![1; 65]))
+            .map(|_| ())
+            .unwrap_err();
+        assert_eq!(err, ElasticHashError::TooManyLevels { levels: 65, max: MAX_LEVELS });
+        assert!(err.to_string().contains("MAX_LEVELS"), "{}", err);
+        assert_eq!(LevelGeometry::Custom(vec![1; 64]).sizes(1 << 30).len(), MAX_LEVELS);
+
+        let layout = |levels: usize| vec![vec![None::<(u32, u32)>]; levels];
+        assert!(ElasticHashTable::from_layout(layout(MAX_LEVELS), 0.1).is_ok());
+        let err = ElasticHashTable::from_layout(layout(MAX_LEVELS + 1), 0.1).err();
+        assert_eq!(err, Some(ElasticHashError::TooManyLevels { levels: MAX_LEVELS + 1, max: MAX_LEVELS }));
+
+        // growth of a table at the cap keeps it there
+        let mut table = at_cap;
+        for i in 0..50 {
+            table.insert(i, i).expect("Insertion failed");
+        }
+        table.try_reserve(200).expect("reserve failed");
+        assert_eq!(table.stats().levels, MAX_LEVELS);
+        assert_eq!(table.get(&49), Some(&49));
+        table.check_invariants().unwrap();
+    }
+
+    #[test]
+    fn test_custom_geometry_scales() {
+        let geometry = LevelGeometry::Custom(vec![10, 30, 60]);
+        assert_eq!(geometry.sizes(100), [10, 30, 60]);
+        assert_eq!(geometry.sizes(200), [20, 60, 120]);
+        assert_eq!(geometry.sizes(7), [1, 2, 4]);
+        assert_eq!(geometry.sizes(3), [1, 1, 1]);
+        assert_eq!(geometry.sizes(2), level_sizes(2));
+        assert_eq!(geometry.sizes(1001).iter().sum::<usize>(), 1001);
+    }
+
+    #[test]
+    fn test_insert_replaces_present_key() {
+        let mut table = ElasticHashTable::with_seed(10, 0.1, 1);
+        let pos = table.insert("a", 1).expect("Insertion failed");
+        let occupancies = table.occupancies.clone();
+        assert_eq!(table.insert("a", 2), Ok(pos));
+        assert_eq!(table.get("a"), Some(&2));
+        assert_eq!(table.insert_or_update("a", 3), Ok(Some(2)));
+        assert_eq!(table.insert_or_update("b", 1), Ok(None));
+        assert_eq!(table.get("a"), Some(&3));
+        assert_eq!(table.num_inserts, 2);
+        assert_eq!(table.occupancies.iter().sum::<usize>(), occupancies.iter().sum::<usize>() + 1);
+
+        // a full table still takes replacements, but no new keys
+        for k in ["c", "d", "e", "f", "g", "h", "i"] {
+            table.insert(k, 0).expect("Insertion failed");
+        }
+        assert!(table.insert("j", 0).is_err());
+        assert_eq!(table.insert_or_update("c", 9), Ok(Some(0)));
+        let d = table.find("d");
+        assert_eq!(table.insert_with("d", || 8).ok(), d);
+        assert_eq!(table.get("d"), Some(&8));
+        assert_eq!(table.insert_batch([("e", 7), ("e", 6)]), Ok(2));
+        assert_eq!(table.get("e"), Some(&6));
+        assert_eq!(table.num_inserts, 9);
+        table.check_invariants().unwrap();
+    }
+
+    #[test]
+    fn test_insert_batch() {
+        let mut table = ElasticHashTable::deterministic(10, 0.1);
+        assert_eq!(table.insert_batch((0..5).map(|i| (i, i * 2))), Ok(5));
+        assert_eq!(table.get(&4), Some(&8));
+
+        // the tenth entry does not fit: the four before it stay, the ones after it are dropped
+        let err = table.insert_batch((5..12).map(|i| (i, i * 2))).unwrap_err();
+        assert_eq!((err.key, err.value), (9, 18));
+        assert_eq!(table.num_inserts, 9);
+        assert_eq!(table.get(&8), Some(&16));
+        assert_eq!(table.get(&10), None);
+    }
+
+    #[test]
+    fn test_extend() {
+        let mut table = ElasticHashTable::deterministic(64, 0.1);
+        table.extend((0..20).map(|i| (i, i * 2)));
+        table.extend([(3, 0), (40, 1)]);
+        assert_eq!((table.len(), table.get(&3), table.get(&40)), (21, Some(&0), Some(&1)));
+
+        let full = std::panic::catch_unwind(|| {
+            let mut table = ElasticHashTable::deterministic(10, 0.1);
+            table.extend((0..12).map(|i| (i, i)));
+        });
+        assert!(full.is_err());
+    }
+
+    #[test]
+    fn test_insert_with_is_lazy() {
+        let mut table = ElasticHashTable::deterministic(10, 0.1);
+        let mut calls = 0;
+        for i in 0..9 {
+            table
+                .insert_with(i, || {
+                    calls += 1;
+                    i * 2
+                })
+                .expect("Insertion failed");
+        }
+        assert_eq!(calls, 9);
+        assert_eq!(table.get(&4), Some(&8));
+
+        // the table is full, so the value is never built
+        assert!(table
+            .insert_with(9, || {
+                calls += 1;
+                18
+            })
+            .is_err());
+        assert_eq!(calls, 9);
+        assert_eq!(table.num_inserts, 9);
+    }
+
+    #[test]
+    fn test_get_mut_and_contains_key() {
+        let mut table: ElasticHashTable<String, Vec<u32>> = ElasticHashTable::with_seed(64, 0.1, 1);
+        for i in 0..20 {
+            table.insert(format!("k{}", i), vec![i]).expect("Insertion failed");
+        }
+        let (len, generation, probes) = (table.len(), table.generation(), table.probe_count());
+        let slot = table.find("k7");
+        table.get_mut("k7").unwrap().push(70);
+        assert_eq!(table.get("k7"), Some(&vec![7, 70]));
+        assert_eq!((table.len(), table.num_inserts, table.generation()), (len, len, generation));
+        assert_eq!(table.occupancies.iter().sum::<usize>(), len);
+        assert_eq!(table.find("k7"), slot);
+        assert!(table.probe_count() > probes);
+
+        assert_eq!(table.get_mut("k20"), None);
+        assert!(table.contains_key("k0") && !table.contains_key("k20"));
+        table.remove("k0");
+        assert!(!table.contains_key("k0") && table.get_mut("k0").is_none());
+        assert_eq!(table.len(), len - 1);
+        table.check_invariants().unwrap();
+    }
+
+    #[test]
+    #[allow(deprecated)]
+    fn test_search_is_get() {
+        let mut table = ElasticHashTable::with_seed(16, 0.1, 1);
+        table.insert(1, "one").expect("Insertion failed");
+        assert_eq!((table.search(&1), table.search(&2)), (table.get(&1), None));
+    }
+
+    #[test]
+    fn test_failed_insert_returns_key_and_value() {
+        // cloning panics, proving the failed path never clones
+        struct NoClone(Vec<u8>);
+        impl Clone for NoClone {
+            fn clone(&self) -> Self {
+                panic!("value was cloned")
+            }
+        }
+
+        let mut small = ElasticHashTable::deterministic(10, 0.1);
+        let mut big = ElasticHashTable::deterministic(100, 0.1);
+        for i in 0..50u32 {
+            let value = NoClone(vec![i as u8; 64]);
+            if let Err(err) = small.insert(format!("key{}", i), value) {
+                assert_eq!(err.key(), &format!("key{}", i));
+                assert!(err.reason().contains("full"));
+                let (key, value) = err.into_parts();
+                big.insert(key, value).expect("Insertion failed");
+            }
+        }
+        assert_eq!(small.num_inserts + big.num_inserts, 50);
+        for i in 0..50u32 {
+            let key = format!("key{}", i);
+            let found = small.get(key.as_str()).or_else(|| big.get(key.as_str()));
+            assert_eq!(found.map(|v| v.0.clone()), Some(vec![i as u8; 64]));
+        }
+
+        // insert_with hands back the unused closure
+        let err = small.insert_with("late".to_string(), || NoClone(vec![1])).unwrap_err();
+        let (key, make) = err.into_parts();
+        big.insert(key, make()).expect("Insertion failed");
+        assert_eq!(big.get("late").map(|v| v.0.len()), Some(1));
+    }
+
+    #[test]
+    fn test_table_is_send() {
+        fn assert_send<T: Send>(_: &T) {}
+        // observer, pressure callback and clock are all required to be Send and Sync
+        let mut table: ElasticHashTable<String, String> =
+            ElasticHashTable::deterministic(16, 0.1).with_metadata(Some(std::sync::Arc::new(crate::SystemClock)));
+        table.set_pressure_callback(&[0.5], Box::new(|_, _| {})).unwrap();
+        assert_send(&table);
+        let handle = std::thread::spawn(move || {
+            table.insert("k".to_string(), "v".to_string()).expect("Insertion failed");
+            table
+        });
+        assert_eq!(handle.join().unwrap().get("k").map(String::as_str), Some("v"));
+    }
+
+    #[test]
+    fn test_keys_and_values_need_not_be_clone() {
+        #[derive(Hash, PartialEq, Eq, Debug)]
+        struct Id(u32);
+        struct Callback(Box<dyn Fn(u32) -> u32>);
+
+        let mut table = ElasticHashTable::with_seed(64, 0.1, 1);
+        for i in 0..40 {
+            table.insert(Id(i), Callback(Box::new(move |x| x + i))).expect("Insertion failed");
+        }
+        // replacing moves the old value out rather than cloning it
+        table.insert(Id(3), Callback(Box::new(|x| x * 100))).expect("Insertion failed");
+        let old = table.insert_or_update(Id(4), Callback(Box::new(|x| x))).expect("Insertion failed");
+        assert_eq!(old.map(|f| (f.0)(1)), Some(5));
+        assert_eq!(table.get(&Id(3)).map(|f| (f.0)(2)), Some(200));
+        table.get_mut(&Id(5)).unwrap().0 = Box::new(|_| 0);
+        assert_eq!(table.remove(&Id(6)).map(|f| (f.0)(1)), Some(7));
+        assert!(table.contains_key(&Id(7)) && !table.contains_key(&Id(6)));
+        let f = table.entry(Id(100)).or_insert(Callback(Box::new(|x| x / 2))).expect("Insertion failed");
+        assert_eq!((f.0)(1), 0);
+        assert_eq!(table.iter().map(|(_, f)| (f.0)(0)).filter(|&x| x == 0).count(), 5);
+
+        // modifying in place and splitting off move values rather than clone them
+        table.modify(&Id(7), |f| f.0 = Box::new(|x| x + 70));
+        table
+            .modify_or_insert(Id(8), Callback(Box::new(|x| x)), |f| f.0 = Box::new(|x| x + 80))
+            .expect("Insertion failed");
+        table.entry(Id(9)).and_modify(|f| f.0 = Box::new(|x| x + 90));
+        let split = table.split_off(|id, _| id.0 >= 30);
+        assert_eq!((split.len(), table.len()), (11, 29));
+        assert_eq!(split.get(&Id(100)).map(|f| (f.0)(1)), Some(0));
+
+        let mut drained: Vec<_> = table.drain().map(|(id, f)| (id.0, (f.0)(1))).collect();
+        drained.sort_unstable();
+        assert_eq!(drained.len(), 29);
+        assert_eq!(drained[..6], [(0, 1), (1, 2), (2, 3), (3, 100), (4, 1), (5, 0)]);
+        assert_eq!(drained[6..9], [(7, 71), (8, 81), (9, 91)]);
+        assert!(table.is_empty());
+    }
+
+    /// run with `cargo test --release -- --ignored --nocapture bench_insert_vec_values`
+    #[test]
+    #[ignore]
+    fn bench_insert_vec_values() {
+        let n = 100_000u64;
+        let values: Vec<Vec<u8>> = (0..n).map(|i| vec![i as u8; 256]).collect();
+        let start = std::time::Instant::now();
+        let mut table = ElasticHashTable::deterministic(2 * n as usize, 0.1);
+        for (i, value) in values.into_iter().enumerate() {
+            table.insert(i as u64, value).expect("Insertion failed");
+        }
+        let fresh = start.elapsed();
+
+        let start = std::time::Instant::now();
+        for i in 0..n {
+            table.insert(i, vec![0; 256]).expect("Insertion failed");
+        }
+        let replaced = start.elapsed();
+        println!("{} Vec<u8> values of 256 bytes: insert {:?}, replace {:?}", n, fresh, replaced);
+    }
+
+    #[test]
+    fn test_pop_drains_table() {
+        let mut table = ElasticHashTable::deterministic(256, 0.1);
+        for i in 0..200 {
+            table.insert(i, i % 7).expect("Insertion failed");
+        }
+
+        let mut popped = Vec::new();
+        while let Some(entry) = table.pop() {
+            popped.push(entry);
+        }
+        popped.sort();
+        assert_eq!(popped, (0..200).map(|i| (i, i % 7)).collect::<Vec<_>>());
+        assert_eq!(table.num_inserts, 0);
+        assert!(table.occupancies.iter().all(|&occ| occ == 0));
+
+        // the drained table is reusable, and pop wraps around to find new entries
+        for i in 0..200 {
+            table.insert(i, i).expect("Insertion failed");
+        }
+        assert_eq!(table.get(&150), Some(&150));
+        assert!(table.pop().is_some());
+        assert_eq!(table.num_inserts, 199);
+    }
+
+    #[test]
+    fn test_iteration_order_is_locked() {
+        let mut table = ElasticHashTable::with_seed(32, 0.1, 42);
+        for key in 0..16u32 {
+            table.insert(key, key).expect("Insertion failed");
+        }
+        table.remove(&3);
+        table.insert(100, 100).expect("Insertion failed");
+        let keys: Vec<u32> = table.keys().copied().collect();
+        assert_eq!(keys, GOLDEN_ORDER);
+
+        let mut by_slot: Vec<_> = keys.iter().map(|k| (table.find(k).unwrap(), *k)).collect();
+        by_slot.sort();
+        assert_eq!(by_slot.into_iter().map(|(_, k)| k).collect::<Vec<_>>(), keys);
+        assert_eq!(table.values().copied().collect::<Vec<_>>(), keys);
+        assert_eq!(table.iter_snapshot().map(|(k, _)| k).collect::<Vec<_>>(), keys);
+        assert_eq!(table.clone().keys().copied().collect::<Vec<_>>(), keys);
+    }
+
+    /// keys of the table in test_iteration_order_is_locked, in canonical order
+    const GOLDEN_ORDER: [u32; 16] = [7, 2, 15, 100, 5, 6, 9, 1, 4, 10, 13, 8, 0, 11, 12, 14];
+
+    #[test]
+    fn test_hash_range_shards_cover_every_entry_once() {
+        let mut table = ElasticHashTable::with_seed(1024, 0.1, 9);
+        for i in 0..700u32 {
+            table.insert(i, i).expect("Insertion failed");
+        }
+        for bits in [0, 1, 3, 7] {
+            let mut seen: Vec<u32> = (0..1u64 << bits)
+                .flat_map(|prefix| table.iter_hash_range(prefix, bits).map(|(k, _)| *k))
+                .collect();
+            seen.sort();
+            assert_eq!(seen, (0..700).collect::<Vec<_>>(), "bits = {}", bits);
+        }
+        let full: Vec<_> = table.iter_hash_range(table.hash_key(&5), 64).collect();
+        assert_eq!(full, [(&5, &5)]);
+
+        let mut sparse = ElasticHashTable::with_seed(64, 0.1, 9);
+        sparse.insert("a", 1).expect("Insertion failed");
+        sparse.insert("b", 2).expect("Insertion failed");
+        let sizes: Vec<usize> = (0..16).map(|prefix| sparse.iter_hash_range(prefix, 4).count()).collect();
+        assert_eq!(sizes.iter().sum::<usize>(), 2);
+        assert!(sizes.iter().filter(|&&n| n == 0).count() >= 14);
+        assert_eq!(sparse.iter_hash_range(16, 4).count(), 0);
+    }
+
+    #[test]
+    fn test_extract_if() {
+        let mut table = ElasticHashTable::deterministic(256, 0.1);
+        for i in 0..200 {
+            table.insert(i, i).expect("Insertion failed");
+        }
+
+        let mut extracted: Vec<_> = table.extract_if(|k, v| {
+            *v += 1;
+            k % 3 == 0
+        }).collect();
+        extracted.sort();
+        assert_eq!(extracted, (0..200).step_by(3).map(|i| (i, i + 1)).collect::<Vec<_>>());
+        assert_eq!(table.num_inserts, 200 - extracted.len());
+        assert_eq!(table.occupancies.iter().sum::<usize>(), table.num_inserts);
+        for i in 0..200 {
+            let expected = if i % 3 == 0 { None } else { Some(&(i + 1)) };
+            assert_eq!(table.get(&i), expected);
+        }
+    }
+
+    #[test]
+    fn test_extract_if_dropped_early() {
+        let mut table = ElasticHashTable::deterministic(256, 0.1);
+        for i in 0..200 {
+            table.insert(i, i).expect("Insertion failed");
+        }
+
+        let taken: Vec<_> = table.extract_if(|_, _| true).take(10).collect();
+        assert_eq!(taken.len(), 10);
+        assert_eq!(table.num_inserts, 190);
+        assert_eq!(table.occupancies.iter().sum::<usize>(), 190);
+        for (k, _) in &taken {
+            assert_eq!(table.get(k), None);
+        }
+
+        let remaining = (0..200).filter(|i| table.get(i).is_some()).count();
+        assert_eq!(remaining, 190);
+    }
+
+    #[test]
+    fn test_get_and_replace_key() {
+        use std::rc::Rc;
+
+        let mut table = ElasticHashTable::deterministic(64, 0.1);
+        let original: Rc<str> = Rc::from("key");
+        table.insert(original.clone(), 1).expect("Insertion failed");
+
+        let stored = table.get_key("key").expect("key present");
+        assert!(Rc::ptr_eq(stored, &original));
+        assert_eq!(table.get_key("other"), None);
+
+        let upgraded: Rc<str> = Rc::from("key");
+        let old = table.replace_key(upgraded.clone()).expect("equal key accepted");
+        assert!(Rc::ptr_eq(&old, &original));
+        assert!(Rc::ptr_eq(table.get_key("key").unwrap(), &upgraded));
+        assert_eq!(table.get("key"), Some(&1));
+
+        assert_eq!(table.replace_key(Rc::from("other")), None);
+        assert!(Rc::ptr_eq(table.get_key("key").unwrap(), &upgraded));
+        assert_eq!(table.num_inserts, 1);
+    }
+
+    #[test]
+    fn test_try_reserve_grows_table() {
+        let mut table = ElasticHashTable::deterministic(64, 0.1);
+        for i in 0..50 {
+            table.insert(i, i).expect("Insertion failed");
+        }
+        assert_eq!(table.try_reserve(5), Ok(()));
+        assert_eq!(table.capacity(), 64);
+
+        assert_eq!(table.try_reserve(200), Ok(()));
+        assert!(table.max_inserts >= 250);
+        assert_eq!(table.num_inserts, 50);
+        assert_eq!(table.occupancies.iter().sum::<usize>(), 50);
+        for i in 0..50 {
+            assert_eq!(table.get(&i), Some(&i));
+        }
+        for i in 50..250 {
+            table.insert(i, i).expect("Insertion failed");
+        }
+        assert_eq!(table.try_reserve(usize::MAX), Err(TryReserveError::CapacityOverflow));
+    }
+
+    #[test]
+    fn test_resize() {
+        let mut table = ElasticHashTable::with_seed(64, 0.1, 2);
+        while !table.is_full() {
+            table.insert(table.len(), table.len()).expect("Insertion failed");
+        }
+        for key in (0..table.len()).step_by(5) {
+            table.remove(&key);
+        }
+        let (len, load_factor) = (table.len(), table.load_factor());
+        table.resize(300).unwrap();
+        assert_eq!(table.capacity(), 300);
+        assert!(table.load_factor() < load_factor / 4.0);
+        assert_eq!(table.len(), len);
+        assert!(table.levels.iter().flatten().all(|slot| !matches!(slot, Slot::Tombstone)));
+        for key in 0..58 {
+            assert_eq!(table.get(&key), (key % 5 != 0).then_some(&key), "{}", key);
+        }
+        table.check_invariants().unwrap();
+
+        // shrinking works down to the smallest capacity that holds every entry
+        let smallest = (1..).find(|&capacity| max_inserts(capacity, 0.1) >= len).unwrap();
+        let err = table.resize(smallest - 1).unwrap_err();
+        assert_eq!(err, ElasticHashError::CapacityTooSmall { needed: len, requested: smallest - 1 });
+        assert_eq!(table.capacity(), 300);
+        assert_eq!(table.resize(0), Err(ElasticHashError::InvalidCapacity));
+        table.resize(smallest).unwrap();
+        assert!(table.is_full());
+        assert_eq!(table.len(), len);
+        table.check_invariants().unwrap();
+    }
+
+    #[test]
+    fn test_grow_on_full() {
+        assert!(ElasticHashTable::<u32, u32>::with_growth(16, 0.1).grows_on_full());
+        let mut table = ElasticHashTable::deterministic(16, 0.1);
+        table.set_grow_on_full(true);
+        let first_max = table.max_inserts();
+        for key in 0..1000u32 {
+            table.insert(key, key).expect("Insertion failed");
+        }
+        assert!(table.capacity() >= 1024 && table.capacity() <= 2048);
+        assert!(table.len() > 50 * first_max);
+        for key in 0..1000 {
+            assert_eq!(table.get(&key), Some(&key));
+        }
+        table.modify_or_insert(1000, 0, |v| *v += 1).unwrap();
+        table.check_invariants().unwrap();
+
+        // growth can be switched off again, and a full table then refuses new keys
+        table.set_grow_on_full(false);
+        let mut key = 2000;
+        while !table.is_full() {
+            table.insert(key, key).expect("Insertion failed");
+            key += 1;
+        }
+        assert!(table.insert(key, key).is_err());
+        assert!(!table.clone().grows_on_full());
+    }
+
+    #[test]
+    fn test_try_reserve_rolls_back_on_alloc_failure() {
+        let mut table = ElasticHashTable::deterministic(64, 0.1);
+        for i in 0..50 {
+            table.insert(i, i).expect("Insertion failed");
+        }
+        let layout = |table: &ElasticHashTable<i32, i32>| -> Vec<Vec<(usize, i32, i32)>> {
+            (0..table.levels.len())
+                .map(|level| table.level_iter(level).unwrap().map(|(idx, k, v)| (idx, *k, *v)).collect())
+                .collect()
+        };
+        let before = layout(&table);
+
+        ALLOC_BUDGET.with(|b| b.set(Some(2)));
+        let res = table.try_reserve(100);
+        ALLOC_BUDGET.with(|b| b.set(None));
+        assert!(matches!(res, Err(TryReserveError::AllocError { .. })));
+
+        assert_eq!(layout(&table), before);
+        assert_eq!(table.capacity(), 64);
+        assert_eq!(table.num_inserts, 50);
+    }
+
+    #[test]
+    fn test_hash_key_matches_hash_with_seed() {
+        let table: ElasticHashTable<String, u32> = ElasticHashTable::with_seed(64, 0.1, 1234);
+        assert_eq!(table.seed(), 1234);
+        for key in ["", "a", "router-key", "ünïcode"] {
+            assert_eq!(table.hash_key(key), hash_with_seed(key, table.seed()));
+        }
+
+        let default: ElasticHashTable<u64, u32> = ElasticHashTable::deterministic(64, 0.1);
+        assert_eq!(default.hash_key(&99), hash_with_seed(&99u64, default.seed()));
+    }
+
+    #[test]
+    fn test_mutable_and_owning_iterators() {
+        let mut table = ElasticHashTable::with_seed(512, 0.1, 3);
+        let mut expected = HashMap::new();
+        for i in 0..400u32 {
+            table.insert(i, i).expect("Insertion failed");
+            expected.insert(i, i);
+        }
+        for i in (0..400u32).step_by(3) {
+            table.remove(&i);
+            expected.remove(&i);
+        }
+        for i in (1..400u32).step_by(7) {
+            if expected.contains_key(&i) {
+                table.insert(i, i + 1000).expect("Insertion failed");
+                expected.insert(i, i + 1000);
+            }
+        }
+
+        let seen: HashMap<u32, u32> = (&table).into_iter().map(|(&k, &v)| (k, v)).collect();
+        assert_eq!(seen, expected);
+        assert_eq!(table.iter().count(), expected.len());
+
+        let generation = table.generation();
+        for (k, v) in &mut table {
+            *v += k;
+        }
+        table.values_mut().for_each(|v| *v *= 2);
+        assert_eq!(table.generation(), generation);
+        assert_eq!(table.iter_mut().len(), expected.len());
+        for (k, v) in &expected {
+            assert_eq!(table.get(k), Some(&((v + k) * 2)));
+        }
+
+        let mut owned = table.clone().into_iter();
+        assert_eq!(owned.len(), expected.len());
+        owned.next();
+        assert_eq!(owned.len(), expected.len() - 1);
+        let migrated: HashMap<u32, u32> = table.into_iter().collect();
+        assert_eq!(migrated.len(), expected.len());
+        assert!(expected.iter().all(|(k, v)| migrated[k] == (v + k) * 2));
+    }
+
+    #[test]
+    fn test_iterators_report_exact_len() {
+        let mut table = ElasticHashTable::deterministic(256, 0.1);
+        for i in 0..150 {
+            table.insert(i, i * 2).expect("Insertion failed");
+        }
+        for i in (0..150).step_by(4) {
+            table.remove(&i);
+        }
+        let len = table.num_inserts;
+
+        let mut iter = table.iter();
+        assert_eq!(iter.len(), len);
+        for consumed in 1..=len {
+            let (k, v) = iter.next().expect("iterator ended early");
+            assert_eq!(*v, k * 2);
+            assert_eq!(iter.len(), len - consumed);
+        }
+        assert_eq!(iter.next(), None);
+        assert_eq!(iter.next(), None);
+
+        let keys: Vec<_> = table.keys().collect();
+        assert_eq!(keys.len(), len);
+        assert_eq!(keys.capacity(), len);
+        assert_eq!(table.values().len(), len);
+        assert_eq!(table.values().sum::<i32>(), keys.iter().map(|k| **k * 2).sum::<i32>());
+
+        let mut extract = table.extract_if(|k, _| k % 2 == 0);
+        assert_eq!(extract.size_hint(), (0, Some(len)));
+        extract.by_ref().for_each(drop);
+        assert_eq!(extract.size_hint(), (0, Some(0)));
+    }
+
+    #[test]
+    fn test_options_roundtrip() {
+        let mut table = ElasticHashTable::with_seed(300, 0.2, 99);
+        assert_eq!(table.delta(), 0.2);
+        assert_eq!(table.c(), 4.0);
+        assert_eq!(table.threshold(), 0.25);
+        assert_eq!(table.hasher_id(), "fnv1a-splitmix64");
+
+        let options = TableOptions::from_json(&table.options().to_json()).expect("valid options");
+        assert_eq!(options, TableOptions { capacity: 300, delta: 0.2, seed: 99 });
+        let mut copy = ElasticHashTable::with_options(options);
+        for i in 0..200 {
+            assert_eq!(table.insert(i, i), copy.insert(i, i));
+        }
+
+        assert!(TableOptions::from_json(r#"{"capacity": 10, "delta": 0.1}"#).is_err());
+        assert!(TableOptions::from_json(r#"{"capacity": 10, "delta": 0.1, "seed": 1, "colour": 2}"#).is_err());
+    }
+
+    #[test]
+    fn test_rehash_with_seed() {
+        let mut table = ElasticHashTable::deterministic(1024, 0.1);
+        // keys sharing their home slot in level 0 under the default seed
+        let clustered: Vec<u32> = (0..)
+            .filter(|k| table.home_slot(k, 0) == Ok(0))
+            .take(40)
+            .collect();
+        for &k in &clustered {
+            table.insert(k, k).expect("Insertion failed");
+        }
+        let avg_probes = |table: &ElasticHashTable<u32, u32>| {
+            clustered.iter().map(|k| table.probe(k).1).sum::<usize>() as f64 / clustered.len() as f64
+        };
+        let before = avg_probes(&table);
+        let placements: Vec<_> = clustered.iter().map(|k| table.find(k)).collect();
+
+        table.rehash_with_seed(12345).expect("rehash failed");
+        assert_eq!(table.seed(), 12345);
+        assert_eq!(table.rehashes(), 1);
+        assert_eq!(table.capacity(), 1024);
+        assert_eq!(table.num_inserts, clustered.len());
+        for &k in &clustered {
+            assert_eq!(table.get(&k), Some(&k));
+        }
+        assert_ne!(clustered.iter().map(|k| table.find(k)).collect::<Vec<_>>(), placements);
+        assert!(avg_probes(&table) < before / 2.0, "{} vs {}", avg_probes(&table), before);
+    }
+
+    #[test]
+    fn test_random_seed_resists_crafted_keys() {
+        // keys crafted to share level 0's home slot under the default seed
+        let crafted: Vec<u32> = {
+            let table = ElasticHashTable::<u32, u32>::deterministic(1024, 0.1);
+            (0..).filter(|k| table.home_slot(k, 0) == Ok(0)).take(60).collect()
+        };
+        let total_probes = |mut table: ElasticHashTable<u32, u32>| {
+            for &k in &crafted {
+                table.insert(k, k).expect("Insertion failed");
+            }
+            crafted.iter().map(|k| table.probe(k).1).sum::<usize>()
+        };
+
+        // any other seed scatters them again; two fixed ones stand in for random seeds,
+        // so the test does not depend on the seed a run happens to draw
+        let fixed = total_probes(ElasticHashTable::deterministic(1024, 0.1));
+        for seed in [1, 0x9e37_79b9_7f4a_7c15] {
+            let other = total_probes(ElasticHashTable::with_seed(1024, 0.1, seed));
+            assert!(other * 2 < fixed, "seed {} probes {} vs default seed {}", seed, other, fixed);
+        }
+
+        let seed = || ElasticHashTable::<u32, u32>::new(16, 0.1).seed();
+        assert_ne!(seed(), seed());
+    }
+
+    #[test]
+    fn test_deterministic_reproduces_layout() {
+        let mut a = ElasticHashTable::deterministic(500, 0.1);
+        let mut b = ElasticHashTable::deterministic(500, 0.1);
+        for i in 0..400 {
+            assert_eq!(a.insert(i, i), b.insert(i, i));
+        }
+        assert_eq!(a.seed(), b.seed());
+    }
+
+    /// every slot as (level, index, state), tombstones included
+    fn dump<K: std::fmt::Debug, V: std::fmt::Debug>(table: &ElasticHashTable<K, V>) -> Vec<String> {
+        let mut slots = Vec::new();
+        for (level, level_slots) in table.levels.iter().enumerate() {
+            for (idx, slot) in level_slots.iter().enumerate() {
+                slots.push(format!("{} {} {:?}", level, idx, slot));
+            }
+        }
+        slots
+    }
+
+    #[test]
+    fn test_clone_from_is_exact_copy() {
+        let mut source = ElasticHashTable::deterministic(256, 0.1);
+        for i in 0..150 {
+            source.insert(i.to_string(), vec![i; 3]).expect("Insertion failed");
+        }
+        let mut dest = source.clone();
+        assert_eq!(dump(&dest), dump(&source));
+
+        // diverge both sides, then resync
+        for i in (0..150).step_by(3) {
+            source.remove(&i.to_string());
+        }
+        for i in 150..180 {
+            source.insert(i.to_string(), vec![i]).expect("Insertion failed");
+        }
+        for i in (1..150).step_by(2) {
+            dest.modify(&i.to_string(), |v| v.push(0));
+        }
+        let buffers: Vec<_> = dest.levels.iter().map(|level| level.as_ptr()).collect();
+
+        dest.clone_from(&source);
+        assert_eq!(dump(&dest), dump(&source));
+        assert_eq!(dest.num_inserts, source.num_inserts);
+        assert_eq!(dest.occupancies, source.occupancies);
+        assert_eq!(dest.summary(), source.summary());
+        assert_eq!(dest.levels.iter().map(|level| level.as_ptr()).collect::<Vec<_>>(), buffers);
+
+        // a differently shaped destination still ends up an exact copy
+        let mut small = ElasticHashTable::deterministic(16, 0.1);
+        small.insert("x".to_string(), vec![1]).expect("Insertion failed");
+        small.clone_from(&source);
+        assert_eq!(dump(&small), dump(&source));
+        assert_eq!(small.get("170"), Some(&vec![170]));
+    }
+
+    /// run with `cargo test --release -- --ignored --nocapture bench_clone`
+    #[test]
+    #[ignore]
+    fn bench_clone_vs_clone_from() {
+        let mut source = ElasticHashTable::deterministic(1 << 16, 0.1);
+        for i in 0..50_000 {
+            source.insert(i, i.to_string()).expect("Insertion failed");
+        }
+        let rounds = 50;
+
+        let start = std::time::Instant::now();
+        let mut snapshot = source.clone();
+        for _ in 0..rounds {
+            snapshot = source.clone();
+        }
+        let clone = start.elapsed();
+
+        let start = std::time::Instant::now();
+        for _ in 0..rounds {
+            snapshot.clone_from(&source);
+        }
+        let clone_from = start.elapsed();
+
+        assert_eq!(snapshot.num_inserts, source.num_inserts);
+        println!("{} snapshots: clone {:?}, clone_from {:?}", rounds, clone, clone_from);
+    }
+
+    #[test]
+    fn test_split_off() {
+        let mut table = ElasticHashTable::deterministic(512, 0.1);
+        for i in 0..400 {
+            table.insert(i, i * 10).expect("Insertion failed");
+        }
+
+        let even = table.split_off(|k, _| k % 2 == 0);
+        assert_eq!(even.num_inserts, 200);
+        assert_eq!(table.num_inserts, 200);
+        assert_eq!(even.seed(), table.seed());
+        assert_eq!(even.delta(), table.delta());
+        assert!(even.capacity() < table.capacity());
+        for i in 0..400 {
+            let (has, lacks) = if i % 2 == 0 { (&even, &table) } else { (&table, &even) };
+            assert_eq!(has.get(&i), Some(&(i * 10)));
+            assert_eq!(lacks.get(&i), None);
+        }
+        assert_eq!(even.check_invariants(), Ok(()));
+        assert_eq!(table.check_invariants(), Ok(()));
+
+        let none = table.split_off(|_, _| false);
+        assert_eq!(none.num_inserts, 0);
+        assert_eq!(table.num_inserts, 200);
+    }
+
+    #[test]
+    fn test_split_off_keeps_settings() {
+        let mut table = ElasticHashTable::with_seed(256, 0.1, 1).with_metadata(None);
+        table.set_grow_on_full(true);
+        table.set_pressure_callback(&[0.9], Box::new(|_, _| {})).unwrap();
+        for i in 0..100u32 {
+            table.insert_tagged(i, i, 1000 + u64::from(i)).expect("Insertion failed");
+        }
+
+        let split = table.split_off(|k, _| k % 2 == 0);
+        assert!(split.has_metadata() && split.grows_on_full());
+        assert_eq!((split.seed(), split.delta(), split.geometry()), (table.seed(), table.delta(), table.geometry()));
+        // tags move with their entries
+        assert!((0..100u32).step_by(2).all(|i| split.get_with_meta(&i) == Some((&i, 1000 + u64::from(i)))));
+        assert_eq!(table.get_with_meta(&1), Some((&1, 1001)));
+        // callbacks are not copied: the pressure callback stays with the table it was set on
+        assert!(split.pressure.is_none() && table.pressure.is_some());
+        split.check_invariants().unwrap();
+    }
+
+    #[test]
+    fn test_rebalance_after_churn() {
+        let mut table = ElasticHashTable::deterministic(1024, 0.1);
+        let mut live = std::collections::BTreeSet::new();
+        let mut next = 0;
+        for round in 0..20 {
+            while table.num_inserts < 850 {
+                table.insert(next, next).expect("Insertion failed");
+                live.insert(next);
+                next += 1;
+            }
+            let victims: Vec<_> = live.iter().copied().filter(|k| (k + round) % 3 == 0).collect();
+            for k in victims {
+                table.remove(&k);
+                live.remove(&k);
+            }
+        }
+        let tombstones = |table: &ElasticHashTable<i32, i32>| {
+            table.levels.iter().flatten().filter(|slot| matches!(slot, Slot::Tombstone)).count()
+        };
+        assert!(tombstones(&table) > 0);
+
+        let report = table.rebalance().expect("rebalance failed");
+        assert!(report.probes_after < report.probes_before, "{:?}", report);
+        assert_eq!(tombstones(&table), 0);
+        assert_eq!(table.num_inserts, live.len());
+        for k in &live {
+            assert_eq!(table.get(k), Some(k));
+        }
+        assert_eq!(table.check_invariants(), Ok(()));
+    }
+
+    /// run with `cargo test --release -- --ignored --nocapture bench_fill_tail`
+    #[test]
+    #[ignore]
+    fn bench_fill_tail() {
+        let n = 1 << 20;
+        let mut table = ElasticHashTable::deterministic(n, 0.001);
+        let tail = table.max_inserts / 100;
+        let head = table.max_inserts - tail;
+        for i in 0..head {
+            table.insert(i, i).expect("Insertion failed");
+        }
+        let start = std::time::Instant::now();
+        let mut failed = 0;
+        for i in head..table.max_inserts {
+            if table.insert(i, i).is_err() {
+                failed += 1;
+            }
+        }
+        println!("last {} inserts: {:?}, {} failed", tail, start.elapsed(), failed);
+    }
+
+    #[test]
+    fn test_from_layout_validates() {
+        let layout = vec![vec![Some((1u32, 1u32)), None], vec![None]];
+        let table = ElasticHashTable::from_layout(layout, 0.1).expect("valid layout");
+        assert_eq!(table.occupancies, vec![1, 0]);
+        assert_eq!(table.num_inserts, 1);
+
+        assert!(ElasticHashTable::<u32, u32>::from_layout(vec![], 0.1).is_err());
+        assert!(ElasticHashTable::<u32, u32>::from_layout(vec![vec![None], vec![]], 0.1).is_err());
+        assert!(ElasticHashTable::<u32, u32>::from_layout(vec![vec![None]], 1.5).is_err());
+        assert!(ElasticHashTable::from_layout(vec![vec![Some((1, 1))], vec![Some((1, 2)), None]], 0.1).is_err());
+        // 2 slots with delta 0.5 allow a single entry
+        assert!(ElasticHashTable::from_layout(vec![vec![Some((1, 1)), Some((2, 2))]], 0.5).is_err());
+    }
+
+    #[test]
+    fn test_fill_to_max_inserts() {
+        for delta in [0.001, 0.01, 0.1] {
+            let mut table = ElasticHashTable::deterministic(20_000, delta);
+            for i in 0..table.max_inserts {
+                table.insert(i, i).expect("Insertion failed");
+            }
+            for i in (0..table.max_inserts).step_by(7) {
+                assert_eq!(table.remove(&i), Some(i));
+            }
+            for i in (0..table.max_inserts).step_by(7) {
+                table.insert(i, i).expect("Insertion failed");
+            }
+            for i in 0..table.max_inserts {
+                assert_eq!(table.get(&i), Some(&i));
+            }
+            assert_eq!(table.check_invariants(), Ok(()));
+        }
+    }
+
+    #[test]
+    fn test_generation_counts_mutations() {
+        let mut table = ElasticHashTable::deterministic(8, 0.1);
+        let mut expected = 0;
+        let mut step = |table: &ElasticHashTable<u32, u32>, changed: bool| {
+            expected += changed as u64;
+            assert_eq!(table.generation(), expected);
+        };
+
+        table.insert(1, 10).expect("Insertion failed");
+        step(&table, true);
+        table.modify(&1, |v| *v += 1);
+        step(&table, true);
+        table.replace_key(1);
+        step(&table, true);
+        table.get(&1);
+        table.iter().count();
+        table.modify(&2, |v| *v += 1);
+        assert_eq!(table.remove(&2), None);
+        step(&table, false);
+        assert_eq!(table.remove(&1), Some(11));
+        step(&table, true);
+        table.try_reserve(100).expect("reserve failed");
+        step(&table, true);
+        table.try_reserve(1).expect("reserve failed");
+        step(&table, false);
+
+        for i in 0..table.max_inserts as u32 {
+            table.insert(i, i).expect("Insertion failed");
+        }
+        let full = table.generation();
+        assert!(table.insert(u32::MAX, 0).is_err());
+        assert_eq!(table.generation(), full);
+        assert_eq!(table.clone().generation(), full);
+    }
+}
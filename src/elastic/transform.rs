@@ -0,0 +1,169 @@
+//! value transforms that keep every key in its slot, so the derived table is never re-probed
+
+use std::hash::Hash;
+
+use super::table::{ElasticHashTable, Slot};
+use crate::TableHasher;
+
+impl<K, V, S> ElasticHashTable<K, V, S>
+where
+    K: Hash + Eq,
+    S: TableHasher,
+{
+    /// the same keys in the same slots, each with value f(key, value); capacity, delta, seed, geometry,
+    /// metadata, growth, tuning and pressure settings carry over. the observer does not, as it watches V
+    pub fn map_values<W>(self, mut f: impl FnMut(&K, V) -> W) -> ElasticHashTable<K, W, S> {
+        self.filter_map_values(|k, v| Some(f(k, v)))
+    }
+
+    /// map_values dropping the entries f returns None for; their slots become tombstones, as with remove,
+    /// and every other key stays where it was
+    pub fn filter_map_values<W>(self, mut f: impl FnMut(&K, V) -> Option<W>) -> ElasticHashTable<K, W, S> {
+        let ElasticHashTable {
+            delta,
+            max_inserts,
+            mut num_inserts,
+            levels,
+            mut occupancies,
+            c,
+            pop_cursor,
+            seed,
+            rehashes,
+            generation,
+            free_cursors,
+            mut displaced,
+            observer: _,
+            pressure,
+            mut meta,
+            probes,
+            probe_stats,
+            geometry,
+            grow_on_full,
+            tuning,
+            hasher,
+        } = self;
+        let mut dropped = 0;
+        let levels = levels
+            .into_iter()
+            .enumerate()
+            .map(|(level, slots)| {
+                let slots = slots.into_iter().enumerate().map(|(idx, slot)| match slot {
+                    Slot::Empty => Slot::Empty,
+                    Slot::Tombstone => Slot::Tombstone,
+                    Slot::Occupied(k, v) => {
+                        // hashed first, as f takes the value and a dropped key leaves with it
+                        let hash = (!displaced.is_empty()).then(|| S::hash_one(&k, seed));
+                        if let Some(w) = f(&k, v) {
+                            return Slot::Occupied(k, w);
+                        }
+                        dropped += 1;
+                        occupancies[level] -= 1;
+                        num_inserts -= 1;
+                        if let Some(meta) = &mut meta {
+                            meta.tags[level][idx] = 0;
+                        }
+                        if let Some(places) = hash.and_then(|hash| displaced.get_mut(&hash)) {
+                            places.retain(|&place| place != (level, idx));
+                        }
+                        Slot::Tombstone
+                    }
+                });
+                slots.collect()
+            })
+            .collect();
+        displaced.retain(|_, places| !places.is_empty());
+        let mut table = ElasticHashTable {
+            delta,
+            max_inserts,
+            num_inserts,
+            levels,
+            occupancies,
+            c,
+            pop_cursor,
+            seed,
+            rehashes,
+            generation: generation + 1,
+            free_cursors,
+            displaced,
+            observer: None,
+            pressure,
+            meta,
+            probes,
+            probe_stats,
+            geometry,
+            grow_on_full,
+            tuning,
+            hasher,
+        };
+        if dropped > 0 {
+            table.update_pressure();
+        }
+        table
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{ElasticHashTable, LevelGeometry, SlotId};
+
+    fn slots<V>(table: &ElasticHashTable<u64, V>) -> Vec<(SlotId, u64)> {
+        let mut slots: Vec<_> = table.keys().map(|&k| (SlotId::from(table.find(&k).unwrap()), k)).collect();
+        slots.sort_by_key(|&(id, _)| (id.level, id.index));
+        slots
+    }
+
+    fn churned() -> ElasticHashTable<u64, u64> {
+        let mut table = ElasticHashTable::with_geometry(512, 0.1, 9, LevelGeometry::Single).unwrap();
+        table.set_grow_on_full(true);
+        table.enable_delta_analysis();
+        for key in 0..450 {
+            table.insert(key, key * 10).expect("Insertion failed");
+        }
+        for key in (0..450).step_by(4) {
+            table.remove(&key);
+        }
+        table
+    }
+
+    #[test]
+    fn test_map_values_keeps_slots_and_settings() {
+        let table = churned();
+        let before = slots(&table);
+        let (options, probes) = (table.options(), table.probe_count());
+        let mapped = table.map_values(|k, v| format!("{}:{}", k, v));
+        // nothing was probed
+        assert_eq!((mapped.options(), mapped.probe_count()), (options, probes));
+        assert_eq!(slots(&mapped), before);
+        assert_eq!(mapped.get(&5).map(String::as_str), Some("5:50"));
+        assert_eq!(mapped.get(&4), None);
+        assert!(mapped.is_single_level() && mapped.grows_on_full() && mapped.tuning.is_some());
+        mapped.check_invariants().unwrap();
+    }
+
+    #[test]
+    fn test_filter_map_values_drops_entries() {
+        let table = churned();
+        let len = table.len();
+        let before = slots(&table);
+        let odd = table.filter_map_values(|k, v| (k % 2 == 1).then_some(v as u32 + 1));
+        let kept: Vec<_> = before.into_iter().filter(|(_, k)| k % 2 == 1).collect();
+        assert_eq!(slots(&odd), kept);
+        assert_eq!(odd.len(), kept.len());
+        assert!(odd.len() < len);
+        assert_eq!((odd.get(&7), odd.get(&6)), (Some(&71), None));
+        odd.check_invariants().unwrap();
+
+        // displaced entries leave the index along with their slot
+        let mut crowded = ElasticHashTable::with_seed(64, 0.02, 3);
+        let mut key = 0;
+        while crowded.displaced.is_empty() {
+            key += 1;
+            if crowded.insert(key, key).is_err() {
+                crowded = ElasticHashTable::with_seed(64, 0.02, key);
+            }
+        }
+        let none = crowded.filter_map_values(|_, _| None::<u64>);
+        assert!(none.is_empty() && none.displaced.is_empty());
+        none.check_invariants().unwrap();
+    }
+}
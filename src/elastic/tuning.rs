@@ -0,0 +1,208 @@
+//! delta tuning: the probe cost of inserts at each load factor, the delta that curve suggests, and an opt-in
+//! move of delta toward the suggestion every time a table that grows on full grows
+
+use std::hash::Hash;
+
+use super::table::ElasticHashTable;
+use crate::TableHasher;
+
+/// number of load factor buckets in the cost curve, each 1 / CURVE_BUCKETS wide
+pub const CURVE_BUCKETS: usize = 20;
+/// an insert costing more than this many times an insert into a nearly empty table is crawling
+pub const COST_RATIO: f64 = 4.0;
+/// inserts a bucket needs before the curve trusts its average
+const MIN_SAMPLES: u64 = 8;
+/// the smallest delta recommended_delta suggests
+const MIN_RECOMMENDED: f64 = 0.01;
+
+/// what enable_delta_analysis records, and the bounds of set_delta_tuning
+#[derive(Clone, Debug, Default)]
+pub(crate) struct DeltaTuning {
+    /// probes spent finding a free slot, by the load factor the insert started at
+    probes: [u64; CURVE_BUCKETS],
+    inserts: [u64; CURVE_BUCKETS],
+    /// (min, max) delta growth may move to; None while only analysing
+    bounds: Option<(f64, f64)>,
+    /// delta before the last adjustment
+    pub(crate) previous_delta: Option<f64>,
+}
+
+impl DeltaTuning {
+    pub(crate) fn record(&mut self, load_factor: f64, probes: u64) {
+        let bucket = ((load_factor * CURVE_BUCKETS as f64) as usize).min(CURVE_BUCKETS - 1);
+        self.probes[bucket] += probes;
+        self.inserts[bucket] += 1;
+    }
+
+    /// (bucket, mean probes) of the buckets with enough samples
+    fn curve(&self) -> impl Iterator<Item = (usize, f64)> + '_ {
+        (0..CURVE_BUCKETS)
+            .filter(|&b| self.inserts[b] >= MIN_SAMPLES)
+            .map(|b| (b, self.probes[b] as f64 / self.inserts[b] as f64))
+    }
+
+    fn recommended_delta(&self) -> Option<f64> {
+        let (_, baseline) = self.curve().next()?;
+        let crawl = COST_RATIO * baseline.max(1.0);
+        let mut top = 0;
+        for (bucket, cost) in self.curve() {
+            if cost > crawl {
+                return Some((1.0 - bucket as f64 / CURVE_BUCKETS as f64).max(MIN_RECOMMENDED));
+            }
+            top = bucket;
+        }
+        Some((1.0 - (top + 1) as f64 / CURVE_BUCKETS as f64).max(MIN_RECOMMENDED))
+    }
+}
+
+impl<K, V, S> ElasticHashTable<K, V, S>
+where
+    K: Hash + Eq,
+    S: TableHasher,
+{
+    /// start recording how many probes each insert of a new key spends finding a free slot, by load factor;
+    /// see probe_cost_curve and recommended_delta
+    pub fn enable_delta_analysis(&mut self) {
+        self.tuning.get_or_insert_with(Box::default);
+    }
+
+    /// (load factor, mean probes per insert) for every load factor bucket with enough recorded inserts,
+    /// lowest load first; empty unless enable_delta_analysis was called
+    pub fn probe_cost_curve(&self) -> Vec<(f64, f64)> {
+        self.tuning.as_ref().map_or(Vec::new(), |tuning| {
+            tuning.curve().map(|(bucket, cost)| (bucket as f64 / CURVE_BUCKETS as f64, cost)).collect()
+        })
+    }
+
+    /// the delta that stops inserts short of the load factor where they start to crawl: 1 minus the lowest
+    /// load whose inserts cost over COST_RATIO times those at the lowest recorded load. when no recorded load
+    /// crawls, 1 minus the highest recorded load, since nothing is known past it. None before any bucket
+    /// has enough samples
+    pub fn recommended_delta(&self) -> Option<f64> {
+        self.tuning.as_ref()?.recommended_delta()
+    }
+
+    /// let growth move delta: each time an insert into a full table that grows on full (see set_grow_on_full)
+    /// doubles the capacity, delta first moves halfway toward recommended_delta, kept within
+    /// [min_delta, max_delta]. enables the analysis; needs 0 < min_delta <= max_delta < 1
+    pub fn set_delta_tuning(&mut self, min_delta: f64, max_delta: f64) -> Result<(), String> {
+        if !(0.0 < min_delta && min_delta <= max_delta && max_delta < 1.0) {
+            return Err(format!("delta bounds [{}, {}] must satisfy 0 < min <= max < 1", min_delta, max_delta));
+        }
+        self.enable_delta_analysis();
+        if let Some(tuning) = &mut self.tuning {
+            tuning.bounds = Some((min_delta, max_delta));
+        }
+        Ok(())
+    }
+
+    /// delta before the last adjustment set_delta_tuning made, None when delta never moved
+    pub fn previous_delta(&self) -> Option<f64> {
+        self.tuning.as_ref()?.previous_delta
+    }
+
+    /// the delta the next growth step should use, None when delta is not tuned or has no recommendation
+    pub(crate) fn tuned_delta(&self) -> Option<f64> {
+        let tuning = self.tuning.as_ref()?;
+        let (min, max) = tuning.bounds?;
+        let target = tuning.recommended_delta()?.clamp(min, max);
+        let step = self.delta + (target - self.delta) / 2.0;
+        // snap once close, so delta settles instead of creeping forever
+        let delta = if (target - step).abs() < 0.005 { target } else { step };
+        Some(delta.clamp(min, max))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{CURVE_BUCKETS, COST_RATIO};
+    use crate::ElasticHashTable;
+
+    /// record samples inserts per bucket costing 1 probe below knee and 20 from it on
+    fn synthetic(table: &mut ElasticHashTable<u32, u32>, knee: f64, samples: u64) {
+        table.enable_delta_analysis();
+        let tuning = table.tuning.as_mut().unwrap();
+        for bucket in 0..CURVE_BUCKETS {
+            let load = (bucket as f64 + 0.5) / CURVE_BUCKETS as f64;
+            for _ in 0..samples {
+                tuning.record(load, if load < knee { 1 } else { 20 });
+            }
+        }
+    }
+
+    #[test]
+    fn test_recommendation_finds_the_knee() {
+        for knee in [0.5, 0.7, 0.85, 0.95] {
+            let mut table = ElasticHashTable::with_seed(64, 0.1, 1);
+            assert_eq!(table.recommended_delta(), None);
+            synthetic(&mut table, knee, 10);
+            let recommended = table.recommended_delta().unwrap();
+            assert!((recommended - (1.0 - knee)).abs() <= 1.0 / CURVE_BUCKETS as f64, "knee {}: {}", knee, recommended);
+            let curve = table.probe_cost_curve();
+            assert_eq!(curve.len(), CURVE_BUCKETS);
+            assert!(curve.iter().all(|&(load, cost)| (cost > COST_RATIO) == (load + 0.025 >= knee)));
+        }
+
+        // a flat curve says nothing past the highest load recorded
+        let mut table = ElasticHashTable::with_seed(64, 0.1, 1);
+        synthetic(&mut table, 2.0, 10);
+        assert_eq!(table.recommended_delta(), Some(0.01));
+    }
+
+    #[test]
+    fn test_real_inserts_are_recorded() {
+        let mut table = ElasticHashTable::with_seed(4096, 0.02, 3);
+        table.enable_delta_analysis();
+        while !table.is_full() {
+            table.insert(table.len() as u32, 0).expect("Insertion failed");
+        }
+        let curve = table.probe_cost_curve();
+        assert_eq!(curve.len(), CURVE_BUCKETS);
+        assert!(curve.last().unwrap().1 > curve[0].1);
+        let recommended = table.recommended_delta().unwrap();
+        assert!(0.0 < recommended && recommended < 1.0);
+        let stats = table.stats();
+        assert_eq!((stats.delta, stats.recommended_delta, stats.previous_delta), (0.02, Some(recommended), None));
+    }
+
+    #[test]
+    fn test_auto_tune_converges_within_bounds() {
+        // recommendation 0.3, inside the bounds: delta converges to it
+        let mut table = ElasticHashTable::with_seed(16, 0.1, 5);
+        table.set_grow_on_full(true);
+        synthetic(&mut table, 0.7, 1_000_000);
+        table.set_delta_tuning(0.05, 0.5).unwrap();
+        let mut deltas = vec![table.delta()];
+        for key in 0..20_000 {
+            table.insert(key, key).expect("Insertion failed");
+            if table.delta() != *deltas.last().unwrap() {
+                assert_eq!(table.previous_delta(), deltas.last().copied());
+                deltas.push(table.delta());
+            }
+        }
+        assert!((table.delta() - 0.3).abs() < 1e-9, "{:?}", deltas);
+        assert!(deltas.windows(2).all(|w| w[0] < w[1]));
+        assert_eq!(table.stats().previous_delta, deltas.iter().rev().nth(1).copied());
+        table.check_invariants().unwrap();
+
+        // recommendation outside the bounds: delta stops at the nearest bound and never leaves them
+        for (min, max) in [(0.05, 0.2), (0.4, 0.6)] {
+            let mut table = ElasticHashTable::with_seed(16, 0.1, 5);
+            table.set_grow_on_full(true);
+            synthetic(&mut table, 0.7, 1_000_000);
+            table.set_delta_tuning(min, max).unwrap();
+            for key in 0..20_000 {
+                table.insert(key, key).expect("Insertion failed");
+                if table.previous_delta().is_some() {
+                    assert!(min <= table.delta() && table.delta() <= max, "{}", table.delta());
+                }
+            }
+            let nearest = if max < 0.3 { max } else { min };
+            assert_eq!(table.delta(), nearest);
+        }
+        let mut table = ElasticHashTable::<u32, u32>::with_seed(16, 0.1, 5);
+        assert!(table.set_delta_tuning(0.3, 0.2).is_err());
+        assert!(table.set_delta_tuning(0.0, 0.2).is_err());
+        assert!(table.set_delta_tuning(0.1, 1.0).is_err());
+    }
+}
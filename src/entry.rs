@@ -0,0 +1,279 @@
+//! entry API: one lookup decides between updating a present key and inserting an absent one
+//!
+//! ```ignore
+//! for word in text.split(' ') {
+//!     *table.entry(word.to_owned()).or_insert(0)? += 1;
+//! }
+//! ```
+
+use std::hash::Hash;
+
+use crate::elastic::table::FreeSlot;
+use crate::elastic::Slot;
+use crate::{ElasticHashError, ElasticHashTable, StableHash, TableHasher};
+
+/// returned by ElasticHashTable::entry
+pub enum Entry<'a, K, V, S = StableHash> {
+    Occupied(OccupiedEntry<'a, K, V, S>),
+    Vacant(VacantEntry<'a, K, V, S>),
+}
+
+/// a present key
+pub struct OccupiedEntry<'a, K, V, S = StableHash> {
+    table: &'a mut ElasticHashTable<K, V, S>,
+    level: usize,
+    idx: usize,
+}
+
+/// an absent key, with the free slot it would be inserted into
+pub struct VacantEntry<'a, K, V, S = StableHash> {
+    table: &'a mut ElasticHashTable<K, V, S>,
+    key: K,
+    /// None when the table had no room or must grow first; insert then looks again
+    free: Option<FreeSlot>,
+}
+
+impl<K, V, S> ElasticHashTable<K, V, S>
+where
+    K: Hash + Eq,
+    S: TableHasher,
+{
+    /// the entry of key, for in-place update or insertion; a vacant entry has already probed for its slot,
+    /// so inserting through it does not probe again
+    pub fn entry(&mut self, key: K) -> Entry<'_, K, V, S> {
+        if let Some((level, idx)) = self.find(&key) {
+            return Entry::Occupied(OccupiedEntry { table: self, level, idx });
+        }
+        // a full table that grows on full only grows once something is inserted
+        let free = if self.grows_on_full() && self.is_full() { None } else { self.locate_free_or_grow(&key).ok() };
+        Entry::Vacant(VacantEntry { table: self, key, free })
+    }
+}
+
+impl<'a, K, V, S> Entry<'a, K, V, S>
+where
+    K: Hash + Eq,
+    S: TableHasher,
+{
+    pub fn key(&self) -> &K {
+        match self {
+            Entry::Occupied(e) => e.key(),
+            Entry::Vacant(e) => e.key(),
+        }
+    }
+
+    /// the value, inserting default if the key is absent; fails like insert when there is no room
+    pub fn or_insert(self, default: V) -> Result<&'a mut V, ElasticHashError> {
+        self.or_insert_with(|| default)
+    }
+
+    /// the value, inserting make() if the key is absent; make is not called when there is no room
+    pub fn or_insert_with<F: FnOnce() -> V>(self, make: F) -> Result<&'a mut V, ElasticHashError> {
+        match self {
+            Entry::Occupied(e) => Ok(e.into_mut()),
+            Entry::Vacant(e) => e.insert_with(make),
+        }
+    }
+
+    /// the value, inserting V::default() if the key is absent
+    pub fn or_default(self) -> Result<&'a mut V, ElasticHashError>
+    where
+        V: Default,
+    {
+        self.or_insert_with(V::default)
+    }
+
+    /// apply f to the value if the key is present
+    pub fn and_modify<F: FnOnce(&mut V)>(mut self, f: F) -> Self {
+        if let Entry::Occupied(e) = &mut self {
+            e.table.modify_slot(e.level, e.idx, f);
+        }
+        self
+    }
+}
+
+impl<'a, K, V, S> OccupiedEntry<'a, K, V, S>
+where
+    K: Hash + Eq,
+    S: TableHasher,
+{
+    pub fn key(&self) -> &K {
+        self.get_key_value().0
+    }
+
+    pub fn get(&self) -> &V {
+        self.get_key_value().1
+    }
+
+    fn get_key_value(&self) -> (&K, &V) {
+        match &self.table.levels[self.level][self.idx] {
+            Slot::Occupied(k, v) => (k, v),
+            _ => unreachable!("entry points at a slot that is not occupied"),
+        }
+    }
+
+    pub fn get_mut(&mut self) -> &mut V {
+        match &mut self.table.levels[self.level][self.idx] {
+            Slot::Occupied(_, v) => v,
+            _ => unreachable!("entry points at a slot that is not occupied"),
+        }
+    }
+
+    /// the value, borrowed for the lifetime of the table borrow
+    pub fn into_mut(self) -> &'a mut V {
+        match &mut self.table.levels[self.level][self.idx] {
+            Slot::Occupied(_, v) => v,
+            _ => unreachable!("entry points at a slot that is not occupied"),
+        }
+    }
+
+    /// replace the value, returning the old one
+    pub fn insert(&mut self, value: V) -> V {
+        self.table.replace_slot(self.level, self.idx, value).expect("entry points at an occupied slot")
+    }
+
+    /// remove the entry from the table
+    pub fn remove_entry(self) -> (K, V) {
+        self.table.take_slot(self.level, self.idx)
+    }
+
+    pub fn remove(self) -> V {
+        self.remove_entry().1
+    }
+}
+
+impl<'a, K, V, S> VacantEntry<'a, K, V, S>
+where
+    K: Hash + Eq,
+    S: TableHasher,
+{
+    pub fn key(&self) -> &K {
+        &self.key
+    }
+
+    pub fn into_key(self) -> K {
+        self.key
+    }
+
+    /// insert value under the key into the slot entry found; fails like insert when there is no room
+    pub fn insert(self, value: V) -> Result<&'a mut V, ElasticHashError> {
+        self.insert_with(|| value)
+    }
+
+    fn insert_with<F: FnOnce() -> V>(self, make: F) -> Result<&'a mut V, ElasticHashError> {
+        let VacantEntry { table, key, free } = self;
+        let free = match free {
+            Some(free) => free,
+            None => table.locate_free_or_grow(&key)?,
+        };
+        let (level, idx) = table.place(free, key, make());
+        match &mut table.levels[level][idx] {
+            Slot::Occupied(_, v) => Ok(v),
+            _ => unreachable!("insert returned a slot that is not occupied"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Entry;
+    use crate::ElasticHashTable;
+
+    #[test]
+    fn test_word_frequencies() {
+        let text = "the cat and the dog and the bird saw the cat";
+        let mut table: ElasticHashTable<String, usize> = ElasticHashTable::deterministic(64, 0.1);
+        for word in text.split(' ') {
+            *table.entry(word.to_owned()).or_insert(0).expect("Insertion failed") += 1;
+        }
+        assert_eq!(table.num_inserts, 6);
+        for (word, count) in [("the", 4), ("cat", 2), ("and", 2), ("dog", 1), ("bird", 1), ("saw", 1)] {
+            assert_eq!(table.get(word), Some(&count));
+        }
+
+        let mut lengths: ElasticHashTable<usize, Vec<String>> = ElasticHashTable::deterministic(64, 0.1);
+        for word in text.split(' ') {
+            lengths.entry(word.len()).or_default().expect("Insertion failed").push(word.to_owned());
+        }
+        assert_eq!(lengths.num_inserts, 2);
+        assert_eq!(lengths.get(&3).map(Vec::len), Some(10));
+        assert_eq!(lengths.get(&4).map(Vec::len), Some(1));
+    }
+
+    #[test]
+    fn test_entry_variants() {
+        let mut table: ElasticHashTable<u32, u32> = ElasticHashTable::with_seed(64, 0.1, 1);
+        table.insert(1, 10).expect("Insertion failed");
+        let generation = table.generation();
+
+        let Entry::Occupied(mut e) = table.entry(1) else {
+            panic!("expected an occupied entry");
+        };
+        assert_eq!((e.key(), e.get()), (&1, &10));
+        assert_eq!(e.insert(11), 10);
+        assert_eq!(table.generation(), generation + 1);
+
+        assert_eq!(*table.entry(1).and_modify(|v| *v += 1).or_insert(0).unwrap(), 12);
+        assert_eq!(*table.entry(2).and_modify(|v| *v += 1).or_insert(0).unwrap(), 0);
+        let mut called = false;
+        table.entry(2).or_insert_with(|| {
+            called = true;
+            5
+        }).unwrap();
+        assert!(!called);
+
+        let Entry::Vacant(e) = table.entry(3) else {
+            panic!("expected a vacant entry");
+        };
+        assert_eq!(e.into_key(), 3);
+        assert_eq!(table.get(&3), None);
+        let Entry::Occupied(e) = table.entry(2) else {
+            panic!("expected an occupied entry");
+        };
+        assert_eq!(e.remove(), 0);
+        assert_eq!(table.num_inserts, 1);
+        table.check_invariants().unwrap();
+    }
+
+    #[test]
+    fn test_vacant_entry_reuses_its_probe() {
+        let mut table: ElasticHashTable<u32, u32> = ElasticHashTable::with_seed(256, 0.1, 1);
+        for key in 0..150 {
+            table.insert(key, key).expect("Insertion failed");
+        }
+        let mut twin = table.clone();
+        let before = table.probe_count();
+        let Entry::Vacant(e) = table.entry(1000) else {
+            panic!("expected a vacant entry");
+        };
+        let probed = e.table.probe_count() - before;
+        let slot = e.free.map(|free| (free.level, free.idx));
+        e.insert(7).unwrap();
+        assert_eq!(table.probe_count() - before, probed);
+        assert_eq!(table.find(&1000), slot);
+        twin.insert(1000, 7).expect("Insertion failed");
+        assert_eq!(twin.find(&1000), slot);
+    }
+
+    #[test]
+    fn test_full_table_entry() {
+        let mut table: ElasticHashTable<u32, u32> = ElasticHashTable::with_seed(32, 0.1, 1);
+        let mut key = 0;
+        while !table.is_full() {
+            table.insert(key, 0).expect("Insertion failed");
+            key += 1;
+        }
+        assert!(table.entry(key).or_insert(1).is_err());
+        assert_eq!(table.get(&key), None);
+        *table.entry(0).or_insert(1).unwrap() += 5;
+        assert_eq!(table.get(&0), Some(&5));
+
+        table.set_grow_on_full(true);
+        let capacity = table.capacity();
+        assert!(matches!(table.entry(key), Entry::Vacant(_)));
+        assert_eq!(table.capacity(), capacity, "an unused entry must not grow the table");
+        assert_eq!(*table.entry(key).or_insert(1).unwrap(), 1);
+        assert!(table.capacity() > capacity);
+        table.check_invariants().unwrap();
+    }
+}
@@ -0,0 +1,72 @@
+//! key comparison for lookups, looser than Borrow: a borrowed view such as `(&str, u32)`
+//! can find `(String, u32)` keys without building the owned key
+
+use std::borrow::Borrow;
+
+/// Q: Equivalent<K> lets a &Q look up K keys; Q must hash exactly like the K keys it is equivalent to
+/// every Q that K borrows as is equivalent through Eq, so &str keeps working for String keys
+pub trait Equivalent<K: ?Sized> {
+    fn equivalent(&self, key: &K) -> bool;
+}
+
+impl<Q, K> Equivalent<K> for Q
+where
+    Q: ?Sized + Eq,
+    K: ?Sized + Borrow<Q>,
+{
+    fn equivalent(&self, key: &K) -> bool {
+        self == key.borrow()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::hash::{Hash, Hasher};
+
+    use super::Equivalent;
+    use crate::ElasticHashTable;
+
+    /// a view of a (String, u32) key, hashed field by field like the tuple
+    struct NameId<'a>(&'a str, u32);
+
+    impl Hash for NameId<'_> {
+        fn hash<H: Hasher>(&self, state: &mut H) {
+            self.0.hash(state);
+            self.1.hash(state);
+        }
+    }
+
+    impl Equivalent<(String, u32)> for NameId<'_> {
+        fn equivalent(&self, key: &(String, u32)) -> bool {
+            self.0 == key.0 && self.1 == key.1
+        }
+    }
+
+    #[test]
+    fn test_composite_key_lookups() {
+        let mut table = ElasticHashTable::deterministic(256, 0.1);
+        for i in 0..150u32 {
+            table.insert((format!("user{}", i % 10), i), i).expect("Insertion failed");
+        }
+        assert_eq!(table.get(&NameId("user3", 13)), Some(&13));
+        assert_eq!(table.get(&NameId("user3", 14)), None);
+        assert_eq!(table.get_key_value(&NameId("user4", 24)), Some((&("user4".to_string(), 24), &24)));
+        assert_eq!(table.modify(&NameId("user5", 5), |v| *v *= 2), Some(()));
+        assert_eq!(table.get(&("user5".to_string(), 5)), Some(&10));
+        assert_eq!(table.remove(&NameId("user6", 16)), Some(16));
+        assert_eq!(table.get(&NameId("user6", 16)), None);
+
+        let views = [NameId("user7", 7), NameId("user7", 8)];
+        let prepared = table.prepare(&views);
+        assert_eq!(table.get_many_prepared(&prepared), Ok(vec![Some(&7), None]));
+    }
+
+    #[test]
+    fn test_borrowed_str_still_works() {
+        let mut table: ElasticHashTable<String, u32> = ElasticHashTable::deterministic(16, 0.1);
+        table.insert("a".to_string(), 1).expect("Insertion failed");
+        assert_eq!(table.get("a"), Some(&1));
+        assert_eq!(table.get_key("a"), Some(&"a".to_string()));
+        assert_eq!(table.remove("a"), Some(1));
+    }
+}
@@ -0,0 +1,303 @@
+//! the stable hash function used by ElasticHashTable
+//!
+//! Unlike std's DefaultHasher, its output is fixed forever for a given key and seed,
+//! on every platform, so external code (routers, sharding, snapshots) can compute the same hashes.
+//!
+//! Algorithm: FNV-1a over the bytes fed by the key's Hash impl, starting from the FNV offset basis
+//! xor seed, with the result passed through the splitmix64 finalizer.
+//! Integers are fed little-endian and usize/isize as 64-bit values, so 32-bit wasm and 64-bit hosts agree.
+
+use std::hash::{BuildHasher, Hash, Hasher};
+use std::marker::PhantomData;
+
+const FNV_OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+const FNV_PRIME: u64 = 0x0000_0100_0000_01b3;
+
+/// hash key with the table's stable algorithm
+/// a table built with `ElasticHashTable::with_seed(.., seed)` satisfies `table.hash_key(k) == hash_with_seed(k, seed)`
+pub fn hash_with_seed<Q: Hash + ?Sized>(key: &Q, seed: u64) -> u64 {
+    let mut hasher = StableHasher::new(seed);
+    key.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// a fresh seed from the platform's random source
+/// on wasm32-unknown-unknown that is crypto.getRandomValues, through getrandom's wasm_js backend,
+/// which the wasm feature turns on
+#[cfg(not(all(target_arch = "wasm32", any(target_os = "unknown", target_os = "none"), not(feature = "wasm"))))]
+pub(crate) fn random_seed() -> u64 {
+    getrandom::u64().expect("OS random source unavailable")
+}
+
+/// wasm32-unknown-unknown without the wasm feature has no random source to ask, so every table
+/// starts from the fixed DEFAULT_SEED; pass a seed of your own through with_seed instead
+#[cfg(all(target_arch = "wasm32", any(target_os = "unknown", target_os = "none"), not(feature = "wasm")))]
+pub(crate) fn random_seed() -> u64 {
+    crate::elastic::table::DEFAULT_SEED
+}
+
+/// splitmix64 finalizer: spreads every input bit over the whole output
+pub(crate) fn mix64(mut z: u64) -> u64 {
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+pub(crate) struct StableHasher {
+    state: u64,
+}
+
+impl StableHasher {
+    pub(crate) fn new(seed: u64) -> Self {
+        StableHasher {
+            state: FNV_OFFSET_BASIS ^ seed,
+        }
+    }
+}
+
+impl Hasher for StableHasher {
+    fn finish(&self) -> u64 {
+        mix64(self.state)
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for &b in bytes {
+            self.state ^= b as u64;
+            self.state = self.state.wrapping_mul(FNV_PRIME);
+        }
+    }
+
+    fn write_u16(&mut self, i: u16) {
+        self.write(&i.to_le_bytes());
+    }
+
+    fn write_u32(&mut self, i: u32) {
+        self.write(&i.to_le_bytes());
+    }
+
+    fn write_u64(&mut self, i: u64) {
+        self.write(&i.to_le_bytes());
+    }
+
+    fn write_u128(&mut self, i: u128) {
+        self.write(&i.to_le_bytes());
+    }
+
+    fn write_usize(&mut self, i: usize) {
+        self.write_u64(i as u64);
+    }
+
+    fn write_i16(&mut self, i: i16) {
+        self.write_u16(i as u16);
+    }
+
+    fn write_i32(&mut self, i: i32) {
+        self.write_u32(i as u32);
+    }
+
+    fn write_i64(&mut self, i: i64) {
+        self.write_u64(i as u64);
+    }
+
+    fn write_i128(&mut self, i: i128) {
+        self.write_u128(i as u128);
+    }
+
+    fn write_isize(&mut self, i: isize) {
+        self.write_u64(i as i64 as u64);
+    }
+}
+
+/// how a table turns keys into hashes: the hasher type parameter of ElasticHashTable
+pub trait TableHasher {
+    /// name reported by ElasticHashTable::hasher_id
+    const ID: &'static str;
+
+    fn hash_one<Q: Hash + ?Sized>(key: &Q, seed: u64) -> u64;
+}
+
+/// the default hasher: hash_with_seed, stable across versions and platforms
+#[derive(Clone, Copy, Debug, Default)]
+pub struct StableHash;
+
+impl TableHasher for StableHash {
+    const ID: &'static str = "fnv1a-splitmix64";
+
+    fn hash_one<Q: Hash + ?Sized>(key: &Q, seed: u64) -> u64 {
+        hash_with_seed(key, seed)
+    }
+}
+
+/// for integer keys: an integer costs one xor and one multiply instead of a loop over its bytes;
+/// the table mixes every hash again per level, so that is enough spread. other writes fall back to FNV-1a.
+/// unlike StableHash, its values are not a stable contract
+#[derive(Clone, Copy, Debug, Default)]
+pub struct IdentityHash;
+
+impl TableHasher for IdentityHash {
+    const ID: &'static str = "identity-multiply";
+
+    fn hash_one<Q: Hash + ?Sized>(key: &Q, seed: u64) -> u64 {
+        let mut hasher = IdentityHasher { state: seed };
+        key.hash(&mut hasher);
+        hasher.finish()
+    }
+}
+
+/// any std BuildHasher, e.g. `BuildHasherDefault<FxHasher>` or ahash's, as a table hasher:
+/// each key is hashed by a hasher from B::default() that is first fed the table's seed, so the seed
+/// still decides the layout. B::default() must build the same hasher every time, which rules out
+/// RandomState; the seed is what keeps untrusted keys from predicting slots
+pub struct FromBuildHasher<B>(PhantomData<fn() -> B>);
+
+impl<B: BuildHasher + Default> TableHasher for FromBuildHasher<B> {
+    const ID: &'static str = "build-hasher";
+
+    fn hash_one<Q: Hash + ?Sized>(key: &Q, seed: u64) -> u64 {
+        let mut hasher = B::default().build_hasher();
+        hasher.write_u64(seed);
+        key.hash(&mut hasher);
+        hasher.finish()
+    }
+}
+
+/// 2^64 / golden ratio, odd, so multiplying by it is a bijection
+const GOLDEN: u64 = 0x9e37_79b9_7f4a_7c15;
+
+struct IdentityHasher {
+    state: u64,
+}
+
+impl Hasher for IdentityHasher {
+    fn finish(&self) -> u64 {
+        self.state
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for &b in bytes {
+            self.state ^= b as u64;
+            self.state = self.state.wrapping_mul(FNV_PRIME);
+        }
+    }
+
+    fn write_u8(&mut self, i: u8) {
+        self.write_u64(i as u64);
+    }
+
+    fn write_u16(&mut self, i: u16) {
+        self.write_u64(i as u64);
+    }
+
+    fn write_u32(&mut self, i: u32) {
+        self.write_u64(i as u64);
+    }
+
+    fn write_u64(&mut self, i: u64) {
+        self.state = (self.state ^ i).wrapping_mul(GOLDEN);
+    }
+
+    fn write_usize(&mut self, i: usize) {
+        self.write_u64(i as u64);
+    }
+
+    fn write_i32(&mut self, i: i32) {
+        self.write_u64(i as u32 as u64);
+    }
+
+    fn write_i64(&mut self, i: i64) {
+        self.write_u64(i as u64);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::hash_with_seed;
+
+    #[test]
+    fn test_golden_values() {
+        // these values are part of the public contract; never update them
+        assert_eq!(hash_with_seed(&0u64, 0), 0x813f_0174_a236_7c13);
+        assert_eq!(hash_with_seed(&42u64, 0), 0xe15f_07fe_f55b_9454);
+        assert_eq!(hash_with_seed(&42u64, 7), 0x78ff_5902_1474_7c83);
+        assert_eq!(hash_with_seed("hello", 0), 0x91d7_ffaf_9cef_40d2);
+        assert_eq!(hash_with_seed("hello", 0xdead_beef), 0xcb91_ddf4_3c5e_21ab);
+    }
+
+    #[test]
+    fn test_width_independence() {
+        // usize is hashed as u64, and String hashes like str
+        assert_eq!(hash_with_seed(&42usize, 0), hash_with_seed(&42u64, 0));
+        assert_eq!(hash_with_seed(&String::from("hello"), 0), hash_with_seed("hello", 0));
+    }
+
+    #[test]
+    fn test_identity_hash_is_injective_on_integers() {
+        use super::{IdentityHash, TableHasher};
+
+        let hashes: std::collections::HashSet<u64> = (0..10_000u64).map(|i| IdentityHash::hash_one(&i, 7)).collect();
+        assert_eq!(hashes.len(), 10_000);
+        assert_eq!(IdentityHash::hash_one(&42usize, 7), IdentityHash::hash_one(&42u64, 7));
+        assert_ne!(IdentityHash::hash_one(&42u64, 7), IdentityHash::hash_one(&42u64, 8));
+    }
+
+    /// a seedless FNV-1a, standing in for an external hasher crate
+    #[derive(Default)]
+    struct Fnv(u64);
+
+    impl std::hash::Hasher for Fnv {
+        fn finish(&self) -> u64 {
+            self.0
+        }
+
+        fn write(&mut self, bytes: &[u8]) {
+            for &b in bytes {
+                self.0 = (self.0 ^ b as u64).wrapping_mul(super::FNV_PRIME);
+            }
+        }
+    }
+
+    type FnvHash = super::FromBuildHasher<std::hash::BuildHasherDefault<Fnv>>;
+
+    #[test]
+    fn test_build_hasher_tables_behave_alike() {
+        use crate::ElasticHashTable;
+
+        let mut custom = ElasticHashTable::<String, u32, FnvHash>::with_hasher(1024, 0.1, 5);
+        let mut stable = ElasticHashTable::<String, u32>::with_seed(1024, 0.1, 5);
+        assert_eq!(custom.hasher_id(), "build-hasher");
+        let mut state = 5u64;
+        for op in 0..5000u32 {
+            state = state.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+            let key = format!("k{}", (state >> 33) % 1200);
+            if (state >> 20).is_multiple_of(3) {
+                assert_eq!(custom.remove(&key), stable.remove(&key));
+            } else {
+                assert_eq!(custom.insert(key.clone(), op).is_ok(), stable.insert(key, op).is_ok());
+            }
+        }
+        for i in 0..1300 {
+            let key = format!("k{}", i);
+            assert_eq!(custom.get(&key), stable.get(&key), "{}", key);
+        }
+        custom.check_invariants().unwrap();
+    }
+
+    #[test]
+    fn test_build_hasher_seed_moves_keys() {
+        use super::TableHasher;
+        use crate::ElasticHashTable;
+
+        let build = |seed| {
+            let mut table = ElasticHashTable::<u64, u64, FnvHash>::with_hasher(1024, 0.1, seed);
+            for key in 0..100 {
+                table.insert(key, key).expect("Insertion failed");
+            }
+            table
+        };
+        let (a, b) = (build(1), build(2));
+        let moved = (0..100u64).filter(|key| a.find(key) != b.find(key)).count();
+        assert!(moved > 90, "only {} of 100 keys moved", moved);
+        assert_eq!(build(1).find(&7), a.find(&7));
+        assert_ne!(FnvHash::hash_one(&7u64, 1), FnvHash::hash_one(&7u64, 2));
+    }
+}
@@ -0,0 +1,80 @@
+//! integer-keyed tables: u64 keys hashed with IdentityHash, one multiply instead of a byte loop
+//!
+//! The JS binding, JsElasticHashTableU64, is in bindings::int_table.
+
+use crate::{ElasticHashTable, IdentityHash};
+
+/// a table keyed by integer IDs
+pub type ElasticIntTable<V> = ElasticHashTable<u64, V, IdentityHash>;
+
+#[cfg(test)]
+mod tests {
+    use super::ElasticIntTable;
+    use crate::ElasticHashTable;
+
+    #[test]
+    fn test_int_table_matches_stable_table() {
+        let mut ints: ElasticIntTable<u64> = ElasticIntTable::with_hasher(4096, 0.1, 3);
+        let mut stable: ElasticHashTable<u64, u64> = ElasticHashTable::with_seed(4096, 0.1, 3);
+        // dense IDs, the case the identity hash is for
+        for id in 0..3000u64 {
+            ints.insert(id, id * 2).expect("Insertion failed");
+            stable.insert(id, id * 2).expect("Insertion failed");
+        }
+        for id in (0..3000u64).step_by(7) {
+            assert_eq!(ints.remove(&id), stable.remove(&id));
+        }
+        for id in 0..3500u64 {
+            assert_eq!(ints.get(&id), stable.get(&id));
+        }
+        assert_eq!(ints.hasher_id(), "identity-multiply");
+        ints.check_invariants().unwrap();
+
+        ints.try_reserve(5000).expect("reserve failed");
+        assert_eq!(ints.get(&2999), Some(&5998));
+    }
+
+    /// run with `cargo test --release -- --ignored --nocapture bench_int_keys`
+    #[test]
+    #[ignore]
+    fn bench_int_keys() {
+        let n = 1_000_000u64;
+        let capacity = 2 * n as usize;
+
+        let mut strings: ElasticHashTable<String, u64> = ElasticHashTable::with_seed(capacity, 0.1, 1);
+        let start = std::time::Instant::now();
+        for i in 0..n {
+            strings.insert(i.to_string(), i).expect("Insertion failed");
+        }
+        let insert = start.elapsed();
+        let start = std::time::Instant::now();
+        for i in 0..n {
+            assert!(strings.get(i.to_string().as_str()).is_some());
+        }
+        println!("String keys: insert {:?}, lookup {:?}", insert / n as u32, start.elapsed() / n as u32);
+
+        let mut stable: ElasticHashTable<u64, u64> = ElasticHashTable::with_seed(capacity, 0.1, 1);
+        let start = std::time::Instant::now();
+        for i in 0..n {
+            stable.insert(i, i).expect("Insertion failed");
+        }
+        let insert = start.elapsed();
+        let start = std::time::Instant::now();
+        for i in 0..n {
+            assert!(stable.get(&i).is_some());
+        }
+        println!("u64 keys, StableHash: insert {:?}, lookup {:?}", insert / n as u32, start.elapsed() / n as u32);
+
+        let mut ints: ElasticIntTable<u64> = ElasticIntTable::with_hasher(capacity, 0.1, 1);
+        let start = std::time::Instant::now();
+        for i in 0..n {
+            ints.insert(i, i).expect("Insertion failed");
+        }
+        let insert = start.elapsed();
+        let start = std::time::Instant::now();
+        for i in 0..n {
+            assert!(ints.get(&i).is_some());
+        }
+        println!("u64 keys, IdentityHash: insert {:?}, lookup {:?}", insert / n as u32, start.elapsed() / n as u32);
+    }
+}
@@ -0,0 +1,271 @@
+//! just enough JSON for the flat config objects passed in from JS, and the nested snapshots of
+//! JsElasticHashTable::fromSnapshot
+
+use std::iter::Peekable;
+use std::str::{Chars, FromStr};
+
+/// split a flat JSON object such as `{"seed": 7, "keyPattern": "a-z"}` into (name, value) pairs
+/// string values are unescaped, other values (numbers, true/false/null) are returned as written;
+/// nested objects and arrays are not supported
+pub(crate) fn flat_object(json: &str) -> Result<Vec<(String, String)>, String> {
+    let mut chars = json.trim().chars().peekable();
+    if chars.next() != Some('{') {
+        return Err("expected a JSON object".into());
+    }
+    let mut fields = Vec::new();
+    skip_whitespace(&mut chars);
+    if chars.peek() == Some(&'}') {
+        chars.next();
+    } else {
+        loop {
+            skip_whitespace(&mut chars);
+            if chars.next() != Some('"') {
+                return Err("expected a field name".into());
+            }
+            let name = string(&mut chars)?;
+            skip_whitespace(&mut chars);
+            if chars.next() != Some(':') {
+                return Err(format!("expected ':' after {}", name));
+            }
+            skip_whitespace(&mut chars);
+            let value = if chars.peek() == Some(&'"') {
+                chars.next();
+                string(&mut chars)?
+            } else {
+                let mut raw = String::new();
+                while let Some(&c) = chars.peek() {
+                    if c == ',' || c == '}' || c.is_whitespace() {
+                        break;
+                    }
+                    if c == '{' || c == '[' {
+                        return Err(format!("nested value for {} is not supported", name));
+                    }
+                    raw.push(c);
+                    chars.next();
+                }
+                if raw.is_empty() {
+                    return Err(format!("missing value for {}", name));
+                }
+                raw
+            };
+            fields.push((name, value));
+            skip_whitespace(&mut chars);
+            match chars.next() {
+                Some(',') => continue,
+                Some('}') => break,
+                _ => return Err("expected ',' or '}'".into()),
+            }
+        }
+    }
+    if chars.next().is_some() {
+        return Err("trailing characters after the object".into());
+    }
+    Ok(fields)
+}
+
+/// a parsed JSON document; numbers are kept as written
+#[cfg_attr(not(feature = "wasm"), allow(dead_code))]
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) enum Value {
+    Null,
+    Bool(bool),
+    Number(String),
+    String(String),
+    Array(Vec<Value>),
+    Object(Vec<(String, Value)>),
+}
+
+/// parse a whole JSON document, nested objects and arrays included
+#[cfg_attr(not(feature = "wasm"), allow(dead_code))]
+pub(crate) fn value(json: &str) -> Result<Value, String> {
+    let mut chars = json.chars().peekable();
+    let value = nested(&mut chars)?;
+    skip_whitespace(&mut chars);
+    if chars.next().is_some() {
+        return Err("trailing characters after the value".into());
+    }
+    Ok(value)
+}
+
+fn nested(chars: &mut Peekable<Chars<'_>>) -> Result<Value, String> {
+    skip_whitespace(chars);
+    match chars.next().ok_or("unexpected end of input")? {
+        '"' => string(chars).map(Value::String),
+        '[' => {
+            let mut items = Vec::new();
+            skip_whitespace(chars);
+            if chars.peek() == Some(&']') {
+                chars.next();
+                return Ok(Value::Array(items));
+            }
+            loop {
+                items.push(nested(chars)?);
+                skip_whitespace(chars);
+                match chars.next() {
+                    Some(',') => continue,
+                    Some(']') => return Ok(Value::Array(items)),
+                    _ => return Err("expected ',' or ']'".into()),
+                }
+            }
+        }
+        '{' => {
+            let mut fields = Vec::new();
+            skip_whitespace(chars);
+            if chars.peek() == Some(&'}') {
+                chars.next();
+                return Ok(Value::Object(fields));
+            }
+            loop {
+                skip_whitespace(chars);
+                if chars.next() != Some('"') {
+                    return Err("expected a field name".into());
+                }
+                let name = string(chars)?;
+                skip_whitespace(chars);
+                if chars.next() != Some(':') {
+                    return Err(format!("expected ':' after {}", name));
+                }
+                fields.push((name, nested(chars)?));
+                skip_whitespace(chars);
+                match chars.next() {
+                    Some(',') => continue,
+                    Some('}') => return Ok(Value::Object(fields)),
+                    _ => return Err("expected ',' or '}'".into()),
+                }
+            }
+        }
+        c => {
+            let mut raw = c.to_string();
+            while let Some(&c) = chars.peek() {
+                if c == ',' || c == ']' || c == '}' || c.is_whitespace() {
+                    break;
+                }
+                raw.push(c);
+                chars.next();
+            }
+            match raw.as_str() {
+                "null" => Ok(Value::Null),
+                "true" => Ok(Value::Bool(true)),
+                "false" => Ok(Value::Bool(false)),
+                _ if raw.parse::<f64>().is_ok() => Ok(Value::Number(raw)),
+                _ => Err(format!("invalid value {}", raw)),
+            }
+        }
+    }
+}
+
+/// parse the raw value of field name
+pub(crate) fn parse<T: FromStr>(name: &str, value: &str) -> Result<T, String> {
+    value
+        .parse()
+        .map_err(|_| format!("invalid value for {}: {}", name, value))
+}
+
+/// quote and escape s as a JSON string; only the bindings emit strings
+#[cfg_attr(not(feature = "wasm"), allow(dead_code))]
+pub(crate) fn quote(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+fn skip_whitespace(chars: &mut Peekable<Chars<'_>>) {
+    while chars.peek().is_some_and(|c| c.is_whitespace()) {
+        chars.next();
+    }
+}
+
+/// the rest of a string whose opening quote was consumed
+fn string(chars: &mut Peekable<Chars<'_>>) -> Result<String, String> {
+    let mut out = String::new();
+    loop {
+        match chars.next().ok_or("unterminated string")? {
+            '"' => return Ok(out),
+            '\\' => match chars.next().ok_or("unterminated string")? {
+                '"' => out.push('"'),
+                '\\' => out.push('\\'),
+                '/' => out.push('/'),
+                'b' => out.push('\u{8}'),
+                'f' => out.push('\u{c}'),
+                'n' => out.push('\n'),
+                'r' => out.push('\r'),
+                't' => out.push('\t'),
+                'u' => {
+                    let hex: String = chars.by_ref().take(4).collect();
+                    let code = u32::from_str_radix(&hex, 16).map_err(|_| format!("invalid escape \\u{}", hex))?;
+                    // surrogate pairs are not combined
+                    out.push(char::from_u32(code).ok_or(format!("invalid escape \\u{}", hex))?);
+                }
+                c => return Err(format!("invalid escape \\{}", c)),
+            },
+            c => out.push(c),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{flat_object, quote, value, Value};
+
+    #[test]
+    fn test_flat_object() {
+        let fields = flat_object(r#" { "seed": 7, "pattern" : "a-z,:\"A", "on": true } "#).unwrap();
+        assert_eq!(
+            fields,
+            vec![
+                ("seed".to_string(), "7".to_string()),
+                ("pattern".to_string(), "a-z,:\"A".to_string()),
+                ("on".to_string(), "true".to_string()),
+            ]
+        );
+        assert_eq!(flat_object("{}").unwrap(), vec![]);
+        assert!(flat_object("[1]").is_err());
+        assert!(flat_object(r#"{"a": {"b": 1}}"#).is_err());
+        assert!(flat_object(r#"{"a": 1"#).is_err());
+        assert!(flat_object(r#"{"a": "x}"#).is_err());
+        assert!(flat_object(r#"{"a": 1} x"#).is_err());
+    }
+
+    #[test]
+    fn test_quote_roundtrip() {
+        let s = "tab\t \"quoted\" back\\slash \u{1}";
+        let json = format!("{{\"s\":{}}}", quote(s));
+        assert_eq!(flat_object(&json).unwrap(), vec![("s".to_string(), s.to_string())]);
+    }
+
+    #[test]
+    fn test_nested_value() {
+        let json = r#" {"a": [1, -2.5e3, "x\"y"], "b": {"c": null, "d": [true, false, []]}, "e": {}} "#;
+        let number = |raw: &str| Value::Number(raw.into());
+        let expected = Value::Object(vec![
+            ("a".into(), Value::Array(vec![number("1"), number("-2.5e3"), Value::String("x\"y".into())])),
+            (
+                "b".into(),
+                Value::Object(vec![
+                    ("c".into(), Value::Null),
+                    ("d".into(), Value::Array(vec![Value::Bool(true), Value::Bool(false), Value::Array(vec![])])),
+                ]),
+            ),
+            ("e".into(), Value::Object(vec![])),
+        ]);
+        assert_eq!(value(json).unwrap(), expected);
+        assert!(value("[1,]").is_err());
+        assert!(value("[1 2]").is_err());
+        assert!(value(r#"{"a" 1}"#).is_err());
+        assert!(value("nul").is_err());
+        assert!(value("[1] x").is_err());
+        assert!(value("").is_err());
+    }
+}
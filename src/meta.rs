@@ -0,0 +1,165 @@
+//! optional per-entry metadata: a u64 kept beside every slot, holding an insert timestamp or a caller tag
+//!
+//! Tables start without it and pay nothing; with_metadata adds one u64 per slot.
+//!
+//! ```ignore
+//! let mut table = ElasticHashTable::new(1024, 0.1).with_metadata(Some(Arc::new(SystemClock)));
+//! table.insert(key, value)?;
+//! let (value, inserted_at) = table.get_with_meta(&key).unwrap();
+//! ```
+
+use std::hash::Hash;
+use std::sync::Arc;
+
+use crate::elastic::table::Upserted;
+use crate::{ElasticHashTable, Equivalent, InsertError, TableHasher};
+
+/// source of the timestamps recorded when metadata is on
+pub trait Clock {
+    fn now(&self) -> u64;
+}
+
+/// seconds since the Unix epoch; not available on wasm32-unknown-unknown, where it panics
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> u64 {
+        std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map_or(0, |d| d.as_secs())
+    }
+}
+
+/// the clock a table reads; Send and Sync so the table can be too
+pub(crate) type SharedClock = Arc<dyn Clock + Send + Sync>;
+
+/// one u64 per slot, laid out like the levels
+#[derive(Clone)]
+pub(crate) struct Metadata {
+    pub(crate) tags: Vec<Vec<u64>>,
+    pub(crate) clock: Option<SharedClock>,
+}
+
+impl Metadata {
+    pub(crate) fn new(sizes: impl IntoIterator<Item = usize>, clock: Option<SharedClock>) -> Self {
+        Metadata {
+            tags: sizes.into_iter().map(|size| vec![0; size]).collect(),
+            clock,
+        }
+    }
+
+    /// the value recorded for a fresh insert or a replacement: the clock's time, or 0 without a clock
+    pub(crate) fn stamp(&self) -> u64 {
+        self.clock.as_ref().map_or(0, |clock| clock.now())
+    }
+}
+
+impl<K, V, S> ElasticHashTable<K, V, S>
+where
+    K: Hash + Eq,
+    S: TableHasher,
+{
+    /// keep a u64 beside every entry: the clock's time at insert and replacement, or 0 without a clock,
+    /// unless set by insert_tagged; entries already in the table get 0
+    pub fn with_metadata(mut self, clock: Option<Arc<dyn Clock + Send + Sync>>) -> Self {
+        self.meta = Some(Metadata::new(self.levels.iter().map(Vec::len), clock));
+        self
+    }
+
+    pub fn has_metadata(&self) -> bool {
+        self.meta.is_some()
+    }
+
+    /// insert with tag as the entry's metadata instead of the clock's time;
+    /// the tag is dropped when the table keeps no metadata
+    pub fn insert_tagged(&mut self, key: K, value: V, tag: u64) -> Result<(usize, usize), InsertError<K, V>> {
+        self.upsert_tagged(key, value, tag).map(|(pos, _)| pos)
+    }
+
+    /// insert_tagged, also returning the value a present key held
+    pub(crate) fn upsert_tagged(&mut self, key: K, value: V, tag: u64) -> Result<Upserted<V>, InsertError<K, V>> {
+        let ((level, idx), old) = self
+            .upsert_with(key, || value)
+            .map_err(|err| err.map_value(|make| make()))?;
+        if let Some(meta) = &mut self.meta {
+            meta.tags[level][idx] = tag;
+        }
+        Ok(((level, idx), old))
+    }
+
+    /// the value under key with its metadata; None when key is absent or the table keeps no metadata
+    pub fn get_with_meta<Q>(&self, key: &Q) -> Option<(&V, u64)>
+    where
+        Q: ?Sized + Hash + Equivalent<K>,
+    {
+        let meta = self.meta.as_ref()?;
+        let pos = self.find(key)?;
+        Some((self.value_at(Some(pos))?, meta.tags[pos.0][pos.1]))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicU64, Ordering};
+    use std::sync::Arc;
+
+    use super::Clock;
+    use crate::ElasticHashTable;
+
+    /// advances by one on every reading
+    #[derive(Default)]
+    struct Ticks(AtomicU64);
+
+    impl Clock for Ticks {
+        fn now(&self) -> u64 {
+            self.0.fetch_add(1, Ordering::Relaxed) + 1
+        }
+    }
+
+    #[test]
+    fn test_tags_round_trip() {
+        let mut table = ElasticHashTable::with_seed(64, 0.1, 1).with_metadata(None);
+        for i in 0..40u64 {
+            table.insert_tagged(i, i * 10, 1000 + i).expect("Insertion failed");
+        }
+        table.insert(40, 400).expect("Insertion failed");
+        assert_eq!(table.get_with_meta(&7), Some((&70, 1007)));
+        assert_eq!(table.get_with_meta(&40), Some((&400, 0)));
+        assert_eq!(table.get_with_meta(&41), None);
+
+        // growth moves the tags with their entries
+        table.try_reserve(200).expect("reserve failed");
+        for i in 0..40u64 {
+            assert_eq!(table.get_with_meta(&i), Some((&(i * 10), 1000 + i)));
+        }
+
+        // removal clears the slot's tag, so a later insert there does not inherit it
+        let (level, idx) = table.find(&7).unwrap();
+        table.remove(&7);
+        assert_eq!(table.meta.as_ref().unwrap().tags[level][idx], 0);
+
+        let copy = table.clone();
+        assert_eq!(copy.get_with_meta(&8), Some((&80, 1008)));
+    }
+
+    #[test]
+    fn test_clock_stamps_inserts_and_replacements() {
+        let clock = Arc::new(Ticks::default());
+        let mut table = ElasticHashTable::deterministic(64, 0.1).with_metadata(Some(clock.clone()));
+        table.insert("a", 1).expect("Insertion failed");
+        table.insert("b", 2).expect("Insertion failed");
+        assert_eq!(table.get_with_meta("a"), Some((&1, 1)));
+        table.modify("a", |v| *v += 1);
+        assert_eq!(table.get_with_meta("a"), Some((&2, 3)));
+        assert_eq!(table.get_with_meta("b"), Some((&2, 2)));
+    }
+
+    #[test]
+    fn test_metadata_off() {
+        let mut table = ElasticHashTable::deterministic(64, 0.1);
+        table.insert_tagged(1, 1, 99).expect("Insertion failed");
+        assert!(!table.has_metadata());
+        assert_eq!(table.get_with_meta(&1), None);
+        assert_eq!(table.get(&1), Some(&1));
+    }
+}
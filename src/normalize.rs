@@ -0,0 +1,119 @@
+//! Unicode NFC normalization for string keys, enabled by the `normalize` feature
+//!
+//! Native tables get it by keying on `NfcString`; the JS binding applies it to every key of a table
+//! created with `{"normalize": "nfc"}`. Plain `String` keys are stored as given.
+//!
+//! ```ignore
+//! let mut table = ElasticHashTable::new(64, 0.1);
+//! table.insert(NfcString::from("cafe\u{301}"), 1)?;
+//! assert_eq!(table.get(&NfcString::from("caf\u{e9}")), Some(&1));
+//! assert_eq!(table.keys().next().unwrap().as_str(), "caf\u{e9}");
+//! ```
+
+use std::borrow::Cow;
+use std::fmt;
+
+use unicode_normalization::{is_nfc_quick, IsNormalized, UnicodeNormalization};
+
+/// s in Normalization Form C
+pub(crate) fn nfc(s: &str) -> Cow<'_, str> {
+    if is_nfc_quick(s.chars()) == IsNormalized::Yes {
+        return Cow::Borrowed(s);
+    }
+    let composed: String = s.nfc().collect();
+    if composed == s {
+        Cow::Borrowed(s)
+    } else {
+        Cow::Owned(composed)
+    }
+}
+
+/// a String key stored in NFC, so composed and decomposed spellings of it are the same key
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct NfcString(String);
+
+impl NfcString {
+    pub fn new(key: String) -> Self {
+        match nfc(&key) {
+            Cow::Borrowed(_) => NfcString(key),
+            Cow::Owned(composed) => NfcString(composed),
+        }
+    }
+
+    /// the key in NFC
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    pub fn into_inner(self) -> String {
+        self.0
+    }
+}
+
+impl fmt::Display for NfcString {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl From<String> for NfcString {
+    fn from(key: String) -> Self {
+        NfcString::new(key)
+    }
+}
+
+impl From<&str> for NfcString {
+    fn from(key: &str) -> Self {
+        NfcString(nfc(key).into_owned())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{nfc, NfcString};
+    use crate::{hash_with_seed, ElasticHashTable};
+
+    #[test]
+    fn test_nfc() {
+        assert_eq!(nfc("e\u{301}"), "\u{e9}");
+        assert_eq!(nfc("\u{e9}"), "\u{e9}");
+        assert!(matches!(nfc("plain ascii"), std::borrow::Cow::Borrowed(_)));
+        // marks are reordered before composing: dot below (220) sorts before dot above (230)
+        assert_eq!(nfc("\u{1e0a}\u{323}"), "\u{1e0c}\u{307}");
+        assert_eq!(nfc("D\u{307}\u{323}"), "\u{1e0c}\u{307}");
+        // singletons and composition exclusions stay decomposed
+        assert_eq!(nfc("\u{212b}"), "\u{c5}");
+        assert_eq!(nfc("\u{2126}"), "\u{3a9}");
+        assert_eq!(nfc("\u{f73}"), "\u{f71}\u{f72}");
+        // Hangul jamo compose algorithmically
+        assert_eq!(nfc("\u{1100}\u{1161}\u{11a8}"), "\u{ac01}");
+        assert_eq!(nfc("\u{ac01}"), "\u{ac01}");
+    }
+
+    #[test]
+    fn test_nfc_string_eq_and_hash() {
+        assert_eq!(NfcString::from("cafe\u{301}"), NfcString::from("caf\u{e9}"));
+        assert_eq!(NfcString::from("cafe\u{301}".to_string()).as_str(), "caf\u{e9}");
+        assert_ne!(NfcString::from("cafe"), NfcString::from("caf\u{e9}"));
+        assert_eq!(hash_with_seed(&NfcString::from("cafe\u{301}"), 3), hash_with_seed("caf\u{e9}", 3));
+    }
+
+    #[test]
+    fn test_nfc_string_table() {
+        let mut table = ElasticHashTable::deterministic(64, 0.1);
+        table.insert(NfcString::from("cafe\u{301}"), 1).expect("Insertion failed");
+        table.insert_or_update(NfcString::from("caf\u{e9}"), 2).expect("Insertion failed");
+        assert_eq!(table.len(), 1);
+        assert_eq!(table.get(&NfcString::from("cafe\u{301}")), Some(&2));
+        assert!(table.contains_key(&NfcString::from("caf\u{e9}")));
+        assert_eq!(table.keys().next().map(NfcString::as_str), Some("caf\u{e9}"));
+        assert_eq!(table.remove(&NfcString::from("cafe\u{301}")), Some(2));
+        assert!(table.is_empty());
+
+        // a plain String table keeps the two spellings apart
+        let mut plain = ElasticHashTable::deterministic(64, 0.1);
+        plain.insert("cafe\u{301}".to_string(), 1).expect("Insertion failed");
+        assert_eq!(plain.get("caf\u{e9}"), None);
+        assert_eq!(plain.get("cafe\u{301}"), Some(&1));
+    }
+}
@@ -0,0 +1,120 @@
+//! mutation observers: keep a secondary structure in sync with an ElasticHashTable
+//!
+//! Every callback runs after the change is made. Values changed through references the table hands out
+//! (raw entries, extract_if's predicate) are not reported.
+
+use crate::SlotId;
+
+/// receives the mutations of a table, see ElasticHashTable::set_observer
+/// every method defaults to doing nothing
+pub trait TableObserver<K, V> {
+    /// key was inserted with value at slot
+    fn on_insert(&mut self, _key: &K, _value: &V, _slot: SlotId) {}
+
+    /// the value under key was replaced, old by new (insert of a present key, OccupiedEntry::insert)
+    fn on_replace(&mut self, _key: &K, _old: &V, _new: &V) {}
+
+    /// the value under key was changed in place to value (modify, modify_or_insert, Entry::and_modify);
+    /// the value from before is not kept, so V need not be Clone
+    fn on_modify(&mut self, _key: &K, _value: &V) {}
+
+    /// key was removed with value (remove, pop, extract_if, split_off, clear, drain, raw entries)
+    fn on_remove(&mut self, _key: &K, _value: &V) {}
+
+    /// every entry was moved into new levels (try_reserve, rehash_with_seed, rebalance),
+    /// so previously reported slots are stale; the capacities differ when the table grew
+    fn on_rebuild(&mut self, _old_capacity: usize, _new_capacity: usize) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use super::TableObserver;
+    use crate::{ElasticHashTable, SlotId};
+
+    #[derive(Clone, Default)]
+    struct Recorder(Arc<Mutex<Vec<String>>>);
+
+    impl TableObserver<u32, u32> for Recorder {
+        fn on_insert(&mut self, key: &u32, value: &u32, _slot: SlotId) {
+            self.0.lock().unwrap().push(format!("insert {}={}", key, value));
+        }
+
+        fn on_replace(&mut self, key: &u32, old: &u32, new: &u32) {
+            self.0.lock().unwrap().push(format!("replace {}: {}->{}", key, old, new));
+        }
+
+        fn on_modify(&mut self, key: &u32, value: &u32) {
+            self.0.lock().unwrap().push(format!("modify {}={}", key, value));
+        }
+
+        fn on_remove(&mut self, key: &u32, value: &u32) {
+            self.0.lock().unwrap().push(format!("remove {}={}", key, value));
+        }
+
+        fn on_rebuild(&mut self, old_capacity: usize, new_capacity: usize) {
+            self.0.lock().unwrap().push(format!("rebuild {}->{}", old_capacity, new_capacity));
+        }
+    }
+
+    #[test]
+    fn test_event_stream() {
+        let recorder = Recorder::default();
+        let mut table = ElasticHashTable::deterministic(8, 0.1);
+        table.set_observer(Box::new(recorder.clone()));
+
+        table.insert(1, 10).expect("Insertion failed");
+        table.insert(2, 20).expect("Insertion failed");
+        table.modify(&1, |v| *v += 1);
+        table.modify_or_insert(3, 30, |v| *v *= 2).expect("Insertion failed");
+        assert_eq!(table.remove(&2), Some(20));
+        assert_eq!(table.remove(&2), None);
+        table.try_reserve(100).expect("reserve failed");
+        let capacity = table.capacity();
+        for i in 4..8 {
+            table.insert(i, i * 10).expect("Insertion failed");
+        }
+        // batch removals report every entry they take out
+        let odd: Vec<_> = table.extract_if(|k, _| k % 2 == 1).collect();
+        assert_eq!(odd.len(), 4);
+        let split = table.split_off(|k, _| *k < 5);
+        assert_eq!(split.get(&4), Some(&40));
+
+        let events = recorder.0.lock().unwrap();
+        let mut extracted = events[11..15].to_vec();
+        extracted.sort();
+        assert_eq!(
+            events[..11],
+            [
+                "insert 1=10",
+                "insert 2=20",
+                "modify 1=11",
+                "insert 3=30",
+                "modify 3=60",
+                "remove 2=20",
+                &format!("rebuild 8->{}", capacity),
+                "insert 4=40",
+                "insert 5=50",
+                "insert 6=60",
+                "insert 7=70",
+            ]
+        );
+        assert_eq!(extracted, ["remove 1=11", "remove 3=60", "remove 5=50", "remove 7=70"]);
+        assert_eq!(events[15..], ["remove 4=40"]);
+    }
+
+    #[test]
+    fn test_observer_is_not_cloned() {
+        let recorder = Recorder::default();
+        let mut table = ElasticHashTable::deterministic(8, 0.1);
+        table.set_observer(Box::new(recorder.clone()));
+        let mut copy = table.clone();
+        copy.insert(1, 1).expect("Insertion failed");
+        assert!(recorder.0.lock().unwrap().is_empty());
+
+        assert!(table.take_observer().is_some());
+        table.insert(1, 1).expect("Insertion failed");
+        assert!(recorder.0.lock().unwrap().is_empty());
+    }
+}
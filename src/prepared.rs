@@ -0,0 +1,150 @@
+//! lookups with precomputed hashes: hash a known key set ahead of time, search it later
+//!
+//! ```ignore
+//! let prepared = table.prepare(next_frame_keys.iter().map(String::as_str));
+//! // ... other work ...
+//! let values = table.get_many_prepared(&prepared)?;
+//! ```
+
+use std::fmt;
+use std::hash::Hash;
+
+use crate::{ElasticHashTable, Equivalent, TableHasher};
+
+/// keys with their hashes under one table seed, returned by ElasticHashTable::prepare
+pub struct PreparedKeys<'a, Q: ?Sized> {
+    seed: u64,
+    keys: Vec<(&'a Q, u64)>,
+}
+
+impl<Q: ?Sized> PreparedKeys<'_, Q> {
+    pub fn len(&self) -> usize {
+        self.keys.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.keys.is_empty()
+    }
+
+    /// seed the hashes were computed with
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+}
+
+/// error returned when prepared keys no longer match the table
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PreparedKeysError {
+    /// the table was rehashed (or the keys were prepared for another table) since prepare
+    SeedMismatch { prepared: u64, table: u64 },
+}
+
+impl fmt::Display for PreparedKeysError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PreparedKeysError::SeedMismatch { prepared, table } => write!(
+                f,
+                "keys were prepared for seed {:#x} but the table uses {:#x}",
+                prepared, table
+            ),
+        }
+    }
+}
+
+impl std::error::Error for PreparedKeysError {}
+
+impl<K, V, S> ElasticHashTable<K, V, S>
+where
+    K: Hash + Eq,
+    S: TableHasher,
+{
+    /// hash keys now, for search_prepared and get_many_prepared to skip hashing later
+    /// the hashes stay valid across inserts, removals and growth, but not across a rehash
+    pub fn prepare<'a, Q>(&self, keys: impl IntoIterator<Item = &'a Q>) -> PreparedKeys<'a, Q>
+    where
+        Q: ?Sized + Hash + Equivalent<K> + 'a,
+    {
+        PreparedKeys {
+            seed: self.seed,
+            keys: keys.into_iter().map(|k| (k, S::hash_one(k, self.seed))).collect(),
+        }
+    }
+
+    /// search for the idx-th prepared key; panics if idx is out of range
+    pub fn search_prepared<Q>(&self, prepared: &PreparedKeys<'_, Q>, idx: usize) -> Result<Option<&V>, PreparedKeysError>
+    where
+        Q: ?Sized + Equivalent<K>,
+    {
+        self.check_prepared(prepared)?;
+        let (key, hash) = prepared.keys[idx];
+        Ok(self.value_at(self.probe_hashed(hash, key).0))
+    }
+
+    /// search for every prepared key, in order
+    pub fn get_many_prepared<Q>(&self, prepared: &PreparedKeys<'_, Q>) -> Result<Vec<Option<&V>>, PreparedKeysError>
+    where
+        Q: ?Sized + Equivalent<K>,
+    {
+        self.check_prepared(prepared)?;
+        Ok(prepared
+            .keys
+            .iter()
+            .map(|&(key, hash)| self.value_at(self.probe_hashed(hash, key).0))
+            .collect())
+    }
+
+    fn check_prepared<Q: ?Sized>(&self, prepared: &PreparedKeys<'_, Q>) -> Result<(), PreparedKeysError> {
+        if prepared.seed != self.seed {
+            return Err(PreparedKeysError::SeedMismatch {
+                prepared: prepared.seed,
+                table: self.seed,
+            });
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::PreparedKeysError;
+    use crate::ElasticHashTable;
+
+    #[test]
+    fn test_prepared_lookups_match_search() {
+        let mut table: ElasticHashTable<String, usize> = ElasticHashTable::deterministic(1024, 0.1);
+        for i in 0..900 {
+            table.insert(format!("key{}", i), i).expect("Insertion failed");
+        }
+        for i in (0..900).step_by(3) {
+            table.remove(format!("key{}", i).as_str());
+        }
+        let queries: Vec<String> = (0..1000).map(|i| format!("key{}", i)).collect();
+        let prepared = table.prepare(queries.iter().map(String::as_str));
+        assert_eq!(prepared.len(), 1000);
+
+        let many = table.get_many_prepared(&prepared).unwrap();
+        for (i, q) in queries.iter().enumerate() {
+            assert_eq!(many[i], table.get(q.as_str()));
+            assert_eq!(table.search_prepared(&prepared, i).unwrap(), table.get(q.as_str()));
+        }
+
+        // growth keeps the seed, so the hashes stay valid
+        table.try_reserve(2000).expect("reserve failed");
+        assert_eq!(table.search_prepared(&prepared, 1).unwrap(), Some(&1));
+    }
+
+    #[test]
+    fn test_stale_prepared_keys_are_rejected() {
+        let mut table: ElasticHashTable<u64, u64> = ElasticHashTable::with_seed(64, 0.1, 1);
+        table.insert(7, 70).expect("Insertion failed");
+        let keys = [7u64];
+        let prepared = table.prepare(&keys);
+        assert_eq!(table.search_prepared(&prepared, 0), Ok(Some(&70)));
+
+        table.rehash_with_seed(2).expect("rehash failed");
+        let stale = PreparedKeysError::SeedMismatch { prepared: 1, table: 2 };
+        assert_eq!(table.search_prepared(&prepared, 0), Err(stale.clone()));
+        assert_eq!(table.get_many_prepared(&prepared), Err(stale));
+        assert_eq!(table.get_many_prepared(&table.prepare(&keys)), Ok(vec![Some(&70)]));
+    }
+}
@@ -0,0 +1,173 @@
+//! raw entry API: look a key up by a borrowed form and only build the owned key on a miss
+//!
+//! ```ignore
+//! match table.raw_entry().from_key(word) {
+//!     RawEntry::Occupied(mut e) => *e.get_mut() += 1,
+//!     RawEntry::Vacant(e) => { e.insert_with_owned_key(word.to_owned(), 1)?; }
+//! }
+//! ```
+
+use std::hash::Hash;
+
+use crate::elastic::Slot;
+use crate::{ElasticHashError, ElasticHashTable, Equivalent, StableHash, TableHasher};
+
+/// returned by ElasticHashTable::raw_entry
+pub struct RawEntryBuilder<'a, K, V, S = StableHash> {
+    table: &'a mut ElasticHashTable<K, V, S>,
+}
+
+pub enum RawEntry<'a, K, V, S = StableHash> {
+    Occupied(RawOccupiedEntry<'a, K, V, S>),
+    Vacant(RawVacantEntry<'a, K, V, S>),
+}
+
+/// an existing entry; no owned key was needed to reach it
+pub struct RawOccupiedEntry<'a, K, V, S = StableHash> {
+    table: &'a mut ElasticHashTable<K, V, S>,
+    level: usize,
+    idx: usize,
+}
+
+/// a missing key; inserting requires the owned key
+pub struct RawVacantEntry<'a, K, V, S = StableHash> {
+    table: &'a mut ElasticHashTable<K, V, S>,
+}
+
+impl<K, V, S> ElasticHashTable<K, V, S>
+where
+    K: Hash + Eq,
+    S: TableHasher,
+{
+    /// start a raw entry lookup, see RawEntryBuilder::from_key
+    pub fn raw_entry(&mut self) -> RawEntryBuilder<'_, K, V, S> {
+        RawEntryBuilder { table: self }
+    }
+}
+
+impl<'a, K, V, S> RawEntryBuilder<'a, K, V, S>
+where
+    K: Hash + Eq,
+    S: TableHasher,
+{
+    /// look key up through its borrowed form
+    pub fn from_key<Q>(self, key: &Q) -> RawEntry<'a, K, V, S>
+    where
+        Q: ?Sized + Hash + Equivalent<K>,
+    {
+        match self.table.find(key) {
+            Some((level, idx)) => RawEntry::Occupied(RawOccupiedEntry {
+                table: self.table,
+                level,
+                idx,
+            }),
+            None => RawEntry::Vacant(RawVacantEntry { table: self.table }),
+        }
+    }
+}
+
+impl<'a, K, V, S> RawOccupiedEntry<'a, K, V, S>
+where
+    K: Hash + Eq,
+    S: TableHasher,
+{
+    pub fn key(&self) -> &K {
+        self.get_key_value().0
+    }
+
+    pub fn get(&self) -> &V {
+        self.get_key_value().1
+    }
+
+    pub fn get_key_value(&self) -> (&K, &V) {
+        match &self.table.levels[self.level][self.idx] {
+            Slot::Occupied(k, v) => (k, v),
+            _ => unreachable!("raw entry points at a slot that is not occupied"),
+        }
+    }
+
+    pub fn get_mut(&mut self) -> &mut V {
+        match &mut self.table.levels[self.level][self.idx] {
+            Slot::Occupied(_, v) => v,
+            _ => unreachable!("raw entry points at a slot that is not occupied"),
+        }
+    }
+
+    /// the value, borrowed for the lifetime of the table borrow
+    pub fn into_mut(self) -> &'a mut V {
+        match &mut self.table.levels[self.level][self.idx] {
+            Slot::Occupied(_, v) => v,
+            _ => unreachable!("raw entry points at a slot that is not occupied"),
+        }
+    }
+
+    /// remove the entry from the table
+    pub fn remove_entry(self) -> (K, V) {
+        self.table.take_slot(self.level, self.idx)
+    }
+}
+
+impl<'a, K, V, S> RawVacantEntry<'a, K, V, S>
+where
+    K: Hash + Eq,
+    S: TableHasher,
+{
+    /// insert with the owned key, which must be equal to the key the lookup was made with
+    pub fn insert_with_owned_key(self, key: K, value: V) -> Result<&'a mut V, ElasticHashError> {
+        let free = self.table.locate_free_or_grow(&key)?;
+        let (level, idx) = self.table.place(free, key, value);
+        match &mut self.table.levels[level][idx] {
+            Slot::Occupied(_, v) => Ok(v),
+            _ => unreachable!("insert returned a slot that is not occupied"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::RawEntry;
+    use crate::ElasticHashTable;
+
+    #[test]
+    fn test_raw_entry_allocates_only_on_miss() {
+        let mut table: ElasticHashTable<String, usize> = ElasticHashTable::deterministic(64, 0.1);
+        let mut owned_keys = 0;
+        let text = "the cat and the dog and the bird";
+
+        for word in text.split(' ') {
+            match table.raw_entry().from_key(word) {
+                RawEntry::Occupied(mut e) => *e.get_mut() += 1,
+                RawEntry::Vacant(e) => {
+                    owned_keys += 1;
+                    e.insert_with_owned_key(word.to_owned(), 1).expect("Insertion failed");
+                }
+            }
+        }
+
+        assert_eq!(owned_keys, 5);
+        assert_eq!(table.get("the"), Some(&3));
+        assert_eq!(table.get("and"), Some(&2));
+        assert_eq!(table.get("dog"), Some(&1));
+        assert_eq!(table.num_inserts, 5);
+    }
+
+    #[test]
+    fn test_raw_occupied_entry() {
+        let mut table: ElasticHashTable<String, usize> = ElasticHashTable::deterministic(64, 0.1);
+        table.insert("a".to_owned(), 1).expect("Insertion failed");
+
+        let RawEntry::Occupied(e) = table.raw_entry().from_key("a") else {
+            panic!("expected an occupied entry");
+        };
+        assert_eq!(e.get_key_value(), (&"a".to_owned(), &1));
+        *e.into_mut() = 5;
+        assert_eq!(table.get("a"), Some(&5));
+
+        let RawEntry::Occupied(e) = table.raw_entry().from_key("a") else {
+            panic!("expected an occupied entry");
+        };
+        assert_eq!(e.remove_entry(), ("a".to_owned(), 5));
+        assert!(matches!(table.raw_entry().from_key("a"), RawEntry::Vacant(_)));
+        assert_eq!(table.num_inserts, 0);
+    }
+}
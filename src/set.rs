@@ -0,0 +1,159 @@
+//! ElasticHashSet: an ElasticHashTable of keys alone
+//!
+//! A set and a table convert into each other without moving a key: into_key_set and into_table_with
+//! keep every key in its slot, along with the capacity, delta, seed and the other settings.
+
+use std::hash::Hash;
+
+use crate::{hasher, ElasticHashTable, Equivalent, InsertError, Keys, StableHash, TableHasher};
+
+/// a set of keys placed by elastic hashing
+#[derive(Clone)]
+pub struct ElasticHashSet<K, S = StableHash> {
+    table: ElasticHashTable<K, (), S>,
+}
+
+impl<K> ElasticHashSet<K>
+where
+    K: Hash + Eq,
+{
+    /// see ElasticHashTable::new
+    pub fn new(capacity: usize, delta: f64) -> Self {
+        Self::with_seed(capacity, delta, hasher::random_seed())
+    }
+
+    /// see ElasticHashTable::with_seed
+    pub fn with_seed(capacity: usize, delta: f64, seed: u64) -> Self {
+        ElasticHashSet { table: ElasticHashTable::with_seed(capacity, delta, seed) }
+    }
+}
+
+impl<K, S> ElasticHashSet<K, S>
+where
+    K: Hash + Eq,
+    S: TableHasher,
+{
+    /// add key; true when it was not in the set
+    pub fn insert(&mut self, key: K) -> Result<bool, InsertError<K, ()>> {
+        self.table.insert_or_update(key, ()).map(|old| old.is_none())
+    }
+
+    pub fn contains<Q>(&self, key: &Q) -> bool
+    where
+        Q: ?Sized + Hash + Equivalent<K>,
+    {
+        self.table.find(key).is_some()
+    }
+
+    /// remove key; true when it was in the set
+    pub fn remove<Q>(&mut self, key: &Q) -> bool
+    where
+        Q: ?Sized + Hash + Equivalent<K>,
+    {
+        self.table.remove(key).is_some()
+    }
+
+    pub fn iter(&self) -> Keys<'_, K, ()> {
+        self.table.keys()
+    }
+
+    pub fn len(&self) -> usize {
+        self.table.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.table.is_empty()
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.table.capacity()
+    }
+
+    /// the table holding the keys, for its statistics and settings
+    pub fn as_table(&self) -> &ElasticHashTable<K, (), S> {
+        &self.table
+    }
+
+    /// a table of the same keys in the same slots, each with value f(key); see ElasticHashTable::map_values
+    pub fn into_table_with<V>(self, mut f: impl FnMut(&K) -> V) -> ElasticHashTable<K, V, S> {
+        self.table.map_values(|k, ()| f(k))
+    }
+}
+
+impl<K, V, S> ElasticHashTable<K, V, S>
+where
+    K: Hash + Eq,
+    S: TableHasher,
+{
+    /// the keys, left in their slots, as a set; see map_values
+    pub fn into_key_set(self) -> ElasticHashSet<K, S> {
+        ElasticHashSet { table: self.map_values(|_, _| ()) }
+    }
+}
+
+impl<K, V, S> From<ElasticHashTable<K, V, S>> for ElasticHashSet<K, S>
+where
+    K: Hash + Eq,
+    S: TableHasher,
+{
+    fn from(table: ElasticHashTable<K, V, S>) -> Self {
+        table.into_key_set()
+    }
+}
+
+impl<K, S> From<ElasticHashSet<K, S>> for ElasticHashTable<K, (), S> {
+    fn from(set: ElasticHashSet<K, S>) -> Self {
+        set.table
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ElasticHashSet;
+    use crate::{ElasticHashTable, IdentityHash};
+
+    #[test]
+    fn test_set_basics() {
+        let mut set = ElasticHashSet::with_seed(64, 0.1, 1);
+        assert!(set.insert("a".to_string()).unwrap());
+        assert!(!set.insert("a".to_string()).unwrap());
+        set.insert("b".to_string()).unwrap();
+        assert!(set.contains("a") && !set.contains("c"));
+        assert!(set.remove("a") && !set.remove("a"));
+        assert_eq!(set.iter().collect::<Vec<_>>(), ["b"]);
+        assert_eq!((set.len(), set.capacity()), (1, 64));
+        while !set.as_table().is_full() {
+            set.insert(set.len().to_string()).unwrap();
+        }
+        assert!(set.insert("new".to_string()).is_err());
+    }
+
+    #[test]
+    fn test_table_and_set_convert_in_place() {
+        let mut table: ElasticHashTable<u64, String, IdentityHash> = ElasticHashTable::with_hasher(1024, 0.2, 7);
+        table.set_grow_on_full(true);
+        for key in 0..800 {
+            table.insert(key * 3, key.to_string()).expect("Insertion failed");
+        }
+        table.remove(&30);
+        let slots: Vec<_> = (0..2400).map(|key| table.find(&key)).collect();
+        let options = table.options();
+
+        let set = table.into_key_set();
+        assert_eq!(set.len(), 799);
+        assert!(set.contains(&3) && !set.contains(&30) && !set.contains(&4));
+        assert_eq!(set.as_table().options(), options);
+        assert_eq!(set.as_table().hasher_id(), "identity-multiply");
+        assert!(set.as_table().grows_on_full());
+
+        let halves = set.into_table_with(|key| key / 3);
+        assert_eq!((halves.get(&300), halves.get(&30)), (Some(&100), None));
+        assert_eq!((0..2400).map(|key| halves.find(&key)).collect::<Vec<_>>(), slots);
+        assert_eq!(halves.options(), options);
+        halves.check_invariants().unwrap();
+
+        let set: ElasticHashSet<u64, IdentityHash> = halves.into();
+        let units: ElasticHashTable<u64, (), IdentityHash> = set.into();
+        assert_eq!(units.len(), 799);
+    }
+}
@@ -0,0 +1,284 @@
+//! shadow mode: a wrapper that runs every operation against a std HashMap as well
+//! and reports any divergence between the two answers.
+//!
+//! Shadowing is compiled in only with the `shadow` feature (and in this crate's own tests);
+//! otherwise ShadowedElasticTable is a plain pass-through, so it can stay in production code.
+//! The pass-through only asks of K and V what the table does; the Clone, PartialEq and Debug
+//! bounds mirroring needs apply only with the feature on.
+
+use std::borrow::Borrow;
+#[cfg(any(test, feature = "shadow"))]
+use std::cell::RefCell;
+#[cfg(any(test, feature = "shadow"))]
+use std::collections::HashMap;
+use std::fmt::{self, Debug};
+use std::hash::Hash;
+
+use crate::{ElasticHashTable, InsertError};
+
+/// the operation that produced a mismatch
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ShadowOp {
+    Insert,
+    Search,
+    Remove,
+}
+
+/// a divergence between the elastic table and the HashMap oracle
+/// key and answers are kept in their Debug form so the report does not depend on the key type
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ShadowMismatch {
+    pub op: ShadowOp,
+    pub key: String,
+    /// answer given by the elastic table
+    pub elastic: Option<String>,
+    /// answer given by the HashMap
+    pub shadow: Option<String>,
+}
+
+impl fmt::Display for ShadowMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "shadow mismatch on {:?}({}): elastic={:?} shadow={:?}",
+            self.op, self.key, self.elastic, self.shadow
+        )
+    }
+}
+
+/// callback receiving every mismatch
+#[cfg(any(test, feature = "shadow"))]
+type MismatchHandler = Box<dyn FnMut(&ShadowMismatch)>;
+
+pub struct ShadowedElasticTable<K, V> {
+    table: ElasticHashTable<K, V>,
+    #[cfg(any(test, feature = "shadow"))]
+    shadow: HashMap<K, V>,
+    /// in a RefCell so search can report through &self
+    #[cfg(any(test, feature = "shadow"))]
+    on_mismatch: RefCell<Option<MismatchHandler>>,
+}
+
+impl<K, V> ShadowedElasticTable<K, V>
+where
+    K: Hash + Eq,
+{
+    pub fn new(capacity: usize, delta: f64) -> Self {
+        Self {
+            table: ElasticHashTable::new(capacity, delta),
+            #[cfg(any(test, feature = "shadow"))]
+            shadow: HashMap::new(),
+            #[cfg(any(test, feature = "shadow"))]
+            on_mismatch: RefCell::new(None),
+        }
+    }
+
+    /// the wrapped table
+    pub fn inner(&self) -> &ElasticHashTable<K, V> {
+        &self.table
+    }
+}
+
+#[cfg(not(any(test, feature = "shadow")))]
+impl<K, V> ShadowedElasticTable<K, V>
+where
+    K: Hash + Eq,
+{
+    /// callback invoked on every mismatch; never called without the `shadow` feature
+    pub fn set_mismatch_handler<F>(&mut self, _handler: F)
+    where
+        F: FnMut(&ShadowMismatch) + 'static,
+    {
+    }
+
+    pub fn insert(&mut self, key: K, value: V) -> Result<(usize, usize), InsertError<K, V>> {
+        self.table.insert(key, value)
+    }
+
+    pub fn search<Q>(&self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Hash + Eq,
+    {
+        self.table.get(key)
+    }
+
+    pub fn remove<Q>(&mut self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Hash + Eq,
+    {
+        self.table.remove(key)
+    }
+}
+
+#[cfg(any(test, feature = "shadow"))]
+impl<K, V> ShadowedElasticTable<K, V>
+where
+    K: Hash + Eq + Clone + Debug,
+    V: Clone + PartialEq + Debug,
+{
+    /// callback invoked on every mismatch
+    /// without one, a mismatch panics in debug builds and is logged as an error in release builds
+    pub fn set_mismatch_handler<F>(&mut self, handler: F)
+    where
+        F: FnMut(&ShadowMismatch) + 'static,
+    {
+        *self.on_mismatch.get_mut() = Some(Box::new(handler));
+    }
+
+    /// insert into both tables, then check that both replaced the same value, if any,
+    /// and that the elastic table reads the new value back
+    pub fn insert(&mut self, key: K, value: V) -> Result<(usize, usize), InsertError<K, V>> {
+        let (pos, old) = self
+            .table
+            .upsert_with(key.clone(), || value.clone())
+            .map_err(|err| err.map_value(|make| make()))?;
+        let elastic = self.table.get(&key).cloned();
+        if elastic.as_ref() != Some(&value) {
+            self.report(ShadowOp::Insert, &key, elastic.as_ref(), Some(&value));
+        }
+        let expected = self.shadow.insert(key.clone(), value);
+        if old != expected {
+            self.report(ShadowOp::Insert, &key, old.as_ref(), expected.as_ref());
+        }
+        Ok(pos)
+    }
+
+    pub fn search<Q>(&self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Hash + Eq + Debug,
+    {
+        let elastic = self.table.get(key);
+        let expected = self.shadow.get(key);
+        if elastic != expected {
+            self.report(ShadowOp::Search, key, elastic, expected);
+        }
+        elastic
+    }
+
+    pub fn remove<Q>(&mut self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Hash + Eq + Debug,
+    {
+        let elastic = self.table.remove(key);
+        let expected = self.shadow.remove(key);
+        if elastic != expected {
+            self.report(ShadowOp::Remove, key, elastic.as_ref(), expected.as_ref());
+        }
+        elastic
+    }
+
+    fn report<Q: ?Sized + Debug>(&self, op: ShadowOp, key: &Q, elastic: Option<&V>, shadow: Option<&V>) {
+        let mismatch = ShadowMismatch {
+            op,
+            key: format!("{:?}", key),
+            elastic: elastic.map(|v| format!("{:?}", v)),
+            shadow: shadow.map(|v| format!("{:?}", v)),
+        };
+        // taken out while it runs, so a handler that searches the table again does not find it borrowed
+        let handler = self.on_mismatch.borrow_mut().take();
+        match handler {
+            Some(mut handler) => {
+                handler(&mismatch);
+                self.on_mismatch.borrow_mut().get_or_insert(handler);
+            }
+            None if cfg!(debug_assertions) => panic!("{}", mismatch),
+            None => log::error!("{}", mismatch),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ShadowMismatch, ShadowOp, ShadowedElasticTable};
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    fn recording(table: &mut ShadowedElasticTable<u32, u32>) -> Rc<RefCell<Vec<ShadowMismatch>>> {
+        let seen = Rc::new(RefCell::new(Vec::new()));
+        let sink = seen.clone();
+        table.set_mismatch_handler(move |m| sink.borrow_mut().push(m.clone()));
+        seen
+    }
+
+    #[test]
+    fn test_shadow_agrees_on_clean_workload() {
+        let mut table = ShadowedElasticTable::new(256, 0.1);
+        let seen = recording(&mut table);
+
+        for i in 0..200 {
+            table.insert(i, i * 3).expect("Insertion failed");
+        }
+        for i in (0..200).step_by(3) {
+            assert_eq!(table.remove(&i), Some(i * 3));
+        }
+        for i in 0..220 {
+            table.search(&i);
+        }
+        assert!(seen.borrow().is_empty(), "unexpected mismatches: {:?}", seen.borrow());
+    }
+
+    #[test]
+    fn test_shadow_catches_corrupted_slot() {
+        let mut table = ShadowedElasticTable::new(64, 0.1);
+        let seen = recording(&mut table);
+
+        for i in 0..40 {
+            table.insert(i, i).expect("Insertion failed");
+        }
+        let (level, idx) = table.insert(7_000, 7).expect("Insertion failed");
+        table.table.corrupt_slot(level, idx, 99);
+
+        assert_eq!(table.search(&7_000), Some(&99));
+        assert_eq!(
+            *seen.borrow(),
+            vec![ShadowMismatch {
+                op: ShadowOp::Search,
+                key: "7000".into(),
+                elastic: Some("99".into()),
+                shadow: Some("7".into()),
+            }]
+        );
+
+        assert_eq!(table.remove(&7_000), Some(99));
+        assert_eq!(seen.borrow().len(), 2);
+        assert_eq!(seen.borrow()[1].op, ShadowOp::Remove);
+    }
+
+    #[test]
+    fn test_shadow_compares_replaced_values() {
+        let mut table = ShadowedElasticTable::new(64, 0.1);
+        let seen = recording(&mut table);
+        let (level, idx) = table.insert(5, 50).expect("Insertion failed");
+        table.table.corrupt_slot(level, idx, 51);
+
+        // the new value reads back fine, but the two tables replaced different values
+        table.insert(5, 52).expect("Insertion failed");
+        assert_eq!(
+            *seen.borrow(),
+            vec![ShadowMismatch {
+                op: ShadowOp::Insert,
+                key: "5".into(),
+                elastic: Some("51".into()),
+                shadow: Some("50".into()),
+            }]
+        );
+        table.insert(6, 60).expect("Insertion failed");
+        let shared = &table;
+        assert_eq!((shared.search(&5), shared.search(&6)), (Some(&52), Some(&60)));
+        assert_eq!(seen.borrow().len(), 1);
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "shadow mismatch on Search")]
+    fn test_shadow_panics_without_handler_in_debug() {
+        let mut table = ShadowedElasticTable::new(16, 0.1);
+        let (level, idx) = table.insert(1u32, 1u32).expect("Insertion failed");
+        table.table.corrupt_slot(level, idx, 2);
+        table.search(&1);
+    }
+}
@@ -0,0 +1,80 @@
+//! tables keyed by `Cow<'static, str>`: a fixed set of static keys costs no allocation,
+//! while the occasional dynamic key is stored owned
+//!
+//! ```ignore
+//! let mut table: StaticKeyTable<u32> = ElasticHashTable::new(64, 0.1);
+//! table.insert(Cow::Borrowed("requests"), 0)?;            // no allocation
+//! table.insert(Cow::Owned(format!("route:{}", path)), 0)?;  // owns its key
+//! table.modify("requests", |n| *n += 1);                    // &str finds either kind
+//! ```
+
+use std::borrow::Cow;
+
+use crate::ElasticHashTable;
+
+/// a table whose keys are static strings or, when they have to be, owned ones
+pub type StaticKeyTable<V> = ElasticHashTable<Cow<'static, str>, V>;
+
+#[cfg(test)]
+mod tests {
+    use std::alloc::{GlobalAlloc, Layout, System};
+    use std::borrow::Cow;
+    use std::cell::Cell;
+
+    use super::StaticKeyTable;
+    use crate::{ElasticHashTable, RawEntry};
+
+    /// the system allocator, counting allocations made by the current thread
+    struct Counting;
+
+    thread_local! {
+        static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+    }
+
+    unsafe impl GlobalAlloc for Counting {
+        unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+            ALLOCATIONS.with(|n| n.set(n.get() + 1));
+            System.alloc(layout)
+        }
+
+        unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+            System.dealloc(ptr, layout)
+        }
+    }
+
+    #[global_allocator]
+    static ALLOCATOR: Counting = Counting;
+
+    const KEYS: [&str; 8] = ["get", "put", "post", "delete", "head", "options", "patch", "trace"];
+
+    #[test]
+    fn test_static_keys_do_not_allocate() {
+        let mut table: StaticKeyTable<u32> = ElasticHashTable::deterministic(64, 0.1);
+        let before = ALLOCATIONS.with(Cell::get);
+        for key in KEYS {
+            table.insert(Cow::Borrowed(key), 0).expect("Insertion failed");
+        }
+        for round in 0..100u32 {
+            for key in KEYS {
+                table.modify(key, |n| *n += round);
+                assert!(table.get(key).is_some());
+            }
+            assert_eq!(table.get("connect"), None);
+        }
+        if let RawEntry::Occupied(mut entry) = table.raw_entry().from_key("get") {
+            *entry.get_mut() += 1;
+        }
+        assert_eq!(ALLOCATIONS.with(Cell::get), before);
+        assert_eq!(table.get("get"), Some(&4951));
+    }
+
+    #[test]
+    fn test_dynamic_keys_mix_with_static_ones() {
+        let mut table: StaticKeyTable<u32> = ElasticHashTable::deterministic(64, 0.1);
+        table.insert(Cow::Borrowed("get"), 1).expect("Insertion failed");
+        table.insert(Cow::Owned(format!("route:{}", 7)), 2).expect("Insertion failed");
+        assert_eq!(table.get("route:7"), Some(&2));
+        assert_eq!(table.get(&Cow::Borrowed("get")), Some(&1));
+        assert!(matches!(table.get_key("route:7"), Some(Cow::Owned(_))));
+    }
+}
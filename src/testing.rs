@@ -0,0 +1,402 @@
+//! soak testing: drive a seeded pseudo-random workload through an ElasticHashTable,
+//! checking every answer against a std HashMap oracle.
+//!
+//! Compiled into the normal build so the wasm module can run it (see `runSoak` in bindings).
+
+use std::collections::HashMap;
+use std::fmt::Write;
+
+use crate::{json, ElasticHashTable, LevelGeometry, ShadowMismatch, ShadowOp, StableHash, TableHasher};
+
+/// at most this many divergences are kept in the report; the rest are only counted
+const MAX_REPORTED_DIVERGENCES: usize = 16;
+
+/// workload parameters
+#[derive(Clone, Debug, PartialEq)]
+pub struct SoakConfig {
+    /// seed of the operation stream and of the table; equal seeds give equal runs
+    pub seed: u64,
+    /// number of operations to run
+    pub ops: usize,
+    /// keys are drawn from 0..key_space
+    pub key_space: u64,
+    pub capacity: usize,
+    pub delta: f64,
+    /// how the table's levels are sized; `"single_level": true` in JSON selects LevelGeometry::Single
+    pub geometry: LevelGeometry,
+}
+
+impl Default for SoakConfig {
+    fn default() -> Self {
+        Self {
+            seed: 1,
+            ops: 10_000,
+            key_space: 1_000,
+            capacity: 2_048,
+            delta: 0.1,
+            geometry: LevelGeometry::Halving,
+        }
+    }
+}
+
+impl SoakConfig {
+    /// parse a flat JSON object such as `{"seed": 7, "ops": 100000}`
+    /// missing fields keep their default value
+    pub fn from_json(json: &str) -> Result<Self, String> {
+        let mut config = Self::default();
+        for (name, value) in json::flat_object(json)? {
+            match name.as_str() {
+                "seed" => config.seed = json::parse(&name, &value)?,
+                "ops" => config.ops = json::parse(&name, &value)?,
+                "key_space" => config.key_space = json::parse(&name, &value)?,
+                "capacity" => config.capacity = json::parse(&name, &value)?,
+                "delta" => config.delta = json::parse(&name, &value)?,
+                "single_level" => {
+                    if json::parse(&name, &value)? {
+                        config.geometry = LevelGeometry::Single;
+                    }
+                }
+                _ => return Err(format!("unknown soak config field: {}", name)),
+            }
+        }
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// check that a table can be built from the config and keys drawn for it
+    pub fn validate(&self) -> Result<(), String> {
+        if self.capacity == 0 || self.key_space == 0 {
+            return Err("capacity and key_space must be positive".into());
+        }
+        if !(0.0 < self.delta && self.delta < 1.0) {
+            return Err("delta must be between 0 and 1".into());
+        }
+        Ok(self.geometry.validate(self.capacity)?)
+    }
+}
+
+/// outcome of a soak run
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SoakReport {
+    pub ops: usize,
+    pub inserts: usize,
+    /// inserts rejected because the table was full; the oracle skips them too
+    pub failed_inserts: usize,
+    pub searches: usize,
+    pub removes: usize,
+    /// number of entries left in the table
+    pub final_len: usize,
+    pub elapsed_ms: f64,
+    pub ops_per_sec: f64,
+    /// average position of the claimed slot in the probe sequence of its level
+    pub insert_probe_avg: f64,
+    /// average number of slots inspected per search
+    pub search_probe_avg: f64,
+    /// total number of divergences from the oracle
+    pub divergence_count: usize,
+    /// the first divergences found
+    pub divergences: Vec<ShadowMismatch>,
+}
+
+impl SoakReport {
+    /// true when the table agreed with the oracle on every operation
+    pub fn passed(&self) -> bool {
+        self.divergence_count == 0
+    }
+
+    fn diverge(&mut self, op: ShadowOp, key: u64, elastic: Option<u64>, shadow: Option<u64>) {
+        self.divergence_count += 1;
+        if self.divergences.len() < MAX_REPORTED_DIVERGENCES {
+            self.divergences.push(ShadowMismatch {
+                op,
+                key: key.to_string(),
+                elastic: elastic.map(|v| v.to_string()),
+                shadow: shadow.map(|v| v.to_string()),
+            });
+        }
+    }
+
+    pub fn to_json(&self) -> String {
+        let mut out = String::new();
+        let _ = write!(
+            out,
+            "{{\"passed\":{},\"ops\":{},\"inserts\":{},\"failed_inserts\":{},\"searches\":{},\"removes\":{},\
+             \"final_len\":{},\"elapsed_ms\":{},\"ops_per_sec\":{},\"insert_probe_avg\":{},\"search_probe_avg\":{},\
+             \"divergence_count\":{},\"divergences\":[",
+            self.passed(),
+            self.ops,
+            self.inserts,
+            self.failed_inserts,
+            self.searches,
+            self.removes,
+            self.final_len,
+            json_number(self.elapsed_ms),
+            json_number(self.ops_per_sec),
+            json_number(self.insert_probe_avg),
+            json_number(self.search_probe_avg),
+            self.divergence_count,
+        );
+        for (i, m) in self.divergences.iter().enumerate() {
+            if i > 0 {
+                out.push(',');
+            }
+            let _ = write!(
+                out,
+                "{{\"op\":\"{:?}\",\"key\":\"{}\",\"elastic\":{},\"shadow\":{}}}",
+                m.op,
+                m.key,
+                json_opt(&m.elastic),
+                json_opt(&m.shadow),
+            );
+        }
+        out.push_str("]}");
+        out
+    }
+}
+
+/// JSON has no NaN / infinity
+fn json_number(x: f64) -> String {
+    if x.is_finite() {
+        x.to_string()
+    } else {
+        "null".into()
+    }
+}
+
+fn json_opt(v: &Option<String>) -> String {
+    v.as_ref().map_or("null".into(), |s| format!("\"{}\"", s))
+}
+
+/// splitmix64: small, seedable and good enough to shuffle a workload
+struct SplitMix64(u64);
+
+impl SplitMix64 {
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+}
+
+/// milliseconds from an arbitrary origin; std::time::Instant is unavailable on wasm32-unknown-unknown
+#[cfg(all(target_arch = "wasm32", feature = "wasm"))]
+pub(crate) fn now_ms() -> f64 {
+    use wasm_bindgen::prelude::*;
+
+    #[wasm_bindgen]
+    extern "C" {
+        #[wasm_bindgen(js_namespace = Date, js_name = now)]
+        fn date_now() -> f64;
+    }
+    date_now()
+}
+
+#[cfg(not(all(target_arch = "wasm32", feature = "wasm")))]
+pub(crate) fn now_ms() -> f64 {
+    use std::sync::OnceLock;
+    use std::time::Instant;
+    static ORIGIN: OnceLock<Instant> = OnceLock::new();
+    ORIGIN.get_or_init(Instant::now).elapsed().as_secs_f64() * 1000.0
+}
+
+/// run the workload described by config: roughly 50% inserts, 30% searches and 20% removes,
+/// followed by a final sweep searching every key the oracle still holds
+/// an insert drawn for a live key replaces its value, as in the oracle
+/// fails, without running anything, on a config SoakConfig::validate rejects
+pub fn soak(config: SoakConfig) -> Result<SoakReport, String> {
+    soak_with::<StableHash>(config)
+}
+
+/// soak a table hashing with S, e.g. `soak_with::<IdentityHash>(config)`
+pub fn soak_with<S: TableHasher>(config: SoakConfig) -> Result<SoakReport, String> {
+    config.validate()?;
+    let mut rng = SplitMix64(config.seed);
+    let mut table =
+        ElasticHashTable::<u64, u64, S>::with_geometry(config.capacity, config.delta, config.seed, config.geometry)?;
+    let mut oracle: HashMap<u64, u64> = HashMap::new();
+    let mut report = SoakReport {
+        ops: config.ops,
+        ..SoakReport::default()
+    };
+    let mut insert_probes = 0;
+    let mut search_probes = 0;
+
+    let start = now_ms();
+    for op in 0..config.ops {
+        let key = rng.next() % config.key_space;
+        let value = op as u64;
+        let roll = rng.next() % 10;
+        if roll < 5 {
+            report.inserts += 1;
+            match table.insert(key, value) {
+                Ok((level, idx)) => {
+                    insert_probes += table.probe_position(&key, level, idx);
+                    oracle.insert(key, value);
+                }
+                Err(_) => report.failed_inserts += 1,
+            }
+        } else if roll < 8 {
+            report.searches += 1;
+            search_probes += table.probe(&key).1;
+            let elastic = table.get(&key).copied();
+            let expected = oracle.get(&key).copied();
+            if elastic != expected {
+                report.diverge(ShadowOp::Search, key, elastic, expected);
+            }
+        } else {
+            report.removes += 1;
+            let elastic = table.remove(&key);
+            let expected = oracle.remove(&key);
+            if elastic != expected {
+                report.diverge(ShadowOp::Remove, key, elastic, expected);
+            }
+        }
+    }
+    report.elapsed_ms = now_ms() - start;
+
+    for (key, value) in &oracle {
+        let elastic = table.get(key).copied();
+        if elastic != Some(*value) {
+            report.diverge(ShadowOp::Search, *key, elastic, Some(*value));
+        }
+    }
+
+    report.final_len = table.num_inserts;
+    report.ops_per_sec = config.ops as f64 / (report.elapsed_ms / 1000.0);
+    let placed = report.inserts - report.failed_inserts;
+    if placed > 0 {
+        report.insert_probe_avg = insert_probes as f64 / placed as f64;
+    }
+    if report.searches > 0 {
+        report.search_probe_avg = search_probes as f64 / report.searches as f64;
+    }
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ci_config(seed: u64) -> SoakConfig {
+        SoakConfig {
+            seed,
+            ops: 20_000,
+            key_space: 600,
+            capacity: 1_024,
+            delta: 0.1,
+            geometry: LevelGeometry::Halving,
+        }
+    }
+
+    #[test]
+    fn test_soak_passes_against_oracle() {
+        for seed in 1..=4 {
+            let report = soak(ci_config(seed)).expect("valid config");
+            assert!(report.passed(), "seed {}: {:?}", seed, report.divergences);
+            assert_eq!(report.inserts + report.searches + report.removes, report.ops);
+            assert!(report.final_len > 0);
+            assert!(report.insert_probe_avg >= 1.0);
+            assert!(report.search_probe_avg >= 1.0);
+        }
+    }
+
+    #[test]
+    fn test_soak_identity_hash() {
+        for seed in 1..=4 {
+            let report = soak_with::<crate::IdentityHash>(ci_config(seed)).expect("valid config");
+            assert!(report.passed(), "seed {}: {:?}", seed, report.divergences);
+            assert!(report.final_len > 0);
+        }
+    }
+
+    #[test]
+    fn test_soak_custom_geometry() {
+        let inverted = LevelGeometry::Custom(vec![64, 128, 256, 576]);
+        let uniform = LevelGeometry::Custom(vec![256; 4]);
+        for geometry in [inverted, uniform] {
+            for seed in 1..=4 {
+                let config = SoakConfig {
+                    geometry: geometry.clone(),
+                    ..ci_config(seed)
+                };
+                let report = soak(config.clone()).expect("valid config");
+                assert!(report.passed(), "{:?} seed {}: {:?}", geometry, seed, report.divergences);
+                assert!(report.final_len > 0);
+                let report = soak_with::<crate::IdentityHash>(config).expect("valid config");
+                assert!(report.passed(), "{:?} seed {}: {:?}", geometry, seed, report.divergences);
+            }
+        }
+    }
+
+    #[test]
+    fn test_soak_single_level() {
+        for seed in 1..=4 {
+            let config = SoakConfig {
+                geometry: LevelGeometry::Single,
+                ..ci_config(seed)
+            };
+            let report = soak(config.clone()).expect("valid config");
+            assert!(report.passed(), "seed {}: {:?}", seed, report.divergences);
+            assert!(report.final_len > 0);
+            let report = soak_with::<crate::IdentityHash>(config).expect("valid config");
+            assert!(report.passed(), "seed {}: {:?}", seed, report.divergences);
+        }
+    }
+
+    #[test]
+    fn test_soak_is_deterministic_per_seed() {
+        let a = soak(ci_config(42)).expect("valid config");
+        let b = soak(ci_config(42)).expect("valid config");
+        assert_eq!(
+            (a.inserts, a.failed_inserts, a.searches, a.removes, a.final_len),
+            (b.inserts, b.failed_inserts, b.searches, b.removes, b.final_len)
+        );
+        assert_eq!(a.insert_probe_avg, b.insert_probe_avg);
+    }
+
+    #[test]
+    fn test_soak_config_from_json() {
+        let config = SoakConfig::from_json(r#"{"seed": 7, "ops": 500, "delta": 0.25}"#).unwrap();
+        assert_eq!(
+            config,
+            SoakConfig {
+                seed: 7,
+                ops: 500,
+                delta: 0.25,
+                ..SoakConfig::default()
+            }
+        );
+        assert_eq!(SoakConfig::from_json(r#"{"single_level": true}"#).unwrap().geometry, LevelGeometry::Single);
+        assert!(SoakConfig::from_json("{}").is_ok());
+        assert!(SoakConfig::from_json(r#"{"speed": 1}"#).is_err());
+        assert!(SoakConfig::from_json(r#"{"delta": 2}"#).is_err());
+        assert!(SoakConfig::from_json("[1]").is_err());
+    }
+
+    #[test]
+    fn test_soak_report_json() {
+        let report = soak(SoakConfig {
+            ops: 100,
+            ..SoakConfig::default()
+        })
+        .expect("valid config");
+        let json = report.to_json();
+        assert!(json.starts_with("{\"passed\":true,\"ops\":100,"));
+        assert!(json.ends_with("\"divergence_count\":0,\"divergences\":[]}"));
+    }
+
+    #[test]
+    fn test_soak_rejects_invalid_configs() {
+        let bad_geometry = SoakConfig {
+            geometry: LevelGeometry::Custom(vec![1_000, 1_000]),
+            ..ci_config(1)
+        };
+        assert!(soak(bad_geometry.clone()).is_err());
+        assert!(soak_with::<crate::IdentityHash>(bad_geometry).is_err());
+        assert!(soak(SoakConfig { key_space: 0, ..ci_config(1) }).is_err());
+        assert!(soak(SoakConfig { delta: 1.0, ..ci_config(1) }).is_err());
+        assert!(soak(SoakConfig { capacity: 0, ..ci_config(1) }).is_err());
+    }
+}